@@ -0,0 +1,84 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alacran::instruments::{delta::Delta, item::Item, value::Value, Assesible};
+use chrono::{TimeDelta, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// Counts allocations so the benchmark can report them per assessment.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn assess(c: &mut Criterion) {
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let mut item = Item::basic_debt(
+        Value::dummy("CAD", -1_000_000.0),
+        0.05,
+        TimeDelta::days(365),
+        start,
+    );
+    item.import_deltas(
+        (0..100_000).map(|i| Delta::payment(start + TimeDelta::hours(i), Value::dummy("CAD", 1.0))),
+    );
+    let time = start + TimeDelta::days(365 * 20);
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(item.assess(time));
+    println!(
+        "allocations per assessment of 100k deltas: {}",
+        ALLOCATIONS.load(Ordering::Relaxed) - before
+    );
+
+    c.bench_function("assess 100k deltas", |b| {
+        b.iter(|| black_box(&item).assess(time))
+    });
+}
+
+fn assess_at_many(c: &mut Criterion) {
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let mut item = Item::basic_debt(
+        Value::dummy("CAD", -1_000_000.0),
+        0.05,
+        TimeDelta::days(365),
+        start,
+    );
+    item.import_deltas(
+        (0..10_000).map(|i| Delta::payment(start + TimeDelta::hours(i), Value::dummy("CAD", 1.0))),
+    );
+    let times: Vec<_> = (0..10_000).map(|i| start + TimeDelta::minutes(i * 61)).collect();
+
+    // One by one replays the fold for every time, so keep the samples few.
+    let mut group = c.benchmark_group("assess 10k times on 10k deltas");
+    group.sample_size(10);
+    group.bench_function("one by one", |b| {
+        b.iter(|| {
+            times
+                .iter()
+                .map(|t| black_box(&item).assess(*t))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("assess_at_many", |b| {
+        b.iter(|| black_box(&item).assess_at_many(&times))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, assess, assess_at_many);
+criterion_main!(benches);
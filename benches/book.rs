@@ -0,0 +1,116 @@
+use alacran::instruments::{book::Book, delta::Delta, item::Item, value::Value, Assesible};
+use chrono::{TimeDelta, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn book(c: &mut Criterion) {
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let mut book = Book::default();
+    for i in 0..100_000 {
+        book.add(Item::basic_debt(
+            Value::dummy("CAD", -(i as f64)),
+            0.05,
+            TimeDelta::days(365),
+            start,
+        ));
+    }
+    let time = start + TimeDelta::days(3650);
+
+    let mut group = c.benchmark_group("book of 100k items");
+    group.bench_function("sum of item values", |b| {
+        b.iter(|| {
+            black_box(&book)
+                .iter()
+                .map(|(_, item)| item.assess(time))
+                .sum::<Option<Value>>()
+        })
+    });
+    group.bench_function("streaming assess", |b| {
+        b.iter(|| black_box(&book).assess(time))
+    });
+    group.finish();
+}
+
+fn bulk_load(c: &mut Criterion) {
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let card = Item::basic_debt(
+        Value::dummy("CAD", -1_000.0),
+        0.2,
+        TimeDelta::days(365),
+        start,
+    );
+    let deltas: Vec<Delta> = (0..20_000)
+        .map(|i| Delta::payment(start + TimeDelta::hours(i), Value::dummy("CAD", 1.0)))
+        .collect();
+
+    let mut group = c.benchmark_group("loading 20k deltas");
+    group.sample_size(10);
+    group.bench_function("one at a time", |b| {
+        b.iter(|| {
+            let mut book = Book::default();
+            let key = book.add(card.clone());
+            for delta in black_box(&deltas) {
+                book.add_delta(key, delta.clone());
+            }
+            book
+        })
+    });
+    group.bench_function("add_item_with_deltas", |b| {
+        b.iter(|| {
+            let mut book = Book::default();
+            book.add_item_with_deltas(card.clone(), black_box(&deltas).clone());
+            book
+        })
+    });
+    group.bench_function("bulk_edit", |b| {
+        b.iter(|| {
+            let mut book = Book::default();
+            let key = book.add(card.clone());
+            book.bulk_edit(key, |item| {
+                item.deltas.extend(black_box(&deltas).iter().cloned())
+            });
+            book
+        })
+    });
+    group.finish();
+}
+
+fn fork(c: &mut Criterion) {
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let mut book = Book::default();
+    let keys: Vec<_> = (0..1_000)
+        .map(|_| {
+            let deltas = (0..100)
+                .map(|i| Delta::payment(start + TimeDelta::days(i), Value::dummy("CAD", 1.0)))
+                .collect();
+            book.add_item_with_deltas(Item::fixed(Value::dummy("CAD", 0.0), start), deltas)
+        })
+        .collect();
+    let tweak = |book: &mut Book| {
+        for key in &keys[..2] {
+            book.add_delta(*key, Delta::payment(start, Value::dummy("CAD", 5.0)));
+        }
+    };
+
+    let mut group = c.benchmark_group("forking a book of 100k deltas");
+    group.bench_function("copying every item", |b| {
+        b.iter(|| {
+            let items: Vec<Item> = black_box(&book).iter().map(|(_, i)| i.clone()).collect();
+            let mut fork = Book::default();
+            for item in items {
+                fork.add(item);
+            }
+            fork
+        })
+    });
+    group.bench_function("clone and tweak two items", |b| {
+        b.iter(|| {
+            let mut fork = black_box(&book).clone();
+            tweak(&mut fork);
+            fork
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, book, bulk_load, fork);
+criterion_main!(benches);
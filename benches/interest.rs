@@ -0,0 +1,71 @@
+use alacran::instruments::{item::Interest, value::Value};
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// The formula before the powi fast path and cached period length.
+fn apply_powf(
+    percent: f64,
+    period: TimeDelta,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    value: &Value,
+) -> Value {
+    let periods =
+        (to - from).num_nanoseconds().unwrap() as f64 / period.num_nanoseconds().unwrap() as f64;
+    value * (1.0 + percent).powf(periods)
+}
+
+/// A 240 month series spends most of its time in whole-period interest.
+fn interest(c: &mut Criterion) {
+    let period = TimeDelta::days(30);
+    let interest = Interest::new(0.01, period);
+    let value = Value::dummy("CAD", 1_000.0);
+    let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let whole: Vec<_> = (0..240).map(|i| start + period * i).collect();
+    let fractional: Vec<_> = whole.iter().map(|t| *t + TimeDelta::hours(1)).collect();
+
+    let mut group = c.benchmark_group("interest");
+    group.bench_function("powf whole periods", |b| {
+        b.iter(|| {
+            whole
+                .iter()
+                .map(|t| apply_powf(0.01, period, start, *t, black_box(&value)).amount())
+                .sum::<f64>()
+        })
+    });
+    group.bench_function("apply whole periods", |b| {
+        b.iter(|| {
+            whole
+                .iter()
+                .map(|t| interest.apply(start, *t, black_box(&value)).amount())
+                .sum::<f64>()
+        })
+    });
+    group.bench_function("powf fractional periods", |b| {
+        b.iter(|| {
+            fractional
+                .iter()
+                .map(|t| apply_powf(0.01, period, start, *t, black_box(&value)).amount())
+                .sum::<f64>()
+        })
+    });
+    group.bench_function("apply fractional periods", |b| {
+        b.iter(|| {
+            fractional
+                .iter()
+                .map(|t| interest.apply(start, *t, black_box(&value)).amount())
+                .sum::<f64>()
+        })
+    });
+    group.bench_function("apply_periods", |b| {
+        b.iter(|| {
+            (0..240)
+                .map(|i| interest.apply_periods(start, i as f64, black_box(&value)).amount())
+                .sum::<f64>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, interest);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Paying off a card, a line of credit and a car loan with 1,200 a month,
+//! smallest balance first against highest rate first.
+//!
+//! Run with `cargo run --example debt_payoff`.
+
+use alacran::{
+    instruments::strategy::{payoff, payoff_plan, PayoffPlan, Strategy},
+    prelude::*,
+};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// The debts and both plans for paying them off.
+pub struct Comparison {
+    pub book: Book,
+    pub debts: Vec<ItemKey>,
+    pub snowball: PayoffPlan,
+    pub avalanche: PayoffPlan,
+    pub start: DateTime<Utc>,
+}
+
+pub fn comparison() -> Comparison {
+    let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let month = Period::Months(1);
+    let mut book = Book::default();
+    let debts = vec![
+        book.add_named(
+            "Visa",
+            Item::basic_debt(Value::new("CAD", -8_000.0), 0.0175, month, start),
+        ),
+        book.add_named(
+            "Line of credit",
+            Item::basic_debt(Value::new("CAD", -4_000.0), 0.0085, month, start),
+        ),
+        book.add_named(
+            "Car loan",
+            Item::basic_debt(Value::new("CAD", -15_000.0), 0.0058, month, start),
+        ),
+    ];
+    let budget = Value::new("CAD", 1_200.0);
+    let snowball = payoff_plan(&book, &debts, budget.clone(), start, Strategy::Snowball);
+    let avalanche = payoff_plan(&book, &debts, budget, start, Strategy::Avalanche);
+    Comparison {
+        book,
+        debts,
+        snowball,
+        avalanche,
+        start,
+    }
+}
+
+fn main() {
+    let Comparison {
+        book,
+        debts,
+        snowball,
+        avalanche,
+        start,
+    } = comparison();
+    for (name, plan) in [("snowball", &snowball), ("avalanche", &avalanche)] {
+        println!(
+            "{name}: {} in interest, debt free on {}",
+            plan.total_interest,
+            plan.debt_free().unwrap().date_naive()
+        );
+        for (key, cleared) in &plan.payoff {
+            let cleared = cleared.unwrap().date_naive();
+            println!("  {} cleared on {cleared}", book.name(*key).unwrap());
+        }
+    }
+    let saved = snowball.total_interest.amount() - avalanche.total_interest.amount();
+    println!("the avalanche saves {saved:.2} CAD");
+    for key in debts {
+        let budget = Value::new("CAD", 1_200.0);
+        if let Some(alone) = payoff(book.get(key).unwrap(), &budget, Period::Months(1), start) {
+            println!(
+                "{} alone with the whole budget: {} payments, {} in interest",
+                book.name(key).unwrap(),
+                alone.payments,
+                alone.interest
+            );
+        }
+    }
+}
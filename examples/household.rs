@@ -0,0 +1,101 @@
+//! A household's net worth over fifteen years: a mortgage paid from chequing,
+//! a salary and living expenses, a house, a car that loses value and a
+//! brokerage account held in US dollars.
+//!
+//! Run with `cargo run --example household`.
+
+use alacran::{
+    instruments::{convert::CURRENCY_EXCHANGE, risk::RiskSpec},
+    prelude::*,
+};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// The household's book and the items the example looks at.
+pub struct Household {
+    pub book: Book,
+    pub chequing: ItemKey,
+    pub mortgage: ItemKey,
+    pub brokerage: ItemKey,
+    pub start: DateTime<Utc>,
+}
+
+/// Builds the household's book, paying the mortgage every month until
+/// `until`.
+pub fn household(until: DateTime<Utc>) -> Household {
+    let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let first = Period::Months(1).step(start, 1);
+    CURRENCY_EXCHANGE.add_conversion("USD", "CAD", 1.36);
+
+    let mut book = Book::default();
+    let chequing = book.add_named(
+        "Chequing",
+        Item::builder(Value::new("CAD", 15_000.0), start)
+            .recurring_payment(first, Period::Months(1), until, Value::new("CAD", 6_500.0))
+            .recurring_payment(first, Period::Months(1), until, Value::new("CAD", -3_200.0))
+            .build(),
+    );
+    let mortgage = book.add_named(
+        "Mortgage",
+        Item::basic_debt(Value::new("CAD", -450_000.0), 0.05, Period::Years(1), start),
+    );
+    book.add_named(
+        "House",
+        Item::builder(Value::new("CAD", 650_000.0), start)
+            .interest(0.03, Period::Years(1))
+            .build(),
+    );
+    let car = book.add_named("Car", Item::fixed(Value::new("CAD", 30_000.0), start));
+    book.attach_risk(
+        car,
+        RiskSpec::LosePercentOverTime {
+            percent: 0.15,
+            period: Period::Years(1),
+            starting: start,
+        },
+    );
+    let brokerage = book.add_named(
+        "Brokerage",
+        Item::builder(Value::new("USD", 40_000.0), start)
+            .interest(0.06, Period::Years(1))
+            .build(),
+    );
+
+    let payment = Value::new("CAD", 2_600.0);
+    for time in (1..).map(|k| Period::Months(1).step(start, k)) {
+        if time > until {
+            break;
+        }
+        book.apply_payment("Chequing", "Mortgage", time, payment.clone())
+            .expect("both accounts are in CAD");
+    }
+    Household {
+        book,
+        chequing,
+        mortgage,
+        brokerage,
+        start,
+    }
+}
+
+fn main() {
+    let horizon = Utc.with_ymd_and_hms(2040, 1, 1, 0, 0, 0).unwrap();
+    let Household {
+        book,
+        mortgage,
+        start,
+        ..
+    } = household(horizon);
+    for years in [0, 1, 5, 10, 15] {
+        let time = Period::Years(1).step(start, years);
+        println!(
+            "after {years:>2} years: worth {}, {} with the car's depreciation, owing {}",
+            book.assess(time),
+            book.assess_risked(time),
+            book.get(mortgage).unwrap().assess(time),
+        );
+    }
+    match book.when_reaches(Value::new("CAD", 1_000_000.0), start, horizon) {
+        Some(time) => println!("a millionaire on {}", time.date_naive()),
+        None => println!("not a millionaire within fifteen years"),
+    }
+}
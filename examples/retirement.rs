@@ -0,0 +1,62 @@
+//! Drawing a retirement account down by 5,000 a month while the rest of it
+//! keeps earning 5% a year, and what is left in today's dollars.
+//!
+//! Run with `cargo run --example retirement`.
+
+use alacran::{instruments::risk::RiskExt, prelude::*};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// The account being drawn down.
+pub struct Drawdown {
+    pub book: Book,
+    pub account: ItemKey,
+    pub start: DateTime<Utc>,
+    /// The last withdrawal planned.
+    pub horizon: DateTime<Utc>,
+}
+
+pub fn drawdown() -> Drawdown {
+    let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let horizon = Period::Years(40).step(start, 1);
+    let mut book = Book::default();
+    let account = book.add_named(
+        "RRSP",
+        Item::builder(Value::new("CAD", 900_000.0), start)
+            .interest(0.05, Period::Years(1))
+            .recurring_payment(
+                Period::Months(1).step(start, 1),
+                Period::Months(1),
+                horizon,
+                Value::new("CAD", -5_000.0),
+            )
+            .build(),
+    );
+    Drawdown {
+        book,
+        account,
+        start,
+        horizon,
+    }
+}
+
+fn main() {
+    let Drawdown {
+        book,
+        start,
+        horizon,
+        ..
+    } = drawdown();
+    let inflation = Interest::new(0.02, Period::Years(1));
+    for years in [0, 5, 10, 20] {
+        let time = Period::Years(1).step(start, years);
+        let real = (&book).in_real_terms(inflation.clone(), start).assess(time);
+        println!(
+            "after {years:>2} years: {} left, {real} in today's dollars",
+            book.assess(time)
+        );
+    }
+    match book.when_reaches(Value::new("CAD", 0.0), start, horizon) {
+        Some(time) => println!("the account runs out on {}", time.date_naive()),
+        None => println!("the account lasts forty years"),
+    }
+}
@@ -0,0 +1,403 @@
+//! Comparing assets against each other.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{
+    book::{Book, ItemKey},
+    convert::{ConversionError, CURRENCY_EXCHANGE},
+    item::Item,
+    risk::monte_carlo,
+    value::Value,
+    Assesible,
+};
+
+/// An item's spread of outcomes, see [risk_report].
+#[derive(Clone, Debug)]
+pub struct ItemRiskRow {
+    pub key: ItemKey,
+    pub name: Option<String>,
+    /// Whether the item has risks attached, the other columns are all its
+    /// assessment if not.
+    pub risky: bool,
+    pub mean: Value,
+    /// The 5th percentile outcome, the worst plausible case.
+    pub p5: Value,
+    /// The 95th percentile outcome.
+    pub p95: Value,
+}
+
+/// Ranks the items of a book from the worst plausible outcome up. Items with
+/// risks attached through [Book::attach_risk] are simulated for `trials`
+/// outcomes each from the same `seed`, the rest are assessed once. Every
+/// column is in the [Book::report_currency].
+pub fn risk_report(book: &Book, time: DateTime<Utc>, trials: usize, seed: u64) -> Vec<ItemRiskRow> {
+    let currency = book.currency();
+    let report = |item, value: Value| {
+        let rate = book
+            .item_report_rate(item, value.currency(), time)
+            .unwrap_or_else(|e| panic!("{e}"));
+        value.amount() * rate
+    };
+
+    let mut rows: Vec<ItemRiskRow> = book
+        .iter()
+        .map(|(key, item)| {
+            let risky = !book.risks(key).is_empty() && trials > 0;
+            let (mean, p5, p95) = match risky {
+                true => {
+                    let asset = book.risky(key).unwrap();
+                    let mut outcomes: Vec<f64> = monte_carlo(&*asset, time, trials, seed)
+                        .into_iter()
+                        .map(|value| report(item, value))
+                        .collect();
+                    outcomes.sort_by(f64::total_cmp);
+                    let at = |p: f64| outcomes[(p * (trials - 1) as f64).round() as usize];
+                    let mean = outcomes.iter().sum::<f64>() / trials as f64;
+                    (mean, at(0.05), at(0.95))
+                }
+                false => {
+                    let value = report(item, item.assess(time));
+                    (value, value, value)
+                }
+            };
+            ItemRiskRow {
+                key,
+                name: book.name(key).map(str::to_string),
+                risky,
+                mean: Value::new(currency, mean),
+                p5: Value::new(currency, p5),
+                p95: Value::new(currency, p95),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| a.p5.amount().total_cmp(&b.p5.amount()));
+    rows
+}
+
+/// The first time from `from` to `to` that `a` is worth more than `b`, with
+/// `b` converted into the currency of `a`. `from` itself if `a` is already
+/// worth at least as much, so identical assets cross straight away. Fails
+/// if `b` cannot be converted.
+///
+/// Like [Book::when_reaches](super::book::Book::when_reaches) the assets are
+/// compared once a day, or once every `tolerance` if that is longer, to find
+/// the first step `a` is ahead in and then bisected until within
+/// `tolerance`. Leads that come and go within a step can be missed.
+pub fn crossover(
+    a: &impl Assesible,
+    b: &impl Assesible,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    tolerance: TimeDelta,
+) -> Result<Option<DateTime<Utc>>, ConversionError> {
+    let currency = a.currency();
+    let ahead = |time| -> Result<bool, ConversionError> {
+        let b = CURRENCY_EXCHANGE.try_convert(b.assess(time), currency)?;
+        Ok(a.assess(time).amount() > b.amount())
+    };
+    let b = CURRENCY_EXCHANGE.try_convert(b.assess(from), currency)?;
+    if a.assess(from).amount() >= b.amount() {
+        return Ok(Some(from));
+    }
+
+    let step = TimeDelta::days(1).max(tolerance);
+    let mut low = from;
+    let mut high = loop {
+        if low >= to {
+            return Ok(None);
+        }
+        let next = (low + step).min(to);
+        if ahead(next)? {
+            break next;
+        }
+        low = next;
+    };
+    while high - low > tolerance {
+        let mid = low + (high - low) / 2;
+        if ahead(mid)? {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Ok(Some(high))
+}
+
+/// How much of an item's change between two times each cause accounts for,
+/// see [Book::attribution]. Every part is in the [Book::report_currency]
+/// and together they add up to `total`.
+#[derive(Clone, Debug)]
+pub struct Attribution {
+    pub key: ItemKey,
+    pub name: Option<String>,
+    /// The change in the item's value, with its risks and any payouts
+    /// accrued but not yet paid.
+    pub total: Value,
+    /// Interest on the balance between deltas.
+    pub interest: Value,
+    /// Payments and every other delta made in the window.
+    pub deltas: Value,
+    /// The change in what prorated payouts have accrued.
+    pub payouts: Value,
+    /// The change from the exchange rate moving, zero for items in the
+    /// report currency.
+    pub fx: Value,
+    /// The change in what the attached risks take off or add.
+    pub risk: Value,
+    /// Whatever the rest do not explain, such as rounding each period,
+    /// capitalization schedules and leases.
+    pub residual: Value,
+}
+
+/// An item's own amounts at a time, in its currency.
+struct Snapshot {
+    /// The assessment plus prorated payouts.
+    native: f64,
+    /// The risked assessment plus payouts, in the report currency.
+    reported: f64,
+    rate: f64,
+}
+
+impl Book {
+    /// Splits the change of every item from `from` to `to` into interest,
+    /// deltas, payouts, exchange rate movement and risk. Interest, deltas and
+    /// payouts are converted at the rate at `to`, the exchange rate part is
+    /// the value at `from` times how much the rate moved. Panics if an item
+    /// cannot be converted into the report currency.
+    pub fn attribution(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Attribution> {
+        let base = self.currency();
+        self.iter_ordered()
+            .map(|(key, item)| {
+                let (start, end) = (self.snapshot(key, item, from), self.snapshot(key, item, to));
+                let (interest, deltas) = interest_and_deltas(item, from, to);
+                let payouts = (end.native - item.assess(to).amount())
+                    - (start.native - item.assess(from).amount());
+                let total = end.reported - start.reported;
+                let fx = start.native * (end.rate - start.rate);
+                let risk = (end.reported - end.native * end.rate)
+                    - (start.reported - start.native * start.rate);
+                let (interest, deltas, payouts) =
+                    (interest * end.rate, deltas * end.rate, payouts * end.rate);
+                let residual = total - interest - deltas - payouts - fx - risk;
+                Attribution {
+                    key,
+                    name: self.name(key).map(str::to_string),
+                    total: Value::new(base, total),
+                    interest: Value::new(base, interest),
+                    deltas: Value::new(base, deltas),
+                    payouts: Value::new(base, payouts),
+                    fx: Value::new(base, fx),
+                    risk: Value::new(base, risk),
+                    residual: Value::new(base, residual),
+                }
+            })
+            .collect()
+    }
+    fn snapshot(&self, key: ItemKey, item: &Item, time: DateTime<Utc>) -> Snapshot {
+        let currency = item.currency();
+        let report = |value: Value| {
+            self.item_report_rate(item, value.currency(), time)
+                .map(|rate| value.amount() * rate)
+                .unwrap_or_else(|e| panic!("{e}"))
+        };
+        let accrued: f64 = item
+            .payouts
+            .iter()
+            .filter_map(|payout| payout.accrued(time))
+            .map(|value| {
+                self.item_rate(item, value.currency(), currency, time)
+                    .map(|rate| value.amount() * rate)
+                    .unwrap_or_else(|e| panic!("{e}"))
+            })
+            .sum();
+        let rate = report(Value::new(currency, 1.0));
+        let risked = report(self.risky(key).unwrap().assess(time));
+        Snapshot {
+            native: item.assess(time).amount() + accrued,
+            reported: risked + accrued * rate,
+            rate,
+        }
+    }
+}
+
+/// The interest on the balance between the deltas made after `from` up to
+/// and including `to`, and what those deltas changed it by, in the item's
+/// currency.
+fn interest_and_deltas(item: &Item, from: DateTime<Utc>, to: DateTime<Utc>) -> (f64, f64) {
+    let currency = item.currency();
+    let growth = |start, end| item.interest().map_or(1.0, |i| i.factor(start, end));
+    let (mut balance, mut last) = (item.assess(from).amount(), from);
+    let (mut interest, mut deltas) = (0.0, 0.0);
+    for delta in item.deltas.iter().filter(|d| from < d.time && d.time <= to) {
+        let grown = balance * growth(last, delta.time);
+        let change = delta.resolve_amount(grown, currency);
+        interest += grown - balance;
+        deltas += change;
+        (balance, last) = (grown + change, delta.time);
+    }
+    interest += balance * (growth(last, to) - 1.0);
+    (interest, deltas)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        convert::{RatePolicy, CURRENCY_EXCHANGE},
+        delta::Delta,
+        item::Item,
+        period::Period,
+        risk::RiskSpec,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{crossover, risk_report};
+
+    /// 10k growing 1% a month against 1k growing 10% a month, which catch
+    /// up when 1.1^m / 1.01^m = 10, after m = 26.97 months.
+    #[test]
+    pub fn test_crossover_month() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        let expensive = Item::builder(Value::new("CAD", 10_000.0), start)
+            .interest(0.01, Period::Months(1))
+            .build();
+        let cheap = Item::builder(Value::new("CAD", 1_000.0), start)
+            .interest(0.10, Period::Months(1))
+            .build();
+
+        let tolerance = TimeDelta::minutes(1);
+        let crossed = crossover(&cheap, &expensive, start, end, tolerance)
+            .unwrap()
+            .unwrap();
+        let month = Period::Months(1);
+        assert!(month.step(start, 26) < crossed && crossed <= month.step(start, 27));
+        assert!(cheap.assess(crossed).amount() > expensive.assess(crossed).amount());
+        let before = crossed - tolerance;
+        assert!(cheap.assess(before).amount() < expensive.assess(before).amount());
+
+        // The expensive one is ahead from the start, falling behind later
+        // does not count.
+        assert_eq!(
+            crossover(&expensive, &cheap, start, end, tolerance),
+            Ok(Some(start))
+        );
+        assert_eq!(
+            crossover(&cheap, &expensive, start, month.step(start, 26), tolerance),
+            Ok(None)
+        );
+        assert_eq!(
+            crossover(&cheap, &cheap, start, end, tolerance),
+            Ok(Some(start))
+        );
+        let unknown = Item::fixed(Value::new("XCO", 1.0), start);
+        assert!(crossover(&cheap, &unknown, start, end, tolerance).is_err());
+    }
+
+    /// A balance that goes above and back below another crosses at the first.
+    #[test]
+    pub fn test_first_crossing() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let up = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let down = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let swinging = Item::builder(Value::new("CAD", 100.0), start)
+            .payment(up, Value::new("CAD", 200.0))
+            .payment(down, Value::new("CAD", -200.0))
+            .build();
+        let flat = Item::fixed(Value::new("CAD", 200.0), start);
+
+        let end = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let crossed = crossover(&swinging, &flat, start, end, TimeDelta::seconds(1))
+            .unwrap()
+            .unwrap();
+        assert!(crossed >= up && crossed - up <= TimeDelta::seconds(1));
+    }
+
+    /// A volatile fund worth more on average still ranks below a savings
+    /// account on its worst plausible case.
+    #[test]
+    pub fn test_risk_report() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let fund = book.add_named("Fund", Item::fixed(Value::new("CAD", 10_000.0), start));
+        let savings = book.add_named("Savings", Item::fixed(Value::new("CAD", 9_000.0), start));
+        assert!(book.attach_risk(
+            fund,
+            RiskSpec::NormalReturn {
+                mean: 0.05,
+                deviation: 0.2
+            }
+        ));
+
+        let rows = risk_report(&book, start, 2_000, 7);
+        assert_eq!(
+            rows.iter().map(|r| r.key).collect::<Vec<_>>(),
+            vec![fund, savings]
+        );
+        let (risky, safe) = (&rows[0], &rows[1]);
+        assert!(risky.risky && !safe.risky);
+        assert_eq!(risky.name.as_deref(), Some("Fund"));
+        assert_value_eq!(risky.mean, Value::new("CAD", 10_500.0), 150.0);
+        assert!(risky.p5.amount() < 9_000.0 && risky.p95.amount() > 12_000.0);
+        assert_eq!(safe.p5.amount(), safe.mean.amount());
+        assert_eq!(safe.p95.amount(), 9_000.0);
+
+        // The same seed gives the same report.
+        assert_eq!(
+            risk_report(&book, start, 2_000, 7)[0].p5.amount(),
+            risky.p5.amount()
+        );
+        assert_eq!(book.detach_risks(fund).len(), 1);
+        assert_eq!(
+            risk_report(&book, start, 2_000, 7)[1].mean.amount(),
+            10_000.0
+        );
+    }
+
+    /// A US dollar account only moves with the rate, a chequing account
+    /// earning interest only with a payment and its interest.
+    #[test]
+    pub fn test_attribution() {
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("ATU", "ATC", on(1, 1), 1.30);
+        CURRENCY_EXCHANGE.add_conversion_on("ATU", "ATC", on(2, 1), 1.40);
+        let mut book = Book::default();
+        book.set_reporting("ATC", RatePolicy::RateAtAssessmentDate);
+        let dollars = book.add_named("Dollars", Item::fixed(Value::new("ATU", 1_000.0), on(1, 1)));
+        let chequing = book.add(
+            Item::builder(Value::new("ATC", 5_000.0), on(1, 1))
+                .interest(0.01, Period::Months(1))
+                .delta(Delta::payment(on(1, 20), Value::new("ATC", -800.0)))
+                .build(),
+        );
+
+        let (from, to) = (on(1, 10), on(2, 10));
+        let parts = book.attribution(from, to);
+        assert_eq!(parts.len(), 2);
+        let usd = parts.iter().find(|a| a.key == dollars).unwrap();
+        assert_eq!(usd.name.as_deref(), Some("Dollars"));
+        assert_value_eq!(usd.total, Value::new("ATC", 100.0), 1e-9);
+        assert_value_eq!(usd.fx, Value::new("ATC", 100.0), 1e-9);
+        for part in [&usd.interest, &usd.deltas, &usd.payouts, &usd.risk] {
+            assert_eq!(part.amount(), 0.0);
+        }
+
+        let cheq = parts.iter().find(|a| a.key == chequing).unwrap();
+        assert_eq!(cheq.deltas.amount(), -800.0);
+        assert_eq!(cheq.fx.amount(), 0.0);
+        let item = book.get(chequing).unwrap();
+        let change = item.assess(to).amount() - item.assess(from).amount();
+        assert_value_eq!(cheq.total, Value::new("ATC", change), 1e-9);
+        assert_value_eq!(cheq.interest, Value::new("ATC", change + 800.0), 1e-9);
+        assert_value_eq!(cheq.residual, Value::new("ATC", 0.0), 1e-9);
+
+        // The items add up to the change of the book.
+        let total: f64 = parts.iter().map(|a| a.total.amount()).sum();
+        let moved = book.assess(to).amount() - book.assess(from).amount();
+        assert_value_eq!(Value::new("ATC", total), Value::new("ATC", moved), 1e-9);
+    }
+}
@@ -0,0 +1,291 @@
+//! Proptest strategies for generating valid instruments.
+//!
+//! Amounts, rates and dates are bounded so assessments stay finite. Items
+//! that are summed together should share a currency, [item_in] and [book_in]
+//! generate those, the [Arbitrary] impls pick currencies at random.
+//! [mixed_book_in] mixes currencies, which then need rates between them.
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    option, prop_oneof,
+    sample::select,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+
+use super::{
+    book::Book,
+    delta::{Delta, DeltaAmount, DeltaKind},
+    item::{Interest, Item},
+    value::{Currency, Value},
+};
+
+const CURRENCIES: &[&str] = &["CAD", "USD", "EUR", "COP"];
+
+/// A time between 2000 and 2030.
+pub fn time() -> impl Strategy<Value = DateTime<Utc>> {
+    let start = Utc
+        .with_ymd_and_hms(2000, 1, 1, 0, 0, 0)
+        .unwrap()
+        .timestamp();
+    let end = Utc
+        .with_ymd_and_hms(2030, 1, 1, 0, 0, 0)
+        .unwrap()
+        .timestamp();
+    (start..end).prop_map(|t| Utc.timestamp_opt(t, 0).unwrap())
+}
+
+/// A value in the currency with an amount within a million either way.
+pub fn value_in(currency: Currency) -> impl Strategy<Value = Value> {
+    (-1e6..1e6).prop_map(move |amount: f64| Value::new(currency, amount))
+}
+
+/// An item with up to eight deltas in the currency.
+pub fn item_in(currency: Currency) -> impl Strategy<Value = Item> {
+    (
+        value_in(currency),
+        option::of(any::<Interest>()),
+        time(),
+        vec(delta_in(currency), 0..8),
+    )
+        .prop_map(|(value, interest, inception, deltas)| {
+            let mut item = Item::fixed(value, inception);
+            item.interest = interest;
+            item.import_deltas(deltas);
+            item
+        })
+}
+
+/// A delta in the currency, percentages stay within 50% either way.
+pub fn delta_in(currency: Currency) -> impl Strategy<Value = Delta> {
+    let amount = prop_oneof![
+        value_in(currency).prop_map(DeltaAmount::Fixed),
+        (-0.5..0.5).prop_map(DeltaAmount::Percent),
+    ];
+    let kind = select(
+        &[
+            DeltaKind::Payment,
+            DeltaKind::Fee,
+            DeltaKind::Adjustment,
+            DeltaKind::Interest,
+        ][..],
+    );
+    (time(), amount, kind).prop_map(|(time, amount, kind)| Delta::new(time, amount, kind))
+}
+
+/// A book of up to `max_items` items in the currency. Every item may be
+/// the child of one added before it, so the structure is always a forest.
+pub fn book_in(currency: Currency, max_items: usize) -> impl Strategy<Value = Book> {
+    book_of(item_in(currency), max_items)
+}
+
+/// Like [book_in] with every item in one of the currencies.
+pub fn mixed_book_in(currencies: Vec<Currency>, max_items: usize) -> impl Strategy<Value = Book> {
+    book_of(select(currencies).prop_flat_map(item_in), max_items)
+}
+
+fn book_of(item: impl Strategy<Value = Item>, max_items: usize) -> impl Strategy<Value = Book> {
+    vec(
+        (item, option::of(any::<proptest::sample::Index>())),
+        1..=max_items.max(1),
+    )
+    .prop_map(|items| {
+        let mut book = Book::default();
+        let mut keys = vec![];
+        for (item, parent) in items {
+            let key = book.add(item);
+            if let Some(parent) = parent.filter(|_| !keys.is_empty()) {
+                book.link_child(*parent.get(&keys), key);
+            }
+            keys.push(key);
+        }
+        book.take_audit_log();
+        book
+    })
+}
+
+impl Arbitrary for Currency {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Currency>;
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        select(CURRENCIES).prop_map(Currency::new).boxed()
+    }
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<Currency>().prop_flat_map(value_in).boxed()
+    }
+}
+
+impl Arbitrary for Interest {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Interest>;
+    /// Yearly rates from 0% to 50% compounding daily to yearly, the rate is
+    /// scaled to the period so shorter periods do not explode.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (0.0..0.5, prop_oneof![Just(1), Just(30), Just(365)])
+            .prop_map(|(rate, days)| {
+                Interest::new(rate * days as f64 / 365.0, TimeDelta::days(days))
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Delta {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Delta>;
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<Currency>().prop_flat_map(delta_in).boxed()
+    }
+}
+
+impl Arbitrary for Item {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Item>;
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<Currency>().prop_flat_map(item_in).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use chrono::TimeDelta;
+    use proptest::{
+        arbitrary::any, collection::vec, prop_assert, prop_assume, proptest, test_runner::Config,
+    };
+
+    use crate::instruments::{
+        convert::{ConversionTable, Exchange, CURRENCY_EXCHANGE},
+        item::Item,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{book_in, mixed_book_in, time, value_in};
+
+    /// Currencies only these tests use and what one unit is worth in the
+    /// first, so the rates between them are consistent.
+    const MIXED: [(&str, f64); 3] = [("PTA", 1.0), ("PTB", 1.25), ("PTC", 0.004)];
+
+    fn mixed() -> Vec<Currency> {
+        static RATES: Once = Once::new();
+        RATES.call_once(|| {
+            for (i, (from, worth)) in MIXED.iter().enumerate() {
+                for (to, other) in &MIXED[i + 1..] {
+                    CURRENCY_EXCHANGE.add_conversion(*from, *to, worth / other);
+                }
+            }
+        });
+        MIXED.iter().map(|(code, _)| Currency::new(code)).collect()
+    }
+
+    proptest! {
+        #![proptest_config(Config::with_cases(64))]
+
+        /// A book is worth what its items are worth on their own, each
+        /// converted into the currency of the book, and so is a plain list
+        /// of the items.
+        #[test]
+        fn test_book_is_converted_sum(book in mixed_book_in(mixed(), 8), at in time()) {
+            let base = book.currency();
+            let converted: Vec<f64> = book
+                .iter_ordered()
+                .map(|(_, item)| {
+                    let rate = CURRENCY_EXCHANGE.rate(item.currency(), base).unwrap();
+                    item.assess(at).amount() * rate
+                })
+                .collect();
+            let sum: f64 = converted.iter().sum();
+            let scale = converted.iter().map(|a| a.abs()).sum::<f64>().max(1.0);
+            let sum = Value::new(base, sum);
+            prop_assert!(book.assess(at).approx_eq(&sum, 1e-9 * scale));
+
+            let items: Vec<Item> = book.iter_ordered().map(|(_, item)| item.clone()).collect();
+            let listed = items.assess(at);
+            prop_assert!(listed.currency() == base);
+            prop_assert!(listed.approx_eq(&sum, 1e-9 * scale));
+        }
+
+        /// Converting around a loop of consistent rates comes back to the
+        /// same value, through a table and through a rate snapshot.
+        #[test]
+        fn test_round_trip_conversion(
+            value in value_in(Currency::new("RTA")),
+            ab in 1e-4..1e4f64,
+            bc in 1e-4..1e4f64,
+        ) {
+            let [a, b, c] = ["RTA", "RTB", "RTC"].map(Currency::new);
+            let table = ConversionTable::new();
+            let exchange = Exchange::new();
+            for (from, to, rate) in [(a, b, ab), (b, c, bc), (c, a, (ab * bc).recip())] {
+                table.add_conversion(from, to, rate);
+                exchange.add_conversion(from, to, rate);
+            }
+            let tolerance = 1e-9 * value.amount().abs().max(1.0);
+
+            let there = table.try_convert(value.clone(), b).unwrap();
+            let back = table.try_convert(there.clone(), a).unwrap();
+            prop_assert!(back.approx_eq(&value, tolerance));
+            let around = table.try_convert(table.try_convert(there, c).unwrap(), a).unwrap();
+            prop_assert!(around.approx_eq(&value, tolerance));
+
+            let attached = value.rebased(&exchange.snapshot());
+            let around = attached.convert_to(b).and_then(|v| v.convert_to(c));
+            let around = around.and_then(|v| v.convert_to(a)).unwrap();
+            prop_assert!(around.approx_eq(&value, tolerance));
+            let both = [attached.clone(), attached.convert_to(c).unwrap()];
+            let sum: Value = both.iter().sum::<Option<Value>>().unwrap();
+            prop_assert!(sum.approx_eq(&(&value * 2.0), 2.0 * tolerance));
+        }
+
+        /// Only deltas move an item at once, in between it changes by no
+        /// more than a second of interest.
+        #[test]
+        fn test_assess_is_continuous(item in any::<Item>(), at in time()) {
+            let next = at + TimeDelta::seconds(1);
+            prop_assume!(!item.deltas.iter().any(|d| at < d.time && d.time <= next));
+            let (now, then) = (item.assess(at), item.assess(next));
+            let tolerance = 1e-6 * now.amount().abs().max(1.0);
+            prop_assert!(then.approx_eq(&now, tolerance), "{now} {then}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_debt_grows_over_time(
+            value in value_in(Currency::new("CAD")),
+            interest in any::<crate::instruments::item::Interest>(),
+            inception in time(),
+            days in 0..3650i64,
+            later in 0..3650i64,
+        ) {
+            let mut debt = Item::fixed(value, inception);
+            debt.interest = Some(interest);
+            let first = debt.assess(inception + TimeDelta::days(days)).amount();
+            let second = debt.assess(inception + TimeDelta::days(days + later)).amount();
+            prop_assert!(second.abs() >= first.abs() * (1.0 - 1e-12));
+            prop_assert!(second.signum() == first.signum() || first == 0.0);
+        }
+
+        #[test]
+        fn test_assess_at_many_matches_assess(item in any::<Item>(), times in vec(time(), 0..16)) {
+            let each: Vec<f64> = times.iter().map(|t| item.assess(*t).amount()).collect();
+            let many: Vec<f64> = item.assess_at_many(&times).iter().map(|v| v.amount()).collect();
+            prop_assert!(many == each);
+        }
+
+        #[test]
+        fn test_book_is_sum_of_items(book in book_in(Currency::new("CAD"), 8), at in time()) {
+            let sum: f64 = book.iter().map(|(_, item)| item.assess(at).amount()).sum();
+            let scale = book.iter().map(|(_, item)| item.assess(at).amount().abs()).sum::<f64>();
+            let sum = Value::new("CAD", sum);
+            prop_assert!(book.assess(at).approx_eq(&sum, 1e-9 * scale.max(1.0)));
+        }
+    }
+}
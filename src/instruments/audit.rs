@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    book::ItemKey,
+    convert::RatePolicy,
+    delta::Delta,
+    groups::GroupId,
+    item::{Interest, Item},
+    risk::RiskSpec,
+    transfer::{Transfer, TransferId},
+    value::Currency,
+};
+
+/// A record of a mutation made through the [super::book::Book], these are kept
+/// in the order they happened so other systems can follow along and a book
+/// can be rebuilt from them with [Book::replay](super::book::Book::replay).
+/// Items are shared with the book rather than copied, an item is only
+/// copied once it is changed after being logged.
+#[derive(Clone, Debug)]
+pub enum AuditEntry {
+    ItemAdded {
+        key: ItemKey,
+        name: Option<String>,
+        item: Arc<Item>,
+    },
+    ItemRemoved {
+        key: ItemKey,
+    },
+    /// The item was changed through [super::book::Book::get_mut] or
+    /// [super::book::Book::bulk_edit], `item` is what it was changed into.
+    ItemReplaced {
+        key: ItemKey,
+        item: Arc<Item>,
+    },
+    ChildAdded {
+        parent: ItemKey,
+        child: ItemKey,
+    },
+    ChildRemoved {
+        parent: ItemKey,
+        child: ItemKey,
+    },
+    ExternalIdSet {
+        key: ItemKey,
+        id: String,
+    },
+    DeltaAdded {
+        key: ItemKey,
+        delta: Delta,
+    },
+    DeltaRemoved {
+        key: ItemKey,
+        delta: Delta,
+    },
+    /// The item was given a name, or had it taken away with `None`.
+    Renamed {
+        key: ItemKey,
+        name: Option<String>,
+    },
+    /// An [ImportPlan](super::import::ImportPlan) was applied, the entries
+    /// just before this one are what it added.
+    ImportApplied {
+        items: usize,
+        deltas: usize,
+        duplicates: usize,
+    },
+    /// Every amount in `old` was rewritten in `new`, divided by `factor`.
+    CurrencyMigrated {
+        old: Currency,
+        new: Currency,
+        factor: f64,
+    },
+    /// A transfer was made, the entries just before this one added its
+    /// deltas.
+    Transferred {
+        transfer: Transfer,
+    },
+    /// A transfer was taken back, the entries just before this one removed
+    /// its deltas.
+    Untransferred {
+        id: TransferId,
+    },
+    /// The item was put just before `before` in
+    /// [Book::ordered_keys](super::book::Book::ordered_keys).
+    Reordered {
+        key: ItemKey,
+        before: ItemKey,
+    },
+    GroupCreated {
+        id: GroupId,
+        name: String,
+    },
+    GroupCurrencySet {
+        id: GroupId,
+        currency: Currency,
+    },
+    /// The item was put into the group, the default group ungroups it.
+    Assigned {
+        key: ItemKey,
+        group: GroupId,
+    },
+    RiskAttached {
+        key: ItemKey,
+        risk: RiskSpec,
+    },
+    RisksDetached {
+        key: ItemKey,
+    },
+    Frozen {
+        key: ItemKey,
+        at: DateTime<Utc>,
+    },
+    ReportingSet {
+        currency: Currency,
+        policy: RatePolicy,
+    },
+    /// The interest cash in the currency earns, taken away with `None`.
+    CashInterestSet {
+        currency: Currency,
+        interest: Option<Interest>,
+    },
+    PrecisionToleranceSet {
+        tolerance: Option<f64>,
+    },
+}
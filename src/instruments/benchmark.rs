@@ -0,0 +1,153 @@
+//! Benchmark rates such as prime that variable rate interest is priced off,
+//! see [Interest::indexed](super::item::Interest::indexed).
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+
+/// The benchmarks indexed interest reads its rates from.
+pub static BENCHMARKS: Lazy<BenchmarkTable> = Lazy::new(BenchmarkTable::new);
+
+/// Identifies a benchmark in a [BenchmarkTable].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BenchmarkId(u64);
+
+impl BenchmarkId {
+    /// Hands out a fresh identifier.
+    fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A named series of rates, each in effect from its date until the next.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Benchmark {
+    pub name: String,
+    /// Sorted by date, at most one rate per date.
+    rates: Vec<(DateTime<Utc>, f64)>,
+}
+
+impl Benchmark {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            rates: vec![],
+        }
+    }
+    /// Adds a rate taking effect on the date.
+    pub fn with_rate(mut self, on: DateTime<Utc>, rate: f64) -> Self {
+        self.set_rate(on, rate);
+        self
+    }
+    /// Sets the rate taking effect on the date, replacing any rate already
+    /// set for that date.
+    pub fn set_rate(&mut self, on: DateTime<Utc>, rate: f64) {
+        match self.rates.binary_search_by_key(&on, |(t, _)| *t) {
+            Ok(index) => self.rates[index].1 = rate,
+            Err(index) => self.rates.insert(index, (on, rate)),
+        }
+    }
+    pub fn rates(&self) -> &[(DateTime<Utc>, f64)] {
+        &self.rates
+    }
+    /// The most recent rate at the time. Before the first rate that one
+    /// applies, `None` if the series is empty.
+    pub fn rate_at(&self, time: DateTime<Utc>) -> Option<f64> {
+        let after = self.rates.partition_point(|(t, _)| *t <= time);
+        self.rates
+            .get(after.saturating_sub(1))
+            .map(|(_, rate)| *rate)
+    }
+    /// The dates the rate changes on strictly between the two times.
+    pub fn changes(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        self.rates
+            .iter()
+            .map(|(t, _)| *t)
+            .filter(move |t| from < *t && *t < to)
+    }
+}
+
+/// Benchmarks by their identifier.
+#[derive(Debug, Default)]
+pub struct BenchmarkTable {
+    series: RwLock<HashMap<BenchmarkId, Benchmark>>,
+}
+
+impl BenchmarkTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds the benchmark under a fresh identifier.
+    pub fn register(&self, benchmark: Benchmark) -> BenchmarkId {
+        let id = BenchmarkId::next();
+        self.series.write().unwrap().insert(id, benchmark);
+        id
+    }
+    /// Sets a rate on a registered benchmark, assessments after the date
+    /// change from then on. `false` if there is no such benchmark.
+    pub fn set_rate(&self, id: BenchmarkId, on: DateTime<Utc>, rate: f64) -> bool {
+        match self.series.write().unwrap().get_mut(&id) {
+            Some(benchmark) => {
+                benchmark.set_rate(on, rate);
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn get(&self, id: BenchmarkId) -> Option<Benchmark> {
+        self.series.read().unwrap().get(&id).cloned()
+    }
+    /// Reads the benchmark without copying it out of the table.
+    pub fn with<T>(&self, id: BenchmarkId, read: impl FnOnce(&Benchmark) -> T) -> Option<T> {
+        self.series.read().unwrap().get(&id).map(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{Benchmark, BenchmarkTable};
+
+    #[test]
+    pub fn test_benchmark_rates() {
+        let on = |m| Utc.with_ymd_and_hms(2025, m, 1, 0, 0, 0).unwrap();
+        let prime = Benchmark::new("Prime")
+            .with_rate(on(6), 0.05)
+            .with_rate(on(1), 0.055)
+            .with_rate(on(6), 0.0525);
+        assert_eq!(prime.rates(), [(on(1), 0.055), (on(6), 0.0525)]);
+        assert_eq!(prime.rate_at(on(1)), Some(0.055));
+        assert_eq!(prime.rate_at(on(5)), Some(0.055));
+        assert_eq!(prime.rate_at(on(6)), Some(0.0525));
+        // Before the first rate it applies anyway.
+        assert_eq!(
+            prime.rate_at(on(1) - chrono::TimeDelta::days(1)),
+            Some(0.055)
+        );
+        assert_eq!(Benchmark::new("Empty").rate_at(on(1)), None);
+        assert_eq!(prime.changes(on(1), on(6)).count(), 0);
+        assert_eq!(prime.changes(on(2), on(7)).collect::<Vec<_>>(), [on(6)]);
+
+        let table = BenchmarkTable::new();
+        let id = table.register(prime);
+        assert!(table.set_rate(id, on(9), 0.0475));
+        assert_eq!(table.with(id, |b| b.rate_at(on(10))), Some(Some(0.0475)));
+        assert_eq!(table.get(id).unwrap().name, "Prime");
+    }
+}
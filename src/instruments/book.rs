@@ -1,38 +1,957 @@
-use std::borrow::Borrow;
+use std::{collections::HashMap, ops::{Deref, DerefMut}, sync::Arc};
 
-use chrono::{DateTime, Duration, NaiveDateTime, TimeDelta, TimeZone, Utc};
-use slotmap::SlotMap;
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::RngCore;
+use slotmap::{SecondaryMap, SlotMap};
 
-use super::{item::Item, value::{Currency, Value}, Assesible};
+use super::{audit::AuditEntry, convert::{ConversionError, RatePolicy, CURRENCY_EXCHANGE}, delta::{AssessMode, Delta, DeltaAmount, DeltaId, DeltaStatus}, events::{BookEvent, Subscribers}, groups::{Group, GroupId}, item::{Interest, Item, Payout}, period::Period, risk::RiskSpec, transfer::Transfer, undo::{Change, History}, value::{Currency, KahanSum, MultiCurrencySum, PrecisionWarning, Value, PRECISION_TOLERANCE}, Assesible};
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct Book {
-    entries: SlotMap<ItemKey, Item>,
+    /// Shared with clones of the book until either changes the item.
+    entries: SlotMap<ItemKey, Arc<Item>>,
+    names: SecondaryMap<ItemKey, String>,
+    external_ids: SecondaryMap<ItemKey, String>,
+    /// How far the book has been synced with a [super::sync::DeltaSource].
+    pub(crate) sync_cursor: Option<super::sync::Cursor>,
+    /// Synced deltas whose item was not in the book, see [Book::sync_pending].
+    pub(crate) sync_pending: Vec<(ItemRef, Delta)>,
+    pub(crate) audit: Vec<AuditEntry>,
+    /// How many audit entries came before the first one in `audit`, handed
+    /// over by [Book::take_audit_log] or made before the book was cloned.
+    pub(crate) audit_offset: u64,
+    /// The currency reports are in and the rates they convert at.
+    reporting: Option<(Currency, RatePolicy)>,
+    pub(crate) transfers: Vec<Transfer>,
+    pub(crate) subscribers: Subscribers,
+    /// Set by [Book::undoable].
+    pub(crate) history: Option<History>,
+    pub(crate) groups: SlotMap<GroupId, Group>,
+    /// The group each item is in, ungrouped items have no entry.
+    pub(crate) membership: SecondaryMap<ItemKey, GroupId>,
+    /// Set by [Book::attach_risk].
+    pub(crate) risks: SecondaryMap<ItemKey, Vec<RiskSpec>>,
+    /// Set by [Book::freeze].
+    pub(crate) frozen: SecondaryMap<ItemKey, DateTime<Utc>>,
+    /// Where each item sits in [Book::ordered_keys], handed out in
+    /// increasing order as items are added.
+    sequence: SecondaryMap<ItemKey, u64>,
+    next_sequence: u64,
+    /// Set by [Book::set_cash_interest].
+    pub(crate) cash_interest: HashMap<Currency, Interest>,
+    /// Set by [Book::set_precision_tolerance].
+    pub(crate) precision_tolerance: Option<f64>,
+}
+
+/// A fork of the book for trying out a scenario. Items are shared with the
+/// original until either side changes them through [Book::get_mut] or any
+/// other mutation, so cloning is cheap however many deltas the items have.
+/// The clone starts without subscribers, with an empty audit log that goes
+/// on from the original's [Book::event_seq] and, if the book is undoable,
+/// with nothing to undo.
+impl Clone for Book {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            names: self.names.clone(),
+            external_ids: self.external_ids.clone(),
+            sync_cursor: self.sync_cursor,
+            sync_pending: self.sync_pending.clone(),
+            audit: vec![],
+            audit_offset: self.event_seq(),
+            reporting: self.reporting,
+            transfers: self.transfers.clone(),
+            subscribers: Subscribers::default(),
+            history: self.history.as_ref().map(History::emptied),
+            groups: self.groups.clone(),
+            membership: self.membership.clone(),
+            risks: self.risks.clone(),
+            frozen: self.frozen.clone(),
+            sequence: self.sequence.clone(),
+            next_sequence: self.next_sequence,
+            cash_interest: self.cash_interest.clone(),
+            precision_tolerance: self.precision_tolerance,
+        }
+    }
+}
+
+/// An assessment alongside a warning when the float total may be mostly
+/// rounding error, see [Book::try_assess_diagnosed].
+#[derive(Clone, Debug)]
+pub struct Assessed {
+    pub value: Value,
+    pub warning: Option<PrecisionWarning>,
+}
+
+/// Something an item has scheduled, see [Book::upcoming].
+#[derive(Clone, Debug)]
+pub enum Upcoming {
+    /// A payout, negative when it leaves cash.
+    Payout(Value),
+    /// The end of a lease, when the asset can be bought for the residual.
+    Buyout(Value),
+    /// The payment settling an [Item::interest_only_loan] at maturity.
+    Balloon(Value),
+}
+
+/// Refers to an item in a way that makes sense outside of the process.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ItemRef {
+    /// The name given to the item in the book.
+    Name(String),
+    /// An identifier of the item in some external system.
+    External(String),
+}
+
+/// An item borrowed from the book by [Book::get_mut]. The item is copied
+/// the first time it is changed, and audited when the borrow ends if it
+/// was.
+pub struct ItemMut<'a> {
+    book: &'a mut Book,
+    key: ItemKey,
+    /// The item before the borrow, shared with the book until it changes.
+    before: Arc<Item>,
+}
+
+impl Deref for ItemMut<'_> {
+    type Target = Item;
+
+    fn deref(&self) -> &Item {
+        &self.book.entries[self.key]
+    }
+}
+
+impl DerefMut for ItemMut<'_> {
+    fn deref_mut(&mut self) -> &mut Item {
+        Arc::make_mut(&mut self.book.entries[self.key])
+    }
+}
+
+impl Drop for ItemMut<'_> {
+    fn drop(&mut self) {
+        if !Arc::ptr_eq(&self.book.entries[self.key], &self.before) {
+            self.book.replaced(self.key, self.before.clone());
+        }
+    }
 }
 
 impl Book {
     /// Adds a new item to the book.
     pub fn add(&mut self, item: Item) -> ItemKey {
-        self.entries.insert(item)
+        let item = Arc::new(item);
+        let key = self.entries.insert(item.clone());
+        self.sequence_last(key);
+        self.audit.push(AuditEntry::ItemAdded { key, name: None, item });
+        self.subscribers.notify(&BookEvent::ItemAdded { key });
+        self.record(Change::Added { key });
+        key
+    }
+    /// Adds a new item to the book under a name.
+    pub fn add_named(&mut self, name: impl Into<String>, item: Item) -> ItemKey {
+        let name = name.into();
+        let item = Arc::new(item);
+        let key = self.entries.insert(item.clone());
+        self.sequence_last(key);
+        self.names.insert(key, name.clone());
+        self.audit.push(AuditEntry::ItemAdded { key, name: Some(name), item });
+        self.subscribers.notify(&BookEvent::ItemAdded { key });
+        self.record(Change::Added { key });
+        key
+    }
+    /// Adds a delta to an item, returning `None` if the item does not exist.
+    pub fn add_delta(&mut self, key: ItemKey, delta: Delta) -> Option<DeltaId> {
+        let id = delta.id;
+        self.item_mut(key)?.push_delta(delta.clone());
+        self.audit.push(AuditEntry::DeltaAdded { key, delta: delta.clone() });
+        self.subscribers.notify(&BookEvent::DeltaAdded { item: key, delta_id: id });
+        self.record(Change::DeltaAdded { key, delta });
+        Some(id)
+    }
+    /// Adds a delta with a fixed amount in another currency than the item,
+    /// capturing the rate it converts into the item's currency at as
+    /// [Delta::rate]. Without `rate` (or one already on the delta) the
+    /// item's [Item::fx_override] is used, or else the dated rate on the
+    /// delta's day. `Ok(None)` if the item is not in the book.
+    pub fn add_delta_converted(
+        &mut self,
+        key: ItemKey,
+        mut delta: Delta,
+        rate: Option<f64>,
+    ) -> Result<Option<DeltaId>, ConversionError> {
+        let Some(item) = self.get(key) else {
+            return Ok(None);
+        };
+        if let DeltaAmount::Fixed(value) = &delta.amount {
+            let (from, to) = (value.currency(), item.currency());
+            if from != to {
+                let captured = match rate.or(delta.rate) {
+                    Some(rate) => rate,
+                    None => item.conversion_rate(from, delta.time)?,
+                };
+                delta.rate = Some(captured);
+            }
+        }
+        Ok(self.add_delta(key, delta))
+    }
+    /// Adds an item along with its deltas, which are sorted once. This is a
+    /// single [BookEvent::ItemAdded] where adding each delta would be one
+    /// event and one sort per delta.
+    pub fn add_item_with_deltas(&mut self, mut item: Item, deltas: Vec<Delta>) -> ItemKey {
+        item.import_deltas(deltas);
+        self.add(item)
+    }
+    /// Edits an item in one go, such as loading a statement of deltas. The
+    /// deltas are sorted once `edit` returns, then the edit is audited as a
+    /// single [AuditEntry::ItemReplaced] and [BookEvent::ItemEdited] and
+    /// undone as one step, like [Book::get_mut]. `false` if the item is not
+    /// in the book.
+    pub fn bulk_edit(&mut self, key: ItemKey, edit: impl FnOnce(&mut Item)) -> bool {
+        let Some(mut item) = self.get_mut(key) else {
+            return false;
+        };
+        edit(&mut item);
+        item.sort_deltas();
+        true
+    }
+    /// Turns a planned delta into an actual one, as one undo step. Returns
+    /// `false` if the item has no such planned delta.
+    pub fn confirm_delta(&mut self, key: ItemKey, id: DeltaId) -> bool {
+        let Some(delta) = self.item_mut(key).and_then(|item| {
+            item.deltas
+                .iter_mut()
+                .find(|d| d.id == id && d.status == DeltaStatus::Planned)
+        }) else {
+            return false;
+        };
+        let planned = delta.clone();
+        delta.status = DeltaStatus::Actual;
+        let actual = delta.clone();
+        self.grouped(|book| {
+            book.audit.push(AuditEntry::DeltaRemoved { key, delta: planned.clone() });
+            book.record(Change::DeltaRemoved { key, delta: planned });
+            book.audit.push(AuditEntry::DeltaAdded { key, delta: actual.clone() });
+            book.record(Change::DeltaAdded { key, delta: actual });
+        });
+        self.subscribers.notify(&BookEvent::ItemEdited { key });
+        true
+    }
+    /// Takes a delta off an item, returning it if it was there.
+    pub fn remove_delta(&mut self, key: ItemKey, id: DeltaId) -> Option<Delta> {
+        let deltas = &mut self.item_mut(key)?.deltas;
+        let delta = deltas.remove(deltas.iter().position(|d| d.id == id)?);
+        self.audit.push(AuditEntry::DeltaRemoved { key, delta: delta.clone() });
+        self.subscribers.notify(&BookEvent::DeltaRemoved { item: key, delta_id: id });
+        self.record(Change::DeltaRemoved { key, delta: delta.clone() });
+        Some(delta)
+    }
+    /// Takes an item out of the book, unlinking it from its parent. Its
+    /// children stay in the book without a parent.
+    pub fn remove(&mut self, key: ItemKey) -> Option<Item> {
+        self.get(key)?;
+        let parent = self.parent(key);
+        if let Some(parent) = parent {
+            self.unlink_child(parent, key);
+        }
+        let item = Arc::unwrap_or_clone(self.entries.remove(key)?);
+        let name = self.names.remove(key);
+        let external_id = self.external_ids.remove(key);
+        self.membership.remove(key);
+        self.risks.remove(key);
+        self.frozen.remove(key);
+        let sequence = self.sequence.remove(key).unwrap_or(self.next_sequence);
+        self.audit.push(AuditEntry::ItemRemoved { key });
+        self.subscribers.notify(&BookEvent::ItemRemoved { key });
+        self.record(Change::Removed {
+            key,
+            item: item.clone(),
+            name,
+            external_id,
+            parent,
+            sequence,
+        });
+        Some(item)
+    }
+    /// Puts a removed item back with everything [Book::remove] took away,
+    /// including its place in [Book::ordered_keys].
+    pub(crate) fn restore(
+        &mut self,
+        mut item: Item,
+        name: Option<String>,
+        external_id: Option<String>,
+        parent: Option<ItemKey>,
+        sequence: u64,
+    ) -> ItemKey {
+        item.children = item
+            .children
+            .iter()
+            .filter_map(|child| self.translate(*child))
+            .collect();
+        let item = Arc::new(item);
+        let key = self.entries.insert(item.clone());
+        self.sequence_at(key, sequence);
+        if let Some(name) = &name {
+            self.names.insert(key, name.clone());
+        }
+        self.audit.push(AuditEntry::ItemAdded { key, name, item });
+        let ordered = self.ordered_keys();
+        if let Some(&before) = ordered.iter().skip_while(|k| **k != key).nth(1) {
+            self.audit.push(AuditEntry::Reordered { key, before });
+        }
+        self.subscribers.notify(&BookEvent::ItemAdded { key });
+        self.record(Change::Added { key });
+        if let Some(id) = external_id {
+            self.set_external_id(key, id);
+        }
+        if let Some(parent) = parent {
+            self.link_child(parent, key);
+        }
+        key
+    }
+    /// Every mutation made through the book since it was created or
+    /// the log was last taken.
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+    /// Empties the audit log, handing over the entries.
+    pub fn take_audit_log(&mut self) -> Vec<AuditEntry> {
+        self.audit_offset += self.audit.len() as u64;
+        std::mem::take(&mut self.audit)
+    }
+    /// The value of the book at every `step` from `from` up to and including `to`.
+    pub fn series(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        step: impl Into<Period>,
+    ) -> Vec<(DateTime<Utc>, Value)> {
+        let step = step.into();
+        assert!(step.step(from, 1) > from, "series step must be positive");
+        let mut series = vec![];
+        for count in 0.. {
+            let time = step.step(from, count);
+            if time > to {
+                break;
+            }
+            series.push((time, self.assess(time)));
+        }
+        series
+    }
+    /// What every item has scheduled after `from` up to and including `to`,
+    /// in order of time.
+    pub fn upcoming(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<(DateTime<Utc>, ItemKey, Upcoming)> {
+        let mut upcoming = vec![];
+        for (key, item) in self.iter_ordered() {
+            for (time, value) in item.payouts.iter().flat_map(|p| p.occurrences(item.inception, to)) {
+                if time > from {
+                    upcoming.push((time, key, Upcoming::Payout(value)));
+                }
+            }
+            if let Some(lease) = &item.lease {
+                let time = lease.buyout_date();
+                if time > from && time <= to {
+                    upcoming.push((time, key, Upcoming::Buyout(lease.residual.clone())));
+                }
+            }
+            let balloon = item.deltas.iter().find(|d| Some(d.id) == item.balloon);
+            if let Some(delta) = balloon.filter(|d| d.time > from && d.time <= to) {
+                let value = delta.resolve(&item.book_value);
+                upcoming.push((delta.time, key, Upcoming::Balloon(value)));
+            }
+        }
+        upcoming.sort_by_key(|(time, _, _)| *time);
+        upcoming
+    }
+    /// The first time from `from` to `horizon` the book reaches `target`,
+    /// that is when its value minus the target changes sign. The book is
+    /// checked once a day to find the first day it crosses and then
+    /// bisected to the second, so crossings that undo themselves within a
+    /// day can be missed. Panics without a rate, see
+    /// [Book::try_when_reaches].
+    pub fn when_reaches(
+        &self,
+        target: Value,
+        from: DateTime<Utc>,
+        horizon: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.try_when_reaches(target, from, horizon)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::when_reaches], failing like [Book::try_assess] does.
+    pub fn try_when_reaches(
+        &self,
+        target: Value,
+        from: DateTime<Utc>,
+        horizon: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, ConversionError> {
+        let target = CURRENCY_EXCHANGE.try_convert(target, self.currency())?.amount();
+        let gap = |time| Ok::<_, ConversionError>(self.try_assess(time)?.amount() - target);
+        let start = gap(from)?;
+        if start == 0.0 {
+            return Ok(Some(from));
+        }
+        let crossed = |time| Ok::<_, ConversionError>(gap(time)?.signum() != start.signum());
+
+        let day = TimeDelta::days(1);
+        let mut low = from;
+        let mut high = loop {
+            let next = (low + day).min(horizon);
+            if crossed(next)? {
+                break next;
+            }
+            if next == horizon {
+                return Ok(None);
+            }
+            low = next;
+        };
+        while high - low > TimeDelta::seconds(1) {
+            let mid = low + (high - low) / 2;
+            if crossed(mid)? {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(Some(high))
+    }
+    /// Iterates over every item in the book.
+    pub fn iter(&self) -> impl Iterator<Item = (ItemKey, &Item)> {
+        self.entries.iter().map(|(key, item)| (key, &**item))
+    }
+    /// Every key in the order the items were added, or were put in with
+    /// [Book::move_before]. Unlike [Book::iter] this does not change as other
+    /// items come and go, and the stores keep it across a save and load.
+    pub fn ordered_keys(&self) -> Vec<ItemKey> {
+        let mut keys: Vec<ItemKey> = self.entries.keys().collect();
+        keys.sort_by_key(|key| self.sequence[*key]);
+        keys
+    }
+    /// Iterates over every item in the order of [Book::ordered_keys].
+    pub fn iter_ordered(&self) -> impl Iterator<Item = (ItemKey, &Item)> {
+        self.ordered_keys()
+            .into_iter()
+            .map(|key| (key, &*self.entries[key]))
+    }
+    /// Puts an item just before another in [Book::ordered_keys], `false` if
+    /// either is not in the book.
+    pub fn move_before(&mut self, key: ItemKey, other: ItemKey) -> bool {
+        let Some(&at) = self.sequence.get(other) else {
+            return false;
+        };
+        if !self.sequence.contains_key(key) || key == other {
+            return key == other;
+        }
+        self.sequence_at(key, at);
+        self.audit.push(AuditEntry::Reordered { key, before: other });
+        true
+    }
+    fn sequence_last(&mut self, key: ItemKey) {
+        self.sequence.insert(key, self.next_sequence);
+        self.next_sequence += 1;
+    }
+    /// Puts an item at `at` in the order, moving what was there and after
+    /// it back by one.
+    fn sequence_at(&mut self, key: ItemKey, at: u64) {
+        for (_, sequence) in self.sequence.iter_mut().filter(|(_, s)| **s >= at) {
+            *sequence += 1;
+        }
+        self.sequence.insert(key, at);
+        self.next_sequence += 1;
+    }
+    /// Finds the item that has this item as a child.
+    pub fn parent(&self, key: ItemKey) -> Option<ItemKey> {
+        self.entries
+            .iter()
+            .find(|(_, item)| item.children.contains(&key))
+            .map(|(k, _)| k)
+    }
+    pub fn get(&self, key: ItemKey) -> Option<&Item> {
+        self.entries.get(key).map(Arc::as_ref)
+    }
+    /// Borrows the item to change it, copying it first if a clone of the
+    /// book still shares it. Once the borrow ends a change is audited as an
+    /// [AuditEntry::ItemReplaced] and undone as one step.
+    pub fn get_mut(&mut self, key: ItemKey) -> Option<ItemMut<'_>> {
+        let before = self.entries.get(key)?.clone();
+        Some(ItemMut {
+            book: self,
+            key,
+            before,
+        })
+    }
+    /// Like [Book::get_mut] for mutations that audit their own changes.
+    pub(crate) fn item_mut(&mut self, key: ItemKey) -> Option<&mut Item> {
+        self.entries.get_mut(key).map(Arc::make_mut)
+    }
+    /// Puts `item` in place of the item under the key, auditing it like an
+    /// edit through [Book::get_mut].
+    pub(crate) fn replace_item(&mut self, key: ItemKey, item: Arc<Item>) {
+        let before = std::mem::replace(&mut self.entries[key], item);
+        self.replaced(key, before);
+    }
+    fn replaced(&mut self, key: ItemKey, before: Arc<Item>) {
+        let item = self.entries[key].clone();
+        self.audit.push(AuditEntry::ItemReplaced { key, item });
+        self.subscribers.notify(&BookEvent::ItemEdited { key });
+        self.record(Change::Replaced { key, item: before });
+    }
+    /// The name of an item, if it has one.
+    pub fn name(&self, key: ItemKey) -> Option<&str> {
+        self.names.get(key).map(String::as_str)
+    }
+    /// Finds every item with the given name.
+    pub fn keys_named(&self, name: &str) -> Vec<ItemKey> {
+        self.names
+            .iter()
+            .filter(|(_, n)| n.as_str() == name)
+            .map(|(k, _)| k)
+            .collect()
+    }
+    /// Finds the item with the given name, names shared by
+    /// several items do not resolve.
+    pub fn by_name(&self, name: &str) -> Option<ItemKey> {
+        match self.keys_named(name).as_slice() {
+            [key] => Some(*key),
+            _ => None,
+        }
+    }
+    /// Records the identifier of an item in an external system, such as a
+    /// UUID, which stays the same across saves where the [ItemKey] does
+    /// not. `false` if the item is not in the book or another item already
+    /// has the identifier.
+    pub fn set_external_id(&mut self, key: ItemKey, id: impl Into<String>) -> bool {
+        let id = id.into();
+        if self.get(key).is_none() || self.by_external_id(&id).is_some_and(|k| k != key) {
+            return false;
+        }
+        self.external_ids.insert(key, id.clone());
+        self.audit.push(AuditEntry::ExternalIdSet { key, id });
+        true
+    }
+    /// Adds a new item under an external identifier, see
+    /// [Book::set_external_id]. `None` without adding it if another item
+    /// already has the identifier.
+    pub fn add_with_external_id(&mut self, id: impl Into<String>, item: Item) -> Option<ItemKey> {
+        let id = id.into();
+        if self.by_external_id(&id).is_some() {
+            return None;
+        }
+        let key = self.add(item);
+        self.set_external_id(key, id);
+        Some(key)
+    }
+    /// The identifier of the item in an external system, if it has one.
+    pub fn external_id(&self, key: ItemKey) -> Option<&str> {
+        self.external_ids.get(key).map(String::as_str)
+    }
+    /// Finds the item with an external identifier.
+    pub fn by_external_id(&self, id: &str) -> Option<ItemKey> {
+        self.external_ids
+            .iter()
+            .find(|(_, e)| e.as_str() == id)
+            .map(|(k, _)| k)
+    }
+    /// Resolves an [ItemRef] to the item it refers to.
+    pub fn resolve(&self, item: &ItemRef) -> Option<ItemKey> {
+        match item {
+            ItemRef::Name(name) => self.by_name(name),
+            ItemRef::External(id) => self.by_external_id(id),
+        }
     }
     /// Adds an item to the book with a parent relationship to another entity.
     pub fn add_child(&mut self, new: Item, parent: ItemKey) -> ItemKey {
-        let key = self.entries.insert(new);
-        self.entries.get_mut(parent).unwrap().add_child(key);
-        key
+        self.grouped(|book| {
+            let key = book.add(new);
+            book.link_child(parent, key);
+            key
+        })
+    }
+    /// Gives an item a new name.
+    pub fn rename(&mut self, key: ItemKey, name: impl Into<String>) -> bool {
+        self.set_name(key, Some(name.into()))
+    }
+    /// Names an item or takes its name away, `false` if it is not in the book.
+    pub(crate) fn set_name(&mut self, key: ItemKey, name: Option<String>) -> bool {
+        if self.get(key).is_none() {
+            return false;
+        }
+        let from = match &name {
+            Some(name) => self.names.insert(key, name.clone()),
+            None => self.names.remove(key),
+        };
+        self.audit.push(AuditEntry::Renamed { key, name: name.clone() });
+        self.subscribers.notify(&BookEvent::Renamed { key });
+        self.record(Change::Renamed { key, from });
+        true
+    }
+    /// Makes an existing item the child of another, taking it from the
+    /// parent it had. See [Book::reparent].
+    pub fn link_child(&mut self, parent: ItemKey, child: ItemKey) -> bool {
+        self.reparent(child, Some(parent))
+    }
+    /// Moves an item under another parent, or to the top of the book with
+    /// `None`. Returns `false` without changing anything if either item is
+    /// not in the book.
+    pub fn reparent(&mut self, child: ItemKey, parent: Option<ItemKey>) -> bool {
+        if self.get(child).is_none() || parent.is_some_and(|p| self.get(p).is_none()) {
+            return false;
+        }
+        let from = self.parent(child);
+        if let Some(from) = from {
+            self.unlink_child(from, child);
+        }
+        if let Some(parent) = parent {
+            self.item_mut(parent).unwrap().add_child(child);
+            self.audit.push(AuditEntry::ChildAdded { parent, child });
+        }
+        self.subscribers.notify(&BookEvent::Reparented {
+            child,
+            from,
+            to: parent,
+        });
+        self.record(Change::Reparented { child, from });
+        true
+    }
+    pub(crate) fn unlink_child(&mut self, parent: ItemKey, child: ItemKey) {
+        self.item_mut(parent).unwrap().children.retain(|c| *c != child);
+        self.audit.push(AuditEntry::ChildRemoved { parent, child });
+    }
+}
+
+
+impl Book {
+    /// The currency the book is assessed in, that of its first item in
+    /// [Book::ordered_keys].
+    pub fn base_currency(&self) -> Option<Currency> {
+        let first = *self.ordered_keys().first()?;
+        Some(self.entries[first].currency())
+    }
+    /// Reports in `currency` converting at the rates the policy calls for,
+    /// the items themselves keep their own currencies.
+    pub fn set_reporting(&mut self, currency: impl Into<Currency>, policy: RatePolicy) {
+        let currency = currency.into();
+        self.reporting = Some((currency, policy));
+        self.audit.push(AuditEntry::ReportingSet { currency, policy });
+    }
+    /// The reporting currency and policy, if one was set.
+    pub fn reporting(&self) -> Option<(Currency, RatePolicy)> {
+        self.reporting
+    }
+    /// The currency the book reports in, the reporting currency if one was
+    /// set and otherwise the [Book::base_currency].
+    pub fn report_currency(&self) -> Option<Currency> {
+        self.reporting
+            .map(|(currency, _)| currency)
+            .or_else(|| self.base_currency())
+    }
+    /// The rate to convert from a currency into the [Book::report_currency]
+    /// at the time, following the reporting policy if one was set and the
+    /// undated rates if not.
+    pub fn report_rate(&self, from: Currency, time: DateTime<Utc>) -> Result<f64, ConversionError> {
+        self.rate_into(from, self.report_currency().unwrap_or(from), time)
+    }
+    /// Like [Book::report_rate] into any currency, still following the
+    /// reporting policy.
+    pub(crate) fn rate_into(
+        &self,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        match self.reporting {
+            Some((_, policy)) => CURRENCY_EXCHANGE.rate_for(policy, from, to, time),
+            None => CURRENCY_EXCHANGE.rate(from, to),
+        }
+    }
+    /// Like [Book::rate_into] for a value of `item`, its
+    /// [Item::fx_override] wins when converting out of the item's currency
+    /// and its inverse when converting into it.
+    pub(crate) fn item_rate(
+        &self,
+        item: &Item,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        let own = item.currency();
+        let locked = match (from == own, to == own) {
+            (true, false) => item.fx_override(to),
+            (false, true) => item.fx_override(from).map(f64::recip),
+            _ => None,
+        };
+        match locked {
+            Some(rate) => Ok(rate),
+            None => self.rate_into(from, to, time),
+        }
+    }
+    /// Like [Book::report_rate] for a value of `item`, see [Book::item_rate].
+    pub(crate) fn item_report_rate(
+        &self,
+        item: &Item,
+        from: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        self.item_rate(item, from, self.report_currency().unwrap_or(from), time)
+    }
+    /// Rewrites every amount in `old` into `new` after a redenomination,
+    /// `factor` old units making a new one. This covers item book values,
+    /// fixed deltas, payouts and minimum payments, percentage deltas need
+    /// no change. Rates captured by deltas and [Item::fx_override]s from or
+    /// into `old` are rescaled to match. The items are undone as one step.
+    /// See
+    /// [ConversionTable::redenominate](super::convert::ConversionTable::redenominate)
+    /// for converting without rewriting.
+    pub fn migrate_currency(&mut self, old: impl Into<Currency>, new: impl Into<Currency>, factor: f64) {
+        let (old, new) = (old.into(), new.into());
+        self.grouped(|book| {
+            let keys: Vec<ItemKey> = book.entries.keys().collect();
+            for key in keys {
+                let before = book.history.is_some().then(|| book.entries[key].clone());
+                migrate_item(Arc::make_mut(&mut book.entries[key]), old, new, factor);
+                if let Some(item) = before {
+                    book.record(Change::Replaced { key, item });
+                }
+            }
+        });
+        self.audit.push(AuditEntry::CurrencyMigrated { old, new, factor });
+        self.subscribers.notify(&BookEvent::CurrencyMigrated { old, new });
+    }
+    /// Assesses the book in its [Book::report_currency], failing if an
+    /// item's currency has no rate to it or one of its deltas none into the
+    /// item's. Each item is converted once and added straight into a
+    /// compensated sum. The total is attached to a
+    /// [RateSnapshot](super::convert::RateSnapshot) of the global exchange
+    /// as it was, so it keeps converting at those rates.
+    pub fn try_assess(&self, time: DateTime<Utc>) -> Result<Value, ConversionError> {
+        self.try_assess_where(time, |_, _| true)
+    }
+    /// Assesses only the items that can be spent at the time, leaving out
+    /// unreleased escrows and anything else that is not
+    /// [Liquidity::Liquid](super::item::Liquidity::Liquid). Panics without a
+    /// rate, see [Book::try_liquid_assess].
+    pub fn liquid_assess(&self, time: DateTime<Utc>) -> Value {
+        self.try_liquid_assess(time).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::liquid_assess], failing like [Book::try_assess] does.
+    pub fn try_liquid_assess(&self, time: DateTime<Utc>) -> Result<Value, ConversionError> {
+        self.try_assess_where(time, |_, item| item.liquidity.is_liquid(time))
+    }
+    /// The assessment of the items in each currency, without converting.
+    pub fn assess_by_currency(&self, time: DateTime<Utc>) -> MultiCurrencySum {
+        let mut sum = MultiCurrencySum::default();
+        for (_, item) in self.iter_ordered() {
+            sum.add(&item.assess(time));
+        }
+        sum
+    }
+    /// The yearly rate charged on the book's interest bearing debts, each
+    /// weighted by what is owed on it at the time. Rates are compounded to a
+    /// year so monthly and yearly rates compare. `None` without such debts.
+    /// Panics without a rate, see [Book::try_weighted_avg_rate].
+    pub fn weighted_avg_rate(&self, time: DateTime<Utc>) -> Option<f64> {
+        self.try_weighted_avg_rate(time).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::weighted_avg_rate], failing like [Book::try_assess] does.
+    pub fn try_weighted_avg_rate(
+        &self,
+        time: DateTime<Utc>,
+    ) -> Result<Option<f64>, ConversionError> {
+        let year = Period::Years(1).step(time, 1);
+        let (mut owed, mut weighted) = (0.0, 0.0);
+        for (_, item) in self.iter() {
+            let Some(interest) = item.interest() else {
+                continue;
+            };
+            let balance = item.try_assess(time)?.amount();
+            if balance >= 0.0 {
+                continue;
+            }
+            let rate = self.item_report_rate(item, item.currency(), time)?;
+            owed -= balance * rate;
+            weighted -= balance * rate * (interest.factor(time, year) - 1.0);
+        }
+        Ok((owed > 0.0).then(|| weighted / owed))
+    }
+    /// The interest every item earns or is charged over the `window` after
+    /// `time` on its balance at `time`, in the [Book::report_currency]. Debts
+    /// count against it. Panics without a rate, see
+    /// [Book::try_interest_accrual].
+    pub fn interest_accrual(&self, time: DateTime<Utc>, window: TimeDelta) -> Value {
+        self.try_interest_accrual(time, window).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::interest_accrual], failing like [Book::try_assess] does.
+    pub fn try_interest_accrual(
+        &self,
+        time: DateTime<Utc>,
+        window: TimeDelta,
+    ) -> Result<Value, ConversionError> {
+        let mut sum = KahanSum::default();
+        for (_, item) in self.iter() {
+            let Some(interest) = item.interest() else {
+                continue;
+            };
+            let accrued = interest.interest(time, time + window, item.try_assess(time)?);
+            sum.add(accrued.amount() * self.item_report_rate(item, item.currency(), time)?);
+        }
+        Ok(Value::new(self.currency(), sum.total()))
+    }
+    fn try_assess_where(
+        &self,
+        time: DateTime<Utc>,
+        keep: impl Fn(ItemKey, &Item) -> bool,
+    ) -> Result<Value, ConversionError> {
+        let Some(base) = self.report_currency() else {
+            return Ok(Value::zero(self.currency()));
+        };
+        self.try_assess_in(base, time, keep)
+    }
+    /// Assesses the items kept in `base`, converting with [Book::item_rate].
+    pub(crate) fn try_assess_in(
+        &self,
+        base: Currency,
+        time: DateTime<Utc>,
+        keep: impl Fn(ItemKey, &Item) -> bool,
+    ) -> Result<Value, ConversionError> {
+        self.try_assess_mode_in(base, time, AssessMode::IncludePlanned, keep)
+    }
+    /// Assesses the book counting only the deltas `mode` counts, so
+    /// [AssessMode::ActualsOnly] leaves out deltas recorded in advance.
+    /// [Book::assess] includes them.
+    pub fn assess_mode(&self, time: DateTime<Utc>, mode: AssessMode) -> Value {
+        let Some(base) = self.report_currency() else {
+            return Value::zero(self.currency());
+        };
+        self.try_assess_mode_in(base, time, mode, |_, _| true)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    fn try_assess_mode_in(
+        &self,
+        base: Currency,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+        keep: impl Fn(ItemKey, &Item) -> bool,
+    ) -> Result<Value, ConversionError> {
+        let sum = self.try_sum_mode_in(base, time, mode, keep)?;
+        Ok(Value::new(base, sum.total()).rebased(&CURRENCY_EXCHANGE.snapshot()))
+    }
+    fn try_sum_mode_in(
+        &self,
+        base: Currency,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+        keep: impl Fn(ItemKey, &Item) -> bool,
+    ) -> Result<KahanSum, ConversionError> {
+        let mut sum = KahanSum::default();
+        for (_, item) in self.iter_ordered().filter(|(key, item)| keep(*key, item)) {
+            let amount = item.try_assess_amount_mode(time, mode)?;
+            match item.currency() {
+                currency if currency == base => sum.add(amount),
+                currency => sum.add(amount * self.item_rate(item, currency, base, time)?),
+            }
+        }
+        Ok(sum)
+    }
+    /// Like [Book::try_assess] with a [PrecisionWarning] when the items
+    /// cancel out so much that the total may be mostly rounding error, such
+    /// as huge offsetting assets and debts. [Book::assess_decimal] gives the
+    /// exact total then.
+    pub fn try_assess_diagnosed(&self, time: DateTime<Utc>) -> Result<Assessed, ConversionError> {
+        let Some(base) = self.report_currency() else {
+            return Ok(Assessed {
+                value: Value::zero(self.currency()),
+                warning: None,
+            });
+        };
+        let sum = self.try_sum_mode_in(base, time, AssessMode::IncludePlanned, |_, _| true)?;
+        Ok(Assessed {
+            value: Value::new(base, sum.total()).rebased(&CURRENCY_EXCHANGE.snapshot()),
+            warning: sum.diagnostics().warning(self.precision_tolerance()),
+        })
+    }
+    /// Sets the fraction of the total the error may reach before
+    /// [Book::try_assess_diagnosed] warns, `None` goes back to
+    /// [PRECISION_TOLERANCE].
+    pub fn set_precision_tolerance(&mut self, tolerance: Option<f64>) {
+        self.precision_tolerance = tolerance;
+        self.audit.push(AuditEntry::PrecisionToleranceSet { tolerance });
+    }
+    pub fn precision_tolerance(&self) -> f64 {
+        self.precision_tolerance.unwrap_or(PRECISION_TOLERANCE)
     }
 }
 
+/// Rewrites the amounts of an item in `old` into `new`, see
+/// [Book::migrate_currency].
+fn migrate_item(item: &mut Item, old: Currency, new: Currency, factor: f64) {
+    let migrate = |value: &mut Value| {
+        if value.currency() == old {
+            *value = Value::new(new, value.amount() / factor);
+        }
+    };
+    // A new unit buys `factor` times what an old one did.
+    let was_old = item.currency() == old;
+    migrate(&mut item.book_value);
+    if let Some(minimum) = &mut item.minimum_payment {
+        migrate(&mut minimum.floor);
+    }
+    for delta in &mut item.deltas {
+        if let DeltaAmount::Fixed(value) = &mut delta.amount {
+            if let Some(rate) = &mut delta.rate {
+                if value.currency() == old {
+                    *rate *= factor;
+                }
+                if was_old {
+                    *rate /= factor;
+                }
+            }
+            migrate(value);
+        }
+    }
+    for (target, rate) in &mut item.fx_overrides {
+        if was_old {
+            *rate *= factor;
+        }
+        if *target == old {
+            *target = new;
+            *rate /= factor;
+        }
+    }
+    for payout in &mut item.payouts {
+        match payout.unadjusted_mut() {
+            Payout::OneTime { amount, .. } | Payout::FixedRecurring { amount, .. } => {
+                migrate(amount)
+            }
+            Payout::InterestOneTime { principal, .. }
+            | Payout::InterestRecurring { principal, .. } => migrate(principal),
+            Payout::Adjusted { .. } => unreachable!(),
+        }
+    }
+}
 
 impl Assesible for Book {
     fn assess(&self, time: DateTime<Utc>) -> Value {
-        self.entries
-            .iter()
-            .map(|(_, v)| v.assess(time))
-            .sum::<Value>()
+        self.try_assess(time).unwrap_or_else(|e| panic!("{e}"))
     }
+    /// The [Book::report_currency], an empty book is worth nothing in CAD.
     fn currency(&self) -> Currency {
-        self.entries.iter().nth(0).unwrap().1.currency()
+        self.report_currency().unwrap_or(Currency::new("CAD"))
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        let currency = self.currency();
+        self.entries
+            .values()
+            .map(|item| {
+                let sampled = item.sample(time, rng);
+                let rate = self
+                    .item_report_rate(item, sampled.currency(), time)
+                    .unwrap_or_else(|e| panic!("{e}"));
+                Value::new(currency, sampled.amount() * rate)
+            })
+            .sum::<Option<Value>>()
+            .unwrap_or_else(|| Value::zero(currency))
     }
 }
 
@@ -45,13 +964,18 @@ slotmap::new_key_type! {
 
 
 #[cfg(test)]
+#[allow(clippy::zero_prefixed_literal, clippy::neg_multiply)]
 mod tests {
+    use std::{
+        sync::{Arc, RwLock},
+        thread,
+    };
 
-    use std::str::FromStr;
+    use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
-    use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
-
-    use crate::instruments::{book::Book, item::{Interest, Item}, value::Value, Assesible};
+    use crate::assert_value_eq;
+    use crate::instruments::{audit::AuditEntry, book::Book, convert::{ConversionError, RatePolicy, CURRENCY_EXCHANGE}, delta::{AssessMode, Delta, DeltaStatus}, item::{ApplicationOrder, Interest, Item, Liquidity}, lint::LintWarning, period::Period, value::{Currency, Value}, Assesible};
 
 
 
@@ -76,7 +1000,7 @@ mod tests {
 
         // panic!("wow {:?}", current_debt);
 
-        assert_eq!(current_debt.non_decimal(), -338224);
+        assert_value_eq!(current_debt, Value::new("CAD", -338_224.96), 0.005);
     }
 
     #[test]
@@ -96,7 +1020,19 @@ mod tests {
             interest: Some(Interest::new(0.04, Duration::days(365))),
             // parent: None,
             deltas: vec![],
-            payouts: vec![]
+            payouts: vec![],
+            minimum_payment: None,
+            liquidity: Liquidity::Liquid,
+            capitalization: None,
+            lease: None,
+            round_each_period: None,
+            application_order: ApplicationOrder::InterestFirst,
+            balloon: None,
+            interest_posted: None,
+            fx_overrides: vec![],
+            units: None,
+            sales: vec![],
+            prices: None,
         };
 
         let mut book = Book::default();
@@ -111,6 +1047,119 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_assess_by_currency() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion("BCU", "BCC", 0.5);
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("BCC", 100.0), start));
+        book.add(Item::fixed(Value::new("BCU", 40.0), start));
+        book.add(Item::fixed(Value::new("BCC", 25.0), start));
+
+        let totals = book.assess_by_currency(start);
+        let amounts: Vec<_> = totals
+            .totals()
+            .map(|(currency, total)| (currency.code().to_string(), total.amount()))
+            .collect();
+        assert_eq!(amounts, [("BCC".to_string(), 125.0), ("BCU".to_string(), 40.0)]);
+        let single = totals.into_single("BCC".into(), &CURRENCY_EXCHANGE).unwrap();
+        assert_eq!(single.amount(), book.assess(start).amount());
+    }
+
+    /// The base currency follows the order of the items rather than the
+    /// slot a removed item left free.
+    #[test]
+    pub fn test_base_currency_ordered() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion("BOA", "BOB", 2.0);
+        let mut book = Book::default();
+        let first = book.add(Item::fixed(Value::new("BOA", 10.0), start));
+        book.add(Item::fixed(Value::new("BOB", 10.0), start));
+        book.remove(first);
+        book.add(Item::fixed(Value::new("BOA", 10.0), start));
+        assert_eq!(book.base_currency(), Some(Currency::new("BOB")));
+        assert_value_eq!(book.assess(start), Value::new("BOB", 30.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_borrowing_cost() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let year = Duration::days(365);
+        let mut book = Book::default();
+        book.add(Item::basic_debt(Value::new("CAD", -10_000.0), 0.05, year, start));
+        book.add(Item::basic_debt(Value::new("CAD", -30_000.0), 0.10, year, start));
+        book.add(Item::basic_debt(Value::new("CAD", 1_000.0), 0.02, year, start));
+        book.add(Item::fixed(Value::new("CAD", 5_000.0), start));
+
+        let blended = book.weighted_avg_rate(start).unwrap();
+        assert!((blended - (500.0 + 3_000.0) / 40_000.0).abs() < 1e-12);
+
+        // A fifth of a year of interest on each balance.
+        let expected = -10_000.0 * (1.05f64.powf(0.2) - 1.0) - 30_000.0 * (1.10f64.powf(0.2) - 1.0)
+            + 1_000.0 * (1.02f64.powf(0.2) - 1.0);
+        let accrual = book.interest_accrual(start, Duration::days(73));
+        assert_value_eq!(accrual, Value::new("CAD", expected), 1e-9);
+        assert_eq!(Book::default().weighted_avg_rate(start), None);
+    }
+
+    /// The streaming sum matches summing every item's assessment.
+    #[test]
+    pub fn test_streaming_assess() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut book = Book::default();
+        for _ in 0..1_000 {
+            let mut item = make_credit_card(rng.gen_range(1..100_000), rng.gen_range(0.0..0.3));
+            item.book_value = item.book_value * rng.gen_range(-1.0..1.0);
+            for _ in 0..rng.gen_range(0..5) {
+                let time = Utc.with_ymd_and_hms(rng.gen_range(2008..2030), 1, 1, 0, 0, 0).unwrap();
+                item.add_delta(time, Value::dummy("CAD", rng.gen_range(0.0..1_000.0)));
+            }
+            book.add(item);
+        }
+
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let summed = book.iter().map(|(_, item)| item.assess(time)).sum::<Option<Value>>();
+        assert_eq!(book.assess(time).amount(), summed.unwrap().amount());
+        assert_eq!(Book::default().try_assess(time).unwrap().amount(), 0.0);
+    }
+
+    /// Readers assessing a shared book never see a half applied delta while
+    /// a writer pays down the debt.
+    #[test]
+    pub fn test_concurrent_assessment() {
+        let book = Arc::new(RwLock::new(Book::default()));
+        let card = book.write().unwrap().add(make_credit_card(10_000, 0.20));
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let book = Arc::clone(&book);
+                thread::spawn(move || {
+                    let mut last = f64::NEG_INFINITY;
+                    for _ in 0..2_000 {
+                        let value = book.read().unwrap().assess(time).amount();
+                        assert!(value >= last);
+                        last = value;
+                    }
+                })
+            })
+            .collect();
+
+        for month in 0..200 {
+            let paid = Utc.with_ymd_and_hms(2008, 2, 1, 0, 0, 0).unwrap() + Duration::days(30 * month);
+            book.write()
+                .unwrap()
+                .add_delta(card, Delta::payment(paid, Value::dummy("CAD", 10.0)));
+        }
+        readers.into_iter().for_each(|r| r.join().unwrap());
+
+        let expected = {
+            let book = book.read().unwrap();
+            book.get(card).unwrap().assess(time).amount()
+        };
+        assert_eq!(book.read().unwrap().assess(time).amount(), expected);
+    }
+
     #[test]
     pub fn partially_paid_credit_card() {
         // Standard credit card with 1000 of debt and a 20% interest.
@@ -124,6 +1173,376 @@ mod tests {
 
         // 7 years later there should be about $55.11CAD on the card.
         let value = credit.assess(Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap());
-        assert_eq!(value.non_decimal(), -55);
+        assert_value_eq!(value, Value::new("CAD", -55.11), 0.005);
+    }
+
+    /// Savings of 100k growing at 5% a year reach 120k, drop below it with a
+    /// big purchase at the start of 2024 and reach it again years later.
+    #[test]
+    pub fn test_when_reaches() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let savings = Item::builder(Value::new("CAD", 100_000.0), start)
+            .interest(0.05, Period::Years(1))
+            .payment(
+                Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                Value::new("CAD", -30_000.0),
+            )
+            .build();
+        let mut book = Book::default();
+        book.add(savings);
+
+        let horizon = Utc.with_ymd_and_hms(2035, 1, 1, 0, 0, 0).unwrap();
+        let found = book.when_reaches(Value::new("CAD", 120_000.0), start, horizon).unwrap();
+        let years = 1.2f64.ln() / 1.05f64.ln();
+        assert!((Period::Years(1).between(start, found) - years).abs() < 1.0 / 365.0);
+        assert_eq!(found.date_naive(), NaiveDate::from_ymd_opt(2023, 9, 26).unwrap());
+
+        // Searching from after the purchase finds the second crossing.
+        let after = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let again = book.when_reaches(Value::new("CAD", 120_000.0), after, horizon);
+        assert!(again.unwrap().year() > 2028);
+        assert_eq!(book.when_reaches(Value::new("CAD", 1e9), start, horizon), None);
+    }
+
+    /// A deposit held in escrow until closing next to a chequing account and
+    /// a house that is never liquid.
+    #[test]
+    pub fn test_liquid_assess() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let closing = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("CAD", 5_000.0), start));
+        let deposit = book.add(Item::escrow(Value::new("CAD", 20_000.0), closing, 0.1, start));
+        let mut house = Item::fixed(Value::new("CAD", 500_000.0), start);
+        house.liquidity = Liquidity::Illiquid;
+        book.add(house);
+
+        assert_eq!(book.assess(before).amount(), 525_000.0);
+        assert_eq!(book.liquid_assess(before).amount(), 5_000.0);
+        assert_eq!(book.liquid_assess(closing).amount(), 25_000.0);
+
+        // Cancelling forfeits 10% and releases the rest straight away.
+        let mut cancelled = book.get(deposit).unwrap().clone();
+        assert!(cancelled.cancel(before).is_some());
+        assert!(cancelled.cancel(before).is_none());
+        assert!(cancelled.liquidity.is_liquid(before));
+        assert!(!cancelled.liquidity.is_liquid(start));
+        assert_eq!(cancelled.assess(start).amount(), 20_000.0);
+        assert_eq!(cancelled.assess(before).amount(), 18_000.0);
+        assert!(Item::fixed(Value::new("CAD", 1.0), start).cancel(before).is_none());
+    }
+
+    /// A debt with no rate into the book's currency fails the reports
+    /// rather than panicking.
+    #[test]
+    pub fn test_reports_without_rate() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("NRA", 1_000.0), start));
+        book.add(Item::basic_debt(Value::new("NRB", -500.0), 0.1, Duration::days(365), start));
+        let missing = ConversionError::NoRate {
+            from: Currency::new("NRB"),
+            to: Currency::new("NRA"),
+        };
+        let horizon = start + Duration::days(30);
+        assert_eq!(book.try_liquid_assess(start).unwrap_err(), missing);
+        assert_eq!(book.try_weighted_avg_rate(start), Err(missing.clone()));
+        let window = Duration::days(30);
+        assert_eq!(book.try_interest_accrual(start, window).unwrap_err(), missing);
+        let target = Value::new("NRA", 2_000.0);
+        assert_eq!(book.try_when_reaches(target, start, horizon), Err(missing));
+    }
+
+    /// Stand-ins for CAD, USD and COP items reported in CAD at the rates of
+    /// 2015 and 2025, with a 2026 rate being the latest.
+    #[test]
+    pub fn test_reporting_policy() {
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        // The table is global, codes of their own keep these rates out of
+        // other tests.
+        CURRENCY_EXCHANGE.add_conversion_on("RPU", "RPC", on(2015), 1.16);
+        CURRENCY_EXCHANGE.add_conversion_on("RPU", "RPC", on(2025), 1.44);
+        CURRENCY_EXCHANGE.add_conversion_on("RPU", "RPC", on(2026), 1.38);
+        CURRENCY_EXCHANGE.add_conversion_on("RPP", "RPC", on(2015), 0.00049);
+        CURRENCY_EXCHANGE.add_conversion_on("RPP", "RPC", on(2025), 0.00032);
+
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("RPC", 1_000.0), on(2010)));
+        let usd = book.add(
+            Item::builder(Value::new("RPU", 1_000.0), on(2010))
+                .payment(on(2020), Value::new("RPU", 500.0))
+                .build(),
+        );
+        book.add(Item::fixed(Value::new("RPP", 1_000_000.0), on(2010)));
+
+        let (early, late) = (on(2015) + Duration::days(180), on(2025) + Duration::days(180));
+        let rpc = |amount| Value::new("RPC", amount);
+
+        book.set_reporting("RPC", RatePolicy::RateAtAssessmentDate);
+        assert_value_eq!(book.assess(early), rpc(1_000.0 + 1_160.0 + 490.0), 1e-6);
+        assert_value_eq!(book.assess(late), rpc(1_000.0 + 2_160.0 + 320.0), 1e-6);
+        assert_eq!(book.currency().code(), "RPC");
+
+        book.set_reporting("RPC", RatePolicy::Latest);
+        assert_value_eq!(book.assess(early), rpc(1_000.0 + 1_380.0 + 320.0), 1e-6);
+        assert_value_eq!(book.assess(late), rpc(1_000.0 + 2_070.0 + 320.0), 1e-6);
+        let series = book.series(early, late, Period::Years(10));
+        assert_value_eq!(series[1].1, book.assess(late), 1e-9);
+
+        book.set_reporting("RPC", RatePolicy::RateAtFixedDate(on(2015)));
+        assert_value_eq!(book.assess(late), rpc(1_000.0 + 1_740.0 + 490.0), 1e-6);
+
+        // Reporting never touches the items.
+        assert_eq!(book.get(usd).unwrap().book_value.amount(), 1_000.0);
+        assert_eq!(book.get(usd).unwrap().currency().code(), "RPU");
+    }
+
+    /// An item in an old currency with deltas from before and after the
+    /// redenomination, the later ones in the new currency.
+    #[test]
+    pub fn test_migrate_currency() {
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.redenominate("RBO", "RBN", 1_000.0, on(2025));
+
+        let mut book = Book::default().undoable();
+        let mut savings = Item::builder(Value::new("RBO", 5_000_000.0), on(2020))
+            .payment(on(2021), Value::new("RBO", -1_000_000.0))
+            .payment(on(2026), Value::new("RBN", -2_000.0))
+            .build();
+        savings.set_fx_override("CAD", 0.001);
+        let savings = book.add(savings);
+        let other = book.add(
+            Item::builder(Value::new("CAD", 100.0), on(2020))
+                .delta(Delta::payment(on(2021), Value::new("RBO", -50_000.0)).with_rate(0.001))
+                .build(),
+        );
+        let before = book.get(savings).unwrap().assess(on(2027));
+        assert_eq!(before.amount(), 2_000_000.0);
+
+        book.migrate_currency("RBO", "RBN", 1_000.0);
+        let item = book.get(savings).unwrap();
+        assert_eq!(item.currency().code(), "RBN");
+        assert_eq!(item.assess(on(2022)).amount(), 4_000.0);
+        assert_eq!(item.assess(on(2027)).amount(), 2_000.0);
+        assert!(item
+            .deltas
+            .iter()
+            .all(|d| d.resolve(&Value::new("RBN", 0.0)).currency().code() == "RBN"));
+        assert_eq!(item.fx_override(Currency::new("CAD")), Some(1.0));
+        let other_item = book.get(other).unwrap();
+        assert_eq!(other_item.currency().code(), "CAD");
+        assert_eq!(other_item.deltas[0].rate, Some(1.0));
+        assert_eq!(other_item.assess(on(2022)).amount(), 50.0);
+        assert!(matches!(
+            book.audit_log().last(),
+            Some(AuditEntry::CurrencyMigrated { factor, .. }) if *factor == 1_000.0
+        ));
+
+        book.undo().unwrap();
+        let item = book.get(savings).unwrap();
+        assert_eq!(item.currency().code(), "RBO");
+        assert_eq!(item.fx_override(Currency::new("CAD")), Some(0.001));
+        assert_eq!(book.get(other).unwrap().deltas[0].rate, Some(0.001));
+    }
+
+    /// A USD invoice payable at a locked 1.30 counts at 1.30 in a CAD book
+    /// while the market is at 1.42.
+    #[test]
+    pub fn test_fx_override() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let (usd, cad) = (Currency::new("USD"), Currency::new("CAD"));
+        CURRENCY_EXCHANGE.add_conversion(usd, cad, 1.42);
+        let mut book = Book::default();
+        book.add_named("Chequing", Item::fixed(Value::new(cad, 1_000.0), start));
+        let mut invoice = Item::fixed(Value::new(usd, -100.0), start);
+        invoice.set_fx_override(cad, 1.30);
+        assert_eq!(invoice.fx_override(cad), Some(1.30));
+        let invoice = book.add_named("Invoice", invoice);
+        assert_value_eq!(book.assess(start), Value::new(cad, 870.0), 1e-9);
+
+        // Paying 130 CAD settles the invoice at the locked rate.
+        book.apply_payment("Chequing", "Invoice", start, Value::new(cad, 130.0)).unwrap();
+        assert_value_eq!(book.get(invoice).unwrap().assess(start), Value::new(usd, 0.0), 1e-9);
+        assert_value_eq!(book.assess(start), Value::new(cad, 870.0), 1e-9);
+
+        assert!(book.lint().is_empty());
+        book.get_mut(invoice).unwrap().set_fx_override(cad, 1.20);
+        assert_eq!(
+            book.lint(),
+            [LintWarning::FxOverrideDeviation {
+                key: invoice,
+                currency: cad,
+                locked: 1.20,
+                market: 1.42,
+            }]
+        );
+    }
+
+    /// Loading a statement in bulk ends up where adding each delta does, as
+    /// A clone shares items until one side changes them.
+    #[test]
+    pub fn test_clone_on_write() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.subscribe(|_| {});
+        let card = book.add_named("Visa", make_credit_card(1_000, 0.2));
+        let chequing = book.add(Item::fixed(Value::new("CAD", 500.0), start));
+
+        let mut fork = book.clone();
+        assert!(fork.audit_log().is_empty());
+        assert_eq!(fork.name(card), Some("Visa"));
+        assert!(Arc::ptr_eq(&book.entries[card], &fork.entries[card]));
+        fork.add_delta(chequing, Delta::payment(start, Value::new("CAD", -200.0)));
+        assert!(Arc::ptr_eq(&book.entries[card], &fork.entries[card]));
+        assert!(!Arc::ptr_eq(&book.entries[chequing], &fork.entries[chequing]));
+
+        assert_eq!(book.get(chequing).unwrap().assess(start).amount(), 500.0);
+        assert_eq!(fork.get(chequing).unwrap().assess(start).amount(), 300.0);
+        assert_eq!(book.assess(start).amount() - fork.assess(start).amount(), 200.0);
+    }
+
+    /// Rent recorded in advance only shows up in projections until it is
+    /// confirmed.
+    #[test]
+    pub fn test_planned_deltas() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let due = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        let later = due + Duration::days(3);
+        let mut book = Book::default().undoable();
+        let chequing = book.add(Item::fixed(Value::new("CAD", 3_000.0), start));
+        book.add_delta(chequing, Delta::payment(start, Value::new("CAD", -50.0)));
+        let rent = Delta::payment(due, Value::new("CAD", -1_800.0)).planned();
+        let id = book.add_delta(chequing, rent).unwrap();
+
+        let actual = book.assess_mode(later, AssessMode::ActualsOnly).amount();
+        let projected = book.assess_mode(later, AssessMode::IncludePlanned).amount();
+        assert_eq!(actual, 2_950.0);
+        assert_eq!(actual - projected, 1_800.0);
+        assert_eq!(book.assess(later).amount(), projected);
+
+        book.take_audit_log();
+        assert!(book.confirm_delta(chequing, id));
+        assert!(!book.confirm_delta(chequing, id));
+        assert_eq!(book.take_audit_log().len(), 2);
+        let delta = &book.get(chequing).unwrap().deltas[1];
+        assert_eq!((delta.id, delta.status), (id, DeltaStatus::Actual));
+        assert_eq!(book.assess_mode(later, AssessMode::ActualsOnly).amount(), projected);
+
+        book.undo().unwrap();
+        assert_eq!(book.assess_mode(later, AssessMode::ActualsOnly).amount(), actual);
+    }
+
+    #[test]
+    pub fn test_fx_gains() {
+        let on = |m| Utc.with_ymd_and_hms(2025, m, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("FXC", "FXU", on(1), 0.75);
+        CURRENCY_EXCHANGE.add_conversion_on("FXC", "FXU", on(3), 0.70);
+
+        let mut book = Book::default();
+        let debt = book.add_named(
+            "Debt",
+            Item::fixed(Value::new("FXU", -1_000.0), on(1)),
+        );
+        // The first payment takes the rate of its day, the second was made at 0.72.
+        let first = Delta::payment(on(2), Value::new("FXC", 400.0));
+        let second = Delta::payment(on(4), Value::new("FXC", 400.0));
+        book.add_delta_converted(debt, first, None).unwrap();
+        book.add_delta_converted(debt, second, Some(0.72)).unwrap();
+        let item = book.get(debt).unwrap();
+        let rates: Vec<_> = item.deltas.iter().map(|d| d.rate).collect();
+        assert_eq!(rates, [Some(0.75), Some(0.72)]);
+        assert_value_eq!(item.assess(on(5)), Value::new("FXU", -412.0), 1e-9);
+
+        // Against the rate at the start both went in at 0.75: 400 * (0.72 - 0.75).
+        let fixed = item.fx_gains(on(1), on(5), RatePolicy::RateAtFixedDate(on(1)));
+        assert_value_eq!(fixed, Value::new("FXU", -12.0), 1e-9);
+        // On its own day the second payment would have gone in at 0.70: 400 * 0.02.
+        let own_day = item.fx_gains(on(1), on(5), RatePolicy::RateAtAssessmentDate);
+        assert_value_eq!(own_day, Value::new("FXU", 8.0), 1e-9);
+        let latest = item.fx_gains(on(1), on(3), RatePolicy::Latest);
+        assert_value_eq!(latest, Value::new("FXU", 20.0), 1e-9);
+        let missing = Delta::payment(on(2), Value::new("FXC", 1.0));
+        assert_eq!(book.add_delta_converted(Default::default(), missing, None), Ok(None));
+
+        let mut out = vec![];
+        book.export_deltas_csv(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert!(csv.ends_with("Debt,2025-04-01T00:00:00+00:00,Payment,400,FXC,0.72\n"));
+        let mut out = vec![];
+        book.export_ledger(&mut out, &Default::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(" @ 0.72 FXU"));
+    }
+
+    /// one event and one undo step.
+    #[test]
+    pub fn test_bulk_load() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let card = Item::basic_debt(Value::new("CAD", -1_000.0), 0.2, Period::Years(1), start);
+        // Out of order so the deltas have to be sorted.
+        let deltas: Vec<Delta> = (0..500)
+            .map(|i| {
+                let time = start + Duration::hours((i * 7919) % 500);
+                Delta::payment(time, Value::new("CAD", 1.0))
+            })
+            .collect();
+        let later = start + Duration::days(30);
+
+        let mut naive = Book::default();
+        let key = naive.add(card.clone());
+        for delta in deltas.clone() {
+            naive.add_delta(key, delta);
+        }
+        let mut bulk = Book::default().undoable();
+        let events = Arc::new(RwLock::new(vec![]));
+        let seen = events.clone();
+        bulk.subscribe(move |event| seen.write().unwrap().push(event.clone()));
+        let loaded = bulk.add_item_with_deltas(card.clone(), deltas.clone());
+        assert_eq!(naive.assess(later).amount(), bulk.assess(later).amount());
+        assert_eq!(events.read().unwrap().len(), 1);
+
+        let edited = bulk.add(card);
+        // The log shares the items with the book rather than copying them.
+        let shared = |item: &Arc<Item>| Arc::ptr_eq(item, &bulk.entries[edited]);
+        let added = bulk.audit_log().last();
+        assert!(matches!(added, Some(AuditEntry::ItemAdded { item, .. }) if shared(item)));
+        bulk.take_audit_log();
+        assert!(bulk.bulk_edit(edited, |item| {
+            item.deltas.extend(deltas);
+            item.deltas.remove(0);
+        }));
+        assert_eq!(bulk.get(edited).unwrap().deltas.len(), 499);
+        let log = bulk.take_audit_log();
+        assert!(
+            matches!(&log[..], [AuditEntry::ItemReplaced { item, .. }] if item.deltas.len() == 499)
+        );
+        let shared = |item: &Arc<Item>| Arc::ptr_eq(item, &bulk.entries[edited]);
+        assert!(matches!(&log[..], [AuditEntry::ItemReplaced { item, .. }] if shared(item)));
+        assert_eq!(events.read().unwrap().len(), 3);
+        let (first, second) = (bulk.get(loaded).unwrap(), bulk.get(edited).unwrap());
+        assert!(second.deltas.windows(2).all(|w| w[0].time <= w[1].time));
+        assert!(first.assess(later).amount() > second.assess(later).amount());
+
+        bulk.undo().unwrap();
+        assert!(bulk.get(edited).unwrap().deltas.is_empty());
+        bulk.remove(loaded);
+        assert!(!bulk.bulk_edit(loaded, |_| {}));
+    }
+
+    /// Linking a child that already has a parent moves it rather than
+    /// counting it under both.
+    #[test]
+    pub fn test_link_child_moves() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let chequing = book.add(Item::fixed(Value::new("CAD", 1_000.0), start));
+        let savings = book.add(Item::fixed(Value::new("CAD", 5_000.0), start));
+        let bonus = book.add(Item::fixed(Value::new("CAD", 100.0), start));
+
+        assert!(book.link_child(chequing, bonus));
+        assert!(book.link_child(savings, bonus));
+        assert_eq!(book.parent(bonus), Some(savings));
+        assert!(book.get(chequing).unwrap().children.is_empty());
+        assert_eq!(book.get(savings).unwrap().children, vec![bonus]);
     }
 }
@@ -0,0 +1,168 @@
+//! Envelope budgeting on top of [Book::cash_flow].
+
+use chrono::{DateTime, Utc};
+
+use super::{book::Book, delta::Granularity, value::Value, Assesible};
+
+/// Spending limits per category for every period.
+#[derive(Clone, Debug)]
+pub struct Budget {
+    pub period: Granularity,
+    /// A category such as `Housing` and what can be spent on it in a
+    /// period. An envelope also covers its subcategories, such as
+    /// `Housing:Rent`, the first envelope that matches wins.
+    pub envelopes: Vec<(String, Value)>,
+}
+
+impl Budget {
+    /// The index of the envelope a category falls into.
+    fn envelope_of(&self, category: &str) -> Option<usize> {
+        self.envelopes.iter().position(|(envelope, _)| {
+            category
+                .strip_prefix(envelope.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+        })
+    }
+}
+
+/// What was spent from one envelope in one period, see [Book::budget_report].
+#[derive(Clone, Debug)]
+pub struct EnvelopeReport {
+    pub start: DateTime<Utc>,
+    /// The start of the next period.
+    pub end: DateTime<Utc>,
+    /// The envelope, `None` for spending no envelope covers.
+    pub category: Option<String>,
+    /// `None` for spending no envelope covers.
+    pub limit: Option<Value>,
+    /// The outflows less refunds, never below zero.
+    pub spent: Value,
+    /// How much more than the limit was spent, `None` if it was not.
+    pub overage: Option<Value>,
+}
+
+impl EnvelopeReport {
+    pub fn is_overspent(&self) -> bool {
+        self.overage.is_some()
+    }
+}
+
+impl Book {
+    /// Compares spending against a budget for every period from `from` up
+    /// to and including `to`, in the currency of the book. Each period has a
+    /// row per envelope in order followed by one for uncategorized spending.
+    ///
+    /// Spending is the net outflow of each category of the
+    /// [Book::cash_flow], so a category that took more in than it paid out
+    /// spent nothing and does not make up for others.
+    pub fn budget_report(
+        &self,
+        budget: &Budget,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<EnvelopeReport> {
+        let currency = self.currency();
+        let mut rows = vec![];
+        for period in self.cash_flow(from, to, budget.period) {
+            let mut spent = vec![0.0; budget.envelopes.len() + 1];
+            for (category, net) in &period.categories {
+                let envelope = category
+                    .as_deref()
+                    .and_then(|c| budget.envelope_of(c))
+                    .unwrap_or(budget.envelopes.len());
+                spent[envelope] += (-net.amount()).max(0.0);
+            }
+
+            let envelopes = budget.envelopes.iter().map(|(category, limit)| {
+                let rate = self
+                    .report_rate(limit.currency(), period.start)
+                    .unwrap_or_else(|e| panic!("{e}"));
+                (Some(category.clone()), Some(limit.amount() * rate))
+            });
+            for ((category, limit), spent) in envelopes.chain([(None, None)]).zip(spent) {
+                let over = limit.map(|limit| spent - limit).filter(|over| *over > 0.0);
+                rows.push(EnvelopeReport {
+                    start: period.start,
+                    end: period.end,
+                    category,
+                    limit: limit.map(|limit| Value::new(currency, limit)),
+                    spent: Value::new(currency, spent),
+                    overage: over.map(|over| Value::new(currency, over)),
+                });
+            }
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::instruments::{
+        book::Book,
+        convert::CURRENCY_EXCHANGE,
+        delta::{Delta, Granularity},
+        item::Item,
+        value::Value,
+    };
+
+    use super::Budget;
+
+    #[test]
+    pub fn test_overspent_envelope() {
+        CURRENCY_EXCHANGE.add_conversion("BGU", "BGC", 1.25);
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let mut chequing = Item::fixed(Value::new("BGC", 5_000.0), on(1, 1));
+        for (time, amount, category) in [
+            (on(1, 5), Value::new("BGC", -300.0), Some("Food:Groceries")),
+            (
+                on(1, 20),
+                Value::new("BGC", -150.0),
+                Some("Food:Restaurants"),
+            ),
+            (on(1, 25), Value::new("BGC", -80.0), Some("Fun")),
+            (on(2, 3), Value::new("BGC", -400.0), Some("Food")),
+            (
+                on(2, 10),
+                Value::new("BGU", -200.0),
+                Some("Food:Restaurants"),
+            ),
+            (on(2, 12), Value::new("BGC", 20.0), Some("Fun")),
+            (on(2, 15), Value::new("BGC", 2_000.0), Some("Income")),
+            (on(2, 28), Value::new("BGC", -60.0), None),
+        ] {
+            let mut delta = Delta::payment(time, amount);
+            delta.category = category.map(str::to_string);
+            chequing.push_delta(delta);
+        }
+        book.add(chequing);
+        let budget = Budget {
+            period: Granularity::Monthly,
+            envelopes: vec![
+                ("Food".to_string(), Value::new("BGC", 600.0)),
+                ("Fun".to_string(), Value::new("BGU", 80.0)),
+            ],
+        };
+
+        let report = book.budget_report(&budget, on(1, 1), on(2, 29));
+        assert_eq!(report.len(), 6);
+        let row = |i: usize| {
+            let r = &report[i];
+            (r.spent.amount(), r.overage.as_ref().map(Value::amount))
+        };
+        assert_eq!(row(0), (450.0, None));
+        assert_eq!(row(1), (80.0, None));
+        assert_eq!(row(2), (0.0, None));
+
+        // 400 plus 200 BGU at 1.25 is 50 over the food envelope.
+        assert!(report[3].is_overspent());
+        assert_eq!(row(3), (650.0, Some(50.0)));
+        assert_eq!(report[3].limit.as_ref().unwrap().amount(), 600.0);
+        // Refunds and income do not count as spending.
+        assert_eq!(row(4), (0.0, None));
+        assert_eq!(report[5].category, None);
+        assert_eq!(row(5), (60.0, None));
+    }
+}
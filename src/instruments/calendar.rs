@@ -0,0 +1,296 @@
+//! Turning calendar days into the instants assessments are made at.
+
+use chrono::{
+    DateTime, Datelike, LocalResult, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+
+/// Which instant of a day a date stands for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DayPolicy {
+    /// The first instant of the day.
+    StartOfDay,
+    /// The last instant of the day, so anything dated that day is included.
+    EndOfDay,
+}
+
+impl DayPolicy {
+    /// The instant the policy picks for a day in UTC.
+    pub fn utc(self, date: NaiveDate) -> DateTime<Utc> {
+        self.instant_in(date, &Utc)
+    }
+    /// The instant the policy picks for a day in the given time zone. Days
+    /// that start in a DST gap start at the first local time that exists.
+    pub fn instant_in<Tz: TimeZone>(self, date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+        match self {
+            DayPolicy::StartOfDay => start_of_day(date, tz),
+            DayPolicy::EndOfDay => {
+                let next = date.succ_opt().expect("date out of range");
+                start_of_day(next, tz) - TimeDelta::nanoseconds(1)
+            }
+        }
+    }
+}
+
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+    // Gaps are at most a few hours, step through the day until a local
+    // time exists.
+    let mut local = date.and_time(NaiveTime::MIN);
+    loop {
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(time) => return time.with_timezone(&Utc),
+            LocalResult::Ambiguous(earliest, _) => return earliest.with_timezone(&Utc),
+            LocalResult::None => local += TimeDelta::minutes(15),
+        }
+    }
+}
+
+/// Days payments are not made on besides weekends.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HolidayCalendar {
+    /// Sorted and without repeats.
+    holidays: Vec<NaiveDate>,
+}
+
+impl HolidayCalendar {
+    pub fn new(holidays: impl IntoIterator<Item = NaiveDate>) -> Self {
+        let mut holidays: Vec<_> = holidays.into_iter().collect();
+        holidays.sort();
+        holidays.dedup();
+        Self { holidays }
+    }
+    pub fn holidays(&self) -> &[NaiveDate] {
+        &self.holidays
+    }
+    /// Whether the date is neither on a weekend nor a holiday.
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && self.holidays.binary_search(&date).is_err()
+    }
+}
+
+/// Where a scheduled payment that lands on a day off moves to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BusinessDayConvention {
+    /// It stays where it is.
+    #[default]
+    None,
+    /// The next business day.
+    Following,
+    /// The next business day, unless that is in the next month in which
+    /// case the previous one.
+    ModifiedFollowing,
+    /// The previous business day.
+    Preceding,
+}
+
+impl BusinessDayConvention {
+    /// The date a payment scheduled on `date` is made on.
+    pub fn adjust(self, date: NaiveDate, calendar: &HolidayCalendar) -> NaiveDate {
+        let step = |mut date: NaiveDate, forwards: bool| {
+            while !calendar.is_business_day(date) {
+                date = match forwards {
+                    true => date.succ_opt(),
+                    false => date.pred_opt(),
+                }
+                .expect("date out of range");
+            }
+            date
+        };
+        match self {
+            BusinessDayConvention::None => date,
+            BusinessDayConvention::Following => step(date, true),
+            BusinessDayConvention::Preceding => step(date, false),
+            BusinessDayConvention::ModifiedFollowing => match step(date, true) {
+                following if following.month() == date.month() => following,
+                _ => step(date, false),
+            },
+        }
+    }
+}
+
+/// A convention and the holidays it skips, applied to the dates of a
+/// schedule, see [Payout::adjusted](super::item::Payout::adjusted).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BusinessDays {
+    pub convention: BusinessDayConvention,
+    pub calendar: HolidayCalendar,
+}
+
+impl BusinessDays {
+    pub fn new(convention: BusinessDayConvention, calendar: HolidayCalendar) -> Self {
+        Self {
+            convention,
+            calendar,
+        }
+    }
+    /// Moves the time to the day the convention picks, keeping its time of
+    /// day.
+    pub fn adjust(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let date = time.date_naive();
+        time + (self.convention.adjust(date, &self.calendar) - date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        item::{Item, Payout, Proration},
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{BusinessDayConvention, BusinessDays, DayPolicy, HolidayCalendar};
+
+    #[test]
+    pub fn test_assess_on() {
+        let date = NaiveDate::from_ymd_opt(2020, 3, 31).unwrap();
+        assert_eq!(
+            DayPolicy::EndOfDay.utc(date),
+            Utc.with_ymd_and_hms(2020, 4, 1, 0, 0, 0).unwrap() - TimeDelta::nanoseconds(1)
+        );
+
+        let mut item = Item::fixed_on(
+            Value::dummy("CAD", 100.0),
+            NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        );
+        item.add_delta(
+            Utc.with_ymd_and_hms(2020, 3, 31, 23, 0, 0).unwrap(),
+            Value::dummy("CAD", 5.0),
+        );
+        let value = item.assess_on(date, DayPolicy::EndOfDay);
+        assert_value_eq!(value, Value::new("CAD", 105.0), 0.005);
+
+        let debt = Item::basic_debt_on(
+            Value::dummy("CAD", -100.0),
+            0.1,
+            TimeDelta::days(365),
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        );
+        let start = debt.assess_on(
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            DayPolicy::StartOfDay,
+        );
+        assert_eq!(start.amount(), -100.0);
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    pub fn test_spring_forward() {
+        use chrono_tz::America::{Santiago, Toronto};
+
+        // Toronto skips 02:00 to 03:00 on this day.
+        let date = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        assert_eq!(
+            DayPolicy::StartOfDay.instant_in(date, &Toronto),
+            Utc.with_ymd_and_hms(2024, 3, 10, 5, 0, 0).unwrap()
+        );
+        assert_eq!(
+            DayPolicy::EndOfDay.instant_in(date, &Toronto),
+            Utc.with_ymd_and_hms(2024, 3, 11, 4, 0, 0).unwrap() - TimeDelta::nanoseconds(1)
+        );
+
+        // Santiago skips midnight itself, the day starts at 01:00.
+        let date = NaiveDate::from_ymd_opt(2024, 9, 8).unwrap();
+        assert_eq!(
+            DayPolicy::StartOfDay.instant_in(date, &Santiago),
+            Utc.with_ymd_and_hms(2024, 9, 8, 4, 0, 0).unwrap()
+        );
+
+        let item = Item::fixed_on(Value::dummy("CAD", 1.0), date);
+        assert_eq!(item.assess_local(date, Toronto).amount(), 1.0);
+    }
+
+    #[test]
+    pub fn test_business_day_adjustment() {
+        let on = |m, d| Utc.with_ymd_and_hms(2025, m, d, 0, 0, 0).unwrap();
+        let start = on(1, 31);
+        let until = on(12, 31);
+        let schedule = |days: &BusinessDays| {
+            Item::builder(Value::new("CAD", 0.0), start)
+                .recurring_payment_adjusted(
+                    start,
+                    Period::Months(1),
+                    until,
+                    Value::new("CAD", -1_000.0),
+                    days,
+                )
+                .build()
+                .deltas
+                .iter()
+                .map(|d| d.time)
+                .collect::<Vec<_>>()
+        };
+        let labour_day = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let calendar = HolidayCalendar::new([labour_day]);
+        assert!(!calendar.is_business_day(labour_day));
+
+        // May 31, Aug 31 and Nov 30 are on weekends.
+        let plain = schedule(&BusinessDays::default());
+        assert_eq!(plain.len(), 12);
+        assert_eq!(
+            (plain[4], plain[7], plain[10]),
+            (on(5, 31), on(8, 31), on(11, 30))
+        );
+
+        let following = schedule(&BusinessDays::new(
+            BusinessDayConvention::Following,
+            HolidayCalendar::default(),
+        ));
+        assert_eq!(following.len(), 12);
+        assert_eq!(following[4], on(6, 2));
+        assert_eq!(following[7], on(9, 1));
+        assert_eq!(following[10], on(12, 1));
+        assert_eq!(following[5], on(6, 30));
+
+        // Labour day pushes the August payment one more day.
+        let with_holiday = schedule(&BusinessDays::new(
+            BusinessDayConvention::Following,
+            calendar.clone(),
+        ));
+        assert_eq!(with_holiday[7], on(9, 2));
+
+        // Following would leave the month, so the payments roll back.
+        let modified = schedule(&BusinessDays::new(
+            BusinessDayConvention::ModifiedFollowing,
+            calendar.clone(),
+        ));
+        assert_eq!(modified[4], on(5, 30));
+        assert_eq!(modified[7], on(8, 29));
+        assert_eq!(modified[10], on(11, 28));
+        assert_eq!(modified[11], on(12, 31));
+
+        // The payout schedule moves the same way.
+        let days = BusinessDays::new(BusinessDayConvention::ModifiedFollowing, calendar);
+        let payout = Payout::FixedRecurring {
+            amount: Value::new("CAD", 50.0),
+            start,
+            frequency: Period::Months(1),
+            proration: Proration::None,
+        }
+        .adjusted(days.clone());
+        let times: Vec<_> = payout.occurrences(start, until).map(|(t, _)| t).collect();
+        assert_eq!(times, modified);
+
+        // Interest runs on the adjusted days.
+        let loan = |days: &BusinessDays| {
+            Item::builder(Value::new("CAD", -20_000.0), start)
+                .interest(0.1, Period::Years(1))
+                .recurring_payment_adjusted(
+                    start,
+                    Period::Months(1),
+                    until,
+                    Value::new("CAD", 1_000.0),
+                    days,
+                )
+                .build()
+        };
+        let late = BusinessDays::new(BusinessDayConvention::Following, HolidayCalendar::default());
+        let (plain, late) = (loan(&BusinessDays::default()), loan(&late));
+        assert_eq!(late.deltas[4].time, on(6, 2));
+        assert!(late.assess(until).amount() < plain.assess(until).amount());
+    }
+}
@@ -0,0 +1,135 @@
+//! The cash a book's payouts settle into, one account per currency.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    audit::AuditEntry,
+    book::Book,
+    delta::Delta,
+    item::{Interest, Item},
+    value::{Currency, Value},
+    Assesible, AssessmentResult,
+};
+
+impl Book {
+    /// Has the cash settled in `currency` earn `interest`, compounding from
+    /// each settlement. `None` leaves the cash flat, as it is by default.
+    pub fn set_cash_interest(&mut self, currency: Currency, interest: Option<Interest>) {
+        match interest.clone() {
+            Some(interest) => self.cash_interest.insert(currency, interest),
+            None => self.cash_interest.remove(&currency),
+        };
+        self.audit.push(AuditEntry::CashInterestSet { currency, interest });
+    }
+    pub fn cash_interest(&self, currency: Currency) -> Option<&Interest> {
+        self.cash_interest.get(&currency)
+    }
+    /// The account the payouts and sales of every item paid in `currency`
+    /// by `until` settle into, an item starting empty at the first
+    /// settlement with one payment per settlement and the
+    /// [Book::cash_interest] of the currency.
+    pub fn cash_account(&self, currency: Currency, until: DateTime<Utc>) -> Item {
+        let settlements: Vec<Delta> = self
+            .iter_ordered()
+            .flat_map(|(_, item)| item.settlements(until))
+            .filter(|(_, value)| value.currency() == currency)
+            .map(|(on, value)| Delta::payment(on, value))
+            .collect();
+        let opened = settlements.iter().map(|d| d.time).min().unwrap_or(until);
+        let mut account = Item::fixed(Value::new(currency, 0.0), opened);
+        account.set_interest(self.cash_interest(currency).cloned());
+        account.import_deltas(settlements);
+        account
+    }
+    /// Assesses every item with [Item::assess_full] in the
+    /// [Book::report_currency]. The cash is what the cash accounts hold at
+    /// the time, settlements and the interest they earned, kept by currency
+    /// in [AssessmentResult::cash_by_currency].
+    pub fn assess_full(&self, time: DateTime<Utc>) -> AssessmentResult {
+        let base = self.currency();
+        let convert = |value: &Value| {
+            let rate = self
+                .rate_into(value.currency(), base, time)
+                .unwrap_or_else(|e| panic!("{e}"));
+            value.amount() * rate
+        };
+        let mut value = 0.0;
+        let mut currencies = BTreeMap::new();
+        for (_, item) in self.iter_ordered() {
+            let full = item.assess_full(time);
+            let rate = self
+                .item_rate(item, item.currency(), base, time)
+                .unwrap_or_else(|e| panic!("{e}"));
+            value += full.value().amount() * rate;
+            for cash in full.cash_by_currency() {
+                currencies.insert(cash.currency().code(), cash.currency());
+            }
+        }
+        let ledger: Vec<Value> = currencies
+            .into_values()
+            .map(|currency| self.cash_account(currency, time).assess(time))
+            .collect();
+        let cash = ledger.iter().map(convert).sum::<f64>();
+        AssessmentResult::with_ledger(Value::new(base, value), Value::new(base, cash), ledger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::{Interest, Item, Payout, Proration},
+        period::Period,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    #[test]
+    pub fn test_cash_interest() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let quarter = Period::Months(3);
+        let end = Period::Years(2).step(start, 1);
+        let dividends = Payout::FixedRecurring {
+            amount: Value::new("CAD", 250.0),
+            start: quarter.step(start, 1),
+            frequency: quarter,
+            proration: Proration::None,
+        };
+        let mut book = Book::default();
+        book.add_named(
+            "Shares",
+            Item::builder(Value::new("CAD", 20_000.0), start)
+                .payout(dividends)
+                .build(),
+        );
+        let cad = Currency::new("CAD");
+        let savings = Interest::new(0.03, Period::Years(1));
+        book.set_cash_interest(cad, Some(savings.clone()));
+
+        // The same dividends paid by hand into a 3% savings account.
+        let mut manual = Item::fixed(Value::new("CAD", 0.0), quarter.step(start, 1));
+        manual.set_interest(Some(savings));
+        for k in 1..=8 {
+            manual.add_delta(quarter.step(start, k), Value::new("CAD", 250.0));
+        }
+        let account = book.cash_account(cad, end);
+        assert_eq!(account.deltas.len(), 8);
+        assert_value_eq!(account.assess(end), manual.assess(end), 1e-9);
+        assert!(manual.assess(end).amount() > 2_000.0);
+
+        let full = book.assess_full(end);
+        assert_value_eq!(full.value().clone(), Value::new("CAD", 20_000.0), 1e-9);
+        assert_value_eq!(full.cash().clone(), manual.assess(end), 1e-9);
+        assert_eq!(full.cash_by_currency().len(), 1);
+
+        // Flat cash is just the dividends.
+        book.set_cash_interest(cad, None);
+        let flat = book.assess_full(end).cash().clone();
+        assert_value_eq!(flat, Value::new("CAD", 2_000.0), 1e-9);
+    }
+}
@@ -0,0 +1,185 @@
+//! Money in and money out of a book over time.
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    book::Book,
+    delta::Granularity,
+    value::{Currency, MultiCurrencySum, Value},
+    Assesible,
+};
+
+/// The cash that moved in one period of a [Book::cash_flow].
+#[derive(Clone, Debug)]
+pub struct CashFlowPeriod {
+    pub start: DateTime<Utc>,
+    /// The start of the next period.
+    pub end: DateTime<Utc>,
+    /// The total of every positive flow.
+    pub inflows: Value,
+    /// The total of every negative flow, as a positive amount.
+    pub outflows: Value,
+    pub net: Value,
+    /// The net of each category in the order they first appear, payouts and
+    /// deltas without a category are under `None`.
+    pub categories: Vec<(Option<String>, Value)>,
+    /// The net in each currency the flows were in, before conversion.
+    pub by_currency: MultiCurrencySum,
+}
+
+impl CashFlowPeriod {
+    fn new(start: DateTime<Utc>, end: DateTime<Utc>, currency: Currency) -> Self {
+        Self {
+            start,
+            end,
+            inflows: Value::zero(currency),
+            outflows: Value::zero(currency),
+            net: Value::zero(currency),
+            categories: vec![],
+            by_currency: MultiCurrencySum::default(),
+        }
+    }
+    fn record(&mut self, category: Option<&str>, amount: f64) {
+        let currency = self.net.currency();
+        if amount >= 0.0 {
+            self.inflows = Value::new(currency, self.inflows.amount() + amount);
+        } else {
+            self.outflows = Value::new(currency, self.outflows.amount() - amount);
+        }
+        self.net = Value::new(currency, self.net.amount() + amount);
+        match self
+            .categories
+            .iter_mut()
+            .find(|(c, _)| c.as_deref() == category)
+        {
+            Some((_, total)) => *total = Value::new(currency, total.amount() + amount),
+            None => self
+                .categories
+                .push((category.map(str::to_string), Value::new(currency, amount))),
+        }
+    }
+}
+
+impl Book {
+    /// The cash that moved each period from `from` up to and including `to`,
+    /// in the currency of the book at the rates of its reporting policy. Flows are the deltas on every item, as
+    /// seen from that item, and the payouts they pay. Interest accrues without
+    /// any money moving so it is left out.
+    ///
+    /// A payment recorded both on a chequing account and on the loan it pays
+    /// cancels out, record it on one side only to see it here.
+    pub fn cash_flow(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Vec<CashFlowPeriod> {
+        let currency = self.currency();
+        let mut periods = vec![];
+        let mut start = granularity.bucket_start(from);
+        while start <= to {
+            let end = granularity.next_bucket(start);
+            periods.push(CashFlowPeriod::new(start, end, currency));
+            start = end;
+        }
+
+        let convert = |item, time: DateTime<Utc>, value: &Value| {
+            self.item_report_rate(item, value.currency(), time)
+                .unwrap_or_else(|e| panic!("{e}"))
+                * value.amount()
+        };
+        let mut record = |item, time: DateTime<Utc>, category: Option<&str>, value: &Value| {
+            if from <= time && time <= to {
+                let index = periods.partition_point(|p| p.end <= time);
+                periods[index].record(category, convert(item, time, value));
+                periods[index].by_currency.add(value);
+            }
+        };
+        for (_, item) in self.iter_ordered() {
+            for (delta, change) in item.delta_amounts() {
+                record(item, delta.time, delta.category.as_deref(), &change);
+            }
+            for payout in &item.payouts {
+                for (time, value) in payout.occurrences(item.inception, to) {
+                    record(item, time, None, &value);
+                }
+            }
+        }
+        periods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{
+        book::Book,
+        delta::{Delta, Granularity},
+        item::Item,
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    /// Salary in, rent and a loan payment out of a chequing account every
+    /// month, with interest on the loan that is not cash.
+    #[test]
+    pub fn test_monthly_cash_flow() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut chequing = Item::fixed(Value::new("CAD", 1_000.0), start);
+        for month in 0..3 {
+            let on = |day| {
+                Period::Months(1).step(Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(), month)
+            };
+            for (day, amount, category) in [
+                (1, -2_000.0, "Housing:Rent"),
+                (15, 5_000.0, "Income:Salary"),
+                (20, -500.0, "Debt:Loan"),
+            ] {
+                let mut delta = Delta::payment(on(day), Value::new("CAD", amount));
+                delta.category = Some(category.to_string());
+                chequing.push_delta(delta);
+            }
+        }
+        let mut book = Book::default();
+        let chequing = book.add(chequing);
+        book.add(Item::basic_debt(
+            Value::new("CAD", -10_000.0),
+            0.01,
+            Period::Months(1),
+            start,
+        ));
+
+        let end = Utc.with_ymd_and_hms(2024, 3, 31, 23, 59, 59).unwrap();
+        let flows = book.cash_flow(start, end, Granularity::Monthly);
+        assert_eq!(flows.len(), 3);
+        for period in &flows {
+            assert_eq!(period.inflows.amount(), 5_000.0);
+            assert_eq!(period.outflows.amount(), 2_500.0);
+            assert_eq!(period.net.amount(), 2_500.0);
+            let by_currency: Vec<_> = period.by_currency.totals().collect();
+            assert_eq!(by_currency.len(), 1);
+            assert_eq!(by_currency[0].1.amount(), 2_500.0);
+            let lines = period
+                .categories
+                .iter()
+                .map(|(c, v)| (c.as_deref().unwrap(), v.amount()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                lines,
+                [
+                    ("Housing:Rent", -2_000.0),
+                    ("Income:Salary", 5_000.0),
+                    ("Debt:Loan", -500.0)
+                ]
+            );
+        }
+
+        // The nets add up to the change in the chequing account.
+        let chequing = book.get(chequing).unwrap();
+        let total: f64 = flows.iter().map(|p| p.net.amount()).sum();
+        let before = chequing.assess(start - TimeDelta::seconds(1)).amount();
+        assert_eq!(total, chequing.assess(end).amount() - before);
+    }
+}
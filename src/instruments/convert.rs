@@ -1,36 +1,548 @@
-use std::sync::RwLock;
-use lazy_static::lazy_static;
-use super::value::{Currency, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use super::{benchmark::BenchmarkId, inflation::index_at, value::{Currency, Value}};
 
-lazy_static! {
-    /// A current exchange.
-    pub static ref CURRENCY_EXCHANGE: ConversionTable = ConversionTable::new();
+/// A current exchange.
+pub static CURRENCY_EXCHANGE: Lazy<ConversionTable> = Lazy::new(ConversionTable::new);
+
+/// Errors that occur while converting between currencies, and so while
+/// assessing items.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// There is no registered rate between the two currencies.
+    NoRate { from: Currency, to: Currency },
+    /// The time is outside of the dated rates and the table does not
+    /// extrapolate, see [Extrapolation::Error].
+    OutOfRange {
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+    },
+    /// Indexed interest floats over a benchmark that is not registered in
+    /// [BENCHMARKS](super::benchmark::BENCHMARKS) or has no rates yet.
+    NoBenchmark(BenchmarkId),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::NoRate { from, to } => {
+                write!(f, "no conversion rate from {from} to {to}")
+            }
+            ConversionError::OutOfRange { from, to, time } => {
+                write!(f, "no conversion rate from {from} to {to} known around {time}")
+            }
+            ConversionError::NoBenchmark(id) => write!(f, "no rates for benchmark {}", id.get()),
+        }
+    }
 }
 
-#[derive(Debug)]
+impl std::error::Error for ConversionError {}
+
+/// Which rate a report converts at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RatePolicy {
+    /// The rate in effect at the time being assessed.
+    RateAtAssessmentDate,
+    /// The rate in effect at a fixed date, whatever the time assessed.
+    RateAtFixedDate(DateTime<Utc>),
+    /// The most recent rate known.
+    Latest,
+}
+
+/// How a rate is read between two dated rates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RateInterpolation {
+    /// The earlier rate holds until the next one.
+    #[default]
+    Previous,
+    /// The rate moves in a straight line between the two. This is done on
+    /// the rate for the direction asked, so between dates the two
+    /// directions are not exact inverses of each other.
+    Linear,
+    /// Whichever rate is closer in time, the earlier one when halfway.
+    Nearest,
+}
+
+/// What a dated rate lookup does outside of the known dates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Extrapolation {
+    /// The first or last rate is used.
+    #[default]
+    Clamp,
+    /// Fails with [ConversionError::OutOfRange].
+    Error,
+}
+
+/// The rates from one currency to another and the dates they took effect,
+/// sorted by those dates.
+type DatedRates = Vec<(DateTime<Utc>, f64)>;
+
+/// A currency replaced by another, `factor` old units making a new one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Redenomination {
+    old: Currency,
+    new: Currency,
+    factor: f64,
+    effective: DateTime<Utc>,
+}
+
+/// Undated rates frozen at a point in time. Values attached to a snapshot
+/// with [Value::rebased] convert at its rates however the [Exchange] it
+/// came from changes afterwards. Cloning is cheap.
+#[derive(Clone, Debug, Default)]
+pub struct RateSnapshot {
+    mappings: Arc<Vec<(Currency, Currency, f64)>>,
+}
+
+impl RateSnapshot {
+    /// The factor to multiply an amount in `from` by to get it in `to`.
+    pub fn rate(&self, from: Currency, to: Currency) -> Result<f64, ConversionError> {
+        if from == to {
+            return Ok(1.0);
+        }
+        self.mappings
+            .iter()
+            .find(|(source, target, _)| *source == from && *target == to)
+            .map(|(_, _, factor)| *factor)
+            .ok_or(ConversionError::NoRate { from, to })
+    }
+    /// Convert a piece of currency at the rates of the snapshot, the result
+    /// is attached to the snapshot.
+    pub fn try_convert(&self, value: &Value, target: Currency) -> Result<Value, ConversionError> {
+        let rate = self.rate(value.currency(), target)?;
+        Ok(Value::new(target, value.amount() * rate).rebased(self))
+    }
+}
+
+/// Undated rates that change over time, every change makes a new
+/// [RateSnapshot] and the ones handed out before keep their rates.
+#[derive(Debug, Default)]
+pub struct Exchange {
+    current: RwLock<RateSnapshot>,
+}
+
+impl Exchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the rate between the two, replacing any rate between them.
+    pub fn add_conversion(
+        &self,
+        source: impl Into<Currency>,
+        target: impl Into<Currency>,
+        factor: f64,
+    ) {
+        let (source, target) = (source.into(), target.into());
+        let pair = |from, to| (from, to) == (source, target) || (from, to) == (target, source);
+        let mut current = self.current.write().unwrap();
+        let mut mappings: Vec<_> = current
+            .mappings
+            .iter()
+            .filter(|(from, to, _)| !pair(*from, *to))
+            .copied()
+            .collect();
+        mappings.push((source, target, factor));
+        mappings.push((target, source, 1.0 / factor));
+        *current = RateSnapshot {
+            mappings: Arc::new(mappings),
+        };
+    }
+    /// The rates as they are now.
+    pub fn snapshot(&self) -> RateSnapshot {
+        self.current.read().unwrap().clone()
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct ConversionTable {
-    mappings: RwLock<Vec<(Currency, Currency, f64)>>
+    /// The undated rates, replaced rather than changed in place while a
+    /// [RateSnapshot] of them is held.
+    mappings: RwLock<RateSnapshot>,
+    /// Rates that took effect at a date by the currencies they convert
+    /// from and to.
+    dated: RwLock<HashMap<(Currency, Currency), DatedRates>>,
+    /// How [ConversionTable::rate_at] reads the dated rates.
+    lookup: RwLock<(RateInterpolation, Extrapolation)>,
+    /// Shared so a lookup can hold on to them while it looks up other rates.
+    redenominations: RwLock<Arc<Vec<Redenomination>>>,
 }
 
 impl ConversionTable {
     pub fn new() -> Self {
         Self {
-            mappings: RwLock::default()
+            mappings: RwLock::default(),
+            dated: RwLock::default(),
+            lookup: RwLock::default(),
+            redenominations: RwLock::default(),
         }
     }
+    /// Sets the rate between the two, replacing any rate between them.
     pub fn add_conversion(&self, source: impl Into<Currency>, target: impl Into<Currency>, factor: f64) {
-        let mut mappings = self.mappings.write().unwrap();
+        let mut current = self.mappings.write().unwrap();
+        let mappings = Arc::make_mut(&mut current.mappings);
         let (source, target) = (source.into(), target.into());
 
+        let pair = |from, to| (from, to) == (source, target) || (from, to) == (target, source);
+        mappings.retain(|(from, to, _)| !pair(*from, *to));
         mappings.push((source, target, factor));
         mappings.push((target, source, 1.0 / factor));
     }
+    /// The undated rates as they are now, values converted by the table are
+    /// attached to such a snapshot.
+    pub fn snapshot(&self) -> RateSnapshot {
+        self.mappings.read().unwrap().clone()
+    }
+    /// Adds a rate that takes effect at a date, these are only used through
+    /// [ConversionTable::rate_at] and the other dated lookups.
+    pub fn add_conversion_on(
+        &self,
+        source: impl Into<Currency>,
+        target: impl Into<Currency>,
+        on: DateTime<Utc>,
+        factor: f64,
+    ) {
+        let mut dated = self.dated.write().unwrap();
+        let (source, target) = (source.into(), target.into());
+
+        for (pair, factor) in [((source, target), factor), ((target, source), 1.0 / factor)] {
+            let rates = dated.entry(pair).or_default();
+            let after = rates.partition_point(|(at, _)| *at <= on);
+            rates.insert(after, (on, factor));
+        }
+    }
+    /// Registers `new` replacing `old` at `effective`, `factor` old units
+    /// making one new one. The two always convert into each other at the
+    /// factor. Dated lookups of other rates go through the old code before
+    /// the effective date and the new one after, so rates only need to be
+    /// known for the code that was in use at the time.
+    pub fn redenominate(
+        &self,
+        old: impl Into<Currency>,
+        new: impl Into<Currency>,
+        factor: f64,
+        effective: DateTime<Utc>,
+    ) {
+        let (old, new) = (old.into(), new.into());
+        self.add_conversion(old, new, 1.0 / factor);
+        Arc::make_mut(&mut self.redenominations.write().unwrap()).push(Redenomination {
+            old,
+            new,
+            factor,
+            effective,
+        });
+    }
+    /// The rate between the two going through the code that was in use at
+    /// the time, `None` if neither is a code out of use at the time.
+    fn redenominated_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+        interpolation: RateInterpolation,
+        extrapolation: Extrapolation,
+    ) -> Option<Result<f64, ConversionError>> {
+        let redenominations = self.redenominations.read().unwrap().clone();
+        let rate = |from, to| self.rate_at_with(from, to, time, interpolation, extrapolation);
+        redenominations.iter().find_map(|r| {
+            let after = time >= r.effective;
+            match (from, to) {
+                (from, to) if from == r.old && after => Some(rate(r.new, to).map(|f| f / r.factor)),
+                (from, to) if from == r.new && !after => Some(rate(r.old, to).map(|f| f * r.factor)),
+                (from, to) if to == r.old && after => Some(rate(from, r.new).map(|f| f * r.factor)),
+                (from, to) if to == r.new && !after => Some(rate(from, r.old).map(|f| f / r.factor)),
+                _ => None,
+            }
+        })
+    }
     /// Convert a piece of currency.
     pub fn convert(&self, value: Value, target: Currency) -> Option<Value> {
-        let handle = self.mappings.read().unwrap();
-        let (_, _, converted) = handle.iter().find(|(from, to, _)| *from == value.currency() && *to == target)?;
-        Some(Value::dummy(target, value.amount() * *converted))
+        self.try_convert(value, target).ok()
+    }
+    /// Convert a piece of currency, reporting which rate was missing on failure.
+    /// The result is attached to the table's current [RateSnapshot].
+    pub fn try_convert(&self, value: Value, target: Currency) -> Result<Value, ConversionError> {
+        self.snapshot().try_convert(&value, target)
+    }
+    /// The factor to multiply an amount in `from` by to get it in `to`.
+    pub fn rate(&self, from: Currency, to: Currency) -> Result<f64, ConversionError> {
+        self.mappings.read().unwrap().rate(from, to)
+    }
+    /// Sets how [ConversionTable::rate_at] reads the dated rates, by default
+    /// it takes the previous rate and clamps outside of them.
+    pub fn set_interpolation(&self, interpolation: RateInterpolation, extrapolation: Extrapolation) {
+        *self.lookup.write().unwrap() = (interpolation, extrapolation);
+    }
+    /// The dated rate at the time read the way the table was set up with
+    /// [ConversionTable::set_interpolation]. Without any dated rates this is
+    /// the undated [ConversionTable::rate].
+    pub fn rate_at(
+        &self,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        let (interpolation, extrapolation) = *self.lookup.read().unwrap();
+        self.rate_at_with(from, to, time, interpolation, extrapolation)
+    }
+    /// Like [ConversionTable::rate_at] with the lookup given for this call.
+    pub fn rate_at_with(
+        &self,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+        interpolation: RateInterpolation,
+        extrapolation: Extrapolation,
+    ) -> Result<f64, ConversionError> {
+        if from == to {
+            return Ok(1.0);
+        }
+        if let Some(rate) = self.redenominated_rate(from, to, time, interpolation, extrapolation) {
+            return rate;
+        }
+        let dated = self.dated.read().unwrap();
+        let rates = dated.get(&(from, to)).map(Vec::as_slice).unwrap_or_default();
+        let (Some(first), Some(last)) = (rates.first(), rates.last()) else {
+            return self.rate(from, to);
+        };
+        if time < first.0 || time > last.0 {
+            return match extrapolation {
+                Extrapolation::Clamp if time < first.0 => Ok(first.1),
+                Extrapolation::Clamp => Ok(last.1),
+                Extrapolation::Error => Err(ConversionError::OutOfRange { from, to, time }),
+            };
+        }
+        let after = rates.partition_point(|(on, _)| *on <= time);
+        if after == rates.len() {
+            return Ok(last.1);
+        }
+        let ((t0, r0), (t1, r1)) = (rates[after - 1], rates[after]);
+        Ok(match interpolation {
+            RateInterpolation::Previous => r0,
+            RateInterpolation::Linear => index_at(rates, time),
+            RateInterpolation::Nearest if time - t0 <= t1 - time => r0,
+            RateInterpolation::Nearest => r1,
+        })
+    }
+    /// The most recent dated rate, or the undated [ConversionTable::rate].
+    pub fn latest_rate(&self, from: Currency, to: Currency) -> Result<f64, ConversionError> {
+        if from == to {
+            return Ok(1.0);
+        }
+        match self.dated.read().unwrap().get(&(from, to)).and_then(|rates| rates.last()) {
+            Some((_, factor)) => Ok(*factor),
+            None => self.rate(from, to),
+        }
+    }
+    /// The rate the policy calls for when assessing at the time.
+    pub fn rate_for(
+        &self,
+        policy: RatePolicy,
+        from: Currency,
+        to: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        match policy {
+            RatePolicy::RateAtAssessmentDate => self.rate_at(from, to, time),
+            RatePolicy::RateAtFixedDate(date) => self.rate_at(from, to, date),
+            RatePolicy::Latest => self.latest_rate(from, to),
+        }
     }
-    
 }
 
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::Item,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{
+        ConversionError, ConversionTable, Exchange, Extrapolation, RateInterpolation,
+        CURRENCY_EXCHANGE,
+    };
+
+    #[test]
+    pub fn test_missing_rate() {
+        let table = ConversionTable::new();
+        table.add_conversion("CAD", "COP", 2911.98);
+
+        assert_eq!(table.rate("CAD".into(), "CAD".into()), Ok(1.0));
+        assert_eq!(
+            table.try_convert(Value::new("CAD", 1.0), Currency::new("USD")).unwrap_err(),
+            ConversionError::NoRate {
+                from: Currency::new("CAD"),
+                to: Currency::new("USD")
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_dated_rates() {
+        let table = ConversionTable::new();
+        let (usd, cad) = (Currency::new("USD"), Currency::new("CAD"));
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        assert!(table.rate_at(usd, cad, on(2020)).is_err());
+
+        table.add_conversion("USD", "CAD", 1.35);
+        assert_eq!(table.rate_at(usd, cad, on(2020)), Ok(1.35));
+
+        table.add_conversion_on("USD", "CAD", on(2025), 1.44);
+        table.add_conversion_on("USD", "CAD", on(2015), 1.16);
+        assert_eq!(table.rate_at(usd, cad, on(2010)), Ok(1.16));
+        assert_eq!(table.rate_at(usd, cad, on(2020)), Ok(1.16));
+        assert_eq!(table.rate_at(usd, cad, on(2025)), Ok(1.44));
+        assert_eq!(table.latest_rate(cad, usd), Ok(1.0 / 1.44));
+        // The undated rate is left alone.
+        assert_eq!(table.rate(usd, cad), Ok(1.35));
+    }
+
+    /// Weekly rates read halfway between two weeks.
+    #[test]
+    pub fn test_rate_interpolation() {
+        let table = ConversionTable::new();
+        let (usd, cad) = (Currency::new("USD"), Currency::new("CAD"));
+        let week = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let next = Utc.with_ymd_and_hms(2024, 1, 8, 0, 0, 0).unwrap();
+        table.add_conversion_on("USD", "CAD", week, 1.30);
+        table.add_conversion_on("USD", "CAD", next, 1.37);
+        let halfway = week + (next - week) / 2;
+
+        assert_eq!(table.rate_at(usd, cad, halfway), Ok(1.30));
+        let read = |time, interpolation| {
+            table.rate_at_with(usd, cad, time, interpolation, Extrapolation::Clamp)
+        };
+        assert!((read(halfway, RateInterpolation::Linear).unwrap() - 1.335).abs() < 1e-12);
+        assert_eq!(read(halfway, RateInterpolation::Nearest), Ok(1.30));
+        assert_eq!(
+            read(halfway + TimeDelta::seconds(1), RateInterpolation::Nearest),
+            Ok(1.37)
+        );
+        assert_eq!(read(next, RateInterpolation::Linear), Ok(1.37));
+
+        // Outside of the known weeks.
+        let later = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        assert_eq!(table.rate_at(usd, cad, later), Ok(1.37));
+        table.set_interpolation(RateInterpolation::Linear, Extrapolation::Error);
+        assert!((table.rate_at(usd, cad, halfway).unwrap() - 1.335).abs() < 1e-12);
+        assert_eq!(
+            table.rate_at(usd, cad, later),
+            Err(ConversionError::OutOfRange {
+                from: usd,
+                to: cad,
+                time: later
+            })
+        );
+        assert_eq!(table.latest_rate(usd, cad), Ok(1.37));
+    }
+
+    /// 1000 old units become one new one in 2025, rates are only known for
+    /// the code in use at the time.
+    #[test]
+    pub fn test_redenomination() {
+        let table = ConversionTable::new();
+        let (old, new, usd) = (Currency::new("RDO"), Currency::new("RDN"), Currency::new("USD"));
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        table.add_conversion_on("USD", "RDO", on(2020), 4_000.0);
+        table.add_conversion_on("RDN", "USD", on(2025), 0.2);
+        table.redenominate(old, new, 1_000.0, on(2025));
+
+        assert_eq!(table.rate(old, new), Ok(0.001));
+        assert_eq!(table.rate_at(new, old, on(2030)), Ok(1_000.0));
+        assert_eq!(table.rate_at(old, usd, on(2024)), Ok(1.0 / 4_000.0));
+        // Old amounts after the change convert through the new code.
+        assert!((table.rate_at(old, usd, on(2026)).unwrap() - 0.0002).abs() < 1e-15);
+        assert!((table.rate_at(usd, old, on(2026)).unwrap() - 5_000.0).abs() < 1e-9);
+        // New amounts before it convert through the old one.
+        assert_eq!(table.rate_at(new, usd, on(2024)), Ok(0.25));
+        assert!(table.rate_at(usd, Currency::new("EUR"), on(2024)).is_err());
+    }
+
+    /// A value keeps the rates it was attached to until it is rebased.
+    #[test]
+    pub fn test_rate_snapshots() {
+        let (rsa, rsb) = (Currency::new("RSA"), Currency::new("RSB"));
+        let exchange = Exchange::new();
+        exchange.add_conversion(rsa, rsb, 2.0);
+        let old = exchange.snapshot();
+        let assessed = Value::new(rsa, 10.0).rebased(&old);
+        exchange.add_conversion(rsb, rsa, 1.0 / 3.0);
+        let new = exchange.snapshot();
+
+        assert_eq!(assessed.convert_to(rsb).unwrap().amount(), 20.0);
+        assert_eq!((assessed.clone() * 2.0).convert_to(rsb).unwrap().amount(), 40.0);
+        let rebased = assessed.rebased(&new);
+        assert_value_eq!(rebased.convert_to(rsb).unwrap(), Value::new(rsb, 30.0), 1e-9);
+        assert_eq!(old.rate(rsa, rsb), Ok(2.0));
+        // Comparisons too, the global exchange has no rate between the two.
+        assert!(Value::new(rsb, 20.0).approx_eq(&assessed, 1e-9));
+        assert!(!Value::new(rsb, 30.0).approx_eq(&assessed, 1e-9));
+        assert!(!Value::new(rsb, 20.0).approx_eq(&Value::new(rsa, 10.0), 1e-9));
+
+        // Sums convert at the rates of the left operand.
+        let left = Value::new(rsb, 0.0).rebased(&new) + assessed.clone();
+        assert_value_eq!(left, Value::new(rsb, 30.0), 1e-9);
+        let left = assessed.clone() + Value::new(rsb, 30.0).rebased(&new);
+        assert_value_eq!(left, Value::new(rsa, 25.0), 1e-9);
+        assert_eq!(
+            Value::new(rsa, 1.0).convert_to(rsb).err(),
+            Some(ConversionError::NoRate { from: rsa, to: rsb })
+        );
+    }
+
+    /// Converted and assessed values are attached to the rates known when
+    /// they were made, rates added later are not seen.
+    #[test]
+    pub fn test_assessed_values_keep_rates() {
+        let (rsc, rsd, rse) = (Currency::new("RSC"), Currency::new("RSD"), Currency::new("RSE"));
+        let table = ConversionTable::new();
+        table.add_conversion(rsc, rsd, 2.0);
+        let converted = table.try_convert(Value::new(rsc, 10.0), rsd).unwrap();
+        table.add_conversion(rsd, rse, 5.0);
+        assert!(converted.convert_to(rse).is_err());
+        assert_eq!(table.try_convert(converted.clone(), rse).unwrap().amount(), 100.0);
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new(rsc, 10.0), start));
+        let assessed = book.assess(start);
+        assert!(assessed.rates().is_some());
+        CURRENCY_EXCHANGE.add_conversion(rsc, rse, 3.0);
+        assert!(assessed.convert_to(rse).is_err());
+        assert_eq!(Value::new(rsc, 10.0).convert_to(rse).unwrap().amount(), 30.0);
+        assert_eq!(book.assess(start).convert_to(rse).unwrap().amount(), 30.0);
+    }
+
+    /// Adding a rate again replaces it, in either direction, and snapshots
+    /// taken before keep the old one.
+    #[test]
+    pub fn test_conversion_replaced() {
+        let (rra, rrb) = (Currency::new("RRA"), Currency::new("RRB"));
+        let table = ConversionTable::new();
+        table.add_conversion(rra, rrb, 2.0);
+        let old = table.snapshot();
+        table.add_conversion(rra, rrb, 4.0);
+        assert_eq!(table.try_convert(Value::new(rra, 10.0), rrb).unwrap().amount(), 40.0);
+        table.add_conversion(rrb, rra, 0.2);
+        assert_value_eq!(
+            table.try_convert(Value::new(rra, 10.0), rrb).unwrap(),
+            Value::new(rrb, 50.0),
+            1e-9
+        );
+        assert_eq!(old.rate(rra, rrb), Ok(2.0));
+
+        CURRENCY_EXCHANGE.add_conversion(rra, rrb, 3.0);
+        CURRENCY_EXCHANGE.add_conversion(rra, rrb, 6.0);
+        assert_eq!(Value::new(rra, 10.0).convert_to(rrb).unwrap().amount(), 60.0);
+    }
+}
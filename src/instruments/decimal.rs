@@ -0,0 +1,422 @@
+//! Exact decimal arithmetic for when "within a cent" is not good enough.
+//!
+//! [DecimalValue] mirrors [Value] but keeps its amount as a [Decimal] so
+//! sums are exact and do not depend on the order they are taken in.
+
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Mul, Sub},
+};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal,
+};
+
+use super::{
+    book::Book,
+    convert::CURRENCY_EXCHANGE,
+    delta::{Delta, DeltaAmount},
+    item::{Interest, Item},
+    period::PeriodAnchor,
+    value::{Currency, Value},
+    Assesible,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecimalValue {
+    currency: Currency,
+    amount: Decimal,
+}
+
+impl DecimalValue {
+    pub fn new<C: Into<Currency>>(currency: C, amount: Decimal) -> Self {
+        Self {
+            currency: currency.into(),
+            amount,
+        }
+    }
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+    pub fn negate(&self) -> Self {
+        Self::new(self.currency, -self.amount)
+    }
+    /// Rounds to the given number of decimal places, using banker's rounding.
+    pub fn round_dp(&self, dp: u32) -> Self {
+        Self::new(self.currency, self.amount.round_dp(dp))
+    }
+    /// Rounds to the minor unit of the currency, see [Currency::decimals].
+    pub fn rounded_to_currency(&self) -> Self {
+        self.round_dp(self.currency.decimals())
+    }
+    /// The amount as a whole number of minor units, rounded like
+    /// [DecimalValue::rounded_to_currency].
+    pub fn to_minor_units(&self) -> i128 {
+        let rounded = self.rounded_to_currency().amount;
+        (rounded * Decimal::from(10u64.pow(self.currency.decimals()))).to_i128().unwrap()
+    }
+    /// Converts into another currency with the global exchange, the rate
+    /// itself is a float so this is only as exact as the rate.
+    pub fn convert(self, target: Currency) -> Self {
+        if self.currency == target {
+            return self;
+        }
+        let rate = CURRENCY_EXCHANGE
+            .rate(self.currency, target)
+            .unwrap_or_else(|e| panic!("{e}"));
+        Self::new(target, self.amount * decimal(rate))
+    }
+}
+
+impl fmt::Debug for DecimalValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.currency)
+    }
+}
+
+/// An amount grew past what a [Decimal] can hold, which compounding over a
+/// long enough span does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecimalOverflow;
+
+impl fmt::Display for DecimalOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the amount does not fit in a decimal")
+    }
+}
+
+impl std::error::Error for DecimalOverflow {}
+
+/// `base` to the power of `exponent` by squaring, `None` if it overflows.
+fn checked_pow(mut base: Decimal, mut exponent: u64) -> Option<Decimal> {
+    let mut result = Decimal::ONE;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.checked_mul(base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(result)
+}
+
+/// A float as a decimal, panicking on NaN and infinities which have no
+/// decimal representation.
+fn decimal(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_else(|| panic!("{value} is not representable as a decimal"))
+}
+
+impl TryFrom<&Value> for DecimalValue {
+    type Error = f64;
+    /// Fails with the amount if it is not finite or out of range.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Decimal::from_f64(value.amount())
+            .map(|amount| Self::new(value.currency(), amount))
+            .ok_or(value.amount())
+    }
+}
+
+impl From<DecimalValue> for Value {
+    fn from(value: DecimalValue) -> Self {
+        Value::new(value.currency, value.amount.to_f64().unwrap())
+    }
+}
+
+impl Add<DecimalValue> for DecimalValue {
+    type Output = DecimalValue;
+    /// The right hand side is converted into the currency of the left.
+    fn add(self, rhs: DecimalValue) -> Self::Output {
+        let rhs = rhs.convert(self.currency);
+        Self::new(self.currency, self.amount + rhs.amount)
+    }
+}
+
+impl Sub<DecimalValue> for DecimalValue {
+    type Output = DecimalValue;
+    fn sub(self, rhs: DecimalValue) -> Self::Output {
+        let rhs = rhs.convert(self.currency);
+        Self::new(self.currency, self.amount - rhs.amount)
+    }
+}
+
+impl Mul<Decimal> for DecimalValue {
+    type Output = DecimalValue;
+    fn mul(self, rhs: Decimal) -> Self::Output {
+        Self::new(self.currency, self.amount * rhs)
+    }
+}
+
+impl Sum<DecimalValue> for Option<DecimalValue> {
+    /// Sums in the currency of the first value, `None` for an empty sum
+    /// like [kahan_sum](super::value::kahan_sum).
+    fn sum<I: Iterator<Item = DecimalValue>>(iter: I) -> Self {
+        iter.reduce(|a, b| a + b)
+    }
+}
+
+impl<'a> Sum<&'a DecimalValue> for Option<DecimalValue> {
+    fn sum<I: Iterator<Item = &'a DecimalValue>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl Interest {
+    /// The decimal counterpart of [Interest::apply]. Whole periods compound by
+    /// squaring in decimals so they are exact, the fractional stub of the
+    /// last period is computed as a float and converted, which keeps it
+    /// reproducible but not exact. Fails if the amount outgrows a [Decimal].
+    pub fn apply_decimal(
+        &self,
+        inception: DateTime<Utc>,
+        current_time: DateTime<Utc>,
+        value: DecimalValue,
+    ) -> Result<DecimalValue, DecimalOverflow> {
+        if self.benchmark().is_some() {
+            // Indexed interest chains the fixed rate spans between changes.
+            if current_time < inception {
+                let segments = self.segments(current_time, inception);
+                return segments
+                    .iter()
+                    .rev()
+                    .try_fold(value, |v, (fixed, start, end)| fixed.apply_decimal(*end, *start, v));
+            }
+            let segments = self.segments(inception, current_time);
+            return segments
+                .iter()
+                .try_fold(value, |v, (fixed, start, end)| fixed.apply_decimal(*start, *end, v));
+        }
+        let (whole, stub) = match self.anchor() {
+            PeriodAnchor::Inception => self.period().split(inception, current_time),
+            _ => {
+                let periods = self.periods(inception, current_time);
+                (periods.floor() as i64, periods - periods.floor())
+            }
+        };
+
+        let rate = Decimal::ONE + decimal(self.rate());
+        let mut factor = checked_pow(rate, whole.unsigned_abs()).ok_or(DecimalOverflow)?;
+        if whole < 0 {
+            factor = Decimal::ONE.checked_div(factor).ok_or(DecimalOverflow)?;
+        }
+        let factor = factor
+            .checked_mul(decimal((1.0 + self.rate()).powf(stub)))
+            .ok_or(DecimalOverflow)?;
+        let amount = value.amount.checked_mul(factor).ok_or(DecimalOverflow)?;
+        Ok(DecimalValue::new(value.currency, amount))
+    }
+}
+
+impl Delta {
+    /// The decimal counterpart of [Delta::resolve].
+    pub fn resolve_decimal(&self, balance: &DecimalValue) -> DecimalValue {
+        match &self.amount {
+            DeltaAmount::Fixed(value) => {
+                DecimalValue::try_from(value).unwrap_or_else(|a| panic!("{a} is not a decimal"))
+            }
+            DeltaAmount::Percent(percent) => *balance * decimal(*percent),
+        }
+    }
+}
+
+impl Item {
+    /// The decimal counterpart of assessing the item, it follows the same
+    /// rules as the float path. Panics if the amount outgrows a [Decimal],
+    /// see [Item::try_assess_decimal].
+    pub fn assess_decimal(&self, time: DateTime<Utc>) -> DecimalValue {
+        self.try_assess_decimal(time).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Assesses the item in decimals, failing if the amount outgrows them.
+    pub fn try_assess_decimal(&self, time: DateTime<Utc>) -> Result<DecimalValue, DecimalOverflow> {
+        let mut book = DecimalValue::try_from(&self.book_value)
+            .unwrap_or_else(|a| panic!("{a} is not a decimal"));
+        match &self.interest {
+            Some(interest) => {
+                let mut incep = self.inception;
+                for delta in self.deltas.iter().take_while(|d| d.time <= time) {
+                    let assessed = interest.apply_decimal(incep, delta.time, book)?;
+                    book = assessed + delta.resolve_decimal(&assessed);
+                    incep = delta.time;
+                }
+                interest.apply_decimal(incep, time, book)
+            }
+            None => {
+                for delta in self.deltas.iter().take_while(|d| d.time <= time) {
+                    book = book + delta.resolve_decimal(&book);
+                }
+                Ok(book)
+            }
+        }
+    }
+}
+
+impl Book {
+    /// The exact total of every item in the book, zero in the book's
+    /// currency if it is empty.
+    pub fn assess_decimal(&self, time: DateTime<Utc>) -> DecimalValue {
+        self.try_assess_decimal(time).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// The exact total of the book, failing if an item outgrows a [Decimal].
+    pub fn try_assess_decimal(&self, time: DateTime<Utc>) -> Result<DecimalValue, DecimalOverflow> {
+        let total = self
+            .iter()
+            .map(|(_, item)| item.try_assess_decimal(time))
+            .sum::<Result<Option<DecimalValue>, _>>()?;
+        Ok(total.unwrap_or_else(|| DecimalValue::new(self.currency(), Decimal::ZERO)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use rust_decimal::Decimal;
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        convert::CURRENCY_EXCHANGE,
+        item::{Interest, Item},
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{DecimalOverflow, DecimalValue};
+
+    fn credit_card(principal: f64, interest: f64) -> Item {
+        Item::basic_debt(
+            Value::new("CAD", -principal),
+            interest,
+            Duration::days(365),
+            Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap(),
+        )
+    }
+
+    #[test]
+    pub fn test_decimal_matches_float() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let card = credit_card(15_000.0, 0.20);
+        let exact = Value::from(card.assess_decimal(time));
+        assert_value_eq!(exact, card.assess(time), 0.01);
+
+        let mut paid = credit_card(1_000.0, 0.20);
+        paid.add_delta(
+            Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(),
+            Value::dummy("CAD", 1000),
+        );
+        let time = Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap();
+        let exact = Value::from(paid.assess_decimal(time));
+        assert_value_eq!(exact, paid.assess(time), 0.01);
+    }
+
+    /// Decimal totals are the same whichever order the items are added in.
+    #[test]
+    pub fn test_decimal_reproducible() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let items = [
+            credit_card(10_000.0, 0.20),
+            credit_card(100.0, 0.02),
+            credit_card(0.1, 0.07),
+            credit_card(0.2, 0.05),
+        ];
+
+        let mut forward = Book::default();
+        items.iter().cloned().for_each(|i| {
+            forward.add(i);
+        });
+        let mut backward = Book::default();
+        items.iter().rev().cloned().for_each(|i| {
+            backward.add(i);
+        });
+
+        assert_eq!(forward.assess_decimal(time), backward.assess_decimal(time));
+        assert_eq!(forward.assess_decimal(time), forward.assess_decimal(time));
+    }
+
+    /// Offsetting trillions leave a few dollars the float path can not get
+    /// right, it warns and the decimal path has the exact residual.
+    #[test]
+    pub fn test_cancellation_warning() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let time = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let mut assets = Item::fixed(Value::new("CAD", 1e12), start);
+        assets.add_delta(start + Duration::days(1), Value::new("CAD", 0.01));
+        let mut debts = Item::fixed(Value::new("CAD", -1e12), start);
+        debts.add_delta(start + Duration::days(1), Value::new("CAD", -0.03));
+        let mut book = Book::default();
+        book.add(assets);
+        book.add(debts);
+        book.add(Item::fixed(Value::new("CAD", 5.0), start));
+
+        let exact = book.assess_decimal(time);
+        assert_eq!(exact.amount(), Decimal::new(498, 2));
+        let assessed = book.try_assess_diagnosed(time).unwrap();
+        assert_eq!(assessed.value.amount(), book.assess(time).amount());
+        assert!((assessed.value.amount() - 4.98).abs() > 1e-6);
+        let warning = assessed.warning.unwrap();
+        assert!(warning.error_bound >= (assessed.value.amount() - 4.98).abs());
+
+        book.set_precision_tolerance(Some(0.01));
+        assert!(book.try_assess_diagnosed(time).unwrap().warning.is_none());
+
+        let mut everyday = Book::default();
+        everyday.add(credit_card(1_000.0, 0.20));
+        everyday.add(Item::fixed(Value::new("CAD", 2_500.0), start));
+        assert!(everyday.try_assess_diagnosed(time).unwrap().warning.is_none());
+    }
+
+    /// Whole periods compound exactly however many there are, and a span
+    /// long enough to outgrow a decimal is an error.
+    #[test]
+    pub fn test_decimal_long_span() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let interest = Interest::new(0.2, Duration::days(365));
+        let one = DecimalValue::new("CAD", Decimal::ONE);
+        let decade = interest.apply_decimal(start, start + Duration::days(3_650), one);
+        assert_eq!(decade.unwrap().amount(), Decimal::new(61_917_364_224, 10));
+
+        // 1.5^250 is around 1.1e44, past the 7.9e28 a decimal holds.
+        let end = start + Duration::days(365 * 250);
+        let steep = Interest::new(0.5, Duration::days(365));
+        assert_eq!(steep.apply_decimal(start, end, one), Err(DecimalOverflow));
+        let card = credit_card(1_000.0, 0.50);
+        assert_eq!(card.try_assess_decimal(end), Err(DecimalOverflow));
+        let mut book = Book::default();
+        book.add(card);
+        assert_eq!(book.try_assess_decimal(end), Err(DecimalOverflow));
+    }
+
+    #[test]
+    pub fn test_decimal_arithmetic() {
+        let a = DecimalValue::new("CAD", Decimal::new(1, 1));
+        let b = DecimalValue::new("CAD", Decimal::new(2, 1));
+        assert_eq!((a + b).amount(), Decimal::new(3, 1));
+        assert_eq!((b - a).amount(), a.amount());
+        assert_eq!(
+            [a, b, a].iter().sum::<Option<DecimalValue>>().unwrap().amount(),
+            Decimal::new(4, 1)
+        );
+    }
+
+    /// Satoshis survive a round trip through a six figure rate.
+    #[test]
+    pub fn test_decimal_crypto_conversion() {
+        let btc = Currency::custom_with_decimals("BTC", 8);
+        CURRENCY_EXCHANGE.add_conversion(btc, "CAD", 91_234.56);
+
+        let holding = DecimalValue::new(btc, Decimal::new(1_530_000, 8));
+        assert_eq!(holding.to_minor_units(), 1_530_000);
+        let cad = holding.convert(Currency::new("CAD"));
+        assert_eq!(cad.amount(), Decimal::new(1_395_888_768, 6));
+        assert_eq!(cad.to_minor_units(), 139_589);
+
+        let back = cad.convert(btc).rounded_to_currency();
+        assert_eq!(back, holding);
+    }
+}
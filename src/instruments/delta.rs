@@ -1,2 +1,566 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::Read,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
 
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeDelta, Utc};
 
+use super::{convert::{ConversionError, CURRENCY_EXCHANGE}, value::{Currency, Value}};
+
+/// Identifies a delta, these are handed out in increasing order so
+/// deltas at the same time apply in the order they were created.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeltaId(u64);
+
+impl DeltaId {
+    /// Hands out a fresh identifier.
+    pub fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, AtomicOrdering::Relaxed))
+    }
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// How much a delta changes an item by.
+#[derive(Clone, Debug)]
+pub enum DeltaAmount {
+    /// A fixed amount of money.
+    Fixed(Value),
+    /// A fraction of the balance at the time the delta is applied,
+    /// so `-0.01` takes one percent off.
+    Percent(f64),
+}
+
+/// What a delta represents, this does not change the math.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum DeltaKind {
+    Payment,
+    Fee,
+    Adjustment,
+    /// Interest written out by [Item::post_interest](super::item::Item::post_interest).
+    Interest,
+}
+
+/// Whether a delta has happened or is only expected to, see
+/// [AssessMode].
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum DeltaStatus {
+    #[default]
+    Actual,
+    /// Recorded in advance, such as next month's rent.
+    Planned,
+}
+
+/// Which deltas an assessment counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AssessMode {
+    /// Only what has actually happened, the current balance.
+    ActualsOnly,
+    /// Planned deltas as well, a projection.
+    #[default]
+    IncludePlanned,
+}
+
+impl AssessMode {
+    /// Whether the delta counts towards an assessment in this mode.
+    pub fn counts(self, delta: &Delta) -> bool {
+        self == AssessMode::IncludePlanned || delta.status == DeltaStatus::Actual
+    }
+}
+
+/// A change to an item at a certain time, these typically
+/// correspond to payments and stuff of the like.
+#[derive(Clone, Debug)]
+pub struct Delta {
+    pub time: DateTime<Utc>,
+    pub amount: DeltaAmount,
+    pub kind: DeltaKind,
+    pub memo: Option<String>,
+    /// A free form category such as `Housing:Rent`, used for budgeting.
+    pub category: Option<String>,
+    pub id: DeltaId,
+    /// The identifier of the transaction in whatever system it was imported
+    /// from, this is what imports deduplicate on.
+    pub external_id: Option<String>,
+    pub status: DeltaStatus,
+    /// The rate a fixed amount in another currency was converted into the
+    /// item's currency at when it was applied, see
+    /// [Book::add_delta_converted](super::book::Book::add_delta_converted).
+    pub rate: Option<f64>,
+    /// The units of a security bought, or sold when negative, see
+    /// [Item::buy](super::item::Item::buy).
+    pub units: Option<f64>,
+}
+
+impl Delta {
+    pub fn new(time: DateTime<Utc>, amount: DeltaAmount, kind: DeltaKind) -> Self {
+        Self {
+            time,
+            amount,
+            kind,
+            memo: None,
+            category: None,
+            id: DeltaId::next(),
+            external_id: None,
+            status: DeltaStatus::Actual,
+            rate: None,
+            units: None,
+        }
+    }
+    /// A payment of a fixed amount.
+    pub fn payment(time: DateTime<Utc>, value: Value) -> Self {
+        Self::new(time, DeltaAmount::Fixed(value), DeltaKind::Payment)
+    }
+    /// A fee of a fixed amount, the sign is taken as is.
+    pub fn fee(time: DateTime<Utc>, value: Value) -> Self {
+        Self::new(time, DeltaAmount::Fixed(value), DeltaKind::Fee)
+    }
+    /// A manual correction of a fixed amount.
+    pub fn adjustment(time: DateTime<Utc>, value: Value) -> Self {
+        Self::new(time, DeltaAmount::Fixed(value), DeltaKind::Adjustment)
+    }
+    /// Interest charged or earned, posted as a fixed amount.
+    pub fn interest(time: DateTime<Utc>, value: Value) -> Self {
+        Self::new(time, DeltaAmount::Fixed(value), DeltaKind::Interest)
+    }
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+    pub fn with_external_id(mut self, id: impl Into<String>) -> Self {
+        self.external_id = Some(id.into());
+        self
+    }
+    /// Fixes the rate the amount converts into the item's currency at.
+    pub fn with_rate(mut self, rate: f64) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+    /// Marks the delta as [DeltaStatus::Planned].
+    pub fn planned(mut self) -> Self {
+        self.status = DeltaStatus::Planned;
+        self
+    }
+    /// The amount of money this delta changes the balance by.
+    pub fn resolve(&self, balance: &Value) -> Value {
+        match &self.amount {
+            DeltaAmount::Fixed(value) => value.clone(),
+            DeltaAmount::Percent(percent) => balance * *percent,
+        }
+    }
+    /// Like [Delta::resolve] but on a bare balance in the given currency,
+    /// fixed amounts in other currencies are converted at the captured
+    /// [Delta::rate] or else at the global exchange's rate on the delta's
+    /// day. Panics without such a rate, see [Delta::try_resolve_amount].
+    pub fn resolve_amount(&self, balance: f64, currency: Currency) -> f64 {
+        self.try_resolve_amount(balance, currency)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Delta::resolve_amount], failing if the amount has no rate into
+    /// the currency.
+    pub fn try_resolve_amount(
+        &self,
+        balance: f64,
+        currency: Currency,
+    ) -> Result<f64, ConversionError> {
+        Ok(match &self.amount {
+            DeltaAmount::Fixed(value) if value.currency() == currency => value.amount(),
+            DeltaAmount::Fixed(value) => {
+                let rate = match self.rate {
+                    Some(rate) => rate,
+                    None => CURRENCY_EXCHANGE.rate_at(value.currency(), currency, self.time)?,
+                };
+                value.amount() * rate
+            }
+            DeltaAmount::Percent(percent) => balance * *percent,
+        })
+    }
+}
+
+impl PartialEq for Delta {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Delta {}
+
+impl PartialOrd for Delta {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Deltas are ordered by time and then by creation.
+impl Ord for Delta {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time.cmp(&other.time).then(self.id.cmp(&other.id))
+    }
+}
+
+/// The size of the buckets deltas are grouped into for reporting.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Granularity {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Granularity {
+    /// The first instant of the bucket containing the time.
+    pub fn bucket_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let month = match self {
+            Granularity::Monthly => time.month(),
+            Granularity::Quarterly => (time.month() - 1) / 3 * 3 + 1,
+            Granularity::Yearly => 1,
+        };
+        NaiveDate::from_ymd_opt(time.year(), month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+    }
+    /// The first instant of the bucket after the one containing the time.
+    pub fn next_bucket(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let months = match self {
+            Granularity::Monthly => 1,
+            Granularity::Quarterly => 3,
+            Granularity::Yearly => 12,
+        };
+        self.bucket_start(time)
+            .checked_add_months(Months::new(months))
+            .unwrap()
+    }
+    /// The last second of the bucket containing the time, this is
+    /// where aggregated deltas are placed.
+    pub fn bucket_end(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        self.next_bucket(time) - TimeDelta::seconds(1)
+    }
+}
+
+/// What [aggregate] nets deltas by, the end of their bucket, their
+/// currency, kind and status.
+type Bucket = (DateTime<Utc>, Currency, DeltaKind, DeltaStatus);
+
+/// Nets the fixed amount deltas within each bucket into a single delta per
+/// currency, kind and status placed at the end of the bucket, so planned
+/// deltas stay planned. Amounts that captured a [Delta::rate] are netted in
+/// `currency`, the one of the item, at that rate. Percentage deltas depend
+/// on the balance when they are applied and deltas trading
+/// [Delta::units] are what the units held and the cost basis are made of,
+/// both are kept as is.
+pub fn aggregate(deltas: &[Delta], granularity: Granularity, currency: Currency) -> Vec<Delta> {
+    let mut result: Vec<Delta> = vec![];
+    let mut netted: Vec<(Bucket, f64, usize)> = vec![];
+    for delta in deltas {
+        match &delta.amount {
+            DeltaAmount::Fixed(value) if delta.units.is_none() => {
+                let (into, amount) = match delta.rate {
+                    Some(rate) if value.currency() != currency => {
+                        (currency, value.amount() * rate)
+                    }
+                    _ => (value.currency(), value.amount()),
+                };
+                let bucket = (granularity.bucket_end(delta.time), into, delta.kind, delta.status);
+                match netted.iter_mut().find(|(b, _, _)| *b == bucket) {
+                    Some((_, sum, count)) => {
+                        *sum += amount;
+                        *count += 1;
+                    }
+                    None => netted.push((bucket, amount, 1)),
+                }
+            }
+            _ => result.push(delta.clone()),
+        }
+    }
+    result.extend(netted.into_iter().map(|((time, currency, kind, status), sum, count)| {
+        let amount = DeltaAmount::Fixed(Value::new(currency, sum));
+        let mut delta = Delta::new(time, amount, kind).with_memo(format!("Net of {count} deltas"));
+        delta.status = status;
+        delta
+    }));
+    result.sort();
+    result
+}
+
+/// Where the amount of a transaction lives in a CSV file.
+#[derive(Clone, Debug)]
+pub enum AmountColumns {
+    /// A single signed amount column.
+    Single(String),
+    /// Separate columns for money going out and coming in, debits
+    /// are taken as negative and credits as positive.
+    Split { debit: String, credit: String },
+}
+
+/// Whose perspective the amounts in a CSV file are written from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignConvention {
+    /// Positive amounts increase the item.
+    AsIs,
+    /// Positive amounts decrease the item, as on most credit card statements.
+    Inverted,
+}
+
+/// Describes the layout of a bank's CSV export, columns are named
+/// by their header.
+#[derive(Clone, Debug)]
+pub struct CsvMapping {
+    pub date: String,
+    pub amount: AmountColumns,
+    /// A [chrono] format string such as `%d/%m/%Y`.
+    pub date_format: String,
+    pub sign: SignConvention,
+    pub currency: Currency,
+    /// An optional column to use as the memo.
+    pub memo: Option<String>,
+    /// An optional column with the bank's identifier of the transaction,
+    /// kept as the [Delta::external_id].
+    pub external_id: Option<String>,
+}
+
+/// A row of a CSV file that could not be imported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file itself could not be read.
+    Csv(csv::Error),
+    /// A column named in the mapping is not in the header.
+    MissingColumn(String),
+    /// Some rows failed to parse, the rows that did parse are kept.
+    Rows {
+        failures: Vec<RowError>,
+        parsed: Vec<Delta>,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Csv(e) => write!(f, "failed to read csv: {e}"),
+            ImportError::MissingColumn(c) => write!(f, "missing column \"{c}\""),
+            ImportError::Rows { failures, .. } => {
+                write!(f, "{} rows failed to import", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  line {}: {}", failure.line, failure.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<csv::Error> for ImportError {
+    fn from(value: csv::Error) -> Self {
+        ImportError::Csv(value)
+    }
+}
+
+/// Parses an amount, allowing for currency symbols and thousands separators.
+/// Empty cells are zero so split debit/credit columns work.
+fn parse_amount(cell: &str) -> Result<f64, String> {
+    let cleaned = cell.trim().replace(['$', ','], "");
+    if cleaned.is_empty() {
+        return Ok(0.0);
+    }
+    cleaned
+        .parse()
+        .map_err(|_| format!("invalid amount \"{cell}\""))
+}
+
+/// Imports the transactions of a CSV file as payments. Rows that fail to parse
+/// do not stop the import, they are collected with their line numbers.
+pub fn import_csv<R: Read>(reader: R, mapping: CsvMapping) -> Result<Vec<Delta>, ImportError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| ImportError::MissingColumn(name.to_string()))
+    };
+
+    let date_column = column(&mapping.date)?;
+    let memo_column = mapping.memo.as_deref().map(column).transpose()?;
+    let id_column = mapping.external_id.as_deref().map(column).transpose()?;
+    let amount_columns = match &mapping.amount {
+        AmountColumns::Single(amount) => (column(amount)?, None),
+        AmountColumns::Split { debit, credit } => (column(credit)?, Some(column(debit)?)),
+    };
+
+    let mut parsed = vec![];
+    let mut failures = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let cell = |i: usize| record.get(i).unwrap_or("");
+
+        let row = NaiveDate::parse_from_str(cell(date_column).trim(), &mapping.date_format)
+            .map_err(|e| format!("invalid date \"{}\": {e}", cell(date_column)))
+            .and_then(|date| {
+                let mut amount = parse_amount(cell(amount_columns.0))?;
+                if let Some(debit) = amount_columns.1 {
+                    amount -= parse_amount(cell(debit))?;
+                }
+                if mapping.sign == SignConvention::Inverted {
+                    amount = -amount;
+                }
+                Ok((date, amount))
+            });
+
+        match row {
+            Ok((date, amount)) => {
+                let time = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+                let mut delta = Delta::payment(time, Value::new(mapping.currency, amount));
+                delta.memo = memo_column.map(|m| cell(m).to_string());
+                delta.external_id = id_column.map(|i| cell(i).to_string());
+                parsed.push(delta);
+            }
+            Err(message) => failures.push(RowError { line, message }),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(ImportError::Rows { failures, parsed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        item::Item,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{
+        aggregate, import_csv, AmountColumns, CsvMapping, Delta, DeltaAmount, DeltaKind,
+        DeltaStatus, Granularity, ImportError, SignConvention,
+    };
+
+    #[test]
+    pub fn test_delta_ordering() {
+        let time = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let first = Delta::payment(time, Value::dummy("CAD", 1.0));
+        let second = Delta::fee(time, Value::dummy("CAD", -1.0));
+        let earlier = Delta::adjustment(time - TimeDelta::days(1), Value::dummy("CAD", 5.0));
+
+        let mut deltas = vec![second.clone(), first.clone(), earlier.clone()];
+        deltas.sort();
+        assert_eq!(deltas, vec![earlier, first, second]);
+    }
+
+    #[test]
+    pub fn test_percent_delta() {
+        let time = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let fee =
+            Delta::new(time, DeltaAmount::Percent(-0.01), DeltaKind::Fee).with_memo("Admin fee");
+        assert_eq!(fee.resolve(&Value::dummy("CAD", 500.0)).amount(), -5.0);
+        assert_eq!(fee.memo.as_deref(), Some("Admin fee"));
+    }
+
+    #[test]
+    pub fn test_import_csv() {
+        let mapping = CsvMapping {
+            date: "Date".into(),
+            amount: AmountColumns::Split {
+                debit: "Debit".into(),
+                credit: "Credit".into(),
+            },
+            date_format: "%d/%m/%Y".into(),
+            sign: SignConvention::AsIs,
+            currency: Currency::new("CAD"),
+            memo: Some("Description".into()),
+            external_id: None,
+        };
+
+        let statement = include_str!("../../tests/fixtures/bank_statement.csv");
+        let Err(ImportError::Rows { failures, parsed }) = import_csv(statement.as_bytes(), mapping)
+        else {
+            panic!("the broken row should have been reported");
+        };
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].line, 4);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[1].memo.as_deref(), Some("Coffee"));
+
+        let start = Utc.with_ymd_and_hms(2008, 1, 1, 0, 0, 0).unwrap();
+        let mut card = Item::fixed(Value::dummy("CAD", -3_000.0), start);
+        card.import_deltas(parsed);
+        let balance = card.assess(Utc.with_ymd_and_hms(2008, 4, 1, 0, 0, 0).unwrap());
+        let expected = Value::new("CAD", -3_000.0 + 1_000.0 - 4.5 + 1_250.0 - 45.5);
+        assert_value_eq!(balance, expected, 1e-9);
+    }
+
+    #[test]
+    pub fn test_granularity_buckets() {
+        let time = Utc.with_ymd_and_hms(2024, 2, 14, 12, 0, 0).unwrap();
+        assert_eq!(
+            Granularity::Monthly.bucket_end(time),
+            Utc.with_ymd_and_hms(2024, 2, 29, 23, 59, 59).unwrap()
+        );
+        assert_eq!(
+            Granularity::Quarterly.bucket_start(time),
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            Granularity::Yearly.next_bucket(time),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_aggregate_nets_by_bucket() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let deltas = (0..60)
+            .map(|d| Delta::payment(start + TimeDelta::days(d), Value::dummy("CAD", 10.0)))
+            .collect::<Vec<_>>();
+
+        let netted = aggregate(&deltas, Granularity::Monthly, Currency::new("CAD"));
+        assert_eq!(netted.len(), 2);
+        assert_eq!(netted[0].resolve(&Value::dummy("CAD", 0.0)).amount(), 310.0);
+        assert_eq!(netted[1].resolve(&Value::dummy("CAD", 0.0)).amount(), 290.0);
+        assert_eq!(netted[1].memo.as_deref(), Some("Net of 29 deltas"));
+
+        // Next month's rent is netted apart from what has happened.
+        let rent = Delta::payment(start + TimeDelta::days(20), Value::dummy("CAD", -1_000.0));
+        let mut deltas = deltas[..31].to_vec();
+        deltas.push(rent.planned());
+        let netted = aggregate(&deltas, Granularity::Monthly, Currency::new("CAD"));
+        assert_eq!(netted.len(), 2);
+        let planned = netted.iter().find(|d| d.status == DeltaStatus::Planned).unwrap();
+        assert_eq!(planned.resolve(&Value::dummy("CAD", 0.0)).amount(), -1_000.0);
+        let actual = netted.iter().find(|d| d.status == DeltaStatus::Actual).unwrap();
+        assert_eq!(actual.resolve(&Value::dummy("CAD", 0.0)).amount(), 310.0);
+
+        // Payments in USD keep the rates they were converted at.
+        let usd = |day, rate: Option<f64>| {
+            let time = start + TimeDelta::days(day);
+            let mut delta = Delta::payment(time, Value::dummy("USD", 100.0));
+            delta.rate = rate;
+            delta
+        };
+        let deltas = [usd(1, Some(1.3)), usd(2, Some(1.4)), usd(3, None)];
+        let netted = aggregate(&deltas, Granularity::Monthly, Currency::new("CAD"));
+        assert_eq!(netted.len(), 2);
+        let converted = netted[0].resolve(&Value::dummy("CAD", 0.0));
+        assert_value_eq!(converted, Value::new("CAD", 270.0), 1e-9);
+        assert_eq!(netted[1].resolve(&Value::dummy("CAD", 0.0)).currency(), Currency::new("USD"));
+    }
+}
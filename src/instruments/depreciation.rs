@@ -0,0 +1,182 @@
+//! Depreciation the way an accountant books it.
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    period::Period,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// Schedules longer than this are cut off, declining balances with no floor
+/// never quite reach it.
+const MAX_PERIODS: i64 = 1000;
+
+/// An asset that loses value on a fixed schedule from `start`.
+#[derive(Clone, Debug)]
+pub enum Depreciation {
+    /// Loses the same amount every period until it is worth `salvage` at
+    /// the end of its `life`.
+    StraightLine {
+        cost: Value,
+        salvage: Value,
+        life: Period,
+        start: DateTime<Utc>,
+    },
+    /// Loses `rate` of what is left every `period`, never going below
+    /// `salvage_floor`.
+    DecliningBalance {
+        cost: Value,
+        rate: f64,
+        period: Period,
+        salvage_floor: Value,
+        start: DateTime<Utc>,
+    },
+}
+
+impl Depreciation {
+    fn start(&self) -> DateTime<Utc> {
+        match self {
+            Depreciation::StraightLine { start, .. }
+            | Depreciation::DecliningBalance { start, .. } => *start,
+        }
+    }
+    /// The value the asset can not drop below.
+    fn floor(&self) -> f64 {
+        match self {
+            Depreciation::StraightLine { salvage, .. } => salvage.amount(),
+            Depreciation::DecliningBalance { salvage_floor, .. } => salvage_floor.amount(),
+        }
+    }
+    /// The depreciation expense of every `step` from the start until the
+    /// asset reaches its salvage value, each at the end of its step.
+    pub fn schedule(&self, step: impl Into<Period>) -> Vec<(DateTime<Utc>, Value)> {
+        let step = step.into();
+        let floor = self.floor();
+        let mut schedule = vec![];
+        let mut previous = self.assess(self.start()).amount();
+        for count in 1..=MAX_PERIODS {
+            let time = step.step(self.start(), count);
+            let value = self.assess(time).amount();
+            schedule.push((time, Value::new(self.currency(), previous - value)));
+            // Within a cent of the floor is as close as books get.
+            if value - floor < 0.005 {
+                break;
+            }
+            previous = value;
+        }
+        schedule
+    }
+}
+
+impl Assesible for Depreciation {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        let start = self.start();
+        let amount = match self {
+            Depreciation::StraightLine {
+                cost,
+                salvage,
+                life,
+                ..
+            } => {
+                // Calendar lives are used up a month at a time so every year
+                // books the same expense, leap or not.
+                let used = match life {
+                    Period::Delta(_) => life.between(start, time),
+                    _ => {
+                        let month = Period::Months(1);
+                        month.between(start, time) / month.between(start, life.step(start, 1))
+                    }
+                }
+                .clamp(0.0, 1.0);
+                cost.amount() - (cost.amount() - salvage.amount()) * used
+            }
+            Depreciation::DecliningBalance {
+                cost,
+                rate,
+                period,
+                salvage_floor,
+                ..
+            } => {
+                let periods = period.between(start, time).max(0.0);
+                (cost.amount() * (1.0 - rate).powf(periods)).max(salvage_floor.amount())
+            }
+        };
+        Value::new(self.currency(), amount)
+    }
+    fn currency(&self) -> Currency {
+        match self {
+            Depreciation::StraightLine { cost, .. }
+            | Depreciation::DecliningBalance { cost, .. } => cost.currency(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{period::Period, value::Value, Assesible};
+
+    use super::Depreciation;
+
+    /// The textbook 10,000 machine with a 1,000 salvage value over 5 years.
+    #[test]
+    pub fn test_straight_line() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let machine = Depreciation::StraightLine {
+            cost: Value::new("CAD", 10_000.0),
+            salvage: Value::new("CAD", 1_000.0),
+            life: Period::Years(5),
+            start,
+        };
+
+        let schedule = machine.schedule(Period::Years(1));
+        assert_eq!(schedule.len(), 5);
+        for (year, (time, expense)) in schedule.iter().enumerate() {
+            assert_eq!(*time, Period::Years(1).step(start, year as i64 + 1));
+            assert_value_eq!(*expense, Value::new("CAD", 1_800.0), 1e-9);
+            let book = Value::new("CAD", 10_000.0 - 1_800.0 * (year + 1) as f64);
+            assert_value_eq!(machine.assess(*time), book, 1e-9);
+        }
+        // Exactly the salvage value at the end of its life and after.
+        assert_eq!(
+            machine.assess(Period::Years(5).step(start, 1)).amount(),
+            1_000.0
+        );
+        assert_eq!(
+            machine.assess(Period::Years(9).step(start, 1)).amount(),
+            1_000.0
+        );
+        assert_eq!(
+            machine.assess(Period::Years(1).step(start, -1)).amount(),
+            10_000.0
+        );
+    }
+
+    /// Double declining balance over 5 years is 40% a year, the floor stops
+    /// the fifth year at 1,000 instead of 777.60.
+    #[test]
+    pub fn test_declining_balance_floor() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let machine = Depreciation::DecliningBalance {
+            cost: Value::new("CAD", 10_000.0),
+            rate: 0.4,
+            period: Period::Years(1),
+            salvage_floor: Value::new("CAD", 1_000.0),
+            start,
+        };
+
+        let expenses = machine
+            .schedule(Period::Years(1))
+            .iter()
+            .map(|(_, v)| (v.amount() * 100.0).round() / 100.0)
+            .collect::<Vec<_>>();
+        assert_eq!(expenses, [4_000.0, 2_400.0, 1_440.0, 864.0, 296.0]);
+        assert_eq!(
+            machine.assess(Period::Years(20).step(start, 1)).amount(),
+            1_000.0
+        );
+    }
+}
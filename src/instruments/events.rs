@@ -0,0 +1,194 @@
+//! Telling the outside world when a [Book] changes.
+
+use std::{
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use super::{
+    book::{Book, ItemKey},
+    delta::DeltaId,
+    transfer::TransferId,
+    value::Currency,
+};
+
+/// A mutation made through the [Book], see [Book::subscribe].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BookEvent {
+    ItemAdded {
+        key: ItemKey,
+    },
+    ItemRemoved {
+        key: ItemKey,
+    },
+    DeltaAdded {
+        item: ItemKey,
+        delta_id: DeltaId,
+    },
+    DeltaRemoved {
+        item: ItemKey,
+        delta_id: DeltaId,
+    },
+    Renamed {
+        key: ItemKey,
+    },
+    Transfer {
+        id: TransferId,
+        from: ItemKey,
+        to: ItemKey,
+    },
+    /// The item moved from one parent to another, `None` being the top of
+    /// the book.
+    Reparented {
+        child: ItemKey,
+        from: Option<ItemKey>,
+        to: Option<ItemKey>,
+    },
+    /// The item was changed through [Book::get_mut] or [Book::bulk_edit].
+    ItemEdited {
+        key: ItemKey,
+    },
+    /// Every amount in `old` is now in `new`, see [Book::migrate_currency].
+    CurrencyMigrated {
+        old: Currency,
+        new: Currency,
+    },
+}
+
+/// Identifies a subscription so it can be cancelled.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+type Callback = Arc<dyn Fn(&BookEvent) + Send + Sync>;
+
+/// The callbacks subscribed to a book.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    next: u64,
+    callbacks: Vec<(SubscriptionId, Callback)>,
+}
+
+impl fmt::Debug for Subscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.callbacks.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl Subscribers {
+    /// Calls every subscriber in the order they subscribed. A subscriber
+    /// that panics is skipped over so the rest still hear about it.
+    pub(crate) fn notify(&self, event: &BookEvent) {
+        for (_, callback) in &self.callbacks {
+            let _ = catch_unwind(AssertUnwindSafe(|| callback(event)));
+        }
+    }
+}
+
+impl Book {
+    /// Calls `callback` right after every mutation made through the book,
+    /// such as [Book::add] or [Book::transfer]. Callbacks only see the event
+    /// and hold no lock on the book. A change made through [Book::get_mut]
+    /// is reported once the borrow ends.
+    pub fn subscribe(
+        &mut self,
+        callback: impl Fn(&BookEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.subscribers.next);
+        self.subscribers.next += 1;
+        self.subscribers.callbacks.push((id, Arc::new(callback)));
+        id
+    }
+    /// Stops calling a subscriber, returning whether it was subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.callbacks.len();
+        self.subscribers.callbacks.retain(|(s, _)| *s != id);
+        self.subscribers.callbacks.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::instruments::{book::Book, delta::Delta, item::Item, value::Value};
+
+    use super::{BookEvent, SubscriptionId};
+
+    fn record(book: &mut Book) -> (SubscriptionId, Arc<Mutex<Vec<BookEvent>>>) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+        let id = book.subscribe(move |event| recorded.lock().unwrap().push(event.clone()));
+        (id, events)
+    }
+
+    #[test]
+    pub fn test_event_sequence() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.subscribe(|_| panic!("a broken subscriber"));
+        let (_, events) = record(&mut book);
+
+        let chequing = book.add(Item::fixed(Value::new("CAD", 1_000.0), start));
+        let savings = book.add_named("Savings", Item::fixed(Value::new("CAD", 0.0), start));
+        let bonus = book.add_child(Item::fixed(Value::new("CAD", 50.0), start), savings);
+        let delta = Delta::payment(start, Value::new("CAD", 100.0));
+        let delta_id = delta.id;
+        book.add_delta(chequing, delta);
+        let transfer = book
+            .transfer(chequing, savings, start, Value::new("CAD", 300.0))
+            .unwrap();
+        book.reparent(bonus, Some(chequing));
+        book.remove(savings);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                BookEvent::ItemAdded { key: chequing },
+                BookEvent::ItemAdded { key: savings },
+                BookEvent::ItemAdded { key: bonus },
+                BookEvent::Reparented {
+                    child: bonus,
+                    from: None,
+                    to: Some(savings)
+                },
+                BookEvent::DeltaAdded {
+                    item: chequing,
+                    delta_id
+                },
+                BookEvent::Transfer {
+                    id: transfer,
+                    from: chequing,
+                    to: savings
+                },
+                BookEvent::Reparented {
+                    child: bonus,
+                    from: Some(savings),
+                    to: Some(chequing)
+                },
+                BookEvent::ItemRemoved { key: savings },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_unsubscribe() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let (id, events) = record(&mut book);
+        let cash = book.add(Item::fixed(Value::new("CAD", 1.0), start));
+        assert!(book.unsubscribe(id));
+        assert!(!book.unsubscribe(id));
+
+        book.add_delta(cash, Delta::payment(start, Value::new("CAD", 1.0)));
+        book.remove(cash);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [BookEvent::ItemAdded { key: cash }]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Writing a book out for spreadsheets.
+
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+
+use super::{book::Book, delta::DeltaAmount, Assesible};
+
+impl Book {
+    /// Writes every item as a CSV row of its name, currency, assessment at
+    /// `time`, interest rate and the period the rate is charged over. Items
+    /// without interest leave the last two empty.
+    pub fn export_csv<W: Write>(&self, writer: W, time: DateTime<Utc>) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["name", "currency", "value", "rate", "period"])?;
+        for (key, item) in self.iter_ordered() {
+            let (rate, period) = match item.interest() {
+                Some(interest) => (interest.rate().to_string(), interest.period().to_string()),
+                None => (String::new(), String::new()),
+            };
+            writer.write_record([
+                self.name(key).unwrap_or_default().to_string(),
+                item.currency().code().to_string(),
+                item.assess(time).amount().to_string(),
+                rate,
+                period,
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+    /// Writes every delta as a CSV row of its item's name, time, kind,
+    /// amount, currency and the rate it was converted into the item's
+    /// currency at. Deltas without a captured [Delta::rate](super::delta::Delta::rate)
+    /// or with a percentage amount leave the columns they lack empty.
+    pub fn export_deltas_csv<W: Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["name", "time", "kind", "amount", "currency", "rate"])?;
+        for (key, item) in self.iter_ordered() {
+            for delta in &item.deltas {
+                let (amount, currency) = match &delta.amount {
+                    DeltaAmount::Fixed(value) => (
+                        value.amount().to_string(),
+                        value.currency().code().to_string(),
+                    ),
+                    DeltaAmount::Percent(_) => (String::new(), String::new()),
+                };
+                writer.write_record([
+                    self.name(key).unwrap_or_default().to_string(),
+                    delta.time.to_rfc3339(),
+                    format!("{:?}", delta.kind),
+                    amount,
+                    currency,
+                    delta.rate.map(|rate| rate.to_string()).unwrap_or_default(),
+                ])?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{
+        book::Book,
+        item::{Interest, Item},
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    #[test]
+    pub fn test_interest_columns() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut visa =
+            Item::basic_debt(Value::new("CAD", -5_000.0), 0.1999, Period::Years(1), start);
+        let interest = visa.interest().unwrap();
+        assert_eq!(
+            (interest.rate(), interest.period()),
+            (0.1999, Period::Years(1))
+        );
+
+        let mut book = Book::default();
+        book.add_named("Visa", visa.clone());
+        book.add_named("Cash", Item::fixed(Value::new("CAD", 250.0), start));
+        let mut out = vec![];
+        book.export_csv(&mut out, start).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,currency,value,rate,period\nVisa,CAD,-5000,0.1999,P1Y\nCash,CAD,250,,\n"
+        );
+
+        // A new rate applies from inception, over deltas already made.
+        visa.add_delta(start + TimeDelta::days(10), Value::new("CAD", 1_000.0));
+        visa.set_interest(Some(Interest::new(0.0, Period::Years(1))));
+        let later = Period::Years(1).step(start, 1);
+        assert_eq!(visa.assess(later).amount(), -4_000.0);
+        visa.set_interest(None);
+        assert!(visa.interest().is_none());
+    }
+
+    #[test]
+    pub fn test_stable_order() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let cash = book.add_named("Cash", Item::fixed(Value::new("CAD", 1.0), start));
+        let gone = book.add_named("Gone", Item::fixed(Value::new("CAD", 2.0), start));
+        let visa = book.add_named("Visa", Item::fixed(Value::new("CAD", 3.0), start));
+        book.remove(gone);
+        // The new item takes the slot that was freed but still comes last.
+        let loan = book.add_named("Loan", Item::fixed(Value::new("CAD", 4.0), start));
+        assert_eq!(book.ordered_keys(), vec![cash, visa, loan]);
+
+        let export = |book: &Book| {
+            let mut out = vec![];
+            book.export_csv(&mut out, start).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let first = export(&book);
+        assert_eq!(first, export(&book));
+        assert_eq!(
+            first,
+            "name,currency,value,rate,period\nCash,CAD,1,,\nVisa,CAD,3,,\nLoan,CAD,4,,\n"
+        );
+
+        assert!(book.move_before(loan, cash));
+        assert!(book.move_before(visa, visa));
+        assert!(!book.move_before(gone, cash));
+        assert_eq!(book.ordered_keys(), vec![loan, cash, visa]);
+        let names: Vec<_> = book
+            .iter_ordered()
+            .map(|(key, _)| book.name(key).unwrap())
+            .collect();
+        assert_eq!(names, ["Loan", "Cash", "Visa"]);
+    }
+}
@@ -0,0 +1,144 @@
+//! How much of a book is held in each currency.
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    book::Book,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// What a book holds in one currency, see [Book::exposure].
+#[derive(Clone, Debug)]
+pub struct CurrencyExposure {
+    pub currency: Currency,
+    /// The assets less the liabilities.
+    pub net: Value,
+    pub assets: Value,
+    /// As a negative amount.
+    pub liabilities: Value,
+    /// The net converted into the currency of the book.
+    pub converted: Value,
+    /// The converted net as a fraction of the book, `0.7` being 70%.
+    pub share: f64,
+}
+
+impl Book {
+    /// The exposure to each currency at `time`, in the order the currencies
+    /// first appear in [Book::iter_ordered]. Every item counts in full
+    /// towards the currency it is denominated in.
+    pub fn exposure(&self, time: DateTime<Utc>) -> Vec<CurrencyExposure> {
+        self.exposure_of(time, false)
+    }
+    /// Like [Book::exposure] with the cash items have been paid by their
+    /// payouts counting towards the currency it was paid in.
+    pub fn exposure_with_payouts(&self, time: DateTime<Utc>) -> Vec<CurrencyExposure> {
+        self.exposure_of(time, true)
+    }
+    fn exposure_of(&self, time: DateTime<Utc>, payouts: bool) -> Vec<CurrencyExposure> {
+        // The assets and liabilities in each currency.
+        let mut held: Vec<(Currency, f64, f64)> = vec![];
+        let mut hold = |value: &Value| {
+            let at = match held.iter().position(|(c, _, _)| *c == value.currency()) {
+                Some(at) => at,
+                None => {
+                    held.push((value.currency(), 0.0, 0.0));
+                    held.len() - 1
+                }
+            };
+            match value.amount() >= 0.0 {
+                true => held[at].1 += value.amount(),
+                false => held[at].2 += value.amount(),
+            }
+        };
+        for (_, item) in self.iter_ordered() {
+            match payouts {
+                true => {
+                    let full = item.assess_full(time);
+                    hold(&Value::new(item.currency(), full.value().amount()));
+                    full.cash_by_currency().iter().for_each(&mut hold);
+                }
+                false => hold(&item.assess(time)),
+            }
+        }
+
+        let base = self.currency();
+        let converted: Vec<f64> = held
+            .iter()
+            .map(|(currency, assets, liabilities)| {
+                let rate = self
+                    .report_rate(*currency, time)
+                    .unwrap_or_else(|e| panic!("{e}"));
+                (assets + liabilities) * rate
+            })
+            .collect();
+        let total: f64 = converted.iter().sum();
+        held.into_iter()
+            .zip(converted)
+            .map(
+                |((currency, assets, liabilities), converted)| CurrencyExposure {
+                    currency,
+                    net: Value::new(currency, assets + liabilities),
+                    assets: Value::new(currency, assets),
+                    liabilities: Value::new(currency, liabilities),
+                    converted: Value::new(base, converted),
+                    share: converted / total,
+                },
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::instruments::{
+        book::Book,
+        convert::CURRENCY_EXCHANGE,
+        item::{Item, Payout},
+        value::Value,
+    };
+
+    #[test]
+    pub fn test_exposure() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion("XEU", "XEC", 1.25);
+        CURRENCY_EXCHANGE.add_conversion("XEC", "XEP", 3_000.0);
+        CURRENCY_EXCHANGE.add_conversion("XEU", "XEP", 3_750.0);
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("XEC", 80_000.0), start));
+        book.add(Item::fixed(Value::new("XEC", -10_000.0), start));
+        // 16k XEU is worth 20k XEC and 30M XEP is worth 10k XEC.
+        let fund = Item::builder(Value::new("XEU", 16_000.0), start)
+            .payout(Payout::OneTime {
+                amount: Value::new("XEP", 3_000_000.0),
+                time: start,
+            })
+            .build();
+        book.add(fund);
+        book.add(Item::fixed(Value::new("XEP", 30_000_000.0), start));
+
+        let exposure = book.exposure(start);
+        let shares: Vec<_> = exposure
+            .iter()
+            .map(|e| (e.currency.code(), (e.share * 100.0).round()))
+            .collect();
+        assert_eq!(shares, [("XEC", 70.0), ("XEU", 20.0), ("XEP", 10.0)]);
+        let home = &exposure[0];
+        assert_eq!(
+            (
+                home.assets.amount(),
+                home.liabilities.amount(),
+                home.net.amount()
+            ),
+            (80_000.0, -10_000.0, 70_000.0)
+        );
+        assert_eq!(exposure[2].converted.amount(), 10_000.0);
+
+        // The 3M XEP the fund paid out adds 1k XEC to the XEP exposure.
+        let exposure = book.exposure_with_payouts(start);
+        assert_eq!(exposure[2].net.amount(), 33_000_000.0);
+        assert!((exposure[2].share - 11.0 / 101.0).abs() < 1e-12);
+    }
+}
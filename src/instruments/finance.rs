@@ -0,0 +1,551 @@
+//! Discounting cash flows, net present value and internal rate of return.
+
+use std::fmt;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{
+    book::{Book, ItemKey},
+    convert::{ConversionError, CURRENCY_EXCHANGE},
+    item::{Interest, Item},
+    period::Period,
+    value::Value,
+    Assesible,
+};
+
+/// Errors from solving for an internal rate of return.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrrError {
+    /// Every flow has the same sign so no rate makes them sum to zero.
+    NoSignChange,
+    /// Neither Newton's method nor bisection found a root.
+    NoConvergence,
+    /// A flow could not be converted into the currency of the first.
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for IrrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IrrError::NoSignChange => write!(
+                f,
+                "cash flows never change sign, there is no rate of return"
+            ),
+            IrrError::NoConvergence => write!(f, "could not find a rate of return"),
+            IrrError::Conversion(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for IrrError {}
+
+impl From<ConversionError> for IrrError {
+    fn from(value: ConversionError) -> Self {
+        IrrError::Conversion(value)
+    }
+}
+
+/// What `pv` grows to between the two times.
+pub fn future_value(
+    pv: &Value,
+    interest: &Interest,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Value {
+    interest.apply(from, to, pv)
+}
+
+/// What `fv` at `to` is worth at `from`, the inverse of [future_value].
+pub fn present_value(
+    fv: &Value,
+    interest: &Interest,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Value {
+    fv * interest.factor(from, to).recip()
+}
+
+/// The payment dates of an annuity starting a period after `start`, for
+/// indexed interest which has no closed form.
+fn annuity_dates(interest: &Interest, start: DateTime<Utc>, n_periods: u32) -> Vec<DateTime<Utc>> {
+    interest.boundaries(start).take(n_periods as usize).collect()
+}
+
+/// The value at `start`, one period before the first of `n_periods`
+/// payments, each a period apart.
+pub fn pv_of_annuity(
+    payment: &Value,
+    interest: &Interest,
+    start: DateTime<Utc>,
+    n_periods: u32,
+) -> Value {
+    if interest.benchmark().is_some() {
+        let dates = annuity_dates(interest, start, n_periods);
+        let factors = dates.iter().map(|d| interest.factor(start, *d).recip());
+        return payment * factors.sum::<f64>();
+    }
+    let rate = interest.rate_at(start);
+    if rate == 0.0 {
+        return payment * n_periods as f64;
+    }
+    payment * ((1.0 - (1.0 + rate).powi(-(n_periods as i32))) / rate)
+}
+
+/// The value at the last of `n_periods` payments, each a period apart with
+/// the first a period after `start`.
+pub fn fv_of_annuity(
+    payment: &Value,
+    interest: &Interest,
+    start: DateTime<Utc>,
+    n_periods: u32,
+) -> Value {
+    if interest.benchmark().is_some() {
+        let dates = annuity_dates(interest, start, n_periods);
+        let Some(last) = dates.last() else {
+            return payment * 0.0;
+        };
+        let factors = dates.iter().map(|d| interest.factor(*d, *last));
+        return payment * factors.sum::<f64>();
+    }
+    let rate = interest.rate_at(start);
+    if rate == 0.0 {
+        return payment * n_periods as f64;
+    }
+    payment * (((1.0 + rate).powi(n_periods as i32) - 1.0) / rate)
+}
+
+/// The value at `as_of` of every payout of the asset after `as_of` up to and
+/// including `horizon`, each discounted back by `discount`. Recurring
+/// payouts never end on their own so the horizon is what terminates them,
+/// see [gordon_growth] for a perpetual stream.
+pub fn dcf(
+    asset: &Item,
+    as_of: DateTime<Utc>,
+    discount: &Interest,
+    horizon: DateTime<Utc>,
+) -> Value {
+    asset
+        .payouts
+        .iter()
+        .flat_map(|payout| payout.flows(asset.inception, horizon))
+        .filter(|(time, _)| *time > as_of)
+        .map(|(time, value)| present_value(&value, discount, as_of, time))
+        .fold(Value::zero(asset.currency()), |sum, value| sum + value)
+}
+
+/// The value at `as_of`, one period before the first payment of a stream
+/// that grows by `growth` every period forever. The discount rate in effect
+/// at `as_of` must exceed the growth, indexed discounting is taken to stay
+/// at that rate.
+pub fn gordon_growth(
+    payment: &Value,
+    discount: &Interest,
+    growth: f64,
+    as_of: DateTime<Utc>,
+) -> Value {
+    let rate = discount.rate_at(as_of);
+    assert!(
+        rate > growth,
+        "a perpetuity must grow slower than it is discounted"
+    );
+    payment * (rate - growth).recip()
+}
+
+/// The value of the flows at the time of the earliest one, discounting by
+/// `rate` every `period`. The result is in the currency of the first flow,
+/// `None` without any flows.
+pub fn npv(
+    rate: f64,
+    flows: &[(DateTime<Utc>, Value)],
+    period: impl Into<Period>,
+) -> Option<Value> {
+    let period = period.into();
+    let start = flows.iter().map(|(t, _)| *t).min()?;
+    flows
+        .iter()
+        .map(|(time, value)| value * (1.0 + rate).powf(-period.between(start, *time)))
+        .sum()
+}
+
+/// The yearly rate at which the flows have a net present value of zero,
+/// counting years as 365 days like Excel's `XIRR`.
+pub fn xirr(flows: &[(DateTime<Utc>, Value)]) -> Result<f64, IrrError> {
+    let Some(currency) = flows.first().map(|(_, v)| v.currency()) else {
+        return Err(IrrError::NoSignChange);
+    };
+    let start = flows.iter().map(|(t, _)| *t).min().unwrap();
+    let year = TimeDelta::days(365).num_nanoseconds().unwrap() as f64;
+    let flows = flows
+        .iter()
+        .map(|(time, value)| {
+            let rate = CURRENCY_EXCHANGE.rate(value.currency(), currency)?;
+            let years = (*time - start).num_nanoseconds().unwrap() as f64 / year;
+            Ok((years, value.amount() * rate))
+        })
+        .collect::<Result<Vec<_>, ConversionError>>()?;
+    if !flows.iter().any(|(_, a)| *a > 0.0) || !flows.iter().any(|(_, a)| *a < 0.0) {
+        return Err(IrrError::NoSignChange);
+    }
+
+    let value = |rate: f64| {
+        flows
+            .iter()
+            .map(|(t, a)| a * (1.0 + rate).powf(-t))
+            .sum::<f64>()
+    };
+    let slope = |rate: f64| {
+        flows
+            .iter()
+            .map(|(t, a)| -t * a * (1.0 + rate).powf(-t - 1.0))
+            .sum::<f64>()
+    };
+
+    // Newton's method converges quickly from a sensible guess.
+    let mut rate = 0.1;
+    for _ in 0..100 {
+        let step = value(rate) / slope(rate);
+        rate -= step;
+        if !rate.is_finite() || rate <= -1.0 {
+            break;
+        }
+        if step.abs() < 1e-12 {
+            return Ok(rate);
+        }
+    }
+
+    // Otherwise bracket a root and bisect, the rate can not go below -100%.
+    let mut low = -1.0 + 1e-9;
+    let mut high = 1.0;
+    while value(low).signum() == value(high).signum() {
+        high *= 2.0;
+        if high > 1e9 {
+            return Err(IrrError::NoConvergence);
+        }
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if value(mid).signum() == value(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok((low + high) / 2.0)
+}
+
+impl Item {
+    /// The cash flows of holding the item until `to`. The book value is paid
+    /// at inception, every delta is paid into the item, payouts are received
+    /// and the item is sold for its assessed value at `to`.
+    pub fn cash_flows(&self, to: DateTime<Utc>) -> Vec<(DateTime<Utc>, Value)> {
+        let mut flows = vec![(self.inception, self.book_value.negate())];
+        flows.extend(
+            self.delta_amounts()
+                .into_iter()
+                .filter(|(delta, _)| delta.time <= to)
+                .map(|(delta, change)| (delta.time, change.negate())),
+        );
+        for payout in &self.payouts {
+            flows.extend(payout.flows(self.inception, to));
+        }
+        flows.push((to, self.assess(to)));
+        flows
+    }
+    /// The yearly internal rate of return of holding the item until `to`, see
+    /// [Item::cash_flows] and [xirr].
+    pub fn irr(&self, to: DateTime<Utc>) -> Result<f64, IrrError> {
+        xirr(&self.cash_flows(to))
+    }
+    /// The return since inception as a fraction, `0.04` being 4%, which is
+    /// the [Item::annualized_return] compounded over the years held. Debts
+    /// return the negative of what they cost, a debt that grew by 5% returns
+    /// `-0.05`. `None` until after inception, without a book value or when
+    /// there is no rate of return.
+    pub fn total_return(&self, time: DateTime<Utc>) -> Option<f64> {
+        let growth = self.growth(time)?;
+        let total = (1.0 + growth).powf(years(self.inception, time)) - 1.0;
+        Some(match self.book_value.amount() < 0.0 {
+            true => -total,
+            false => total,
+        })
+    }
+    /// The yearly return since inception, counting years as 365 days like
+    /// [xirr]. Items without deltas or payouts return the simple ratio of
+    /// the value to the book value, others their money-weighted [Item::irr].
+    /// Debts follow the sign of [Item::total_return].
+    pub fn annualized_return(&self, time: DateTime<Utc>) -> Option<f64> {
+        let growth = self.growth(time)?;
+        Some(match self.book_value.amount() < 0.0 {
+            true => -growth,
+            false => growth,
+        })
+    }
+    /// The yearly rate the balance grew at since inception, a debt that
+    /// grows grows at a positive rate.
+    fn growth(&self, time: DateTime<Utc>) -> Option<f64> {
+        let book = self.book_value.amount();
+        if time <= self.inception || book == 0.0 {
+            return None;
+        }
+        let growth = match self.deltas.is_empty() && self.payouts.is_empty() {
+            true => {
+                let ratio = self.assess(time).amount() / book;
+                ratio.powf(years(self.inception, time).recip()) - 1.0
+            }
+            false => self.irr(time).ok()?,
+        };
+        growth.is_finite().then_some(growth)
+    }
+}
+
+/// The years between two times as [xirr] counts them.
+fn years(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_seconds() as f64 / (365.0 * 86_400.0)
+}
+
+/// The returns of an item since inception, see [Book::returns].
+#[derive(Clone, Debug)]
+pub struct ItemReturn {
+    pub key: ItemKey,
+    pub total: Option<f64>,
+    pub annualized: Option<f64>,
+}
+
+impl Book {
+    /// The [Item::total_return] and [Item::annualized_return] of every
+    /// item at `time`, in the order of [Book::iter_ordered].
+    pub fn returns(&self, time: DateTime<Utc>) -> Vec<ItemReturn> {
+        self.iter_ordered()
+            .map(|(key, item)| ItemReturn {
+                key,
+                total: item.total_return(time),
+                annualized: item.annualized_return(time),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        benchmark::{Benchmark, BENCHMARKS},
+        book::Book,
+        delta::Delta,
+        item::{Interest, Item, Payout, Proration},
+        period::Period,
+        value::Value,
+    };
+
+    use super::{
+        dcf, future_value, fv_of_annuity, gordon_growth, npv, present_value, pv_of_annuity, xirr,
+        IrrError,
+    };
+
+    /// The example from the documentation of Excel's `XIRR`.
+    #[test]
+    pub fn test_xirr_matches_excel() {
+        let flows = [
+            ((2008, 1, 1), -10_000.0),
+            ((2008, 3, 1), 2_750.0),
+            ((2008, 10, 30), 4_250.0),
+            ((2009, 2, 15), 3_250.0),
+            ((2009, 4, 1), 2_750.0),
+        ]
+        .map(|((y, m, d), amount)| {
+            (
+                Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap(),
+                Value::new("USD", amount),
+            )
+        });
+        let rate = xirr(&flows).unwrap();
+        assert!((rate - 0.373362535).abs() < 1e-6, "{rate}");
+        let discounted = npv(rate, &flows, TimeDelta::days(365)).unwrap();
+        assert_value_eq!(discounted, Value::new("USD", 0.0), 1e-6);
+    }
+
+    #[test]
+    pub fn test_no_solution() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let flows = [
+            (start, Value::new("CAD", 100.0)),
+            (start + TimeDelta::days(365), Value::new("CAD", 50.0)),
+        ];
+        assert_eq!(xirr(&flows), Err(IrrError::NoSignChange));
+        assert_eq!(xirr(&[]), Err(IrrError::NoSignChange));
+    }
+
+    /// Buying a rental for 100k that pays 500 a month and is sold for 100k
+    /// yields a little over 6% a year.
+    #[test]
+    pub fn test_item_irr() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        let rental = Item::builder(Value::new("CAD", 100_000.0), start)
+            .payout(Payout::FixedRecurring {
+                amount: Value::new("CAD", 500.0),
+                start: start + TimeDelta::days(31),
+                frequency: Period::Months(1),
+                proration: Proration::None,
+            })
+            .build();
+
+        let rate = rental.irr(end).unwrap();
+        assert!((rate - (1.005f64.powi(12) - 1.0)).abs() < 1e-3, "{rate}");
+        assert!(
+            npv(rate, &rental.cash_flows(end), TimeDelta::days(365))
+                .unwrap()
+                .amount()
+                .abs()
+                < 1e-3
+        );
+    }
+
+    /// 1000 growing 4% a year returns 4% a year, and a contribution half way
+    /// through makes it the money-weighted rate.
+    #[test]
+    pub fn test_returns() {
+        let start = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let savings = Item::builder(Value::new("CAD", 1_000.0), start)
+            .interest(0.04, Period::Years(1))
+            .build();
+        let total = savings.total_return(end).unwrap();
+        assert!((total - (1.04f64.powi(2) - 1.0)).abs() < 1e-9, "{total}");
+        let annualized = savings.annualized_return(end).unwrap();
+        assert!((annualized - 0.04).abs() < 1e-9, "{annualized}");
+        assert_eq!(savings.annualized_return(start), None);
+        assert_eq!(savings.total_return(start - TimeDelta::days(1)), None);
+        assert_eq!(
+            Item::fixed(Value::new("CAD", 0.0), start).total_return(end),
+            None
+        );
+
+        let mut topped_up = savings.clone();
+        let middle = Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap();
+        topped_up.push_delta(Delta::payment(middle, Value::new("CAD", 500.0)));
+        let rate = xirr(&topped_up.cash_flows(end)).unwrap();
+        assert!((topped_up.annualized_return(end).unwrap() - rate).abs() < 1e-12);
+        assert!((rate - 0.04).abs() < 1e-6, "{rate}");
+        let total = topped_up.total_return(end).unwrap();
+        assert!((total - ((1.0 + rate).powi(2) - 1.0)).abs() < 1e-9);
+
+        // A debt growing by 5% a year costs 5% a year.
+        let debt = Item::basic_debt(Value::new("CAD", -1_000.0), 0.05, Period::Years(1), start);
+        let mut book = Book::default();
+        let key = book.add(debt);
+        book.add(savings);
+        let returns = book.returns(end);
+        assert_eq!(returns[0].key, key);
+        assert!((returns[0].annualized.unwrap() + 0.05).abs() < 1e-9);
+        assert!((returns[0].total.unwrap() + 0.1025).abs() < 1e-9);
+        assert!((returns[1].annualized.unwrap() - 0.04).abs() < 1e-9);
+    }
+
+    /// Factors from standard compound interest tables, to six places.
+    #[test]
+    pub fn test_table_factors() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let one = Value::new("CAD", 1.0);
+        let years = |n| Period::Years(1).step(start, n);
+        let factor = |amount: f64| Value::new("CAD", amount);
+
+        let four = Interest::new(0.04, Period::Years(1));
+        assert_value_eq!(future_value(&one, &four, start, years(15)), factor(1.800944), 5e-7);
+        let ten = Value::new("CAD", 10_000.0);
+        assert_eq!(
+            future_value(&ten, &four, start, years(15)).non_decimal(),
+            18_009
+        );
+
+        let five = Interest::new(0.05, Period::Years(1));
+        assert_value_eq!(
+            present_value(&one, &five, start, years(10)),
+            factor(0.613913),
+            5e-7
+        );
+        assert_value_eq!(pv_of_annuity(&one, &five, start, 10), factor(7.721735), 5e-7);
+        assert_value_eq!(fv_of_annuity(&one, &five, start, 10), factor(12.577893), 5e-7);
+
+        let eight = Interest::new(0.08, Period::Years(1));
+        assert_value_eq!(
+            present_value(&one, &eight, start, years(20)),
+            factor(0.214548),
+            5e-7
+        );
+        assert_value_eq!(pv_of_annuity(&one, &eight, start, 20), factor(9.818147), 5e-7);
+        assert_value_eq!(fv_of_annuity(&one, &eight, start, 20), factor(45.761964), 5e-7);
+
+        let none = Interest::new(0.0, Period::Years(1));
+        assert_eq!(pv_of_annuity(&one, &none, start, 7).amount(), 7.0);
+        assert_eq!(fv_of_annuity(&one, &none, start, 7).amount(), 7.0);
+        let round = present_value(
+            &future_value(&ten, &five, start, years(3)),
+            &five,
+            start,
+            years(3),
+        );
+        assert_value_eq!(round, Value::new("CAD", 10_000.0), 1e-9);
+    }
+
+    /// Payments under indexed interest are discounted at the rate in effect
+    /// over each period rather than a single closed form.
+    #[test]
+    pub fn test_indexed_annuity() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let one = Value::new("CAD", 1.0);
+        let index = BENCHMARKS.register(
+            Benchmark::new("Annuity")
+                .with_rate(start, 0.05)
+                .with_rate(Period::Years(1).step(start, 1), 0.10),
+        );
+        let indexed = Interest::indexed(index, 0.0, Period::Years(1));
+        let pv = 1.0 / 1.05 + 1.0 / (1.05 * 1.10);
+        assert_value_eq!(pv_of_annuity(&one, &indexed, start, 2), Value::new("CAD", pv), 1e-9);
+        assert_value_eq!(fv_of_annuity(&one, &indexed, start, 2), Value::new("CAD", 2.10), 1e-9);
+        assert_value_eq!(
+            gordon_growth(&one, &indexed, 0.0, Period::Years(1).step(start, 1)),
+            Value::new("CAD", 10.0),
+            1e-9
+        );
+    }
+
+    /// A ten year bond with a face value of 1000 paying a 100 coupon every
+    /// year is worth 100 * 7.721735 + 1000 * 0.613913 at 5%.
+    #[test]
+    pub fn test_dcf_bond() {
+        let issue = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let maturity = Period::Years(10).step(issue, 1);
+        let bond = Item::builder(Value::new("CAD", 0.0), issue)
+            .payout(Payout::FixedRecurring {
+                amount: Value::new("CAD", 100.0),
+                start: Period::Years(1).step(issue, 1),
+                frequency: Period::Years(1),
+                proration: Proration::None,
+            })
+            .payout(Payout::OneTime {
+                amount: Value::new("CAD", 1_000.0),
+                time: maturity,
+            })
+            .build();
+        let five = Interest::new(0.05, Period::Years(1));
+
+        let price = dcf(&bond, issue, &five, maturity);
+        assert_value_eq!(price, Value::new("CAD", 1_386.09), 0.01);
+        // Only payments after the valuation date count.
+        let after_first = dcf(&bond, Period::Years(1).step(issue, 1), &five, maturity);
+        assert_value_eq!(after_first, Value::new("CAD", 1_386.09 * 1.05 - 100.0), 0.01);
+
+        // The coupons alone approach a perpetuity with a long enough horizon.
+        let coupons = Item {
+            payouts: bond.payouts[..1].to_vec(),
+            ..bond.clone()
+        };
+        let forever = gordon_growth(&Value::new("CAD", 100.0), &five, 0.0, issue);
+        assert_eq!(forever.amount(), 2_000.0);
+        let long = dcf(&coupons, issue, &five, Period::Years(500).step(issue, 1));
+        assert_value_eq!(long, forever, 1e-6);
+    }
+}
@@ -0,0 +1,225 @@
+//! Groups of items within a [Book], such as "Retirement" or "House".
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    convert::ConversionError,
+    value::{Currency, Value},
+};
+
+slotmap::new_key_type! {
+    /// Refers to a group of a book, the default key is the group of every
+    /// item that was not assigned to one.
+    pub struct GroupId;
+}
+
+/// A named set of items assessed together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    /// The currency the group is assessed in, the book's
+    /// [Book::report_currency] if `None`.
+    pub currency: Option<Currency>,
+}
+
+/// The group ungrouped items are reported under.
+static UNGROUPED: Lazy<Group> = Lazy::new(|| Group {
+    name: "Ungrouped".to_string(),
+    currency: None,
+});
+
+impl Book {
+    /// Creates an empty group.
+    pub fn create_group(&mut self, name: impl Into<String>) -> GroupId {
+        let name = name.into();
+        let id = self.groups.insert(Group {
+            name: name.clone(),
+            currency: None,
+        });
+        self.audit.push(AuditEntry::GroupCreated { id, name });
+        id
+    }
+    /// Assesses the group in `currency` rather than the book's currency,
+    /// `false` if the group is not in the book or is the default one.
+    pub fn set_group_currency(&mut self, id: GroupId, currency: impl Into<Currency>) -> bool {
+        match self.groups.get_mut(id) {
+            Some(group) => {
+                let currency = currency.into();
+                group.currency = Some(currency);
+                self.audit.push(AuditEntry::GroupCurrencySet { id, currency });
+                true
+            }
+            None => false,
+        }
+    }
+    /// The group, the default group being named "Ungrouped".
+    pub fn group(&self, id: GroupId) -> Option<&Group> {
+        match id == GroupId::default() {
+            true => Some(&UNGROUPED),
+            false => self.groups.get(id),
+        }
+    }
+    /// Every group starting with the default one.
+    pub fn groups(&self) -> impl Iterator<Item = (GroupId, &Group)> {
+        std::iter::once((GroupId::default(), &*UNGROUPED)).chain(self.groups.iter())
+    }
+    /// Puts an item into a group, taking it out of the one it was in.
+    /// Assigning to the default group ungroups it. `false` if either is not
+    /// in the book.
+    pub fn assign(&mut self, key: ItemKey, group: GroupId) -> bool {
+        if self.get(key).is_none() || self.group(group).is_none() {
+            return false;
+        }
+        match group == GroupId::default() {
+            true => self.membership.remove(key),
+            false => self.membership.insert(key, group),
+        };
+        self.audit.push(AuditEntry::Assigned { key, group });
+        true
+    }
+    /// The group an item is in, the default group if it was never assigned.
+    pub fn group_of(&self, key: ItemKey) -> GroupId {
+        self.membership.get(key).copied().unwrap_or_default()
+    }
+    /// The items in a group, in the order of [Book::ordered_keys].
+    pub fn members(&self, id: GroupId) -> impl Iterator<Item = ItemKey> + '_ {
+        self.ordered_keys()
+            .into_iter()
+            .filter(move |key| self.group_of(*key) == id)
+    }
+    /// Moves every item of one group into another, returning how many moved.
+    pub fn move_group(&mut self, from: GroupId, to: GroupId) -> usize {
+        if self.group(to).is_none() {
+            return 0;
+        }
+        let moving: Vec<ItemKey> = self.members(from).collect();
+        for key in &moving {
+            self.assign(*key, to);
+        }
+        moving.len()
+    }
+    /// Assesses the items of a group in its currency, `None` if there is no
+    /// currency to assess in as with an empty book that has no reporting
+    /// currency.
+    pub fn group_assess(&self, id: GroupId, time: DateTime<Utc>) -> Option<Value> {
+        self.try_group_assess(id, time)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::group_assess], failing if an item's currency has no rate
+    /// to the group's.
+    pub fn try_group_assess(
+        &self,
+        id: GroupId,
+        time: DateTime<Utc>,
+    ) -> Result<Option<Value>, ConversionError> {
+        let Some(currency) = self
+            .group(id)
+            .and_then(|group| group.currency)
+            .or_else(|| self.report_currency())
+        else {
+            return Ok(None);
+        };
+        self.try_assess_in(currency, time, |key, _| self.group_of(key) == id)
+            .map(Some)
+    }
+    /// The assessment of every group in the order of [Book::groups], the
+    /// default group only if it has items. Groups [Book::group_assess] has
+    /// no currency for are left out.
+    pub fn group_subtotals(&self, time: DateTime<Utc>) -> Vec<(GroupId, Value)> {
+        self.groups()
+            .filter(|(id, _)| *id != GroupId::default() || self.members(*id).next().is_some())
+            .filter_map(|(id, _)| Some((id, self.group_assess(id, time)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book, convert::CURRENCY_EXCHANGE, item::Item, value::Value, Assesible,
+    };
+
+    use super::GroupId;
+
+    #[test]
+    pub fn test_group_subtotals() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let retirement = book.create_group("Retirement");
+        let house = book.create_group("House");
+        let daily = book.create_group("Daily");
+
+        let rrsp = book.add(Item::fixed(Value::new("CAD", 80_000.0), start));
+        let tfsa = book.add(Item::fixed(Value::new("CAD", 20_000.0), start));
+        let home = book.add(Item::fixed(Value::new("CAD", 600_000.0), start));
+        let mortgage = book.add(Item::basic_debt(
+            Value::new("CAD", -450_000.0),
+            0.05,
+            chrono::Duration::days(365),
+            start,
+        ));
+        let chequing = book.add(Item::fixed(Value::new("CAD", 3_000.0), start));
+        for (key, group) in [
+            (rrsp, retirement),
+            (tfsa, retirement),
+            (home, house),
+            (mortgage, house),
+            (chequing, daily),
+        ] {
+            assert!(book.assign(key, group));
+        }
+
+        let subtotals = book.group_subtotals(later);
+        assert_eq!(subtotals.len(), 3);
+        let sum: Option<Value> = subtotals.iter().map(|(_, v)| v).sum();
+        assert_value_eq!(sum.unwrap(), book.assess(later), 1e-6);
+        assert_eq!(book.group_assess(retirement, later).unwrap().amount(), 100_000.0);
+
+        // Items are in at most one group and ungrouped ones fall under the
+        // default group.
+        assert!(book.assign(tfsa, daily));
+        assert_eq!(book.group_of(tfsa), daily);
+        assert_eq!(book.members(retirement).count(), 1);
+        let cash = book.add(Item::fixed(Value::new("CAD", 50.0), start));
+        assert_eq!(book.group_of(cash), GroupId::default());
+        assert_eq!(book.group(GroupId::default()).unwrap().name, "Ungrouped");
+        assert_eq!(book.group_subtotals(later)[0].1.amount(), 50.0);
+
+        // Groups move as a unit.
+        assert_eq!(book.move_group(daily, retirement), 2);
+        assert_eq!(book.group_assess(retirement, later).unwrap().amount(), 103_000.0);
+        assert_eq!(book.group_assess(daily, later).unwrap().amount(), 0.0);
+        assert_eq!(book.groups().count(), 4);
+
+        // An empty book has nothing to assess its groups in.
+        let mut empty = Book::default();
+        let spare = empty.create_group("Spare");
+        assert!(empty.group_assess(spare, later).is_none());
+        assert!(empty.group_subtotals(later).is_empty());
+    }
+
+    #[test]
+    pub fn test_group_currency() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion("GRU", "GRC", 1.25);
+        let mut book = Book::default();
+        let travel = book.create_group("Travel");
+        book.add(Item::fixed(Value::new("GRC", 1_000.0), start));
+        let card = book.add(Item::fixed(Value::new("GRU", 400.0), start));
+        book.assign(card, travel);
+
+        assert_eq!(book.group_assess(travel, start).unwrap().amount(), 500.0);
+        assert!(book.set_group_currency(travel, "GRU"));
+        let value = book.group_assess(travel, start).unwrap();
+        assert_eq!((value.currency().code(), value.amount()), ("GRU", 400.0));
+        assert!(!book.set_group_currency(GroupId::default(), "GRU"));
+        assert_eq!(book.assess(start).amount(), 1_500.0);
+    }
+}
@@ -0,0 +1,359 @@
+//! Imports in two steps: an [ImportPlan] says what an import would do to a
+//! book, so it can be shown for confirmation, and [Book::apply_plan] does it.
+
+use std::{fmt, io::Read};
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey, ItemRef},
+    convert::ConversionError,
+    delta::{import_csv, CsvMapping, Delta, DeltaAmount, DeltaId, ImportError},
+    item::Item,
+    sync::Cursor,
+    value::Currency,
+    Assesible,
+};
+
+/// Where a planned delta goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanTarget {
+    /// An item already in the book.
+    Existing(ItemKey),
+    /// The item at this index of [ImportPlan::items].
+    New(usize),
+}
+
+/// A conversion of a planned delta into its item's currency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedConversion {
+    pub from: Currency,
+    pub to: Currency,
+    /// The rate the delta will capture, applying the plan fails on an error.
+    pub rate: Result<f64, ConversionError>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlannedDelta {
+    pub target: PlanTarget,
+    pub delta: Delta,
+    pub conversion: Option<PlannedConversion>,
+}
+
+/// An item an import would add.
+#[derive(Clone, Debug)]
+pub struct PlannedItem {
+    pub name: Option<String>,
+    pub external_id: Option<String>,
+    pub item: Item,
+}
+
+/// What an import would do to a book, see [Book::apply_plan].
+#[derive(Clone, Debug, Default)]
+pub struct ImportPlan {
+    pub items: Vec<PlannedItem>,
+    pub deltas: Vec<PlannedDelta>,
+    /// Deltas whose external identifier is already on their item, or on an
+    /// earlier delta of the import.
+    pub duplicates: Vec<(PlanTarget, Delta)>,
+    /// Deltas whose item could not be found in the book.
+    pub unresolved: Vec<(ItemRef, Delta)>,
+    /// How far a [DeltaSource](super::sync::DeltaSource) was read, the book
+    /// resumes from here once the plan is applied.
+    pub cursor: Option<Cursor>,
+}
+
+impl ImportPlan {
+    /// Plans adding an item, returning where its deltas go.
+    pub fn add_item(&mut self, item: PlannedItem) -> PlanTarget {
+        self.items.push(item);
+        PlanTarget::New(self.items.len() - 1)
+    }
+    /// Plans adding the delta to the target, or sets it aside as a
+    /// duplicate. Deltas in another currency than their item plan the
+    /// conversion [Book::add_delta_converted] would make.
+    pub fn add_delta(&mut self, book: &Book, target: PlanTarget, delta: Delta) {
+        let item = match target {
+            PlanTarget::Existing(key) => book.get(key).expect("planned onto a missing item"),
+            PlanTarget::New(index) => &self.items[index].item,
+        };
+        let seen = |d: &Delta| d.external_id.is_some() && d.external_id == delta.external_id;
+        let planned = self.deltas.iter().filter(|p| p.target == target);
+        if item.deltas.iter().any(seen) || planned.map(|p| &p.delta).any(seen) {
+            self.duplicates.push((target, delta));
+            return;
+        }
+        let conversion = match &delta.amount {
+            DeltaAmount::Fixed(value) if value.currency() != item.currency() => {
+                Some(PlannedConversion {
+                    from: value.currency(),
+                    to: item.currency(),
+                    rate: match delta.rate {
+                        Some(rate) => Ok(rate),
+                        None => item.conversion_rate(value.currency(), delta.time),
+                    },
+                })
+            }
+            _ => None,
+        };
+        self.deltas.push(PlannedDelta {
+            target,
+            delta,
+            conversion,
+        });
+    }
+    /// Whether applying the plan would leave the book as it is.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.deltas.is_empty()
+    }
+}
+
+/// What [Book::apply_plan] did.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReceipt {
+    /// The keys of [ImportPlan::items], in the same order.
+    pub items: Vec<ItemKey>,
+    pub deltas: Vec<(ItemKey, DeltaId)>,
+    pub duplicates: usize,
+}
+
+/// Why [Book::apply_plan] left the book untouched. `row` indexes
+/// [ImportPlan::deltas].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApplyError {
+    /// The item a delta goes to is no longer in the book.
+    MissingItem { row: usize, key: ItemKey },
+    /// A delta has no rate into its item's currency.
+    Conversion { row: usize, error: ConversionError },
+    /// Another item in the book took the external identifier of a new item.
+    ExternalIdTaken(String),
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::MissingItem { row, key } => {
+                write!(f, "row {row} goes to {key:?} which is not in the book")
+            }
+            ApplyError::Conversion { row, error } => write!(f, "row {row}: {error}"),
+            ApplyError::ExternalIdTaken(id) => {
+                write!(
+                    f,
+                    "an item with the external id {id:?} is already in the book"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+impl Book {
+    /// Plans adding each delta to the item it refers to.
+    pub fn plan_deltas(&self, entries: impl IntoIterator<Item = (ItemRef, Delta)>) -> ImportPlan {
+        let mut plan = ImportPlan::default();
+        for (item_ref, delta) in entries {
+            match self.resolve(&item_ref).filter(|k| self.get(*k).is_some()) {
+                Some(key) => plan.add_delta(self, PlanTarget::Existing(key), delta),
+                None => plan.unresolved.push((item_ref, delta)),
+            }
+        }
+        plan
+    }
+    /// Plans importing a CSV file onto the item, see [import_csv]. Any row
+    /// that fails to parse fails the plan.
+    pub fn plan_csv<R: Read>(
+        &self,
+        reader: R,
+        mapping: CsvMapping,
+        item: ItemRef,
+    ) -> Result<ImportPlan, ImportError> {
+        let deltas = import_csv(reader, mapping)?;
+        Ok(self.plan_deltas(deltas.into_iter().map(|d| (item.clone(), d))))
+    }
+    /// Carries out the plan as a single undo step, followed by an
+    /// [AuditEntry::ImportApplied]. Nothing changes if any part of it
+    /// cannot be applied.
+    pub fn apply_plan(&mut self, plan: ImportPlan) -> Result<ImportReceipt, ApplyError> {
+        for planned in &plan.items {
+            if let Some(id) = planned.external_id.as_deref() {
+                if self.by_external_id(id).is_some() {
+                    return Err(ApplyError::ExternalIdTaken(id.to_string()));
+                }
+            }
+        }
+        for (row, planned) in plan.deltas.iter().enumerate() {
+            if let PlanTarget::Existing(key) = planned.target {
+                if self.get(key).is_none() {
+                    return Err(ApplyError::MissingItem { row, key });
+                }
+            }
+            if let Some(Err(error)) = planned.conversion.as_ref().map(|c| &c.rate) {
+                let error = error.clone();
+                return Err(ApplyError::Conversion { row, error });
+            }
+        }
+
+        let ImportPlan {
+            items,
+            deltas,
+            duplicates,
+            unresolved,
+            cursor,
+        } = plan;
+        let receipt = self.grouped(|book| {
+            let mut receipt = ImportReceipt {
+                duplicates: duplicates.len(),
+                ..Default::default()
+            };
+            for planned in items {
+                let key = match planned.name {
+                    Some(name) => book.add_named(name, planned.item),
+                    None => book.add(planned.item),
+                };
+                if let Some(id) = planned.external_id {
+                    book.set_external_id(key, id);
+                }
+                receipt.items.push(key);
+            }
+            for PlannedDelta {
+                target,
+                mut delta,
+                conversion,
+            } in deltas
+            {
+                let key = match target {
+                    PlanTarget::Existing(key) => key,
+                    PlanTarget::New(index) => receipt.items[index],
+                };
+                if let Some(PlannedConversion { rate: Ok(rate), .. }) = conversion {
+                    delta.rate = Some(rate);
+                }
+                let id = book.add_delta(key, delta).unwrap();
+                receipt.deltas.push((key, id));
+            }
+            receipt
+        });
+        self.audit.push(AuditEntry::ImportApplied {
+            items: receipt.items.len(),
+            deltas: receipt.deltas.len(),
+            duplicates: receipt.duplicates,
+        });
+        if cursor.is_some() {
+            self.sync_cursor = cursor;
+            self.sync_pending = unresolved;
+        }
+        Ok(receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{
+        audit::AuditEntry,
+        book::{Book, ItemRef},
+        convert::ConversionError,
+        delta::Delta,
+        item::Item,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{ApplyError, PlanTarget};
+
+    fn payment(day: i64, amount: f64, id: &str) -> Delta {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        Delta::payment(start + TimeDelta::days(day), Value::new("CAD", amount)).with_external_id(id)
+    }
+
+    #[test]
+    pub fn test_import_plan() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let visa = book.add_named("Visa", Item::fixed(Value::new("CAD", -500.0), start));
+        book.add_delta(visa, payment(1, 100.0, "t1"));
+        let visa_ref = || ItemRef::Name("Visa".to_string());
+
+        let plan = book.plan_deltas([
+            (visa_ref(), payment(1, 100.0, "t1")),
+            (visa_ref(), payment(2, 50.0, "t2")),
+            (visa_ref(), payment(2, 50.0, "t2")),
+            (visa_ref(), payment(3, 25.0, "t3")),
+            (ItemRef::Name("Amex".to_string()), payment(3, 10.0, "a1")),
+        ]);
+        assert_eq!(plan.deltas.len(), 2);
+        assert_eq!(plan.duplicates.len(), 2);
+        assert_eq!(plan.duplicates[0].0, PlanTarget::Existing(visa));
+        assert_eq!(plan.unresolved.len(), 1);
+        // Planning leaves the book alone.
+        assert_eq!(book.get(visa).unwrap().deltas.len(), 1);
+
+        let audited = book.audit_log().len();
+        let receipt = book.apply_plan(plan).unwrap();
+        assert_eq!(receipt.deltas.len(), 2);
+        assert_eq!(receipt.duplicates, 2);
+        assert_eq!(
+            book.get(visa)
+                .unwrap()
+                .assess(start + TimeDelta::days(5))
+                .amount(),
+            -325.0
+        );
+        assert!(matches!(
+            book.audit_log()[audited..],
+            [
+                AuditEntry::DeltaAdded { .. },
+                AuditEntry::DeltaAdded { .. },
+                AuditEntry::ImportApplied {
+                    items: 0,
+                    deltas: 2,
+                    duplicates: 2
+                }
+            ]
+        ));
+        // The same import again is all duplicates.
+        let again = book.plan_deltas([(visa_ref(), payment(2, 50.0, "t2"))]);
+        assert!(again.is_empty());
+    }
+
+    #[test]
+    pub fn test_failed_plan_is_atomic() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let visa = book.add_named("Visa", Item::fixed(Value::new("CAD", -500.0), start));
+        let visa_ref = || ItemRef::Name("Visa".to_string());
+        // Nothing converts IPX into CAD so the second row cannot be applied.
+        let foreign = Delta::payment(start + TimeDelta::days(2), Value::new("IPX", 10.0));
+        let plan = book.plan_deltas([
+            (visa_ref(), payment(1, 100.0, "t1")),
+            (visa_ref(), foreign),
+            (visa_ref(), payment(3, 25.0, "t3")),
+        ]);
+        let conversion = plan.deltas[1].conversion.clone().unwrap();
+        let missing = ConversionError::NoRate {
+            from: Currency::new("IPX"),
+            to: Currency::new("CAD"),
+        };
+        assert_eq!(conversion.rate, Err(missing.clone()));
+
+        let audited = book.audit_log().len();
+        assert_eq!(
+            book.apply_plan(plan).unwrap_err(),
+            ApplyError::Conversion {
+                row: 1,
+                error: missing
+            }
+        );
+        assert!(book.get(visa).unwrap().deltas.is_empty());
+        assert_eq!(book.audit_log().len(), audited);
+
+        // A plan onto an item removed since fails the same way.
+        let plan = book.plan_deltas([(visa_ref(), payment(1, 100.0, "t1"))]);
+        book.remove(visa);
+        assert_eq!(
+            book.apply_plan(plan).unwrap_err(),
+            ApplyError::MissingItem { row: 0, key: visa }
+        );
+    }
+}
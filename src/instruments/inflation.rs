@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+
+use super::{
+    item::Interest,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// How prices grow over time.
+#[derive(Clone)]
+pub enum Inflation {
+    /// A constant rate of inflation.
+    Rate(Interest),
+    /// A price index such as the CPI, sorted by date. Between points the
+    /// index is linearly interpolated and outside of them it is clamped
+    /// to the nearest point.
+    Index(Vec<(DateTime<Utc>, f64)>),
+}
+
+impl Inflation {
+    /// How much prices have grown between the two dates.
+    pub fn growth(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        match self {
+            Inflation::Rate(interest) => interest.factor(from, to),
+            Inflation::Index(points) => index_at(points, to) / index_at(points, from),
+        }
+    }
+}
+
+/// Reads the index at a certain time, interpolating between points.
+pub(crate) fn index_at(points: &[(DateTime<Utc>, f64)], time: DateTime<Utc>) -> f64 {
+    let after = points.partition_point(|(t, _)| *t <= time);
+    if after == 0 {
+        return points[0].1;
+    }
+    if after == points.len() {
+        return points[after - 1].1;
+    }
+    let (t0, v0) = points[after - 1];
+    let (t1, v1) = points[after];
+    let progress =
+        (time - t0).num_nanoseconds().unwrap() as f64 / (t1 - t0).num_nanoseconds().unwrap() as f64;
+    v0 + (v1 - v0) * progress
+}
+
+/// Assesses an asset in real terms, that is in the purchasing power
+/// of the base date rather than in nominal amounts.
+pub struct RealTerms<A: Assesible> {
+    pub asset: A,
+    pub inflation: Inflation,
+    pub base_date: DateTime<Utc>,
+}
+
+impl<A: Assesible> RealTerms<A> {
+    /// Deflates with a constant rate of inflation.
+    pub fn new(asset: A, inflation: Interest, base_date: DateTime<Utc>) -> Self {
+        Self {
+            asset,
+            inflation: Inflation::Rate(inflation),
+            base_date,
+        }
+    }
+    /// Deflates with a price index, the points do not need to be sorted.
+    pub fn from_index(
+        asset: A,
+        mut index: Vec<(DateTime<Utc>, f64)>,
+        base_date: DateTime<Utc>,
+    ) -> Self {
+        assert!(!index.is_empty(), "a price index needs at least one point");
+        index.sort_by_key(|(t, _)| *t);
+        Self {
+            asset,
+            inflation: Inflation::Index(index),
+            base_date,
+        }
+    }
+}
+
+impl<A: Assesible> Assesible for RealTerms<A> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        self.asset.assess(time) * (1.0 / self.inflation.growth(self.base_date, time))
+    }
+    fn currency(&self) -> Currency {
+        self.asset.currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        self.asset.sample(time, rng) * (1.0 / self.inflation.growth(self.base_date, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::{Interest, Item},
+        risk::RiskExt,
+        value::Value,
+        Assesible,
+    };
+
+    use super::RealTerms;
+
+    #[test]
+    pub fn test_constant_inflation() {
+        let base = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::dummy("CAD", 1_000.0), base));
+
+        let real = book.in_real_terms(Interest::new(0.02, TimeDelta::days(365)), base);
+        let value = real.assess(base + TimeDelta::days(3650));
+        assert_value_eq!(value, Value::new("CAD", 1_000.0 / 1.02f64.powi(10)), 1e-9);
+    }
+
+    #[test]
+    pub fn test_index_inflation() {
+        let base = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let real = RealTerms::from_index(
+            Item::fixed(Value::dummy("CAD", 1_000.0), base),
+            vec![(later, 120.0), (base, 100.0)],
+            base,
+        );
+
+        assert_value_eq!(real.assess(later), Value::new("CAD", 1_000.0 * 100.0 / 120.0), 1e-9);
+
+        // Halfway between the points the index is interpolated to 110.
+        let halfway = base + (later - base) / 2;
+        assert_value_eq!(real.assess(halfway), Value::new("CAD", 1_000.0 * 100.0 / 110.0), 1e-9);
+    }
+}
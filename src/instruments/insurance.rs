@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+
+use super::{
+    convert::CURRENCY_EXCHANGE,
+    period::Period,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// Insures an [Assesible] against losses, any drop below the baseline
+/// minus the deductible is reimbursed in exchange for a premium paid at
+/// the start of every period.
+pub struct Insured<A: Assesible> {
+    pub asset: A,
+    /// The value the asset was insured at.
+    pub baseline: Value,
+    /// The part of a loss that is not covered.
+    pub deductible: Value,
+    pub premium_per_period: Value,
+    pub period: Period,
+    /// When the first premium is paid and coverage starts.
+    pub start: DateTime<Utc>,
+}
+
+/// Converts a value into the given currency, panicking with the
+/// conversion error if there is no rate.
+fn in_currency(value: &Value, currency: Currency) -> Value {
+    CURRENCY_EXCHANGE
+        .try_convert(value.clone(), currency)
+        .unwrap_or_else(|e| panic!("{e}"))
+}
+
+impl<A: Assesible> Insured<A> {
+    /// The amount of premiums paid up to and including the time.
+    pub fn premiums_paid(&self, time: DateTime<Utc>) -> Value {
+        let currency = self.asset.currency();
+        if time < self.start {
+            return Value::new(currency, 0.0);
+        }
+        let paid = self.period.split(self.start, time).0 + 1;
+        in_currency(&self.premium_per_period, currency) * paid as f64
+    }
+    /// Applies the coverage floor and the premiums to an outcome of the asset.
+    fn cover(&self, time: DateTime<Utc>, outcome: Value) -> Value {
+        if time < self.start {
+            return outcome;
+        }
+        let currency = outcome.currency();
+        let floor = in_currency(&self.baseline, currency).amount()
+            - in_currency(&self.deductible, currency).amount();
+        Value::new(
+            currency,
+            outcome.amount().max(floor) - self.premiums_paid(time).amount(),
+        )
+    }
+}
+
+impl<A: Assesible> Assesible for Insured<A> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        self.cover(time, self.asset.assess(time))
+    }
+    fn currency(&self) -> Currency {
+        self.asset.currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        self.cover(time, self.asset.sample(time, rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{item::Item, period::Period, risk::RiskExt, value::Value, Assesible};
+
+    use super::Insured;
+
+    fn insure<A: Assesible>(asset: A) -> Insured<A> {
+        Insured {
+            asset,
+            baseline: Value::dummy("CAD", 100_000.0),
+            deductible: Value::dummy("CAD", 10_000.0),
+            premium_per_period: Value::dummy("CAD", 500.0),
+            period: Period::Years(1),
+            start: Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    pub fn test_crashed_asset_is_floored() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let crashed = Item::fixed(Value::dummy("CAD", 100_000.0), start).with_certain_loss(0.3);
+        let insured = insure(crashed);
+
+        // Three premiums have been paid by the middle of the third year.
+        let time = start + TimeDelta::days(365 * 2 + 180);
+        assert_value_eq!(insured.assess(time), Value::new("CAD", 88_500.0), 0.005);
+    }
+
+    #[test]
+    pub fn test_healthy_asset_only_pays_premiums() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let insured = insure(Item::fixed(Value::dummy("CAD", 100_000.0), start));
+
+        assert_value_eq!(insured.assess(start), Value::new("CAD", 99_500.0), 0.005);
+        let time = start + TimeDelta::days(365 * 2 + 180);
+        assert_value_eq!(insured.assess(time), Value::new("CAD", 98_500.0), 0.005);
+    }
+}
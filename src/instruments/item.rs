@@ -1,11 +1,12 @@
 use std::borrow::Borrow;
 
-use chrono::{DateTime, TimeDelta, Utc};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
 
-use super::{book::ItemKey, value::{Currency, Value}, Assesible};
+use super::{benchmark::{Benchmark, BenchmarkId, BENCHMARKS}, book::ItemKey, calendar::{BusinessDays, DayPolicy}, lease::Lease, position::PriceSeries, convert::{ConversionError, ConversionTable, RatePolicy, CURRENCY_EXCHANGE}, period::{Period, PeriodAnchor, PeriodError}, sale::Sale, delta::{aggregate, AssessMode, Delta, DeltaAmount, DeltaId, DeltaKind, DeltaStatus, Granularity}, value::{Currency, MultiCurrencySum, Rounding, Value}, Assesible, AssessmentResult};
 
 
 // / An item to be put on the books.
+#[derive(Clone, Debug)]
 pub struct Item {
     pub book_value: Value,
     pub interest: Option<Interest>,
@@ -14,16 +15,68 @@ pub struct Item {
     pub children: Vec<ItemKey>,
 
     /// Changes, these typically correspond to payments and stuff of the like.
-    pub deltas: Vec<(DateTime<Utc>, Value)>,
+    pub deltas: Vec<Delta>,
 
     /// Does this item have any sort of recurring payout of a fixed amount?
-    pub payouts: Vec<Payout>
+    pub payouts: Vec<Payout>,
 
+    /// The least that has to be paid every month, credit cards set this.
+    pub minimum_payment: Option<MinPayment>,
 
+    /// Whether the value can be spent, see [Book::liquid_assess](super::book::Book::liquid_assess).
+    pub liquidity: Liquidity,
+
+    /// How often accrued interest is added to the principal. Between these
+    /// the interest accrues simply on the principal and deltas only change
+    /// the principal, see [Item::breakdown]. `None` compounds continuously.
+    /// A calendar [PeriodAnchor] on the interest capitalizes at its month or
+    /// quarter ends instead. Set through [Item::set_capitalization], which
+    /// only takes positive intervals.
+    pub(crate) capitalization: Option<TimeDelta>,
+
+    /// Set by [Item::lease], the item is then assessed at what the lease
+    /// still has due rather than from its book value and deltas.
+    pub lease: Option<Box<Lease>>,
+
+    /// Rounds the balance to this many decimals at every interest period,
+    /// capitalization and delta like a statement does. `None` keeps full
+    /// precision.
+    pub round_each_period: Option<(u32, Rounding)>,
+
+    /// Whether a delta at a capitalization boundary comes before or after
+    /// the interest is added, see [ApplicationOrder].
+    pub application_order: ApplicationOrder,
+
+    /// The delta that pays off an [Item::interest_only_loan] at maturity,
+    /// listed by [Book::upcoming](super::book::Book::upcoming).
+    pub balloon: Option<DeltaId>,
+
+    /// Set by [Item::post_interest], the interest up to this time is in the
+    /// deltas and the interest rate only applies after it.
+    pub interest_posted: Option<DateTime<Utc>>,
+
+    /// Locked rates the item converts into other currencies at, see
+    /// [Item::set_fx_override].
+    pub fx_overrides: Vec<(Currency, f64)>,
+
+    /// The units of a security held from the inception, later buys and
+    /// sales add to them, see [Item::units_at].
+    pub units: Option<f64>,
+
+    /// Set by [Item::sell], the deltas that sold part of the item and what
+    /// each sale brought in.
+    pub sales: Vec<Sale>,
+
+    /// Set by [Item::position], the units held are valued at the price of
+    /// the series on top of the balance, which is the cash traded for them.
+    pub prices: Option<Box<PriceSeries>>,
 }
 
 
 
+/// A payment made by an item, amounts may be in any currency and are
+/// received in that currency.
+#[derive(Clone, Debug)]
 pub enum Payout {
     OneTime {
         amount: Value,
@@ -37,17 +90,218 @@ pub enum Payout {
     FixedRecurring {
         amount: Value,
         start: DateTime<Utc>,
-        frequency: TimeDelta
+        frequency: Period,
+        proration: Proration
     },
     InterestRecurring {
         principal: Value,
         start: DateTime<Utc>,
-        frequency: TimeDelta,
-        interest: Interest
+        frequency: Period,
+        interest: Interest,
+        proration: Proration
+    },
+    /// The payout paid on the days `days` moves its payments to, see
+    /// [Payout::adjusted].
+    Adjusted {
+        payout: Box<Payout>,
+        days: BusinessDays
     }
 }
 
+/// The payments of a [Payout], see [Payout::occurrences].
+#[derive(Clone, Debug)]
+pub struct Occurrences<'a> {
+    payout: &'a Payout,
+    inception: DateTime<Utc>,
+    until: DateTime<Utc>,
+    /// The index of the next payment, `None` once past `until`.
+    next: Option<i64>,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = (DateTime<Utc>, Value);
 
+    fn next(&mut self) -> Option<Self::Item> {
+        let k = self.next?;
+        match self.payout.occurrence(self.inception, k) {
+            Some((time, value)) if time <= self.until => {
+                self.next = Some(k + 1);
+                Some((time, value))
+            }
+            _ => {
+                self.next = None;
+                None
+            }
+        }
+    }
+    /// Payments come in order of time, so the `n` skipped are all before
+    /// the one returned and need not be computed.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.next = self.next.map(|k| k + n as i64);
+        self.next()
+    }
+}
+
+/// How a recurring payout counts the period it is part way through, see
+/// [Item::assess_full].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Proration {
+    /// Only whole periods count.
+    #[default]
+    None,
+    /// The current period has accrued in proportion to how much of it
+    /// has passed.
+    Linear,
+}
+
+
+
+/// What happens first when a delta falls on a capitalization boundary of
+/// an item, see [Item::capitalization]. Fixed deltas end up the same either
+/// way, percentage deltas see the interest of the period ending then only
+/// if it comes first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ApplicationOrder {
+    #[default]
+    InterestFirst,
+    PaymentFirst,
+}
+
+/// Whether an item's value can be spent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Liquidity {
+    Liquid,
+    /// Such as a house or a locked in pension.
+    Illiquid,
+    /// Held in escrow until `release`. Cancelling the deal before then
+    /// forfeits `forfeit_percent` of the funds and releases the rest.
+    Escrow {
+        release: DateTime<Utc>,
+        forfeit_percent: f64,
+    },
+}
+
+impl Liquidity {
+    pub fn is_liquid(&self, time: DateTime<Utc>) -> bool {
+        match self {
+            Liquidity::Liquid => true,
+            Liquidity::Illiquid => false,
+            Liquidity::Escrow { release, .. } => time >= *release,
+        }
+    }
+}
+
+/// A minimum payment of `percent` of the balance owed, but never less than
+/// `floor` unless less is owed.
+#[derive(Clone, Debug)]
+pub struct MinPayment {
+    pub floor: Value,
+    pub percent: f64,
+}
+
+impl MinPayment {
+    /// The minimum payment on `owed`, a positive amount in `currency`.
+    pub fn amount(&self, owed: f64, currency: Currency) -> f64 {
+        let floor = CURRENCY_EXCHANGE
+            .rate(self.floor.currency(), currency)
+            .unwrap_or_else(|e| panic!("{e}"))
+            * self.floor.amount();
+        (owed * self.percent).max(floor).min(owed)
+    }
+}
+
+impl Payout {
+    /// Moves every payment to a business day, amounts are still worked out
+    /// over the unadjusted periods.
+    pub fn adjusted(self, days: BusinessDays) -> Self {
+        Payout::Adjusted { payout: Box::new(self), days }
+    }
+    /// The payout without any [Payout::Adjusted] around it.
+    pub fn unadjusted(&self) -> &Payout {
+        match self {
+            Payout::Adjusted { payout, .. } => payout.unadjusted(),
+            payout => payout,
+        }
+    }
+    /// Like [Payout::unadjusted] for changing the payout.
+    pub fn unadjusted_mut(&mut self) -> &mut Payout {
+        match self {
+            Payout::Adjusted { payout, .. } => payout.unadjusted_mut(),
+            payout => payout,
+        }
+    }
+    /// Every payment up to and including `to`, see [Payout::occurrences].
+    pub fn flows(&self, inception: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(DateTime<Utc>, Value)> {
+        self.occurrences(inception, to).collect()
+    }
+    /// Every payment up to and including `until` in order, computed as they
+    /// are reached. Interest on a one time payout accrues from the inception
+    /// of the item, recurring interest pays what the principal earned over
+    /// each period, which follow the anchor of the interest. Skipping ahead
+    /// with [Iterator::nth] does not compute the payments skipped.
+    pub fn occurrences(&self, inception: DateTime<Utc>, until: DateTime<Utc>) -> Occurrences<'_> {
+        Occurrences {
+            payout: self,
+            inception,
+            until,
+            next: Some(0),
+        }
+    }
+    /// The payment with index `k`, `None` past the last one.
+    fn occurrence(&self, inception: DateTime<Utc>, k: i64) -> Option<(DateTime<Utc>, Value)> {
+        match self {
+            Payout::OneTime { amount, time } => (k == 0).then(|| (*time, amount.clone())),
+            Payout::InterestOneTime { principal, time, interest } => {
+                (k == 0).then(|| (*time, interest.apply(inception, *time, principal)))
+            }
+            // A frequency that never moves would repeat forever, it pays once.
+            Payout::FixedRecurring { frequency, .. }
+            | Payout::InterestRecurring { frequency, .. }
+                if k > 0 && frequency.validate().is_err() =>
+            {
+                None
+            }
+            Payout::FixedRecurring { amount, start, frequency, .. } => {
+                Some((frequency.step(*start, k), amount.clone()))
+            }
+            Payout::InterestRecurring { principal, start, frequency, interest, .. } => {
+                let at = |k| interest.anchor.boundary(*frequency, *start, k);
+                let (from, to) = (at(k), at(k + 1));
+                Some((to, principal * (interest.factor(from, to) - 1.0)))
+            }
+            Payout::Adjusted { payout, days } => {
+                payout.occurrence(inception, k).map(|(time, value)| (days.adjust(time), value))
+            }
+        }
+    }
+    /// What has accrued towards the next payment at `time` but not been paid,
+    /// `None` unless this is a recurring payout with [Proration::Linear].
+    pub fn accrued(&self, time: DateTime<Utc>) -> Option<Value> {
+        match self {
+            Payout::Adjusted { payout, .. } => payout.accrued(time),
+            Payout::FixedRecurring { amount, start, frequency, proration: Proration::Linear }
+                if time >= *start =>
+            {
+                Some(amount * frequency.split(*start, time).1)
+            }
+            Payout::InterestRecurring {
+                principal,
+                start,
+                frequency,
+                interest,
+                proration: Proration::Linear,
+            } if time >= *start => {
+                let at = |k| interest.anchor.boundary(*frequency, *start, k);
+                let whole = interest.anchor.index(*frequency, *start, time);
+                let (from, to) = (at(whole), at(whole + 1));
+                let stub = (time - from).num_nanoseconds().unwrap() as f64
+                    / (to - from).num_nanoseconds().unwrap() as f64;
+                Some(principal * ((interest.factor(from, to) - 1.0) * stub))
+            }
+            _ => None,
+        }
+    }
+}
 
 impl Item {
     pub fn fixed(value: Value, inception: DateTime<Utc>) -> Self {
@@ -58,13 +312,25 @@ impl Item {
             inception,
             interest: None,
             deltas: vec![],
-            payouts: vec![]
+            payouts: vec![],
+            minimum_payment: None,
+            liquidity: Liquidity::Liquid,
+            capitalization: None,
+            lease: None,
+            round_each_period: None,
+            application_order: ApplicationOrder::InterestFirst,
+            balloon: None,
+            interest_posted: None,
+            fx_overrides: vec![],
+            units: None,
+            sales: vec![],
+            prices: None,
         }
     }
     pub fn basic_debt(
         value: Value,
         interest: f64,
-        period: TimeDelta,
+        period: impl Into<Period>,
         inception: DateTime<Utc>,
     ) -> Self {
         Self {
@@ -72,70 +338,847 @@ impl Item {
             children: vec![],
             // parent: None,
             inception,
-            interest: Some(Interest {
-                percent: interest,
-                period,
-            }),
+            interest: Some(Interest::new(interest, period)),
             deltas: vec![],
-            payouts: vec![]
+            payouts: vec![],
+            minimum_payment: None,
+            liquidity: Liquidity::Liquid,
+            capitalization: None,
+            lease: None,
+            round_each_period: None,
+            application_order: ApplicationOrder::InterestFirst,
+            balloon: None,
+            interest_posted: None,
+            fx_overrides: vec![],
+            units: None,
+            sales: vec![],
+            prices: None,
+        }
+    }
+    /// A credit card carrying `balance`, negative like [Item::basic_debt],
+    /// at a yearly `rate` charged monthly with a minimum payment.
+    pub fn credit_card(
+        balance: Value,
+        rate: f64,
+        min_payment: MinPayment,
+        inception: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            minimum_payment: Some(min_payment),
+            ..Self::basic_debt(balance, rate / 12.0, Period::Months(1), inception)
         }
     }
+    /// Funds held in escrow from `inception` until `release`, see [Item::cancel].
+    pub fn escrow(
+        amount: Value,
+        release: DateTime<Utc>,
+        forfeit_percent_if_cancelled: f64,
+        inception: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            liquidity: Liquidity::Escrow {
+                release,
+                forfeit_percent: forfeit_percent_if_cancelled,
+            },
+            ..Self::fixed(amount, inception)
+        }
+    }
+    pub fn builder(value: Value, inception: DateTime<Utc>) -> ItemBuilder {
+        ItemBuilder::new(value, inception)
+    }
+    /// A fixed item whose inception is the start of a day in UTC.
+    pub fn fixed_on(value: Value, inception: NaiveDate) -> Self {
+        Self::fixed(value, DayPolicy::StartOfDay.utc(inception))
+    }
+    /// A debt whose inception is the start of a day in UTC.
+    pub fn basic_debt_on(
+        value: Value,
+        interest: f64,
+        period: impl Into<Period>,
+        inception: NaiveDate,
+    ) -> Self {
+        Self::basic_debt(value, interest, period, DayPolicy::StartOfDay.utc(inception))
+    }
+    /// Adds a payment of a fixed amount.
     pub fn add_delta(&mut self, time: DateTime<Utc>, value: Value) {
-        self.deltas.push((time, value));
-        self.deltas.sort_by_key(|(f, _)| *f);
+        self.push_delta(Delta::payment(time, value));
+    }
+    /// Adds many deltas at once, sorting only once.
+    pub fn import_deltas(&mut self, deltas: impl IntoIterator<Item = Delta>) {
+        self.deltas.extend(deltas);
+        self.sort_deltas();
+    }
+    /// Replaces the deltas in each bucket with their net, see [aggregate]. This
+    /// moves payments to the end of their bucket so it slightly changes the
+    /// interest on interest bearing items.
+    pub fn aggregate_deltas(&mut self, granularity: Granularity) {
+        self.deltas = aggregate(&self.deltas, granularity, self.currency());
+    }
+    /// The deltas netted per bucket for reporting, the item is left untouched.
+    pub fn deltas_aggregated(&self, granularity: Granularity) -> Vec<Delta> {
+        aggregate(&self.deltas, granularity, self.currency())
+    }
+    /// Adds a delta, keeping the deltas sorted.
+    pub fn push_delta(&mut self, delta: Delta) {
+        self.deltas.push(delta);
+        self.sort_deltas();
+    }
+    /// Orders the deltas by time, deltas at the same time stay in the order
+    /// they were added and apply in that order.
+    pub(crate) fn sort_deltas(&mut self) {
+        self.deltas.sort_by_key(|d| d.time);
+    }
+    /// Cancels an escrow before its release, the forfeited part is taken off
+    /// as a fee and the rest is released at `time`. Returns `None` if the
+    /// item is not an escrow or it has already been released.
+    pub fn cancel(&mut self, time: DateTime<Utc>) -> Option<DeltaId> {
+        let Liquidity::Escrow { release, forfeit_percent } = &mut self.liquidity else {
+            return None;
+        };
+        if time >= *release {
+            return None;
+        }
+        *release = time;
+        let forfeit = Delta::new(time, DeltaAmount::Percent(-*forfeit_percent), DeltaKind::Fee)
+            .with_memo("Escrow forfeited");
+        let id = forfeit.id;
+        self.push_delta(forfeit);
+        Some(id)
     }
     pub fn add_child(&mut self, key: ItemKey) {
         self.children.push(key)
     }
-    
+    /// The per-period rate of the item's interest at the time, zero if it
+    /// has none.
+    pub fn interest_rate(&self, time: DateTime<Utc>) -> f64 {
+        self.interest.as_ref().map_or(0.0, |i| i.rate_at(time))
+    }
+    /// Converts the item into `target` at `rate` rather than the book's
+    /// rates, such as an invoice payable at a contractual rate. Replaces any
+    /// earlier override into `target`.
+    pub fn set_fx_override(&mut self, target: impl Into<Currency>, rate: f64) {
+        let target = target.into();
+        self.fx_overrides.retain(|(currency, _)| *currency != target);
+        self.fx_overrides.push((target, rate));
+    }
+    /// The locked rate into `target`, if there is one.
+    pub fn fx_override(&self, target: Currency) -> Option<f64> {
+        self.fx_overrides
+            .iter()
+            .find(|(currency, _)| *currency == target)
+            .map(|(_, rate)| *rate)
+    }
+    /// The rate an amount in `from` converts into the item's currency at on
+    /// the day, its locked rate if there is one or else the dated rate.
+    pub(crate) fn conversion_rate(
+        &self,
+        from: Currency,
+        time: DateTime<Utc>,
+    ) -> Result<f64, ConversionError> {
+        match self.fx_override(from) {
+            Some(locked) => Ok(locked.recip()),
+            None => CURRENCY_EXCHANGE.rate_at(from, self.currency(), time),
+        }
+    }
+    /// The interest the item earns or is charged, if any.
+    pub fn interest(&self) -> Option<&Interest> {
+        self.interest.as_ref()
+    }
+    /// How often accrued interest is added to the principal, see
+    /// [ItemBuilder::capitalization].
+    pub fn capitalization(&self) -> Option<TimeDelta> {
+        self.capitalization
+    }
+    /// Changes how often accrued interest is added to the principal, `None`
+    /// compounding continuously. The interval must be strictly positive, a
+    /// rejected one leaves the item as it was.
+    pub fn set_capitalization(&mut self, every: Option<TimeDelta>) -> Result<(), PeriodError> {
+        if let Some(every) = every {
+            Period::Delta(every).validate()?;
+        }
+        self.capitalization = every;
+        Ok(())
+    }
+    /// Swaps out the interest. Assessments are not replayed so the new rate
+    /// applies retroactively, to the balance from inception and every delta
+    /// already on the item.
+    pub fn set_interest(&mut self, interest: Option<Interest>) {
+        self.interest = interest;
+    }
+    /// Creates a copy of this item with the interest rate swapped out, keeping
+    /// the period. Items without interest get a yearly period.
+    pub fn with_interest_rate(&self, rate: f64) -> Item {
+        let period = self
+            .interest
+            .as_ref()
+            .map(|i| i.period)
+            .unwrap_or(Period::Delta(TimeDelta::days(365)));
+        Item {
+            interest: Some(Interest::new(rate, period)),
+            ..self.clone()
+        }
+    }
+    /// The interest a debt accrues from `from` to `to` on what is owed at
+    /// `from`, positive and zero if nothing is owed or there is no interest.
+    pub fn accrual(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Value {
+        let owed = (-self.assess_amount(from)).max(0.0);
+        let accrued = self.interest.as_ref().map_or(0.0, |i| owed * (i.factor(from, to) - 1.0));
+        Value::new(self.currency(), accrued)
+    }
+    /// The minimum payments due every month after `from` up to and including
+    /// `to`, each computed on the balance after the payments before it. They
+    /// stop once the item is paid off, items without a minimum have none.
+    pub fn project_minimum_payments(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Delta> {
+        let Some(minimum) = &self.minimum_payment else {
+            return vec![];
+        };
+        let currency = self.currency();
+        let month = Period::Months(1);
+        let mut projected = self.clone();
+        let mut payments = vec![];
+        for count in 1.. {
+            let time = month.step(from, count);
+            let owed = -projected.assess_amount(time);
+            // Within half a cent is paid off.
+            if time > to || owed < 0.005 {
+                break;
+            }
+            let payment = Delta::payment(time, Value::new(currency, minimum.amount(owed, currency)));
+            projected.push_delta(payment.clone());
+            payments.push(payment);
+        }
+        payments
+    }
+    /// Assesses the item alongside the cash its payouts have paid by the
+    /// time. Cash is kept in the currency it was paid in, see
+    /// [AssessmentResult::cash_by_currency], and totalled in the item's
+    /// currency at the rate of each payment's date. Payouts prorated with
+    /// [Proration::Linear] add what has accrued in the current period to the
+    /// value at the rate of `time`, it only becomes cash once paid.
+    pub fn assess_full(&self, time: DateTime<Utc>) -> AssessmentResult {
+        self.assess_full_with(&CURRENCY_EXCHANGE, time)
+    }
+    /// The cash the item pays by `until`, its payouts and the proceeds of
+    /// its sales.
+    pub fn settlements(
+        &self,
+        until: DateTime<Utc>,
+    ) -> impl Iterator<Item = (DateTime<Utc>, Value)> + '_ {
+        let sales = self
+            .sales
+            .iter()
+            .filter(move |sale| sale.time <= until)
+            .map(|sale| (sale.time, sale.proceeds.clone()));
+        self.payouts
+            .iter()
+            .flat_map(move |p| p.occurrences(self.inception, until))
+            .chain(sales)
+    }
+    /// Like [Item::assess_full] converting with the given table.
+    pub fn assess_full_with(&self, table: &ConversionTable, time: DateTime<Utc>) -> AssessmentResult {
+        let currency = self.currency();
+        let into = |value: &Value, on: DateTime<Utc>| {
+            table
+                .rate_at(value.currency(), currency, on)
+                .unwrap_or_else(|e| panic!("{e}"))
+                * value.amount()
+        };
+        let mut ledger = MultiCurrencySum::default();
+        let mut cash = 0.0;
+        for (on, value) in self.settlements(time) {
+            cash += into(&value, on);
+            ledger.add(&value);
+        }
+        let accrued: f64 = self
+            .payouts
+            .iter()
+            .filter_map(|payout| payout.accrued(time))
+            .map(|value| into(&value, time))
+            .sum();
+        AssessmentResult::with_ledger(
+            Value::new(currency, self.assess_amount(time) + accrued),
+            Value::new(currency, cash),
+            ledger.totals().map(|(_, total)| total).collect(),
+        )
+    }
+    /// Every delta alongside the amount it actually changed the balance by
+    /// in the item's currency, this matters for percentage deltas whose
+    /// amount depends on the balance. The amounts come from the same fold
+    /// as [Assesible::assess], so the book value, the amounts and the
+    /// interest add up to the assessment.
+    pub fn delta_amounts(&self) -> Vec<(&Delta, Value)> {
+        self.try_delta_amounts().unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// [Item::delta_amounts], failing if a delta in another currency has no
+    /// rate for its day.
+    pub fn try_delta_amounts(&self) -> Result<Vec<(&Delta, Value)>, ConversionError> {
+        let currency = self.currency();
+        let mut amounts = Vec::with_capacity(self.deltas.len());
+        if let Some(last) = self.deltas.last() {
+            self.fold(last.time, AssessMode::IncludePlanned, &mut |delta, change| {
+                amounts.push((delta, Value::new(currency, change)))
+            })?;
+        }
+        Ok(amounts)
+    }
+    /// The realized exchange gain or loss, in the item's currency, on the
+    /// actual deltas in `(from, to]` that captured a [Delta::rate]: what
+    /// they converted to at that rate less what they would have at the
+    /// rate `policy` gives for their day.
+    pub fn fx_gains(&self, from: DateTime<Utc>, to: DateTime<Utc>, policy: RatePolicy) -> Value {
+        let currency = self.currency();
+        let gains = self
+            .deltas
+            .iter()
+            .filter(|d| from < d.time && d.time <= to && d.status == DeltaStatus::Actual)
+            .filter_map(|delta| match (&delta.amount, delta.rate) {
+                (DeltaAmount::Fixed(value), Some(captured)) if value.currency() != currency => {
+                    let reference = CURRENCY_EXCHANGE
+                        .rate_for(policy, value.currency(), currency, delta.time)
+                        .unwrap_or_else(|e| panic!("{e}"));
+                    Some(value.amount() * (captured - reference))
+                }
+                _ => None,
+            });
+        Value::new(currency, gains.sum::<f64>())
+    }
 }
 
 impl Assesible for Item {
     fn assess(&self, time: DateTime<Utc>) -> Value {
-        if self.deltas.is_empty() {
-            if self.interest.is_some() {
-                self.interest
-                    .as_ref()
-                    .unwrap()
-                    .apply(self.inception, time, &self.book_value)
-            } else {
-                self.book_value.clone()
+        Value::new(self.book_value.currency(), self.assess_amount(time))
+    }
+    fn currency(&self) -> Currency {
+        self.book_value.currency()
+    }
+}
+
+impl Item {
+    /// The assessed amount in the item's currency, see [Assesible::assess].
+    pub(crate) fn assess_amount(&self, time: DateTime<Utc>) -> f64 {
+        self.assess_amount_mode(time, AssessMode::IncludePlanned)
+    }
+    /// Assesses the item counting only the deltas `mode` counts, see
+    /// [DeltaStatus](super::delta::DeltaStatus).
+    pub fn assess_mode(&self, time: DateTime<Utc>, mode: AssessMode) -> Value {
+        Value::new(self.currency(), self.assess_amount_mode(time, mode))
+    }
+    /// Like [Assesible::assess], failing if a delta in another currency has
+    /// no rate into the item's on its day or the interest floats over a
+    /// benchmark without rates.
+    pub fn try_assess(&self, time: DateTime<Utc>) -> Result<Value, ConversionError> {
+        self.try_assess_mode(time, AssessMode::IncludePlanned)
+    }
+    /// Like [Item::assess_mode], failing the same way as [Item::try_assess].
+    pub fn try_assess_mode(
+        &self,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+    ) -> Result<Value, ConversionError> {
+        Ok(Value::new(self.currency(), self.try_assess_amount_mode(time, mode)?))
+    }
+    /// Like [Item::assess_amount] with the deltas `mode` counts.
+    pub(crate) fn assess_amount_mode(&self, time: DateTime<Utc>, mode: AssessMode) -> f64 {
+        self.try_assess_amount_mode(time, mode)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Item::assess_amount_mode], failing the same way as
+    /// [Item::try_assess].
+    pub(crate) fn try_assess_amount_mode(
+        &self,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+    ) -> Result<f64, ConversionError> {
+        let balance = self.balance_mode(time, mode)?;
+        Ok(match &self.prices {
+            Some(prices) => balance + self.units_at(time).unwrap_or(0.0) * prices.price(time),
+            None => balance,
+        })
+    }
+    /// The balance without the units a [Item::position] holds.
+    fn balance_mode(&self, time: DateTime<Utc>, mode: AssessMode) -> Result<f64, ConversionError> {
+        if let Some(lease) = &self.lease {
+            return Ok(-lease.remaining(time));
+        }
+        self.fold(time, mode, &mut |_, _| {})
+    }
+    /// The balance at `time`, handing `visit` every counted delta with what
+    /// it changed the balance by in the item's currency.
+    fn fold<'a>(
+        &'a self,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+        visit: &mut dyn FnMut(&'a Delta, f64),
+    ) -> Result<f64, ConversionError> {
+        // The fold runs on the bare amount, every delta is converted into the
+        // item's currency as it is applied.
+        if let Some(interest) = &self.interest {
+            interest.check_benchmark()?;
+        }
+        let currency = self.book_value.currency();
+        let mut book = self.book_value.amount();
+        if let Some((principal, accrued)) = self.capitalized(time, mode, visit)? {
+            return Ok(principal + accrued);
+        }
+        let counted = self
+            .deltas
+            .iter()
+            .take_while(|d| d.time <= time)
+            .filter(|d| mode.counts(d));
+        if let Some(interest) = &self.interest {
+            let mut incep = self.inception;
+            // If we have hit the maximum time stop applying payments, we do not want to factor this into our calculations.
+            for delta in counted {
+                let assessed = self.grow(interest, book, incep, delta.time);
+                let change = delta.try_resolve_amount(assessed, currency)?;
+                visit(delta, change);
+                book = self.round(assessed + change);
+                incep = delta.time;
             }
+            book = self.grow(interest, book, incep, time);
         } else {
-            if self.interest.is_some() {
-                let mut book = self.book_value.clone();
-                let mut incep = self.inception;
-                for (rtime, payment) in &self.deltas {
-                    if *rtime > time {
-                        // If we have hit the maximum time stop applying payments, we do not want to factor this into our calculations.
-                        break;
-                    }
+            for delta in counted {
+                let change = delta.try_resolve_amount(book, currency)?;
+                visit(delta, change);
+                book = self.round(book + change);
+            }
+        }
+        Ok(book)
+    }
+    /// Assesses the item at every time in one pass over its deltas, in the
+    /// order the times were given. The results are the same as calling
+    /// [Assesible::assess] at each time, leases, positions and capitalized
+    /// items are assessed that way.
+    pub fn assess_at_many(&self, times: &[DateTime<Utc>]) -> Vec<Value> {
+        let currency = self.currency();
+        let capitalized = self.interest.is_some() && self.capitalization.is_some();
+        if self.lease.is_some() || self.prices.is_some() || capitalized {
+            return times.iter().map(|time| self.assess(*time)).collect();
+        }
+        let mut order: Vec<usize> = (0..times.len()).collect();
+        order.sort_by_key(|i| times[*i]);
 
-                    let assessed = self.interest.as_ref().unwrap().apply(incep, *rtime, book);
-                    book = assessed + payment.clone();
-                    incep = *rtime;
+        // The same fold as [Item::assess_amount], stopping at each time.
+        let mut amounts = vec![0.0; times.len()];
+        let (mut book, mut incep) = (self.book_value.amount(), self.inception);
+        let mut deltas = self.deltas.iter().peekable();
+        for index in order {
+            let time = times[index];
+            while let Some(delta) = deltas.next_if(|d| d.time <= time) {
+                match &self.interest {
+                    Some(interest) => {
+                        let assessed = self.grow(interest, book, incep, delta.time);
+                        book = self.round(assessed + delta.resolve_amount(assessed, currency));
+                        incep = delta.time;
+                    }
+                    None => book = self.round(book + delta.resolve_amount(book, currency)),
                 }
+            }
+            amounts[index] = match &self.interest {
+                Some(interest) => self.grow(interest, book, incep, time),
+                None => book,
+            };
+        }
+        amounts.into_iter().map(|amount| Value::new(currency, amount)).collect()
+    }
+    /// Rounds like [Item::round_each_period], leaving the amount as it is
+    /// if that is not set.
+    fn round(&self, amount: f64) -> f64 {
+        match self.round_each_period {
+            Some((decimals, rounding)) => rounding.round(amount, decimals),
+            None => amount,
+        }
+    }
+    /// Applies interest from `from` to `to`, rounding after every whole
+    /// period when [Item::round_each_period] is set. The period part way
+    /// through is not rounded as it has not been charged yet.
+    fn grow(&self, interest: &Interest, amount: f64, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        let from = match self.interest_posted {
+            Some(posted) if posted > from => posted.min(to),
+            _ => from,
+        };
+        if self.round_each_period.is_none() {
+            return amount * interest.factor(from, to);
+        }
+        let (mut amount, mut at) = (amount, from);
+        for next in interest.boundaries(from).take_while(|next| *next <= to) {
+            amount = self.round(amount * interest.factor(at, next));
+            at = next;
+        }
+        amount * interest.factor(at, to)
+    }
+    /// The principal and the interest accrued on it since it was last
+    /// capitalized, `None` unless the item has both interest and a
+    /// [Item::capitalization]. Capitalization comes before deltas at the
+    /// same time.
+    fn capitalized<'a>(
+        &'a self,
+        time: DateTime<Utc>,
+        mode: AssessMode,
+        visit: &mut dyn FnMut(&'a Delta, f64),
+    ) -> Result<Option<(f64, f64)>, ConversionError> {
+        let (Some(interest), Some(every)) = (&self.interest, self.capitalization) else {
+            return Ok(None);
+        };
+        let currency = self.book_value.currency();
+        let (mut principal, mut accrued) = (self.book_value.amount(), 0.0);
+        let mut last = self.inception;
+        let posted = self.interest_posted.unwrap_or(self.inception);
+        let accrue = |principal: f64, from: DateTime<Utc>, to| {
+            principal * interest.simple(from.max(posted).min(to), to)
+        };
+        // A calendar anchor capitalizes at its month or quarter ends instead.
+        let next = |after| interest.anchor.next_end(after).unwrap_or(after + every);
 
-                self.interest.as_ref().unwrap().apply(incep, time, book)
+        let mut boundary = next(self.inception);
+        let mut deltas = self
+            .deltas
+            .iter()
+            .take_while(|d| d.time <= time)
+            .filter(|d| mode.counts(d))
+            .peekable();
+        loop {
+            let next_delta = deltas.peek().map(|d| d.time);
+            let before = |t| match self.application_order {
+                ApplicationOrder::InterestFirst => boundary <= t,
+                ApplicationOrder::PaymentFirst => boundary < t,
+            };
+            if boundary <= time && next_delta.is_none_or(before) {
+                accrued += accrue(principal, last, boundary);
+                principal = self.round(principal + accrued);
+                accrued = 0.0;
+                last = boundary;
+                boundary = next(boundary);
+            } else if let Some(delta) = deltas.next() {
+                accrued += accrue(principal, last, delta.time);
+                // Interest that is about to be added is not part of the
+                // balance yet when the payment goes first.
+                let balance = match delta.time == boundary {
+                    true => principal,
+                    false => principal + accrued,
+                };
+                let change = delta.try_resolve_amount(balance, currency)?;
+                visit(delta, change);
+                principal = self.round(principal + change);
+                last = delta.time;
             } else {
-                self.book_value.clone() + self.deltas.iter().map(|(_, i)| i).sum()
+                break;
             }
         }
+        accrued += accrue(principal, last, time);
+        Ok(Some((principal, accrued)))
     }
-    fn currency(&self) -> Currency {
-        self.book_value.currency()
+    /// Writes the interest out as [DeltaKind::Interest] deltas, one every
+    /// `frequency` since it was last posted and one for the part of a period
+    /// left before `until`. The interest rate then only applies after
+    /// `until` and each posting comes before the deltas at its time, so the
+    /// item assesses the same as before at every posting.
+    pub fn post_interest(&mut self, until: DateTime<Utc>, frequency: TimeDelta) {
+        assert!(frequency > TimeDelta::zero(), "postings must be apart");
+        if self.interest.is_none() || self.lease.is_some() {
+            return;
+        }
+        let currency = self.currency();
+        let start = self.interest_posted.unwrap_or(self.inception);
+        let mut ends: Vec<_> = (1..)
+            .map(|k| start + frequency * k)
+            .take_while(|end| *end < until)
+            .collect();
+        if start < until {
+            ends.push(until);
+        }
+        // The balance at a time before the deltas made then.
+        let before = |item: &Item, time| {
+            let mut item = item.clone();
+            item.deltas.retain(|d| d.time < time);
+            item.assess_amount(time)
+        };
+        let implied = self.clone();
+        for end in ends {
+            let target = before(&implied, end);
+            self.interest_posted = Some(end);
+            let interest = target - before(self, end);
+            let delta = Delta::interest(end, Value::new(currency, interest)).with_memo("Interest");
+            let at = self.deltas.partition_point(|d| d.time < end);
+            self.deltas.insert(at, delta);
+        }
+    }
+    /// Splits the assessment into the principal and the interest accrued but
+    /// not yet capitalized, all of it is principal without a
+    /// [Item::capitalization].
+    pub fn breakdown(&self, time: DateTime<Utc>) -> Breakdown {
+        let currency = self.currency();
+        let (principal, accrued) = self
+            .capitalized(time, AssessMode::IncludePlanned, &mut |_, _| {})
+            .unwrap_or_else(|e| panic!("{e}"))
+            .unwrap_or_else(|| (self.assess_amount(time), 0.0));
+        Breakdown {
+            principal: Value::new(currency, principal),
+            uncapitalized: Value::new(currency, accrued),
+        }
+    }
+}
+
+/// What an item is made up of at a time, see [Item::breakdown].
+#[derive(Clone, Debug)]
+pub struct Breakdown {
+    pub principal: Value,
+    /// Interest accrued since it was last added to the principal.
+    pub uncapitalized: Value,
+}
+
+
+/// Builds up an [Item] one piece at a time.
+#[derive(Clone, Debug)]
+pub struct ItemBuilder {
+    item: Item,
+}
+
+impl ItemBuilder {
+    pub fn new(value: Value, inception: DateTime<Utc>) -> Self {
+        Self {
+            item: Item::fixed(value, inception),
+        }
+    }
+    /// Compounds the rate once every period.
+    pub fn interest(mut self, rate: f64, period: impl Into<Period>) -> Self {
+        self.item.interest = Some(Interest::new(rate, period));
+        self
+    }
+    pub fn delta(mut self, delta: Delta) -> Self {
+        self.item.push_delta(delta);
+        self
+    }
+    /// Adds a payment of a fixed amount.
+    pub fn payment(self, time: DateTime<Utc>, value: Value) -> Self {
+        self.delta(Delta::payment(time, value))
+    }
+    /// Adds a payment of `value` every period from `start` up to and
+    /// including `until`, such as a salary or rent. Panics unless the period
+    /// is longer than zero.
+    pub fn recurring_payment(
+        self,
+        start: DateTime<Utc>,
+        every: impl Into<Period>,
+        until: DateTime<Utc>,
+        value: Value,
+    ) -> Self {
+        self.recurring_payment_adjusted(start, every, until, value, &BusinessDays::default())
+    }
+    /// Like [ItemBuilder::recurring_payment] with each payment moved to the
+    /// day `days` picks, which is when interest sees it. `until` bounds the
+    /// scheduled days before they are moved.
+    pub fn recurring_payment_adjusted(
+        mut self,
+        start: DateTime<Utc>,
+        every: impl Into<Period>,
+        until: DateTime<Utc>,
+        value: Value,
+        days: &BusinessDays,
+    ) -> Self {
+        let every = every
+            .into()
+            .validate()
+            .unwrap_or_else(|e| panic!("invalid payment period: {e}"));
+        let times = (0..).map(|k| every.step(start, k)).take_while(|time| *time <= until);
+        for time in times {
+            self = self.payment(days.adjust(time), value.clone());
+        }
+        self
+    }
+    /// Panics if a recurring payout's frequency is not strictly positive.
+    pub fn payout(mut self, payout: Payout) -> Self {
+        if let Payout::FixedRecurring { frequency, .. }
+        | Payout::InterestRecurring { frequency, .. } = payout.unadjusted()
+        {
+            frequency
+                .validate()
+                .unwrap_or_else(|e| panic!("invalid payout frequency: {e}"));
+        }
+        self.item.payouts.push(payout);
+        self
+    }
+    /// Adds accrued interest to the principal once every `every`. Panics
+    /// unless it is longer than zero, see [ItemBuilder::try_capitalization].
+    pub fn capitalization(self, every: TimeDelta) -> Self {
+        self.try_capitalization(every)
+            .unwrap_or_else(|e| panic!("invalid capitalization: {e}"))
+    }
+    /// Adds accrued interest to the principal once every `every`, which must
+    /// be strictly positive.
+    pub fn try_capitalization(mut self, every: TimeDelta) -> Result<Self, PeriodError> {
+        self.item.set_capitalization(Some(every))?;
+        Ok(self)
+    }
+    /// Anchors the periods of the interest set so far, see [PeriodAnchor].
+    pub fn period_anchor(mut self, anchor: PeriodAnchor) -> Self {
+        if let Some(interest) = &mut self.item.interest {
+            interest.anchor = anchor;
+        }
+        self
+    }
+    /// Sets what comes first at a capitalization boundary.
+    pub fn application_order(mut self, order: ApplicationOrder) -> Self {
+        self.item.application_order = order;
+        self
+    }
+    /// Rounds the balance like a statement, see [Item::round_each_period].
+    pub fn round_each_period(mut self, decimals: u32, rounding: Rounding) -> Self {
+        self.item.round_each_period = Some((decimals, rounding));
+        self
+    }
+    pub fn build(self) -> Item {
+        self.item
     }
 }
 
 
+/// How [Interest] treats a time before the one it accrues from, such as
+/// assessing an item before its inception.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InceptionPolicy {
+    /// The interest runs backwards, so earlier times are discounted.
+    #[default]
+    Discount,
+    /// Nothing accrues before the start, the value is held as it is.
+    Hold,
+}
+
+/// Reads a registered benchmark, panics if it is missing or has no rates.
+fn benchmark_rate<T>(id: BenchmarkId, read: impl FnOnce(&Benchmark) -> Option<T>) -> T {
+    BENCHMARKS
+        .with(id, read)
+        .flatten()
+        .unwrap_or_else(|| panic!("no rates for benchmark {id:?}"))
+}
+
+#[derive(Clone, Debug)]
 pub struct Interest {
-    percent: f64,  
-    period: TimeDelta,
+    percent: f64,
+    period: Period,
+    /// The length of fixed periods, kept so it is not recomputed per call.
+    period_nanos: Option<i64>,
+    anchor: PeriodAnchor,
+    before_inception: InceptionPolicy,
+    /// The benchmark the rate floats over, `percent` being the spread.
+    index: Option<BenchmarkId>,
 }
 
 impl Interest {
-    pub fn new(percent: f64, period: TimeDelta) -> Self {
-        Self { percent, period }
+    /// Panics unless the period is longer than zero, see [Interest::try_new].
+    pub fn new(percent: f64, period: impl Into<Period>) -> Self {
+        Self::try_new(percent, period).unwrap_or_else(|e| panic!("invalid interest: {e}"))
+    }
+    /// Interest charged every `period`, which must be strictly positive.
+    pub fn try_new(percent: f64, period: impl Into<Period>) -> Result<Self, PeriodError> {
+        let mut interest = Self {
+            percent,
+            period: Period::Years(1),
+            period_nanos: None,
+            anchor: PeriodAnchor::Inception,
+            before_inception: InceptionPolicy::Discount,
+            index: None,
+        };
+        interest.set_period(period)?;
+        Ok(interest)
+    }
+    /// Variable rate interest charged at the benchmark's most recent rate
+    /// in [BENCHMARKS] plus `spread`, compounding every `period`.
+    pub fn indexed(benchmark: BenchmarkId, spread: f64, period: impl Into<Period>) -> Self {
+        let mut interest = Self::new(spread, period);
+        interest.index = Some(benchmark);
+        interest
+    }
+    /// The benchmark the rate floats over, if it does.
+    pub fn benchmark(&self) -> Option<BenchmarkId> {
+        self.index
+    }
+    /// Fails if the rate floats over a benchmark that is not registered or
+    /// has no rates, the rate could not be read then.
+    pub(crate) fn check_benchmark(&self) -> Result<(), ConversionError> {
+        match self.index {
+            Some(id) if BENCHMARKS.with(id, |b| b.rates().is_empty()) != Some(false) => {
+                Err(ConversionError::NoBenchmark(id))
+            }
+            _ => Ok(()),
+        }
+    }
+    /// The rate charged per period at the time, the benchmark plus the
+    /// spread for indexed interest.
+    pub fn rate_at(&self, time: DateTime<Utc>) -> f64 {
+        match self.index {
+            Some(id) => self.percent + benchmark_rate(id, |b| b.rate_at(time)),
+            None => self.percent,
+        }
+    }
+    /// The span from `from` to `to` split where the benchmark changes, each
+    /// with the fixed rate interest in effect over it. Interest that is not
+    /// indexed is a single span.
+    pub(crate) fn segments(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<(Interest, DateTime<Utc>, DateTime<Utc>)> {
+        let Some(id) = self.index else {
+            return vec![(self.clone(), from, to)];
+        };
+        let changes = benchmark_rate(id, |b| Some(b.changes(from, to).collect::<Vec<_>>()));
+        let mut start = from;
+        changes
+            .into_iter()
+            .chain([to])
+            .map(|end| {
+                let fixed = Self {
+                    percent: self.rate_at(start),
+                    index: None,
+                    ..self.clone()
+                };
+                let segment = (fixed, start, end);
+                start = end;
+                segment
+            })
+            .collect()
+    }
+    /// Decides what happens before the start, see [InceptionPolicy].
+    pub fn before_inception(mut self, policy: InceptionPolicy) -> Self {
+        self.before_inception = policy;
+        self
+    }
+    pub fn inception_policy(&self) -> InceptionPolicy {
+        self.before_inception
+    }
+    /// Puts the period boundaries where `anchor` says.
+    pub fn anchored(mut self, anchor: PeriodAnchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+    pub fn anchor(&self) -> PeriodAnchor {
+        self.anchor
+    }
+    /// The rate charged every period, `0.05` being 5%. For indexed interest
+    /// this is the spread, see [Interest::rate_at].
+    pub fn rate(&self) -> f64 {
+        self.percent
+    }
+    /// Changes the rate, for indexed interest this is the spread.
+    pub fn set_rate(&mut self, percent: f64) {
+        self.percent = percent;
+    }
+    /// How often the interest compounds.
+    pub fn period(&self) -> Period {
+        self.period
+    }
+    /// Changes how often the interest compounds, the period must be strictly
+    /// positive like in [Interest::try_new]. A rejected period leaves the
+    /// interest as it was.
+    pub fn set_period(&mut self, period: impl Into<Period>) -> Result<(), PeriodError> {
+        let period = period.into().validate()?;
+        self.period_nanos = match period {
+            Period::Delta(delta) => Some(delta.num_nanoseconds().unwrap()),
+            _ => None,
+        };
+        self.period = period;
+        Ok(())
     }
     /// Apply the interest formula to the value, this
     /// introspects on the settings of this [Interest] object
@@ -146,10 +1189,82 @@ impl Interest {
         current_time: DateTime<Utc>,
         value: R,
     ) -> Value {
-        let delta = current_time - inception;
-        let periods = delta.num_nanoseconds().unwrap() as f64
-            / self.period.num_nanoseconds().unwrap() as f64;
-        value.borrow() * (1.0 + self.percent).powf(periods)
+        value.borrow() * self.factor(inception, current_time)
+    }
+    /// The factor a value grows by between the two times. When
+    /// `current_time` is before `inception` this follows the
+    /// [InceptionPolicy].
+    pub fn factor(&self, inception: DateTime<Utc>, current_time: DateTime<Utc>) -> f64 {
+        if current_time < inception && self.before_inception == InceptionPolicy::Hold {
+            return 1.0;
+        }
+        if self.index.is_some() {
+            let (from, to) = (inception.min(current_time), inception.max(current_time));
+            let factor: f64 = self
+                .segments(from, to)
+                .iter()
+                .map(|(fixed, start, end)| fixed.factor(*start, *end))
+                .product();
+            return if current_time < inception { factor.recip() } else { factor };
+        }
+        if self.anchor != PeriodAnchor::Inception {
+            let periods = self.periods(inception, current_time);
+            return match periods.fract() == 0.0 && periods.abs() < i32::MAX as f64 {
+                true => (1.0 + self.percent).powi(periods as i32),
+                false => (1.0 + self.percent).powf(periods),
+            };
+        }
+        let Some(period_nanos) = self.period_nanos else {
+            let (whole, fraction) = self.period.split(inception, current_time);
+            return match i32::try_from(whole) {
+                Ok(whole) if fraction == 0.0 => (1.0 + self.percent).powi(whole),
+                _ => (1.0 + self.percent).powf(whole as f64 + fraction),
+            };
+        };
+        let elapsed = (current_time - inception).num_nanoseconds().unwrap();
+
+        // Spans of whole periods, to within a nanosecond, take the cheaper powi.
+        let whole = elapsed.div_euclid(period_nanos);
+        let rest = elapsed.rem_euclid(period_nanos);
+        let whole = match rest {
+            0 | 1 => Some(whole),
+            r if period_nanos - r <= 1 => Some(whole + 1),
+            _ => None,
+        };
+        match whole.and_then(|w| i32::try_from(w).ok()) {
+            Some(whole) => (1.0 + self.percent).powi(whole),
+            None => (1.0 + self.percent).powf(elapsed as f64 / period_nanos as f64),
+        }
+    }
+    /// The number of periods from `from` to `to`, following the anchor.
+    pub fn periods(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        self.anchor.between(self.period, from, to)
+    }
+    /// The simple interest on one unit from `from` to `to`, the rate of each
+    /// period times the periods it was in effect for.
+    pub(crate) fn simple(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        if self.index.is_none() {
+            return self.percent * self.periods(from, to);
+        }
+        self.segments(from, to)
+            .iter()
+            .map(|(fixed, start, end)| fixed.simple(*start, *end))
+            .sum()
+    }
+    /// The period boundaries after `from`, following the anchor.
+    pub fn boundaries(&self, from: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        (1..).map(move |k| self.anchor.boundary(self.period, from, k))
+    }
+    /// Applies the interest for a number of periods directly, for callers
+    /// that step through time and already know how far they moved. The rate
+    /// is the one in effect at `from`, where the periods start.
+    pub fn apply_periods<R: Borrow<Value>>(
+        &self,
+        from: DateTime<Utc>,
+        periods: f64,
+        value: R,
+    ) -> Value {
+        value.borrow() * (1.0 + self.rate_at(from)).powf(periods)
     }
     /// This returns the actual interest amounts.
     pub fn interest<R: Borrow<Value>>(
@@ -165,9 +1280,630 @@ impl Interest {
 
 #[cfg(test)]
 mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{
+        book::Book,
+        delta::Granularity,
+        period::{Period, PeriodAnchor, PeriodError},
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use crate::instruments::convert::{ConversionError, ConversionTable, CURRENCY_EXCHANGE};
+
+    use crate::instruments::value::Rounding;
+
+    use crate::instruments::delta::{Delta, DeltaAmount, DeltaKind};
+
+    use crate::assert_value_eq;
+    use crate::instruments::benchmark::{Benchmark, BenchmarkTable, BENCHMARKS};
+
+    use super::{ApplicationOrder, InceptionPolicy, Interest, Item, Payout, Proration};
+
+    /// The formula before the powi fast path.
+    fn apply_powf(interest: &Interest, from: DateTime<Utc>, to: DateTime<Utc>, value: &Value) -> f64 {
+        let periods = (to - from).num_nanoseconds().unwrap() as f64
+            / interest.period.approximate().num_nanoseconds().unwrap() as f64;
+        value.amount() * (1.0 + interest.percent).powf(periods)
+    }
+
+    #[test]
+    pub fn test_indexed_interest() {
+        let on = |m| Utc.with_ymd_and_hms(2025, m, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let prime = BENCHMARKS.register(
+            Benchmark::new("Prime")
+                .with_rate(on(1), 0.055)
+                .with_rate(on(4), 0.0525)
+                .with_rate(on(9), 0.05),
+        );
+        // Prime + 0.5%.
+        let interest = Interest::indexed(prime, 0.005, Period::Years(1));
+        assert_eq!(interest.rate_at(on(5)), 0.0525 + 0.005);
+        let mut heloc = Item::fixed(Value::new("CAD", -50_000.0), on(1));
+        heloc.set_interest(Some(interest.clone()));
+
+        let chained = |segments: &[(f64, DateTime<Utc>, DateTime<Utc>)]| {
+            segments.iter().fold(heloc.book_value.clone(), |value, (rate, from, to)| {
+                Interest::new(*rate, Period::Years(1)).apply(*from, *to, value)
+            })
+        };
+        let three = [
+            (0.06, on(1), on(4)),
+            (0.0575, on(4), on(9)),
+            (0.055, on(9), end),
+        ];
+        assert_value_eq!(heloc.assess(end), chained(&three), 1e-9);
+        let june = chained(&[three[0], (0.0575, on(4), on(6))]);
+        assert_value_eq!(heloc.assess(on(6)), june, 1e-9);
+        let before = heloc.assess(on(6));
+
+        // Every other read of the rate follows the benchmark as well.
+        assert_eq!(heloc.interest_rate(on(5)), 0.0525 + 0.005);
+        assert_value_eq!(
+            interest.apply_periods(on(5), 2.0, &heloc.book_value),
+            &heloc.book_value * (1.0 + 0.0525 + 0.005f64).powi(2),
+            1e-9
+        );
+        let mut capitalized = heloc.clone();
+        capitalized.capitalization = Some(TimeDelta::days(3650));
+        let simple: f64 = three
+            .iter()
+            .map(|(rate, from, to)| rate * Period::Years(1).between(*from, *to))
+            .sum();
+        assert_value_eq!(
+            capitalized.assess(end),
+            &heloc.book_value * (1.0 + simple),
+            1e-9
+        );
+
+        // A cut in November only changes what comes after it.
+        assert!(BENCHMARKS.set_rate(prime, on(11), 0.045));
+        let four = [three[0], three[1], (0.055, on(9), on(11)), (0.05, on(11), end)];
+        assert_value_eq!(heloc.assess(on(6)), before, 0.0);
+        assert_value_eq!(heloc.assess(end), chained(&four), 1e-9);
+        // Looking back before inception undoes the same spans.
+        let back = Interest::indexed(prime, 0.005, Period::Years(1)).factor(end, on(1));
+        assert_value_eq!(chained(&four) * back, heloc.book_value, 1e-9);
+    }
+
+    /// A benchmark that is not registered, or has no rates yet, fails the
+    /// assessment rather than panicking.
+    #[test]
+    pub fn test_missing_benchmark() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let elsewhere = BenchmarkTable::new().register(Benchmark::new("Elsewhere"));
+        let mut heloc = Item::fixed(Value::new("CAD", -1_000.0), start);
+        heloc.set_interest(Some(Interest::indexed(elsewhere, 0.005, Period::Years(1))));
+        assert_eq!(
+            heloc.try_assess(start).unwrap_err(),
+            ConversionError::NoBenchmark(elsewhere)
+        );
+        let mut book = Book::default();
+        book.add(heloc.clone());
+        assert_eq!(book.try_assess(start).unwrap_err(), ConversionError::NoBenchmark(elsewhere));
+
+        let empty = BENCHMARKS.register(Benchmark::new("Empty"));
+        heloc.set_interest(Some(Interest::indexed(empty, 0.005, Period::Years(1))));
+        assert_eq!(heloc.try_assess(start).unwrap_err(), ConversionError::NoBenchmark(empty));
+        BENCHMARKS.set_rate(empty, start, 0.05);
+        assert_value_eq!(heloc.try_assess(start).unwrap(), Value::new("CAD", -1_000.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_interest_periods_positive() {
+        let zero = TimeDelta::zero();
+        assert_eq!(
+            Interest::try_new(0.05, zero).unwrap_err(),
+            PeriodError::Empty(Period::Delta(zero))
+        );
+        assert_eq!(
+            Interest::try_new(0.05, Period::Months(0)).unwrap_err(),
+            PeriodError::Empty(Period::Months(0))
+        );
+        let back = TimeDelta::days(-365);
+        assert_eq!(
+            Interest::try_new(0.05, back).unwrap_err(),
+            PeriodError::Negative(Period::Delta(back))
+        );
+        let caught = std::panic::catch_unwind(|| Interest::new(0.05, Period::Years(0)));
+        let message = *caught.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(message, "invalid interest: periods must be longer than zero, got P0Y");
+
+        // A recurring payout that never moves pays once rather than forever.
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let stuck = Payout::FixedRecurring {
+            amount: Value::new("CAD", 10.0),
+            start,
+            frequency: Period::Delta(zero),
+            proration: Proration::None,
+        };
+        assert_eq!(stuck.occurrences(start, start + TimeDelta::days(1)).count(), 1);
+    }
+
+    #[test]
+    pub fn test_recurring_payment() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap();
+        let rent = Item::builder(Value::new("CAD", 0.0), start)
+            .recurring_payment(start, Period::Months(1), until, Value::new("CAD", -1_000.0))
+            .build();
+        assert_eq!(rent.deltas.len(), 12);
+        assert_eq!(rent.deltas[1].time, Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap());
+        assert_eq!(rent.assess(until).amount(), -12_000.0);
+    }
+
+    /// Times before the inception are discounted unless the policy holds them.
+    #[test]
+    pub fn test_reversed_times() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let year = Period::Years(1).step(start, 1);
+        let interest = Interest::new(0.1, Period::Years(1));
+        assert_eq!(interest.inception_policy(), InceptionPolicy::Discount);
+        assert!((interest.factor(year, start) - 1.0 / 1.1).abs() < 1e-12);
+
+        let held = interest.clone().before_inception(InceptionPolicy::Hold);
+        assert_eq!(held.factor(year, start), 1.0);
+        assert!((held.factor(start, year) - 1.1).abs() < 1e-12);
+
+        let mut item = Item::fixed(Value::new("CAD", 100.0), year);
+        item.set_interest(Some(held));
+        assert_eq!(item.assess(start).amount(), 100.0);
+        item.set_interest(Some(interest));
+        assert_value_eq!(item.assess(start), Value::new("CAD", 100.0 / 1.1), 1e-9);
+    }
+
+    /// Changing the period revalidates it and the fast path follows along.
+    #[test]
+    pub fn test_interest_setters() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut interest = Interest::new(0.1, TimeDelta::days(365));
+        assert!(interest.set_period(Period::Months(0)).is_err());
+        assert_eq!(interest.period(), Period::Delta(TimeDelta::days(365)));
+
+        interest.set_period(TimeDelta::days(30)).unwrap();
+        interest.set_rate(0.01);
+        let later = start + TimeDelta::days(60);
+        assert!((interest.factor(start, later) - 1.01f64.powi(2)).abs() < 1e-12);
+        interest.set_period(Period::Months(1)).unwrap();
+        let march = Period::Months(2).step(start, 1);
+        assert!((interest.factor(start, march) - 1.01f64.powi(2)).abs() < 1e-12);
+    }
+
+    #[test]
+    pub fn test_interest_fast_path() {
+        let interest = Interest::new(0.2, TimeDelta::days(365));
+        let value = Value::dummy("CAD", -15_000.0);
+        let start = Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap();
+
+        for years in [0, 1, 7, 17, 40] {
+            let end = start + TimeDelta::days(365 * years);
+            let fast = interest.apply(start, end, &value).amount();
+            let old = apply_powf(&interest, start, end, &value);
+            assert!((fast - old).abs() <= old.abs() * 1e-14, "{years} years: {fast} vs {old}");
+        }
+        for end in [
+            start + TimeDelta::days(100),
+            Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap(),
+            start + TimeDelta::days(365) + TimeDelta::nanoseconds(2),
+        ] {
+            let fast = interest.apply(start, end, &value).amount();
+            assert_eq!(fast, apply_powf(&interest, start, end, &value));
+        }
+        assert_eq!(
+            interest.apply_periods(start, 2.0, &value).amount(),
+            apply_powf(&interest, start, start + TimeDelta::days(730), &value)
+        );
+    }
+
+    /// Calendar years compound a whole period on every anniversary,
+    /// leap days and all.
+    #[test]
+    pub fn test_interest_calendar_years() {
+        let interest = Interest::new(0.1, Period::Years(1));
+        let value = Value::dummy("CAD", 1_000.0);
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2004, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(interest.apply(start, end, &value).amount(), 1_000.0 * 1.1f64.powi(4));
+
+        // A debt taken out on February 29th accrues a full year by the 28th.
+        let leap = Utc.with_ymd_and_hms(2000, 2, 29, 0, 0, 0).unwrap();
+        let next = Utc.with_ymd_and_hms(2001, 2, 28, 0, 0, 0).unwrap();
+        assert_eq!(interest.apply(leap, next, &value).amount(), 1_100.0);
+
+        // Whereas 365 days overshoots a calendar year containing one.
+        let days = Interest::new(0.1, TimeDelta::days(365));
+        let year = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+        assert!(days.apply(start, year, &value).amount() > 1_100.0);
+        assert_eq!(interest.apply(start, year, &value).amount(), 1_100.0);
+    }
+
+    /// Deltas after the assessed time are left out whether or not the item
+    /// bears interest.
+    #[test]
+    pub fn test_future_deltas_ignored() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let later = start + TimeDelta::days(30);
+        let mut cash = Item::fixed(Value::dummy("CAD", 1_000.0), start);
+        cash.add_delta(later, Value::dummy("CAD", 250.0));
+
+        assert_eq!(cash.assess(start + TimeDelta::days(10)).amount(), 1_000.0);
+        assert_eq!(cash.assess(later).amount(), 1_250.0);
+    }
 
     #[test]
     pub fn test_item_fixed() {
 
     }
-}
\ No newline at end of file
+
+    /// Daily payments netted to the end of each month accrue a little more
+    /// interest since they are applied later.
+    #[test]
+    pub fn test_aggregation_drift() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut debt = Item::basic_debt(
+            Value::dummy("CAD", -10_000.0),
+            0.20,
+            TimeDelta::days(365),
+            start,
+        );
+        for day in 0..366 {
+            let time = start + TimeDelta::days(day) + TimeDelta::hours(12);
+            debt.add_delta(time, Value::dummy("CAD", 20.0));
+        }
+
+        let end = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let before = debt.assess(end);
+        assert_eq!(debt.deltas_aggregated(Granularity::Monthly).len(), 12);
+
+        debt.aggregate_deltas(Granularity::Monthly);
+        let after = debt.assess(end);
+
+        assert_eq!(debt.deltas.len(), 12);
+        assert!(after.amount() < before.amount());
+        // Payments land half a month late on average, roughly 7,320 * 20% * 15 / 365 = 60
+        // of extra interest, which we bound at 2% of the balance.
+        let tolerance = 0.02 * before.amount().abs();
+        assert_value_eq!(after, before, tolerance);
+    }
+
+    /// A bond paying a 50 coupon every six months, assessed halfway through
+    /// the second period and on its boundaries.
+    #[test]
+    pub fn test_coupon_proration() {
+        let issue = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let bond = |proration| {
+            Item::builder(Value::new("CAD", 1_000.0), issue)
+                .payout(Payout::FixedRecurring {
+                    amount: Value::new("CAD", 50.0),
+                    start: issue + TimeDelta::days(180),
+                    frequency: Period::Delta(TimeDelta::days(180)),
+                    proration,
+                })
+                .build()
+        };
+        let (none, linear) = (bond(Proration::None), bond(Proration::Linear));
+
+        let halfway = issue + TimeDelta::days(270);
+        assert_eq!(none.assess_full(halfway).value().amount(), 1_000.0);
+        assert_eq!(linear.assess_full(halfway).value().amount(), 1_025.0);
+        assert_eq!(linear.assess_full(halfway).cash().amount(), 50.0);
+        assert_eq!(none.assess_full(halfway).cash().amount(), 50.0);
+
+        for time in [issue, issue + TimeDelta::days(180), issue + TimeDelta::days(360)] {
+            let (a, b) = (none.assess_full(time), linear.assess_full(time));
+            assert_eq!(a.value().amount(), b.value().amount());
+            assert_eq!(a.cash().amount(), b.cash().amount());
+        }
+    }
+
+    /// A USD fund paying a CAD distribution every quarter.
+    #[test]
+    pub fn test_foreign_payout() {
+        let on = |month| Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        let table = ConversionTable::new();
+        table.add_conversion_on("CAD", "USD", on(1), 0.75);
+        table.add_conversion_on("CAD", "USD", on(7), 0.70);
+        let fund = Item::builder(Value::new("USD", 10_000.0), on(1))
+            .payout(Payout::FixedRecurring {
+                amount: Value::new("CAD", 100.0),
+                start: on(4),
+                frequency: Period::Months(3),
+                proration: Proration::None,
+            })
+            .build();
+
+        let first = fund.assess_full_with(&table, on(5));
+        let later = fund.assess_full_with(&table, on(10));
+        let ledger = |result: &super::AssessmentResult| {
+            let cash = result.cash_by_currency();
+            (cash.len(), cash[0].currency().code(), cash[0].amount())
+        };
+        assert_eq!(ledger(&first), (1, "CAD", 100.0));
+        assert_eq!(ledger(&later), (1, "CAD", 300.0));
+        assert_eq!(later.value().amount(), 10_000.0);
+        assert_eq!(later.value().currency().code(), "USD");
+        assert_value_eq!(*later.cash(), Value::new("USD", 75.0 + 70.0 + 70.0), 1e-9);
+    }
+
+    /// Deltas without a captured rate convert at the rate of their day, and
+    /// fail to assess without one.
+    #[test]
+    pub fn test_foreign_delta_rates() {
+        let on = |month| Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("FDA", "FDB", on(1), 2.0);
+        CURRENCY_EXCHANGE.add_conversion_on("FDA", "FDB", on(6), 3.0);
+        let mut item = Item::builder(Value::new("FDB", 1_000.0), on(1))
+            .payment(on(3), Value::new("FDA", 100.0))
+            .payment(on(8), Value::new("FDA", 100.0))
+            .build();
+        assert_value_eq!(item.try_assess(on(12)).unwrap(), Value::new("FDB", 1_500.0), 1e-9);
+
+        item.add_delta(on(10), Value::new("FDC", 1.0));
+        assert_eq!(
+            item.try_assess(on(12)).unwrap_err(),
+            ConversionError::NoRate {
+                from: Currency::new("FDC"),
+                to: Currency::new("FDB")
+            }
+        );
+        assert_value_eq!(item.try_assess(on(9)).unwrap(), Value::new("FDB", 1_500.0), 1e-9);
+    }
+
+    /// Each delta amount is the jump in the assessment at the delta, with
+    /// capitalization, rounding, a dated rate and a percentage delta.
+    #[test]
+    pub fn test_delta_amounts_reconcile() {
+        let on = |month| Utc.with_ymd_and_hms(2024, month, 15, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("DAA", "DAB", on(1), 2.0);
+        let mut savings = Item::builder(Value::new("DAB", 1_000.0), on(1))
+            .interest(0.01, Period::Months(1))
+            .capitalization(TimeDelta::days(30))
+            .payment(on(3), Value::new("DAA", 100.0))
+            .build();
+        savings.round_each_period = Some((2, Rounding::HalfEven));
+        savings.push_delta(Delta::new(on(5), DeltaAmount::Percent(-0.1), DeltaKind::Fee));
+
+        let amounts = savings.delta_amounts();
+        assert_eq!(amounts.len(), 2);
+        assert_value_eq!(amounts[0].1, Value::new("DAB", 200.0), 1e-9);
+        for (delta, amount) in amounts {
+            let before = savings.assess(delta.time - TimeDelta::nanoseconds(1));
+            let jump = savings.assess(delta.time).amount() - before.amount();
+            assert!((jump - amount.amount()).abs() < 0.01, "{jump} against {amount:?}");
+        }
+    }
+
+    /// A card opened on January 31st charging 1% a month, capitalized and
+    /// paying interest at month ends through a leap February.
+    #[test]
+    pub fn test_month_end_anchor() {
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        let card = Item::builder(Value::new("CAD", -1_000.0), on(1, 31))
+            .interest(0.01, Period::Months(1))
+            .period_anchor(PeriodAnchor::CalendarMonthEnd)
+            .capitalization(TimeDelta::days(30))
+            .build();
+        let cad = |amount| Value::new("CAD", amount);
+
+        // A day of January is capitalized at its end, then all of February.
+        let january = -1_000.0 - 10.0 / 31.0;
+        assert_value_eq!(card.breakdown(on(2, 1)).principal, cad(january), 1e-9);
+        assert_value_eq!(card.assess(on(2, 15)), cad(january * (1.0 + 0.01 * 14.0 / 29.0)), 1e-9);
+        let february = january * 1.01;
+        let march = card.breakdown(on(3, 15));
+        assert_value_eq!(march.principal, cad(february), 1e-9);
+        assert_value_eq!(march.uncapitalized, cad(february * 0.01 * 14.0 / 31.0), 1e-9);
+
+        let interest =
+            Interest::new(0.01, Period::Months(1)).anchored(PeriodAnchor::CalendarMonthEnd);
+        assert!((interest.factor(on(1, 31), on(3, 31)) - 1.01 * 1.01).abs() < 1e-9);
+        let payout = Payout::InterestRecurring {
+            principal: Value::new("CAD", 1_000.0),
+            start: on(1, 31),
+            frequency: Period::Months(1),
+            interest,
+            proration: Proration::Linear,
+        };
+        let paid = payout.flows(on(1, 31), on(4, 1));
+        let times: Vec<_> = paid.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, [on(2, 1), on(3, 1), on(4, 1)]);
+        assert_value_eq!(paid[0].1, cad(1_000.0 * (1.01f64.powf(1.0 / 31.0) - 1.0)), 1e-9);
+        assert_value_eq!(paid[1].1, cad(10.0), 1e-9);
+        let accrued = payout.accrued(on(2, 15)).unwrap();
+        assert_value_eq!(accrued, cad(10.0 * 14.0 / 29.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_capitalization_positive() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let zero = TimeDelta::zero();
+        let builder = Item::builder(Value::new("CAD", 100.0), start);
+        assert_eq!(
+            builder.try_capitalization(zero).unwrap_err(),
+            PeriodError::Empty(Period::Delta(zero))
+        );
+
+        let mut item = Item::builder(Value::new("CAD", 100.0), start)
+            .interest(0.05, Period::Years(1))
+            .capitalization(TimeDelta::days(30))
+            .build();
+        let back = TimeDelta::days(-30);
+        assert_eq!(
+            item.set_capitalization(Some(back)).unwrap_err(),
+            PeriodError::Negative(Period::Delta(back))
+        );
+        assert_eq!(item.capitalization(), Some(TimeDelta::days(30)));
+        item.set_capitalization(None).unwrap();
+        assert_value_eq!(item.assess(start), Value::new("CAD", 100.0), 1e-9);
+
+        let caught = std::panic::catch_unwind(|| {
+            Item::builder(Value::new("CAD", 100.0), start).capitalization(zero)
+        });
+        let message = *caught.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(message, "invalid capitalization: periods must be longer than zero, got P0D");
+    }
+
+    /// A 10k line of credit at 7.3% a year, 2.00 a day, capitalized every 30
+    /// days with 1k paid back on day 15, against a bank's spreadsheet.
+    #[test]
+    pub fn test_monthly_capitalization() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let day = |n| start + TimeDelta::days(n);
+        let line = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.073, TimeDelta::days(365))
+            .capitalization(TimeDelta::days(30))
+            .payment(day(15), Value::new("CAD", 1_000.0))
+            .build();
+        let cad = |amount| Value::new("CAD", amount);
+
+        // Day 15: 30.00 accrued on 10k, then 27.00 on the 9k left.
+        assert_value_eq!(line.assess(day(15)), cad(-9_030.0), 1e-9);
+        assert_value_eq!(line.assess(day(30)), cad(-9_057.0), 1e-9);
+        assert_value_eq!(line.breakdown(day(30)).uncapitalized, cad(0.0), 1e-9);
+        let mid = line.breakdown(day(45));
+        assert_value_eq!(mid.principal, cad(-9_057.0), 1e-9);
+        assert_value_eq!(mid.uncapitalized, cad(-27.171), 1e-9);
+        assert_value_eq!(line.assess(day(60)), cad(-9_111.342), 1e-9);
+
+        // Compounding continuously ends up somewhere else.
+        let continuous = Item { capitalization: None, ..line.clone() };
+        assert!((continuous.assess(day(60)).amount() - -9_111.342).abs() > 0.1);
+        assert_value_eq!(continuous.breakdown(day(60)).uncapitalized, cad(0.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_lazy_occurrences() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        let until = Period::Years(40).step(start, 1);
+        let month = Period::Months(1);
+        let rent = Payout::FixedRecurring {
+            amount: Value::new("CAD", 1_500.0),
+            start,
+            frequency: month,
+            proration: Proration::None,
+        };
+
+        let hundredth = rent.occurrences(start, until).nth(99).unwrap();
+        assert_eq!(hundredth.0, Utc.with_ymd_and_hms(2032, 4, 30, 0, 0, 0).unwrap());
+        assert_eq!(rent.occurrences(start, until).count(), 481);
+
+        // Skipping a trillion payments only works out the one landed on,
+        // working out the rest would take far longer than the timeout.
+        let ticks = Payout::FixedRecurring {
+            amount: Value::new("CAD", 1.0),
+            start,
+            frequency: Period::Delta(TimeDelta::nanoseconds(1)),
+            proration: Proration::None,
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(ticks.occurrences(start, until).nth(1_000_000_000_000)));
+        let (time, _) = receiver.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(time, start + TimeDelta::seconds(1_000));
+
+        // The same payments as expanding every period up front, each counted
+        // from the start so month ends do not drift.
+        let coupon = Payout::InterestRecurring {
+            principal: Value::new("CAD", 10_000.0),
+            start,
+            frequency: month,
+            interest: Interest::new(0.004, month),
+            proration: Proration::None,
+        };
+        let periods: Vec<_> = (0..)
+            .map(|k| (month.step(start, k), month.step(start, k + 1)))
+            .take_while(|(_, to)| *to <= until)
+            .collect();
+        let naive: f64 = periods
+            .iter()
+            .map(|(from, to)| 10_000.0 * (Interest::new(0.004, month).factor(*from, *to) - 1.0))
+            .sum();
+        let total: Option<Value> = coupon.occurrences(start, until).map(|(_, v)| v).sum();
+        assert_value_eq!(total.unwrap(), Value::new("CAD", naive), 1e-6);
+        assert!(coupon.occurrences(start, start).next().is_none());
+    }
+
+    #[test]
+    pub fn test_assess_at_many() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let day = |n| start + TimeDelta::days(n);
+        let mut loan = Item::basic_debt(Value::new("CAD", -5_000.0), 0.06, Period::Years(1), start);
+        for n in (10..400).step_by(10) {
+            loan.add_delta(day(n), Value::new("CAD", 100.0));
+        }
+        let times = [day(365), day(0), day(-5), day(10), day(10), day(123), day(800)];
+        let each: Vec<f64> = times.iter().map(|t| loan.assess(*t).amount()).collect();
+        let many: Vec<f64> = loan.assess_at_many(&times).iter().map(Value::amount).collect();
+        assert_eq!(many, each);
+
+        let capitalized = Item { capitalization: Some(TimeDelta::days(30)), ..loan };
+        let each: Vec<f64> = times.iter().map(|t| capitalized.assess(*t).amount()).collect();
+        let many: Vec<f64> = capitalized.assess_at_many(&times).iter().map(Value::amount).collect();
+        assert_eq!(many, each);
+    }
+
+    /// A 10% paydown on the day 60.00 of interest is capitalized.
+    #[test]
+    pub fn test_application_order() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let boundary = start + TimeDelta::days(30);
+        let line = |order| {
+            let paydown = Delta::new(boundary, DeltaAmount::Percent(-0.1), DeltaKind::Payment);
+            Item::builder(Value::new("CAD", -10_000.0), start)
+                .interest(0.073, TimeDelta::days(365))
+                .capitalization(TimeDelta::days(30))
+                .application_order(order)
+                .delta(paydown)
+                .build()
+        };
+        let cad = |amount| Value::new("CAD", amount);
+
+        // 10% of 10,060.00 against 10% of 10,000.00, 6.00 apart.
+        let interest_first = line(ApplicationOrder::InterestFirst).assess(boundary);
+        let payment_first = line(ApplicationOrder::PaymentFirst).assess(boundary);
+        assert_value_eq!(interest_first, cad(-9_054.0), 1e-9);
+        assert_value_eq!(payment_first, cad(-9_060.0), 1e-9);
+
+        // Deltas at the same time apply in the order they were added.
+        let halve = Delta::new(boundary, DeltaAmount::Percent(-0.5), DeltaKind::Payment);
+        let add = Delta::payment(boundary, Value::new("CAD", 100.0));
+        let mut cash = Item::fixed(Value::new("CAD", 1_000.0), start);
+        cash.push_delta(add.clone());
+        cash.push_delta(halve.clone());
+        assert_eq!(cash.assess(boundary).amount(), 550.0);
+        let mut cash = Item::fixed(Value::new("CAD", 1_000.0), start);
+        cash.import_deltas([halve, add]);
+        assert_eq!(cash.assess(boundary).amount(), 600.0);
+    }
+
+    /// Two years of a loan at 1.5% a month paid down 300 a month, the bank
+    /// charging interest to the cent before each payment.
+    #[test]
+    pub fn test_statement_rounding() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = Period::Months(1);
+        let mut builder = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.015, month)
+            .round_each_period(2, Rounding::HalfAwayFromZero);
+        for count in 1..=24 {
+            builder = builder.payment(month.step(start, count), Value::new("CAD", 300.0));
+        }
+        let loan = builder.build();
+
+        // The statements, kept in cents.
+        let mut owed: i64 = 1_000_000;
+        for _ in 0..24 {
+            owed += (owed * 15 + 500) / 1_000;
+            owed -= 30_000;
+        }
+        let end = month.step(start, 24);
+        assert_eq!(loan.assess(end).amount(), -(owed as f64) / 100.0);
+
+        let exact = Item { round_each_period: None, ..loan.clone() };
+        let drift = (exact.assess(end).amount() - loan.assess(end).amount()).abs();
+        assert!(drift > 0.005 && drift < 0.10, "{drift}");
+    }
+}
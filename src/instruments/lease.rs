@@ -0,0 +1,145 @@
+//! Leases and other installment purchases.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{
+    item::{Interest, Item, Payout},
+    period::Period,
+    value::Value,
+};
+
+/// Fixed payments for a term with the option to buy the asset at the end,
+/// see [Item::lease]. The asset itself is never owned along the way.
+#[derive(Clone, Debug)]
+pub struct Lease {
+    /// What each payment costs, as a positive amount.
+    pub payment: Value,
+    pub frequency: TimeDelta,
+    /// How many payments there are.
+    pub term: u32,
+    /// What buying the asset at the end of the term costs, it is never owed.
+    pub residual: Value,
+    pub start: DateTime<Utc>,
+    /// What the payments still due are discounted at, `None` counts them
+    /// at face value.
+    pub discount: Option<Interest>,
+}
+
+impl Lease {
+    /// When each payment is due, the first at the start.
+    pub fn due_dates(&self) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        (0..self.term).map(|k| self.start + self.frequency * k as i32)
+    }
+    /// The end of the term, when the buyout is decided.
+    pub fn buyout_date(&self) -> DateTime<Utc> {
+        self.start + self.frequency * self.term as i32
+    }
+    /// What the payments due after `time` are worth at `time`, a payment due
+    /// at `time` counts as made.
+    pub fn remaining(&self, time: DateTime<Utc>) -> f64 {
+        let payment = self.payment.amount().abs();
+        self.due_dates()
+            .filter(|due| *due > time)
+            .map(|due| match &self.discount {
+                Some(discount) => payment / discount.factor(time, due),
+                None => payment,
+            })
+            .sum()
+    }
+}
+
+impl Item {
+    /// A lease of `term` payments every `frequency` from `start`. It is
+    /// assessed at what is still owed, negative like [Item::basic_debt], and
+    /// every payment is a payout leaving cash. The `residual` is only listed
+    /// by [Book::upcoming](super::book::Book::upcoming).
+    pub fn lease(
+        payment: Value,
+        frequency: TimeDelta,
+        term: u32,
+        residual: Value,
+        start: DateTime<Utc>,
+    ) -> Self {
+        assert!(
+            frequency > TimeDelta::zero(),
+            "lease payments must be apart"
+        );
+        let lease = Lease {
+            payment: Value::new(payment.currency(), payment.amount().abs()),
+            frequency,
+            term,
+            residual,
+            start,
+            discount: None,
+        };
+        let outgoing = Value::new(payment.currency(), -lease.payment.amount());
+        Self {
+            payouts: lease
+                .due_dates()
+                .map(|time| Payout::OneTime {
+                    amount: outgoing.clone(),
+                    time,
+                })
+                .collect(),
+            lease: Some(Box::new(lease)),
+            ..Self::fixed(Value::new(payment.currency(), 0.0), start)
+        }
+    }
+    /// Discounts the payments a lease still has due at `rate` every
+    /// `period`, other items are left as they are.
+    pub fn discounted(mut self, rate: f64, period: impl Into<Period>) -> Self {
+        if let Some(lease) = &mut self.lease {
+            lease.discount = Some(Interest::new(rate, period));
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::{Book, Upcoming},
+        item::Item,
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    #[test]
+    pub fn test_car_lease() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = TimeDelta::days(30);
+        let car = Item::lease(
+            Value::new("CAD", 500.0),
+            month,
+            36,
+            Value::new("CAD", 15_000.0),
+            start,
+        )
+        .discounted(0.005, month);
+        assert_eq!(car.payouts.len(), 36);
+
+        // Half way through, with the 19th payment just made, 17 are left and
+        // the next is a month away.
+        let mid = start + month * 18;
+        let expected: f64 = (1..=17).map(|k| 500.0 / 1.005f64.powi(k)).sum();
+        assert_value_eq!(car.assess(mid), Value::new("CAD", -expected), 1e-6);
+        assert_eq!(car.assess_full(mid).cash().amount(), -500.0 * 19.0);
+
+        // Once the last payment is made only the buyout is left to decide.
+        let mut book = Book::default();
+        let key = book.add(car);
+        let last = start + month * 35;
+        assert_eq!(book.assess(last).amount(), 0.0);
+        let end = start + month * 36;
+        let upcoming = book.upcoming(last, Period::Years(1).step(last, 1));
+        assert_eq!(upcoming.len(), 1);
+        let (time, item, event) = &upcoming[0];
+        assert_eq!((*time, *item), (end, key));
+        assert!(matches!(event, Upcoming::Buyout(residual) if residual.amount() == 15_000.0));
+        assert_eq!(book.upcoming(start, end).len(), 36);
+    }
+}
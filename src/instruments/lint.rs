@@ -0,0 +1,428 @@
+//! Advisory checks for books that are probably not what was meant.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    convert::CURRENCY_EXCHANGE,
+    delta::{DeltaAmount, DeltaId, DeltaKind},
+    item::{Item, Payout},
+    period::Period,
+    value::Currency,
+    Assesible,
+};
+
+/// How far, as a fraction of the market rate, an [Item::fx_override] may
+/// be from it before [Book::lint] flags it.
+pub const FX_OVERRIDE_TOLERANCE: f64 = 0.1;
+
+/// Something odd about an item, see [Book::lint].
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintWarning {
+    /// The item has a minimum payment like a debt but is owed money.
+    DebtInCredit {
+        key: ItemKey,
+    },
+    DeltaBeforeInception {
+        key: ItemKey,
+        delta: DeltaId,
+    },
+    /// A payout, by its index on the item, starts before the item does.
+    PayoutBeforeInception {
+        key: ItemKey,
+        payout: usize,
+    },
+    /// The item's currency can not be converted into the book's.
+    NoConversion {
+        key: ItemKey,
+        currency: Currency,
+    },
+    /// The interest, or a payout paying interest, has an empty period so it
+    /// can not be applied.
+    ZeroInterestPeriod {
+        key: ItemKey,
+    },
+    /// A delta after the item was frozen, see [Book::freeze].
+    DeltaAfterFreeze {
+        key: ItemKey,
+        delta: DeltaId,
+    },
+    /// The recurring payment is below the interest accrued over its period
+    /// so the balance grows forever, both in the item's currency.
+    NegativeAmortization {
+        key: ItemKey,
+        payment: f64,
+        accrual: f64,
+    },
+    /// The locked rate into `currency` is further from the latest market
+    /// rate than [FX_OVERRIDE_TOLERANCE].
+    FxOverrideDeviation {
+        key: ItemKey,
+        currency: Currency,
+        locked: f64,
+        market: f64,
+    },
+}
+
+impl LintWarning {
+    /// The item the warning is about.
+    pub fn key(&self) -> ItemKey {
+        match self {
+            LintWarning::DebtInCredit { key }
+            | LintWarning::DeltaBeforeInception { key, .. }
+            | LintWarning::PayoutBeforeInception { key, .. }
+            | LintWarning::NoConversion { key, .. }
+            | LintWarning::ZeroInterestPeriod { key }
+            | LintWarning::DeltaAfterFreeze { key, .. }
+            | LintWarning::NegativeAmortization { key, .. }
+            | LintWarning::FxOverrideDeviation { key, .. } => *key,
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintWarning::DebtInCredit { key } => {
+                write!(f, "{key:?} has a minimum payment but a positive balance")
+            }
+            LintWarning::DeltaBeforeInception { key, delta } => {
+                write!(
+                    f,
+                    "delta {} of {key:?} is before the item's inception",
+                    delta.get()
+                )
+            }
+            LintWarning::PayoutBeforeInception { key, payout } => {
+                write!(
+                    f,
+                    "payout {payout} of {key:?} starts before the item's inception"
+                )
+            }
+            LintWarning::NoConversion { key, currency } => {
+                write!(
+                    f,
+                    "{key:?} is in {currency} which has no rate to the book's currency"
+                )
+            }
+            LintWarning::ZeroInterestPeriod { key } => {
+                write!(f, "{key:?} has interest over an empty period")
+            }
+            LintWarning::DeltaAfterFreeze { key, delta } => {
+                write!(
+                    f,
+                    "delta {} of {key:?} is after the item was frozen",
+                    delta.get()
+                )
+            }
+            LintWarning::NegativeAmortization {
+                key,
+                payment,
+                accrual,
+            } => {
+                write!(
+                    f,
+                    "{key:?} is paid {payment:.2} a period but accrues {accrual:.2} of interest"
+                )
+            }
+            LintWarning::FxOverrideDeviation {
+                key,
+                currency,
+                locked,
+                market,
+            } => {
+                write!(
+                    f,
+                    "{key:?} converts into {currency} at {locked} but the market rate is {market}"
+                )
+            }
+        }
+    }
+}
+
+/// The recurring payment of a debt and the interest accrued over its
+/// period after the last delta. The last two payments recur when they are
+/// for the same fixed amount, otherwise the minimum payment recurs monthly.
+/// The item must have interest over a period that is not empty.
+fn recurring_payment(item: &Item) -> Option<(f64, f64)> {
+    let currency = item.currency();
+    let payments: Vec<_> = item
+        .deltas
+        .iter()
+        .filter(|d| d.kind == DeltaKind::Payment)
+        .filter_map(|d| match &d.amount {
+            DeltaAmount::Fixed(_) => Some((d.time, d.resolve_amount(0.0, currency))),
+            DeltaAmount::Percent(_) => None,
+        })
+        .collect();
+    let from = item.deltas.last().map_or(item.inception, |d| d.time);
+    match payments[..] {
+        [.., (previous, a), (last, b)] if a == b && last > previous => {
+            Some((b, item.accrual(from, from + (last - previous)).amount()))
+        }
+        _ => {
+            let owed = -item.assess(from).amount();
+            if owed <= 0.0 {
+                return None;
+            }
+            let minimum = item.minimum_payment.as_ref()?.amount(owed, currency);
+            let accrual = item.accrual(from, Period::Months(1).step(from, 1));
+            Some((minimum, accrual.amount()))
+        }
+    }
+}
+
+impl Book {
+    /// Marks an item as closed from `at`, such as a card that was paid off
+    /// and cancelled. Deltas are still accepted but [Book::lint] flags them.
+    /// `false` if the item is not in the book.
+    pub fn freeze(&mut self, key: ItemKey, at: DateTime<Utc>) -> bool {
+        if self.get(key).is_none() {
+            return false;
+        }
+        self.frozen.insert(key, at);
+        self.audit.push(AuditEntry::Frozen { key, at });
+        true
+    }
+    /// When the item was frozen, if it was.
+    pub fn frozen_since(&self, key: ItemKey) -> Option<DateTime<Utc>> {
+        self.frozen.get(key).copied()
+    }
+    /// Checks every item for things that are probably mistakes, in the
+    /// order of [Book::iter_ordered]. Nothing is changed.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let base = self.currency();
+        let mut warnings = vec![];
+        for (key, item) in self.iter_ordered() {
+            if item.minimum_payment.is_some() && item.book_value.amount() > 0.0 {
+                warnings.push(LintWarning::DebtInCredit { key });
+            }
+            for delta in item.deltas.iter().filter(|d| d.time < item.inception) {
+                warnings.push(LintWarning::DeltaBeforeInception {
+                    key,
+                    delta: delta.id,
+                });
+            }
+            for (payout, _) in item.payouts.iter().enumerate().filter(|(_, p)| {
+                let start = match p.unadjusted() {
+                    Payout::OneTime { time, .. } | Payout::InterestOneTime { time, .. } => time,
+                    Payout::FixedRecurring { start, .. }
+                    | Payout::InterestRecurring { start, .. } => start,
+                    Payout::Adjusted { .. } => unreachable!(),
+                };
+                *start < item.inception
+            }) {
+                warnings.push(LintWarning::PayoutBeforeInception { key, payout });
+            }
+            let currency = item.currency();
+            if self.rate_into(currency, base, item.inception).is_err() {
+                warnings.push(LintWarning::NoConversion { key, currency });
+            }
+            // Interest itself rejects an empty period, the payouts paying it
+            // out do not.
+            let empty = |period: Period| period.step(item.inception, 1) <= item.inception;
+            let mut paid_every = item.payouts.iter().filter_map(|p| match p.unadjusted() {
+                Payout::InterestRecurring { frequency, .. } => Some(*frequency),
+                _ => None,
+            });
+            if item.interest().is_some_and(|i| empty(i.period())) || paid_every.any(empty) {
+                warnings.push(LintWarning::ZeroInterestPeriod { key });
+            } else if let Some((payment, accrual)) =
+                item.interest().and_then(|_| recurring_payment(item))
+            {
+                if payment < accrual {
+                    warnings.push(LintWarning::NegativeAmortization {
+                        key,
+                        payment,
+                        accrual,
+                    });
+                }
+            }
+            for &(target, locked) in &item.fx_overrides {
+                let Ok(market) = CURRENCY_EXCHANGE.latest_rate(currency, target) else {
+                    continue;
+                };
+                if (locked - market).abs() > market.abs() * FX_OVERRIDE_TOLERANCE {
+                    warnings.push(LintWarning::FxOverrideDeviation {
+                        key,
+                        currency: target,
+                        locked,
+                        market,
+                    });
+                }
+            }
+            if let Some(frozen) = self.frozen_since(key) {
+                for delta in item.deltas.iter().filter(|d| d.time > frozen) {
+                    warnings.push(LintWarning::DeltaAfterFreeze {
+                        key,
+                        delta: delta.id,
+                    });
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        delta::Delta,
+        item::{Interest, Item, MinPayment, Payout, Proration},
+        period::Period,
+        value::Value,
+    };
+
+    use crate::instruments::strategy::amortization_schedule;
+
+    use super::LintWarning;
+
+    #[test]
+    pub fn test_pathological_books() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let before = start - TimeDelta::days(1);
+        let later = start + TimeDelta::days(40);
+        let mut book = Book::default();
+        let fine = book.add(Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.05,
+            Period::Years(1),
+            start,
+        ));
+        assert!(book.lint().is_empty());
+
+        let card = book.add(Item::credit_card(
+            Value::new("CAD", 500.0),
+            0.2,
+            MinPayment {
+                percent: 0.03,
+                floor: Value::new("CAD", 10.0),
+            },
+            start,
+        ));
+        let early = Delta::payment(before, Value::new("CAD", 5.0));
+        let early_id = early.id;
+        let mut chequing = Item::fixed(Value::new("CAD", 100.0), start);
+        chequing.push_delta(early);
+        let chequing = book.add(chequing);
+        let pension = book.add(
+            Item::builder(Value::new("CAD", 0.0), start)
+                .payout(Payout::FixedRecurring {
+                    amount: Value::new("CAD", 100.0),
+                    start,
+                    frequency: Period::Months(1),
+                    proration: Proration::None,
+                })
+                .payout(Payout::OneTime {
+                    amount: Value::new("CAD", 50.0),
+                    time: before,
+                })
+                .build(),
+        );
+        let foreign = book.add(Item::fixed(Value::new("LNTX", 10.0), start));
+        let mut broken = Item::fixed(Value::new("CAD", 10.0), start);
+        broken.payouts.push(Payout::InterestRecurring {
+            principal: Value::new("CAD", 10.0),
+            start,
+            frequency: Period::Months(0),
+            interest: Interest::new(0.01, Period::Months(1)),
+            proration: Proration::None,
+        });
+        let broken = book.add(broken);
+        let late = book.add_delta(fine, Delta::payment(later, Value::new("CAD", 5.0)));
+        assert!(book.freeze(fine, start + TimeDelta::days(30)));
+
+        let warnings = book.lint();
+        assert_eq!(
+            warnings,
+            vec![
+                LintWarning::DeltaAfterFreeze {
+                    key: fine,
+                    delta: late.unwrap()
+                },
+                LintWarning::DebtInCredit { key: card },
+                LintWarning::DeltaBeforeInception {
+                    key: chequing,
+                    delta: early_id
+                },
+                LintWarning::PayoutBeforeInception {
+                    key: pension,
+                    payout: 1
+                },
+                LintWarning::NoConversion {
+                    key: foreign,
+                    currency: "LNTX".into()
+                },
+                LintWarning::ZeroInterestPeriod { key: broken },
+            ]
+        );
+        assert_eq!(warnings[5].key(), broken);
+        assert!(warnings[4].to_string().contains("LNTX"));
+    }
+
+    /// 50 a month on 15k at 20% a year, which accrues 250 a month.
+    #[test]
+    pub fn test_negative_amortization() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = Period::Months(1);
+        let minimum = MinPayment {
+            percent: 0.03,
+            floor: Value::new("CAD", 10.0),
+        };
+        let mut card = Item::credit_card(Value::new("CAD", -15_000.0), 0.2, minimum.clone(), start);
+        for k in 1..=2 {
+            card.push_delta(Delta::payment(
+                month.step(start, k),
+                Value::new("CAD", 50.0),
+            ));
+        }
+        let schedule = amortization_schedule(&card, &Value::new("CAD", 50.0), month, start, 12);
+        assert_eq!(schedule.len(), 12);
+        assert!(schedule.iter().all(|row| row.negative_amortization));
+        assert_value_eq!(schedule[0].interest, Value::new("CAD", 250.0), 1e-9);
+        assert!(schedule[0].principal.amount() < 0.0);
+        assert!(schedule[11].balance.amount() > 15_000.0);
+
+        let mut book = Book::default();
+        let key = book.add(card.clone());
+        let warnings = book.lint();
+        assert_eq!(warnings.len(), 1);
+        let LintWarning::NegativeAmortization {
+            payment, accrual, ..
+        } = &warnings[0]
+        else {
+            panic!("the payments do not cover the interest");
+        };
+        assert_eq!((warnings[0].key(), *payment), (key, 50.0));
+        // The balance has grown past 15k but February is short.
+        assert!(*accrual > 200.0);
+
+        // The minimum payment of 3% covers the interest, as does 300 a month.
+        let mut book = Book::default();
+        book.add(Item::credit_card(
+            Value::new("CAD", -15_000.0),
+            0.2,
+            minimum,
+            start,
+        ));
+        card.push_delta(Delta::payment(
+            month.step(start, 3),
+            Value::new("CAD", 300.0),
+        ));
+        card.push_delta(Delta::payment(
+            month.step(start, 4),
+            Value::new("CAD", 300.0),
+        ));
+        book.add(card.clone());
+        assert!(book.lint().is_empty());
+        let schedule = amortization_schedule(&card, &Value::new("CAD", 300.0), month, start, 12);
+        assert!(schedule.iter().all(|row| !row.negative_amortization));
+    }
+}
@@ -0,0 +1,101 @@
+//! Loans that are not paid down evenly.
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    delta::Delta,
+    item::{Interest, Item},
+    period::Period,
+    value::Value,
+};
+
+impl Item {
+    /// A loan such as a construction loan, negative like [Item::basic_debt].
+    /// Every `period` up to `interest_only_until` only the interest is paid
+    /// so the balance is back at the principal after each payment. At
+    /// `maturity` a balloon payment settles everything still owed, which is
+    /// the principal plus the interest since the last payment.
+    pub fn interest_only_loan(
+        principal: Value,
+        rate: f64,
+        period: impl Into<Period>,
+        interest_only_until: DateTime<Utc>,
+        maturity: DateTime<Utc>,
+        inception: DateTime<Utc>,
+    ) -> Self {
+        let period = period.into();
+        let currency = principal.currency();
+        let owed = principal.amount().abs();
+        let mut loan = Self::basic_debt(Value::new(currency, -owed), rate, period, inception);
+
+        let mut last = inception;
+        for k in 1.. {
+            let time = period.step(inception, k);
+            if time > interest_only_until || time >= maturity {
+                break;
+            }
+            let payment = Delta::payment(time, Value::new(currency, owed * rate));
+            loan.push_delta(payment.with_memo("Interest only"));
+            last = time;
+        }
+        let settled = owed * Interest::new(rate, period).factor(last, maturity);
+        let balloon = Delta::payment(maturity, Value::new(currency, settled)).with_memo("Balloon");
+        loan.balloon = Some(balloon.id);
+        loan.push_delta(balloon);
+        loan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::{Book, Upcoming},
+        item::Item,
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    #[test]
+    pub fn test_interest_only_loan() {
+        let on = |year, month| Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+        let loan = Item::interest_only_loan(
+            Value::new("CAD", 400_000.0),
+            0.005,
+            Period::Months(1),
+            on(2025, 1),
+            on(2025, 4),
+            on(2024, 1),
+        );
+        // Twelve interest only payments of 2000 and then the balloon.
+        assert_eq!(loan.deltas.len(), 13);
+        assert_eq!(loan.deltas[0].resolve(&loan.book_value).amount(), 2_000.0);
+
+        // The balance is back at the principal after every payment.
+        for month in 1..=12 {
+            let time = Period::Months(1).step(on(2024, 1), month);
+            assert_value_eq!(loan.assess(time), Value::new("CAD", -400_000.0), 1e-6);
+        }
+        // Nothing is paid after the switch, so three months of interest are
+        // added to the balloon.
+        let balloon = 400_000.0 * 1.005f64.powi(3);
+        let owed = loan.assess(on(2025, 4) - TimeDelta::seconds(1)).amount();
+        assert!(owed < -400_000.0 && owed > -balloon);
+        assert_value_eq!(loan.assess(on(2025, 4)), Value::new("CAD", 0.0), 1e-6);
+        assert_value_eq!(loan.assess(on(2030, 1)), Value::new("CAD", 0.0), 1e-6);
+
+        let mut book = Book::default();
+        let key = book.add(loan);
+        let upcoming = book.upcoming(on(2025, 1), on(2026, 1));
+        assert_eq!(upcoming.len(), 1);
+        let (time, item, event) = &upcoming[0];
+        assert_eq!((*time, *item), (on(2025, 4), key));
+        let Upcoming::Balloon(value) = event else {
+            panic!("the balloon is upcoming");
+        };
+        assert_value_eq!(*value, Value::new("CAD", balloon), 1e-6);
+    }
+}
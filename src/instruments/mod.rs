@@ -1,18 +1,141 @@
-use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use calendar::DayPolicy;
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::RngCore;
 use value::{Currency, Value};
 
+pub mod analysis;
 pub mod book;
 pub mod value;
 pub mod delta;
 pub mod risk;
 pub mod convert;
+pub mod benchmark;
 pub mod item;
+pub mod lint;
+pub mod insurance;
+pub mod inflation;
+pub mod fees;
+pub mod sync;
+pub mod import;
+pub mod audit;
+pub mod events;
+pub mod export;
+pub mod exposure;
+pub mod groups;
+pub mod transfer;
+pub mod undo;
+pub mod calendar;
+pub mod budget;
+pub mod cash;
+pub mod cashflow;
+pub mod depreciation;
+pub mod lease;
+pub mod loan;
+pub mod position;
+pub mod reconcile;
+pub mod replay;
+pub mod report;
+pub mod sale;
+pub mod stats;
+pub mod period;
+pub mod finance;
+pub mod strategy;
+pub mod tax;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+#[cfg(feature = "decimal")]
+pub mod decimal;
+
+// Books are assessed from many threads at once, keep the core types
+// shareable so a regression fails to compile.
+static_assertions::assert_impl_all!(value::Value: Send, Sync);
+static_assertions::assert_impl_all!(item::Item: Send, Sync);
+static_assertions::assert_impl_all!(book::Book: Send, Sync);
+static_assertions::assert_impl_all!(risk::Risk<'static, item::Item>: Send, Sync);
+static_assertions::assert_impl_all!(convert::ConversionTable: Send, Sync);
 
 pub trait Assesible {
     /// Asseses the value of an asset at a certain time.
     fn assess(&self, time: DateTime<Utc>) -> Value;
     /// Get the primary currency type of the asset.
     fn currency(&self) -> Currency;
+    /// Draws a single random outcome of the assessment, this is what
+    /// the Monte Carlo machinery runs on. Deterministic assets just
+    /// return their regular assessment.
+    fn sample(&self, time: DateTime<Utc>, _rng: &mut dyn RngCore) -> Value {
+        self.assess(time)
+    }
+    /// Assesses the value at the start or end of a day in UTC.
+    fn assess_on(&self, date: NaiveDate, policy: DayPolicy) -> Value {
+        self.assess(policy.utc(date))
+    }
+    /// Assesses the value at the end of a day in a local time zone.
+    #[cfg(feature = "chrono-tz")]
+    fn assess_local(&self, date: NaiveDate, tz: chrono_tz::Tz) -> Value {
+        self.assess(DayPolicy::EndOfDay.instant_in(date, &tz))
+    }
+}
+
+impl<T: Assesible + ?Sized> Assesible for &T {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        (**self).assess(time)
+    }
+    fn currency(&self) -> Currency {
+        (**self).currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        (**self).sample(time, rng)
+    }
+}
+
+impl<T: Assesible + ?Sized> Assesible for Box<T> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        (**self).assess(time)
+    }
+    fn currency(&self) -> Currency {
+        (**self).currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        (**self).sample(time, rng)
+    }
+}
+
+impl<T: Assesible + ?Sized> Assesible for Arc<T> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        (**self).assess(time)
+    }
+    fn currency(&self) -> Currency {
+        (**self).currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        (**self).sample(time, rng)
+    }
+}
+
+/// A collection of assets is assessed as their sum, much like a
+/// [book::Book] without the bookkeeping.
+impl<T: Assesible> Assesible for Vec<T> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        self.iter()
+            .map(|a| a.assess(time))
+            .sum::<Option<Value>>()
+            .unwrap_or_else(|| Value::zero(self.currency()))
+    }
+    /// The currency of the first asset, an empty collection is worth
+    /// nothing in CAD like an empty [book::Book].
+    fn currency(&self) -> Currency {
+        self.first()
+            .map(Assesible::currency)
+            .unwrap_or(Currency::new("CAD"))
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        self.iter()
+            .map(|a| a.sample(time, rng))
+            .sum::<Option<Value>>()
+            .unwrap_or_else(|| Value::zero(self.currency()))
+    }
 }
 
 pub struct AssessmentResult {
@@ -20,11 +143,83 @@ pub struct AssessmentResult {
     value: Value,
     /// The excess amounts, this is usually just the sum of money from payouts
     /// and as added to cash on the books.
-    cash: Value
+    cash: Value,
+    /// The cash kept in the currencies it was received in, one value per
+    /// currency.
+    ledger: Vec<Value>
 }
 
 impl AssessmentResult {
     pub fn new(value: Value, cash: Value) -> Self {
-        Self { value, cash }
+        Self { value, ledger: vec![cash.clone()], cash }
+    }
+    /// A result whose cash came in several currencies, `cash` being their
+    /// total in one of them.
+    pub fn with_ledger(value: Value, cash: Value, ledger: Vec<Value>) -> Self {
+        Self { value, cash, ledger }
+    }
+    /// The primary value of the assessment.
+    pub fn value(&self) -> &Value {
+        &self.value
     }
-}
\ No newline at end of file
+    /// The cash generated alongside the assessment.
+    pub fn cash(&self) -> &Value {
+        &self.cash
+    }
+    /// The cash in each currency it was received in.
+    pub fn cash_by_currency(&self) -> &[Value] {
+        &self.ledger
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::Item,
+        risk::Risk,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    #[test]
+    pub fn test_heterogeneous_portfolio() {
+        let inception = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::dummy("CAD", 1_000.0), inception));
+        book.add(Item::fixed(Value::dummy("CAD", 500.0), inception));
+
+        let portfolio: Vec<Box<dyn Assesible>> = vec![
+            Box::new(Item::fixed(Value::dummy("CAD", 250.0), inception)),
+            Box::new(book),
+        ];
+        assert_value_eq!(portfolio.assess(now), Value::new("CAD", 1_750.0), 0.005);
+        assert_eq!(portfolio.currency().code(), "CAD");
+
+        let risky = Risk::CertainLossPercentage {
+            asset: Box::new(portfolio) as Box<dyn Assesible>,
+            percent: 0.5,
+        };
+        assert_value_eq!(risky.assess(now), Value::new("CAD", 875.0), 0.005);
+    }
+
+    #[test]
+    pub fn test_assess_through_references() {
+        let inception = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let debt = Item::basic_debt(Value::dummy("CAD", -100.0), 0.1, TimeDelta::days(365), inception);
+
+        let risky = Risk::CertainLossPercentage {
+            asset: &debt,
+            percent: 1.0,
+        };
+        let time = inception + TimeDelta::days(365);
+        assert_value_eq!(risky.assess(time), debt.assess(time), 0.005);
+        assert_eq!(Vec::<Item>::new().currency(), Currency::new("CAD"));
+    }
+}
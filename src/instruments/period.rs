@@ -0,0 +1,357 @@
+//! Lengths of time that follow the calendar.
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Months, TimeDelta, TimeZone, Utc};
+
+/// How often something happens. Calendar periods step by months so a year
+/// is a year whether or not it has a February 29th.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Period {
+    Years(u32),
+    Months(u32),
+    /// A fixed length of time.
+    Delta(TimeDelta),
+}
+
+impl From<TimeDelta> for Period {
+    fn from(value: TimeDelta) -> Self {
+        Period::Delta(value)
+    }
+}
+
+impl fmt::Display for Period {
+    /// An ISO 8601 duration such as `P1Y` or `PT86400S`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::Years(years) => write!(f, "P{years}Y"),
+            Period::Months(months) => write!(f, "P{months}M"),
+            Period::Delta(delta) => write!(f, "{delta}"),
+        }
+    }
+}
+
+/// Why a [Period] cannot be used to step through time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeriodError {
+    /// The period has no length, stepping by it never moves.
+    Empty(Period),
+    /// The period goes backwards in time.
+    Negative(Period),
+    /// A calendar period of a number of months no [Period] can hold.
+    Months(i64),
+}
+
+impl fmt::Display for PeriodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeriodError::Empty(period) => {
+                write!(f, "periods must be longer than zero, got {period}")
+            }
+            PeriodError::Negative(period) => {
+                write!(f, "periods must go forwards in time, got {period}")
+            }
+            PeriodError::Months(months) => write!(
+                f,
+                "calendar periods must be between 1 and {} months, got {months}",
+                u32::MAX
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PeriodError {}
+
+impl Period {
+    /// A calendar period of `months`, in whole years when it divides into
+    /// them.
+    pub fn calendar(months: i64) -> Result<Self, PeriodError> {
+        let period = match u32::try_from(months) {
+            Ok(months) if months > 0 && months % 12 == 0 => Period::Years(months / 12),
+            Ok(months) => Period::Months(months),
+            Err(_) => return Err(PeriodError::Months(months)),
+        };
+        period.validate()
+    }
+    /// The period itself if it is strictly positive and its months fit in
+    /// a `u32`.
+    pub fn validate(self) -> Result<Self, PeriodError> {
+        let positive = match self {
+            Period::Delta(delta) if delta < TimeDelta::zero() => {
+                return Err(PeriodError::Negative(self))
+            }
+            Period::Delta(delta) => delta > TimeDelta::zero(),
+            Period::Years(years) if self.months().is_none() => {
+                return Err(PeriodError::Months(years as i64 * 12))
+            }
+            _ => self.months().is_some_and(|months| months > 0),
+        };
+        match positive {
+            true => Ok(self),
+            false => Err(PeriodError::Empty(self)),
+        }
+    }
+    /// The number of months in a calendar period, `None` for fixed
+    /// periods and years too long to count in months.
+    fn months(&self) -> Option<u32> {
+        match self {
+            Period::Years(years) => years.checked_mul(12),
+            Period::Months(months) => Some(*months),
+            Period::Delta(_) => None,
+        }
+    }
+    /// The time `count` periods after `time`, counted from `time` so month
+    /// ends that get clamped do not drift.
+    pub fn step(&self, time: DateTime<Utc>, count: i64) -> DateTime<Utc> {
+        let stepped = match self {
+            Period::Delta(delta) => delta
+                .num_nanoseconds()
+                .and_then(|nanos| nanos.checked_mul(count))
+                .map(TimeDelta::nanoseconds)
+                .or_else(|| {
+                    let seconds = delta.num_seconds().checked_mul(count)?;
+                    TimeDelta::try_seconds(seconds)
+                })
+                .and_then(|delta| time.checked_add_signed(delta)),
+            _ => self
+                .months()
+                .and_then(|months| u32::try_from(months as i64 * count.abs()).ok())
+                .and_then(|total| match count >= 0 {
+                    true => time.checked_add_months(Months::new(total)),
+                    false => time.checked_sub_months(Months::new(total)),
+                }),
+        };
+        stepped.expect("time out of range")
+    }
+    /// Splits the span from `from` to `to` into whole periods and the
+    /// fraction of the period after them. Fixed periods divide exactly,
+    /// calendar periods take the fraction of the period the rest falls in.
+    /// A period that does not [Period::validate] never completes, the span
+    /// holds none of it.
+    pub fn split(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> (i64, f64) {
+        if self.validate().is_err() {
+            return (0, 0.0);
+        }
+        match self {
+            Period::Delta(delta) => {
+                let elapsed = (to - from).num_nanoseconds().unwrap();
+                let length = delta.num_nanoseconds().unwrap();
+                (
+                    elapsed.div_euclid(length),
+                    elapsed.rem_euclid(length) as f64 / length as f64,
+                )
+            }
+            _ => {
+                let months = self.months().unwrap() as i64;
+                let apart = (to.year() as i64 - from.year() as i64) * 12 + to.month() as i64
+                    - from.month() as i64;
+                let mut whole = apart.div_euclid(months);
+                while self.step(from, whole) > to {
+                    whole -= 1;
+                }
+                while self.step(from, whole + 1) <= to {
+                    whole += 1;
+                }
+                let start = self.step(from, whole);
+                let end = self.step(from, whole + 1);
+                let fraction = (to - start).num_nanoseconds().unwrap() as f64
+                    / (end - start).num_nanoseconds().unwrap() as f64;
+                (whole, fraction)
+            }
+        }
+    }
+    /// The number of periods from `from` to `to`, negative if `to` is earlier.
+    pub fn between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        let (whole, fraction) = self.split(from, to);
+        whole as f64 + fraction
+    }
+    /// A fixed length close to the period, a year is 365 days and a month
+    /// is a twelfth of that. Periods too long for a [TimeDelta] are as long
+    /// as one gets.
+    pub fn approximate(&self) -> TimeDelta {
+        match self {
+            Period::Delta(delta) => *delta,
+            _ => self
+                .months()
+                .and_then(|months| i32::try_from(months).ok())
+                .and_then(|months| TimeDelta::days(365).checked_mul(months))
+                .map_or(TimeDelta::MAX, |length| length / 12),
+        }
+    }
+}
+
+/// Where the boundaries of periods fall, which decides when interest is
+/// capitalized or rounded and how a part of a period is counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PeriodAnchor {
+    /// Periods are counted from whenever they start, such as the inception
+    /// of the item, so one started on the 31st ends on the last day of
+    /// shorter months.
+    #[default]
+    Inception,
+    /// The first period is cut short at the end of the month, every later
+    /// one ends on a month end. Month ends are at midnight UTC.
+    CalendarMonthEnd,
+    /// Like [PeriodAnchor::CalendarMonthEnd] with quarter ends.
+    CalendarQuarterEnd,
+}
+
+impl PeriodAnchor {
+    /// The first month or quarter end after `time`, `None` when anchored to
+    /// the inception.
+    pub fn next_end(self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let months = match self {
+            PeriodAnchor::Inception => return None,
+            PeriodAnchor::CalendarMonthEnd => 1,
+            PeriodAnchor::CalendarQuarterEnd => 3,
+        };
+        let month = time.month0() / months * months + 1;
+        let start = Utc
+            .with_ymd_and_hms(time.year(), month, 1, 0, 0, 0)
+            .unwrap();
+        Some(Period::Months(months).step(start, 1))
+    }
+    /// The boundary `k` periods after `origin`, the zeroth being the origin
+    /// itself.
+    pub fn boundary(self, period: Period, origin: DateTime<Utc>, k: i64) -> DateTime<Utc> {
+        match self.next_end(origin) {
+            Some(first) if k > 0 => period.step(first, k - 1),
+            _ => period.step(origin, k),
+        }
+    }
+    /// The `k` of the last [PeriodAnchor::boundary] at or before `time`.
+    pub fn index(self, period: Period, origin: DateTime<Utc>, time: DateTime<Utc>) -> i64 {
+        match self.next_end(origin) {
+            Some(first) if time >= first => 1 + period.split(first, time).0,
+            Some(_) => 0,
+            None => period.split(origin, time).0,
+        }
+    }
+    /// The number of periods from `from` to `to`. Parts of a period count
+    /// as the fraction of the period they fall in, so with a calendar
+    /// anchor a part of February counts for more than as much of March.
+    pub fn between(self, period: Period, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        match self.next_end(from) {
+            Some(first) => period.between(first, to) - period.between(first, from),
+            None => period.between(from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use super::{Period, PeriodAnchor, PeriodError};
+
+    #[test]
+    pub fn test_calendar_periods() {
+        let leap = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2004, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(Period::Years(1).between(leap, later), 4.0);
+        assert!(Period::Delta(TimeDelta::days(365)).between(leap, later) > 4.0);
+
+        // Half of February in a leap year.
+        let feb = Utc.with_ymd_and_hms(2000, 2, 1, 0, 0, 0).unwrap();
+        let mid = feb + TimeDelta::hours(12 * 29);
+        assert_eq!(Period::Months(1).between(feb, mid), 0.5);
+        assert!((-1.0..0.0).contains(&Period::Months(1).between(mid, feb)));
+
+        // Anniversaries of February 29th land on the 28th.
+        let day = Utc.with_ymd_and_hms(2000, 2, 29, 0, 0, 0).unwrap();
+        let anniversary = Period::Years(1).step(day, 1);
+        assert_eq!(
+            anniversary,
+            Utc.with_ymd_and_hms(2001, 2, 28, 0, 0, 0).unwrap()
+        );
+        assert_eq!(Period::Years(1).between(day, anniversary), 1.0);
+        assert_eq!(
+            Period::Years(1).step(day, 4),
+            Utc.with_ymd_and_hms(2004, 2, 29, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_validate() {
+        assert_eq!(Period::Months(1).validate(), Ok(Period::Months(1)));
+        assert_eq!(
+            Period::Years(0).validate(),
+            Err(PeriodError::Empty(Period::Years(0)))
+        );
+        let zero = Period::Delta(TimeDelta::zero());
+        assert_eq!(zero.validate(), Err(PeriodError::Empty(zero)));
+        let back = Period::Delta(TimeDelta::days(-1));
+        assert_eq!(back.validate(), Err(PeriodError::Negative(back)));
+        assert!(back
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("forwards"));
+    }
+
+    #[test]
+    pub fn test_calendar_constructor() {
+        assert_eq!(Period::calendar(3), Ok(Period::Months(3)));
+        assert_eq!(Period::calendar(24), Ok(Period::Years(2)));
+        assert_eq!(
+            Period::calendar(0),
+            Err(PeriodError::Empty(Period::Months(0)))
+        );
+        assert_eq!(Period::calendar(-1), Err(PeriodError::Months(-1)));
+        assert_eq!(
+            Period::calendar(1 << 40),
+            Err(PeriodError::Months(1 << 40))
+        );
+    }
+
+    #[test]
+    pub fn test_degenerate_periods() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2004, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(Period::Months(0).split(start, later), (0, 0.0));
+        assert_eq!(
+            Period::Delta(TimeDelta::zero()).split(start, later),
+            (0, 0.0)
+        );
+        assert_eq!(
+            Period::Years(u32::MAX).validate(),
+            Err(PeriodError::Months(u32::MAX as i64 * 12))
+        );
+
+        // Counts past what fits in an i32 are not truncated.
+        let second = Period::Delta(TimeDelta::seconds(1));
+        let count = i64::from(i32::MAX) + 1;
+        assert_eq!(second.step(start, count), start + TimeDelta::seconds(count));
+    }
+
+    #[test]
+    pub fn test_period_anchor() {
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        let month = Period::Months(1);
+        let (inception, month_end) = (PeriodAnchor::Inception, PeriodAnchor::CalendarMonthEnd);
+
+        // Started on the 31st, periods from inception end on the last day of
+        // shorter months while month end ones cut the first short.
+        let ends = |anchor: PeriodAnchor| {
+            (1..=3)
+                .map(|k| anchor.boundary(month, on(1, 31), k))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ends(inception), [on(2, 29), on(3, 31), on(4, 30)]);
+        assert_eq!(ends(month_end), [on(2, 1), on(3, 1), on(4, 1)]);
+        assert_eq!(inception.index(month, on(1, 31), on(2, 29)), 1);
+        assert_eq!(month_end.index(month, on(1, 31), on(2, 28)), 1);
+        assert_eq!(month_end.index(month, on(1, 31), on(3, 1)), 2);
+
+        // Half of February is worth more than half of March.
+        let feb = month_end.between(month, on(2, 15), on(3, 15));
+        assert!((feb - (15.0 / 29.0 + 14.0 / 31.0)).abs() < 1e-12);
+        assert_eq!(inception.between(month, on(2, 15), on(3, 15)), 1.0);
+        assert_eq!(month_end.between(month, on(1, 31), on(3, 31)), 2.0);
+
+        let quarter = PeriodAnchor::CalendarQuarterEnd;
+        assert_eq!(quarter.next_end(on(1, 31)), Some(on(4, 1)));
+        assert_eq!(quarter.next_end(on(4, 1)), Some(on(7, 1)));
+        assert_eq!(inception.next_end(on(1, 31)), None);
+    }
+}
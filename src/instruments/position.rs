@@ -0,0 +1,239 @@
+//! Holdings valued off a price history rather than an interest formula.
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    delta::{Delta, DeltaId},
+    inflation::index_at,
+    item::Item,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// How a [PriceSeries] reads prices between two points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// The last known price holds until the next one.
+    Step,
+    /// Prices move in a straight line from one point to the next.
+    Linear,
+}
+
+/// What a [PriceSeries] answers for times outside of its points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// The price of the closest point.
+    Nearest,
+    /// There is no price, so holdings are worth nothing.
+    Zero,
+}
+
+/// Prices of a security over time, sorted by date.
+#[derive(Clone, Debug)]
+pub struct PriceSeries {
+    points: Vec<(DateTime<Utc>, f64)>,
+    pub interpolation: Interpolation,
+    /// The policy for times before the first point.
+    pub before: OutOfRange,
+    /// The policy for times after the last point.
+    pub after: OutOfRange,
+}
+
+impl PriceSeries {
+    /// A step series that holds its first and last price outside of its
+    /// points, the points do not need to be sorted.
+    pub fn new(mut points: Vec<(DateTime<Utc>, f64)>) -> Self {
+        assert!(
+            !points.is_empty(),
+            "a price series needs at least one point"
+        );
+        points.sort_by_key(|(t, _)| *t);
+        Self {
+            points,
+            interpolation: Interpolation::Step,
+            before: OutOfRange::Nearest,
+            after: OutOfRange::Nearest,
+        }
+    }
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+    pub fn with_out_of_range(mut self, before: OutOfRange, after: OutOfRange) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+    pub fn points(&self) -> &[(DateTime<Utc>, f64)] {
+        &self.points
+    }
+    /// The price of a single unit at a certain time.
+    pub fn price(&self, time: DateTime<Utc>) -> f64 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if time < first.0 {
+            return match self.before {
+                OutOfRange::Nearest => first.1,
+                OutOfRange::Zero => 0.0,
+            };
+        }
+        if time > last.0 {
+            return match self.after {
+                OutOfRange::Nearest => last.1,
+                OutOfRange::Zero => 0.0,
+            };
+        }
+        match self.interpolation {
+            Interpolation::Step => {
+                self.points[self.points.partition_point(|(t, _)| *t <= time) - 1].1
+            }
+            Interpolation::Linear => index_at(&self.points, time),
+        }
+    }
+}
+
+impl Item {
+    /// A holding of `units` of a security priced off `prices` in
+    /// `currency`, starting at the first price. Its value is the units at
+    /// the price of the series plus the cash paid for or received from
+    /// trading them, which is its balance. A trade so does not change the
+    /// value at the moment it is made, only how it moves afterwards.
+    pub fn position(units: f64, prices: PriceSeries, currency: impl Into<Currency>) -> Item {
+        let mut item = Item::fixed(Value::zero(currency.into()), prices.points[0].0);
+        item.units = Some(units);
+        item.prices = Some(Box::new(prices));
+        item
+    }
+    /// Trades `units` at an explicit price per unit, negative units sell.
+    /// The cash goes through the balance, unlike [Item::buy] which adds
+    /// what is paid to the value of the item.
+    pub fn trade(&mut self, time: DateTime<Utc>, units: f64, price: f64) -> DeltaId {
+        let cash = Value::new(self.currency(), -units * price);
+        let mut delta = Delta::payment(time, cash).with_memo("Trade");
+        delta.units = Some(units);
+        let id = delta.id;
+        self.push_delta(delta);
+        id
+    }
+    /// Trades `units` at the price of the series, negative units sell.
+    /// `None` if the item is not a [Item::position].
+    pub fn trade_at_market(&mut self, time: DateTime<Utc>, units: f64) -> Option<DeltaId> {
+        let price = self.prices.as_ref()?.price(time);
+        Some(self.trade(time, units, price))
+    }
+    /// What the units alone are worth at a certain time, zero if the item
+    /// is not a [Item::position].
+    pub fn market_value(&self, time: DateTime<Utc>) -> Value {
+        let price = self.prices.as_ref().map_or(0.0, |prices| prices.price(time));
+        Value::new(self.currency(), self.units_at(time).unwrap_or(0.0) * price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        delta::{Delta, Granularity},
+        item::Item,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{Interpolation, OutOfRange, PriceSeries};
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap()
+    }
+
+    fn xeqt() -> PriceSeries {
+        PriceSeries::new(vec![(day(11), 30.0), (day(1), 20.0), (day(21), 25.0)])
+    }
+
+    #[test]
+    pub fn test_interpolation() {
+        let halfway = day(6);
+
+        let step = Item::position(120.0, xeqt(), "CAD");
+        assert_eq!(step.assess(halfway).amount(), 120.0 * 20.0);
+        assert_eq!(
+            step.assess(day(11) - TimeDelta::seconds(1)).amount(),
+            120.0 * 20.0
+        );
+        assert_eq!(step.assess(day(11)).amount(), 120.0 * 30.0);
+
+        let linear = Item::position(
+            120.0,
+            xeqt().with_interpolation(Interpolation::Linear),
+            "CAD",
+        );
+        assert_value_eq!(linear.assess(halfway), Value::new("CAD", 120.0 * 25.0), 1e-9);
+        assert_value_eq!(linear.assess(day(16)), Value::new("CAD", 120.0 * 27.5), 1e-9);
+
+        // Outside of the series.
+        assert_eq!(step.assess(day(31)).amount(), 120.0 * 25.0);
+        let bounded = Item::position(
+            120.0,
+            xeqt().with_out_of_range(OutOfRange::Zero, OutOfRange::Nearest),
+            "CAD",
+        );
+        assert_eq!(bounded.assess(day(1) - TimeDelta::days(1)).amount(), 0.0);
+        assert_eq!(bounded.assess(day(31)).amount(), 120.0 * 25.0);
+    }
+
+    #[test]
+    pub fn test_buy_mid_series() {
+        let mut position = Item::position(120.0, xeqt(), "CAD");
+        position.trade_at_market(day(11), 30.0).unwrap();
+        position.trade_at_market(day(21), -50.0).unwrap();
+
+        // Nothing changes before the buy.
+        assert_eq!(position.assess(day(5)).amount(), 120.0 * 20.0);
+        // The cash paid offsets the new units at the moment of the buy...
+        assert_eq!(position.units_at(day(11)), Some(150.0));
+        assert_eq!(position.assess(day(11)).amount(), 120.0 * 30.0);
+        // ...but the new units follow the price down afterwards.
+        assert_eq!(position.units_at(day(25)), Some(100.0));
+        assert_eq!(
+            position.assess(day(25)).amount(),
+            120.0 * 25.0 + 30.0 * (25.0 - 30.0)
+        );
+        assert_eq!(position.market_value(day(25)).amount(), 100.0 * 25.0);
+        assert!(Item::fixed(Value::zero("CAD"), day(1)).trade_at_market(day(2), 1.0).is_none());
+    }
+
+    /// Netting a month of deltas keeps the trades, so the units held and
+    /// the cost basis stay as they were.
+    #[test]
+    pub fn test_aggregate_position() {
+        let mut position = Item::position(120.0, xeqt(), "CAD");
+        position.trade_at_market(day(11), 30.0).unwrap();
+        position.trade_at_market(day(21), -50.0).unwrap();
+        position.push_delta(Delta::fee(day(5), Value::new("CAD", -5.0)));
+        position.push_delta(Delta::fee(day(15), Value::new("CAD", -3.0)));
+
+        let mut netted = position.clone();
+        netted.aggregate_deltas(Granularity::Monthly);
+        assert_eq!(netted.deltas.len(), 3);
+        let february = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        for time in [day(11), day(25), february] {
+            assert_eq!(netted.units_at(time), position.units_at(time));
+        }
+        assert_value_eq!(netted.assess(february), position.assess(february), 1e-9);
+        assert_value_eq!(netted.cost_basis(), position.cost_basis(), 1e-9);
+    }
+
+    /// Positions go in a book like any other item.
+    #[test]
+    pub fn test_position_in_book() {
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("CAD", 1_000.0), day(1)));
+        let xeqt = book.add(Item::position(120.0, xeqt(), "CAD"));
+        assert_eq!(book.assess(day(11)).amount(), 1_000.0 + 120.0 * 30.0);
+        assert_eq!(
+            book.get(xeqt).unwrap().assess_at_many(&[day(1), day(11)])[1].amount(),
+            120.0 * 30.0
+        );
+    }
+}
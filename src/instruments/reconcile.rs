@@ -0,0 +1,157 @@
+//! Checking a book against what the bank says.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{
+    book::{Book, ItemKey},
+    convert::CURRENCY_EXCHANGE,
+    delta::{DeltaAmount, DeltaId},
+    item::Item,
+    value::Value,
+    Assesible,
+};
+
+/// How many of the most recent deltas a [ReconcileReport] lists.
+const SUSPECTS: usize = 5;
+
+/// How an item compares to an external statement, see [Book::reconcile].
+#[derive(Clone, Debug)]
+pub struct ReconcileReport {
+    pub item: ItemKey,
+    pub at: DateTime<Utc>,
+    /// What the book assesses the item at.
+    pub assessed: Value,
+    /// The statement balance in the item's currency.
+    pub statement: Value,
+    /// The statement less the assessment, what the book is missing.
+    pub discrepancy: Value,
+    /// The most recent deltas up to `at`, latest first.
+    pub suspects: Vec<DeltaId>,
+}
+
+impl ReconcileReport {
+    /// Whether the two agree to the minor unit of the currency.
+    pub fn is_reconciled(&self) -> bool {
+        self.discrepancy.to_minor_units() == 0
+    }
+}
+
+impl Item {
+    /// Pairs of fixed deltas at most `window` apart whose amounts are within
+    /// `tolerance` of each other, such as a payment imported twice from
+    /// overlapping exports. Percentage deltas are never flagged.
+    pub fn find_duplicate_deltas(
+        &self,
+        window: TimeDelta,
+        tolerance: Value,
+    ) -> Vec<(DeltaId, DeltaId)> {
+        let currency = self.currency();
+        let rate = CURRENCY_EXCHANGE
+            .rate(tolerance.currency(), currency)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let tolerance = (tolerance.amount() * rate).abs();
+        let fixed: Vec<_> = self
+            .deltas
+            .iter()
+            .filter(|d| matches!(d.amount, DeltaAmount::Fixed(_)))
+            .map(|d| (d.id, d.time, d.resolve_amount(0.0, currency)))
+            .collect();
+
+        let mut pairs = vec![];
+        for (i, (first, time, amount)) in fixed.iter().enumerate() {
+            let close = fixed[i + 1..]
+                .iter()
+                .take_while(|(_, other, _)| *other - *time <= window);
+            for (second, _, other) in close {
+                if (amount - other).abs() <= tolerance {
+                    pairs.push((*first, *second));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+impl Book {
+    /// Compares an item to a statement balance at a time, converting the
+    /// statement into the item's currency. `None` if the item is not in
+    /// the book.
+    pub fn reconcile(
+        &self,
+        item: ItemKey,
+        statement_balance: Value,
+        at: DateTime<Utc>,
+    ) -> Option<ReconcileReport> {
+        let found = self.get(item)?;
+        let currency = found.currency();
+        let rate = self
+            .rate_into(statement_balance.currency(), currency, at)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let statement = Value::new(currency, statement_balance.amount() * rate);
+        let assessed = found.assess(at);
+        Some(ReconcileReport {
+            item,
+            at,
+            discrepancy: Value::new(currency, statement.amount() - assessed.amount()),
+            assessed,
+            statement,
+            suspects: found
+                .deltas
+                .iter()
+                .rev()
+                .skip_while(|d| d.time > at)
+                .take(SUSPECTS)
+                .map(|d| d.id)
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{book::Book, delta::Delta, item::Item, value::Value};
+
+    #[test]
+    pub fn test_double_import() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let paid = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        let statement = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        let mut card = Item::fixed(Value::new("CAD", -1_000.0), start);
+        let payment = Delta::payment(paid, Value::new("CAD", 200.0));
+        let doubled = Delta::payment(paid + TimeDelta::hours(1), Value::new("CAD", 200.0));
+        let (first, second) = (payment.id, doubled.id);
+        card.import_deltas([
+            payment,
+            doubled,
+            Delta::payment(statement, Value::new("CAD", 200.0)),
+            Delta::payment(paid, Value::new("CAD", 250.0)),
+        ]);
+
+        let pairs = card.find_duplicate_deltas(TimeDelta::days(3), Value::new("CAD", 0.01));
+        assert_eq!(pairs, vec![(first, second)]);
+        assert_eq!(
+            card.find_duplicate_deltas(TimeDelta::days(3), Value::new("CAD", 60.0))
+                .len(),
+            3
+        );
+
+        let mut book = Book::default();
+        let key = book.add(card);
+        let report = book
+            .reconcile(key, Value::new("CAD", -800.0), paid + TimeDelta::days(1))
+            .unwrap();
+        assert_eq!(report.assessed.amount(), -350.0);
+        assert_eq!(report.discrepancy.amount(), -450.0);
+        assert!(!report.is_reconciled());
+        assert_eq!(report.suspects.len(), 3);
+        assert!(report.suspects.contains(&second));
+
+        let report = book
+            .reconcile(key, Value::new("CAD", -150.0), statement)
+            .unwrap();
+        assert!(report.is_reconciled());
+        assert_eq!(book.get(key).unwrap().deltas.len(), 4);
+    }
+}
@@ -0,0 +1,533 @@
+//! Rebuilding a [Book] from its audit log.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    delta::{Delta, DeltaAmount},
+    groups::GroupId,
+    item::Item,
+    transfer::TransferId,
+};
+
+/// Why a stream of [AuditEntry]s could not be replayed, usually because it
+/// was edited by hand or is missing entries. `seq` is where the entry sits
+/// in the stream.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplayError {
+    /// The entry refers to an item that was never added or already removed.
+    UnknownItem { seq: u64, key: ItemKey },
+    /// The entry adds an item that is already in the book.
+    DuplicateItem { seq: u64, key: ItemKey },
+    /// The entry unlinks a child from an item that is not its parent.
+    NotAChild {
+        seq: u64,
+        parent: ItemKey,
+        child: ItemKey,
+    },
+    /// Another item already has the external identifier.
+    DuplicateExternalId { seq: u64, key: ItemKey, id: String },
+    /// The entry removes a delta the item does not have.
+    MissingDelta {
+        seq: u64,
+        key: ItemKey,
+        delta: Box<Delta>,
+    },
+    /// The entry removes a delta the item has, but with other contents.
+    DeltaMismatch {
+        seq: u64,
+        key: ItemKey,
+        recorded: Box<Delta>,
+        found: Box<Delta>,
+    },
+    /// The deltas of a transfer are not on its items.
+    TransferWithoutDeltas { seq: u64, id: TransferId },
+    /// The entry takes back a transfer that was never made.
+    UnknownTransfer { seq: u64, id: TransferId },
+    /// The entry refers to a group that was never created.
+    UnknownGroup { seq: u64, id: GroupId },
+}
+
+fn describe(delta: &Delta) -> String {
+    let amount = match &delta.amount {
+        DeltaAmount::Fixed(value) => value.to_string(),
+        DeltaAmount::Percent(percent) => format!("{}%", percent * 100.0),
+    };
+    format!("{amount} at {}", delta.time.to_rfc3339())
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::UnknownItem { seq, key } => {
+                write!(f, "event {seq} refers to item {key:?}, which is not in the book")
+            }
+            ReplayError::DuplicateItem { seq, key } => {
+                write!(f, "event {seq} adds item {key:?}, which is already in the book")
+            }
+            ReplayError::NotAChild { seq, parent, child } => write!(
+                f,
+                "event {seq} unlinks {child:?} from {parent:?}, which is not its parent"
+            ),
+            ReplayError::DuplicateExternalId { seq, key, id } => write!(
+                f,
+                "event {seq} gives {key:?} the external id {id:?}, which another item has"
+            ),
+            ReplayError::MissingDelta { seq, key, delta } => write!(
+                f,
+                "event {seq} removes a delta of {} from {key:?}, which has no such delta",
+                describe(delta)
+            ),
+            ReplayError::DeltaMismatch {
+                seq,
+                key,
+                recorded,
+                found,
+            } => write!(
+                f,
+                "event {seq} removes delta {} of {} from {key:?}, but the book has it as {}",
+                recorded.id.get(),
+                describe(recorded),
+                describe(found)
+            ),
+            ReplayError::TransferWithoutDeltas { seq, id } => write!(
+                f,
+                "event {seq} records transfer {} without its deltas on the items",
+                id.get()
+            ),
+            ReplayError::UnknownTransfer { seq, id } => write!(
+                f,
+                "event {seq} takes back transfer {}, which was never made",
+                id.get()
+            ),
+            ReplayError::UnknownGroup { seq, id } => {
+                write!(f, "event {seq} refers to group {id:?}, which was never created")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Whether two deltas say the same thing, whatever their ids.
+fn same_delta(a: &Delta, b: &Delta) -> bool {
+    let amount = match (&a.amount, &b.amount) {
+        (DeltaAmount::Fixed(a), DeltaAmount::Fixed(b)) => {
+            a.currency() == b.currency() && a.amount() == b.amount()
+        }
+        (DeltaAmount::Percent(a), DeltaAmount::Percent(b)) => a == b,
+        _ => false,
+    };
+    amount
+        && a.time == b.time
+        && a.kind == b.kind
+        && a.memo == b.memo
+        && a.category == b.category
+        && a.external_id == b.external_id
+        && a.status == b.status
+        && a.rate == b.rate
+        && a.units == b.units
+}
+
+/// A book being rebuilt, with the key each recorded key was given.
+struct Replayer {
+    book: Book,
+    keys: HashMap<ItemKey, ItemKey>,
+    groups: HashMap<GroupId, GroupId>,
+    seq: u64,
+}
+
+impl Replayer {
+    fn key(&self, key: ItemKey) -> Result<ItemKey, ReplayError> {
+        self.keys
+            .get(&key)
+            .copied()
+            .filter(|k| self.book.get(*k).is_some())
+            .ok_or(ReplayError::UnknownItem { seq: self.seq, key })
+    }
+
+    /// The default group is the same in every book.
+    fn group(&self, id: GroupId) -> Result<GroupId, ReplayError> {
+        if id == GroupId::default() {
+            return Ok(id);
+        }
+        self.groups
+            .get(&id)
+            .copied()
+            .ok_or(ReplayError::UnknownGroup { seq: self.seq, id })
+    }
+
+    fn children(&self, item: &Item) -> Result<Item, ReplayError> {
+        let mut item = item.clone();
+        item.children = item
+            .children
+            .iter()
+            .map(|child| self.key(*child))
+            .collect::<Result<_, _>>()?;
+        Ok(item)
+    }
+
+    fn apply(&mut self, entry: &AuditEntry) -> Result<(), ReplayError> {
+        let seq = self.seq;
+        match entry {
+            AuditEntry::ItemAdded { key, name, item } => {
+                if self.key(*key).is_ok() {
+                    return Err(ReplayError::DuplicateItem { seq, key: *key });
+                }
+                let item = self.children(item)?;
+                let added = match name {
+                    Some(name) => self.book.add_named(name.clone(), item),
+                    None => self.book.add(item),
+                };
+                self.keys.insert(*key, added);
+            }
+            AuditEntry::ItemRemoved { key } => {
+                let removed = self.key(*key)?;
+                self.book.remove(removed);
+                self.keys.remove(key);
+            }
+            AuditEntry::ItemReplaced { key, item } => {
+                let replaced = self.key(*key)?;
+                self.book.replace_item(replaced, Arc::new(self.children(item)?));
+            }
+            AuditEntry::ChildAdded { parent, child } => {
+                let (parent, child) = (self.key(*parent)?, self.key(*child)?);
+                self.book.link_child(parent, child);
+            }
+            AuditEntry::ChildRemoved { parent, child } => {
+                let (linked, unlinked) = (self.key(*parent)?, self.key(*child)?);
+                if !self.book.get(linked).unwrap().children.contains(&unlinked) {
+                    return Err(ReplayError::NotAChild {
+                        seq,
+                        parent: *parent,
+                        child: *child,
+                    });
+                }
+                self.book.unlink_child(linked, unlinked);
+            }
+            AuditEntry::ExternalIdSet { key, id } => {
+                if !self.book.set_external_id(self.key(*key)?, id.clone()) {
+                    return Err(ReplayError::DuplicateExternalId {
+                        seq,
+                        key: *key,
+                        id: id.clone(),
+                    });
+                }
+            }
+            AuditEntry::DeltaAdded { key, delta } => {
+                self.book.add_delta(self.key(*key)?, delta.clone());
+            }
+            AuditEntry::DeltaRemoved { key, delta } => {
+                let item = self.book.get(self.key(*key)?).unwrap();
+                // Ids do not survive being written out, a delta saying the
+                // same thing is the one that was removed.
+                let id = match item.deltas.iter().find(|d| d.id == delta.id) {
+                    Some(found) if !same_delta(found, delta) => {
+                        return Err(ReplayError::DeltaMismatch {
+                            seq,
+                            key: *key,
+                            recorded: Box::new(delta.clone()),
+                            found: Box::new(found.clone()),
+                        })
+                    }
+                    Some(found) => found.id,
+                    None => item
+                        .deltas
+                        .iter()
+                        .find(|d| same_delta(d, delta))
+                        .ok_or_else(|| ReplayError::MissingDelta {
+                            seq,
+                            key: *key,
+                            delta: Box::new(delta.clone()),
+                        })?
+                        .id,
+                };
+                self.book.remove_delta(self.key(*key)?, id);
+            }
+            AuditEntry::Renamed { key, name } => {
+                self.book.set_name(self.key(*key)?, name.clone());
+            }
+            AuditEntry::ImportApplied { .. } => self.book.audit.push(entry.clone()),
+            AuditEntry::CurrencyMigrated { old, new, factor } => {
+                self.book.migrate_currency(*old, *new, *factor);
+            }
+            AuditEntry::Transferred { transfer } => {
+                let mut transfer = transfer.clone();
+                transfer.from = self.key(transfer.from)?;
+                transfer.to = self.key(transfer.to)?;
+                let has = |key, id| self.book.get(key).unwrap().deltas.iter().any(|d| d.id == id);
+                if !has(transfer.from, transfer.from_delta) || !has(transfer.to, transfer.to_delta) {
+                    return Err(ReplayError::TransferWithoutDeltas { seq, id: transfer.id });
+                }
+                self.book.transfers.push(transfer.clone());
+                self.book.audit.push(AuditEntry::Transferred { transfer });
+            }
+            AuditEntry::Untransferred { id } => {
+                if !self.book.transfers.iter().any(|t| t.id == *id) {
+                    return Err(ReplayError::UnknownTransfer { seq, id: *id });
+                }
+                self.book.transfers.retain(|t| t.id != *id);
+                self.book.audit.push(entry.clone());
+            }
+            AuditEntry::Reordered { key, before } => {
+                let (key, before) = (self.key(*key)?, self.key(*before)?);
+                self.book.move_before(key, before);
+            }
+            AuditEntry::GroupCreated { id, name } => {
+                let created = self.book.create_group(name.clone());
+                self.groups.insert(*id, created);
+            }
+            AuditEntry::GroupCurrencySet { id, currency } => {
+                self.book.set_group_currency(self.group(*id)?, *currency);
+            }
+            AuditEntry::Assigned { key, group } => {
+                self.book.assign(self.key(*key)?, self.group(*group)?);
+            }
+            AuditEntry::RiskAttached { key, risk } => {
+                self.book.attach_risk(self.key(*key)?, risk.clone());
+            }
+            AuditEntry::RisksDetached { key } => {
+                self.book.detach_risks(self.key(*key)?);
+            }
+            AuditEntry::Frozen { key, at } => {
+                self.book.freeze(self.key(*key)?, *at);
+            }
+            AuditEntry::ReportingSet { currency, policy } => {
+                self.book.set_reporting(*currency, *policy);
+            }
+            AuditEntry::CashInterestSet { currency, interest } => {
+                self.book.set_cash_interest(*currency, interest.clone());
+            }
+            AuditEntry::PrecisionToleranceSet { tolerance } => {
+                self.book.set_precision_tolerance(*tolerance);
+            }
+        }
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+impl Book {
+    /// The sequence number the next audit entry will get, entries are
+    /// numbered from zero when the book is created.
+    pub fn event_seq(&self) -> u64 {
+        self.audit_offset + self.audit.len() as u64
+    }
+    /// The audit entries from sequence number `seq` on, for following the
+    /// book from another device. `None` if some of them were handed over by
+    /// [Book::take_audit_log] or made before the book was cloned.
+    pub fn events_since(&self, seq: u64) -> Option<&[AuditEntry]> {
+        let start = usize::try_from(seq.checked_sub(self.audit_offset)?).ok()?;
+        Some(self.audit.get(start..).unwrap_or(&[]))
+    }
+    /// Rebuilds a book from every audit entry it made since it was created.
+    /// Keys are handed out in the order the original book handed them out,
+    /// so a book replayed from its whole stream has the same [ItemKey]s, and
+    /// the same goes for groups. The entries of the new book match the
+    /// stream.
+    pub fn replay<'a>(events: impl IntoIterator<Item = &'a AuditEntry>) -> Result<Book, ReplayError> {
+        let mut replayer = Replayer {
+            book: Book::default(),
+            keys: HashMap::new(),
+            groups: HashMap::new(),
+            seq: 0,
+        };
+        for entry in events {
+            replayer.apply(entry)?;
+        }
+        Ok(replayer.book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::instruments::{
+        audit::AuditEntry,
+        book::Book,
+        convert::RatePolicy,
+        delta::{Delta, DeltaAmount, DeltaKind},
+        groups::GroupId,
+        item::{Interest, Item},
+        period::Period,
+        risk::RiskSpec,
+        sale::SaleSize,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::ReplayError;
+
+    /// A book put through every kind of audited mutation.
+    fn mutated() -> Book {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let cad = |amount: f64| Value::new("CAD", amount);
+        let mut book = Book::default().undoable();
+
+        let chequing = book.add_named("Chequing", Item::fixed(cad(2_000.0), start));
+        let savings = book.add_named(
+            "Savings",
+            Item::basic_debt(cad(5_000.0), 0.03, Duration::days(365), start),
+        );
+        let visa = book
+            .add_with_external_id(
+                "visa-7f3a",
+                Item::basic_debt(cad(-1_500.0), 0.2, Duration::days(365), start),
+            )
+            .unwrap();
+        let bonus = book.add_child(Item::fixed(cad(100.0), start), savings);
+        let coffee = book
+            .add_delta(chequing, Delta::payment(start + Duration::days(3), cad(-4.5)))
+            .unwrap();
+        let rent = Delta::payment(start + Duration::days(31), cad(-1_200.0)).planned();
+        let rent = book.add_delta(chequing, rent).unwrap();
+        book.confirm_delta(chequing, rent);
+        book.remove_delta(chequing, coffee);
+        book.bulk_edit(savings, |item| {
+            for month in 1..4 {
+                item.push_delta(Delta::payment(start + Duration::days(30 * month), cad(250.0)));
+            }
+        });
+        book.transfer(chequing, savings, start + Duration::days(40), cad(300.0))
+            .unwrap();
+        book.rename(visa, "Visa");
+        book.apply_payment("Chequing", "Visa", start + Duration::days(45), cad(200.0))
+            .unwrap();
+        book.undo().unwrap();
+        book.reparent(bonus, Some(chequing));
+        book.reparent(bonus, None);
+        book.move_before(visa, chequing);
+        let change = book.add_child(Item::fixed(cad(12.0), start), chequing);
+        book.remove(change);
+        book.undo().unwrap();
+        book.add_item_with_deltas(
+            Item::fixed(cad(50.0), start),
+            vec![Delta::fee(start + Duration::days(10), cad(-5.0))],
+        );
+        book.bulk_edit(bonus, |item| {
+            let time = start + Duration::days(60);
+            item.push_delta(Delta::new(time, DeltaAmount::Percent(0.1), DeltaKind::Adjustment));
+        });
+        book.get_mut(bonus)
+            .unwrap()
+            .sell(start + Duration::days(70), SaleSize::Fraction(0.5), cad(60.0))
+            .unwrap();
+        let mut visa_card = book.get_mut(visa).unwrap();
+        visa_card.set_interest(Some(Interest::new(0.25, Period::Years(1))));
+        drop(visa_card);
+        book.get_mut(chequing).unwrap().book_value = cad(0.0);
+        book.undo().unwrap();
+
+        let daily = book.create_group("Daily");
+        book.set_group_currency(daily, "USD");
+        book.assign(chequing, daily);
+        book.assign(visa, daily);
+        book.assign(visa, GroupId::default());
+        book.attach_risk(savings, RiskSpec::CertainLossPercentage { percent: 0.1 });
+        book.attach_risk(visa, RiskSpec::NormalReturn { mean: 0.0, deviation: 0.1 });
+        book.detach_risks(visa);
+        book.freeze(chequing, start + Duration::days(50));
+        book.set_reporting("CAD", RatePolicy::Latest);
+        let savings_rate = Interest::new(0.02, Period::Years(1));
+        book.set_cash_interest(Currency::new("CAD"), Some(savings_rate));
+        book.set_precision_tolerance(Some(0.01));
+        book
+    }
+
+    #[test]
+    pub fn test_replay_round_trip() {
+        let book = mutated();
+        let replayed = Book::replay(book.audit_log()).unwrap();
+
+        assert_eq!(replayed.ordered_keys(), book.ordered_keys());
+        for key in book.ordered_keys() {
+            let (original, copy) = (book.get(key).unwrap(), replayed.get(key).unwrap());
+            assert_eq!(replayed.name(key), book.name(key));
+            assert_eq!(replayed.external_id(key), book.external_id(key));
+            assert_eq!(replayed.parent(key), book.parent(key));
+            assert_eq!(copy.children, original.children);
+            assert_eq!(copy.deltas, original.deltas);
+            let rate = |item: &Item| item.interest().map(Interest::rate);
+            assert_eq!(rate(copy), rate(original));
+            assert_eq!(copy.cost_basis().amount(), original.cost_basis().amount());
+            assert_eq!(replayed.group_of(key), book.group_of(key));
+            let risks = |book: &Book| format!("{:?}", book.risks(key));
+            assert_eq!(risks(&replayed), risks(&book));
+            assert_eq!(replayed.frozen_since(key), book.frozen_since(key));
+        }
+        assert_eq!(
+            replayed.groups().collect::<Vec<_>>(),
+            book.groups().collect::<Vec<_>>()
+        );
+        assert_eq!(replayed.reporting(), book.reporting());
+        let cad = Currency::new("CAD");
+        let rate = |book: &Book| book.cash_interest(cad).map(Interest::rate);
+        assert_eq!(rate(&replayed), rate(&book));
+        assert_eq!(replayed.precision_tolerance(), book.precision_tolerance());
+        let transfers = |book: &Book| {
+            book.transfers()
+                .iter()
+                .map(|t| (t.id, t.from, t.to, t.from_delta, t.to_delta))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(transfers(&replayed), transfers(&book));
+        assert_eq!(replayed.event_seq(), book.event_seq());
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        for days in [0, 30, 90, 400, 3_000] {
+            let time = start + Duration::days(days);
+            assert_eq!(replayed.assess(time).amount(), book.assess(time).amount());
+        }
+    }
+
+    #[test]
+    pub fn test_events_since() {
+        let mut book = mutated();
+        let seq = book.event_seq();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        book.add(Item::fixed(Value::new("CAD", 1.0), start));
+        assert_eq!(book.events_since(seq).unwrap().len(), 1);
+        assert!(book.events_since(seq + 5).unwrap().is_empty());
+
+        // Syncing in two halves ends up where the whole stream does.
+        let mut stream = book.events_since(0).unwrap()[..seq as usize].to_vec();
+        stream.extend_from_slice(book.events_since(seq).unwrap());
+        assert_eq!(Book::replay(&stream).unwrap().ordered_keys(), book.ordered_keys());
+
+        book.take_audit_log();
+        assert!(book.events_since(seq).is_none());
+        assert!(book.events_since(seq + 1).unwrap().is_empty());
+        assert_eq!(book.clone().event_seq(), seq + 1);
+    }
+
+    #[test]
+    pub fn test_replay_divergence() {
+        let mut events = mutated().take_audit_log();
+        let (seq, removed) = events
+            .iter_mut()
+            .enumerate()
+            .find_map(|(seq, entry)| match entry {
+                AuditEntry::DeltaRemoved { delta, .. } => Some((seq, delta)),
+                _ => None,
+            })
+            .unwrap();
+        removed.amount = DeltaAmount::Fixed(Value::new("CAD", -40.5));
+
+        let error = Book::replay(&events).unwrap_err();
+        assert!(matches!(error, ReplayError::DeltaMismatch { seq: s, .. } if s == seq as u64));
+        let message = error.to_string();
+        assert!(message.starts_with(&format!("event {seq} removes delta")), "{message}");
+        assert!(message.contains("-40.50 CAD at 2024-02-01"), "{message}");
+        assert!(message.contains("but the book has it as -1200.00 CAD"), "{message}");
+
+        // Losing the entry that added an item leaves later ones dangling.
+        let mut events = mutated().take_audit_log();
+        events.remove(1);
+        assert!(matches!(
+            Book::replay(&events),
+            Err(ReplayError::UnknownItem { .. })
+        ));
+    }
+}
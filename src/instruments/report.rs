@@ -0,0 +1,287 @@
+//! Plain text statements for a single item.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    book::{Book, ItemKey},
+    delta::DeltaKind,
+    value::Value,
+    Assesible,
+};
+
+/// What happened to an item over a period, see [statement]. The opening
+/// balance, the lines, the interest and the other changes always add up to
+/// the closing one.
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub name: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub opening: Value,
+    /// The deltas after the start up to and including the end.
+    pub lines: Vec<StatementLine>,
+    /// The interest accrued between the deltas, each stretch on the balance
+    /// at its start as [Interest::interest](super::item::Interest::interest)
+    /// has it.
+    pub interest: Value,
+    /// Whatever changed the balance that neither the deltas nor the interest
+    /// account for, such as capitalization schedules, rounding each period
+    /// and leases.
+    pub other: Value,
+    /// The cash the item paid out over the period, this does not change
+    /// the balance.
+    pub payouts: Vec<(DateTime<Utc>, Value)>,
+    pub closing: Value,
+}
+
+/// One delta on a [Statement].
+#[derive(Clone, Debug)]
+pub struct StatementLine {
+    pub time: DateTime<Utc>,
+    pub kind: DeltaKind,
+    pub memo: Option<String>,
+    /// What the delta changed the balance by, in the item's currency.
+    pub amount: Value,
+}
+
+/// The statement of the item from `start` to `end`, `None` if the book has
+/// no item under `key`. The balances come from
+/// [Item::breakdown](super::item::Item::breakdown) so the closing balance
+/// is what the item assesses to at the end.
+pub fn statement(
+    book: &Book,
+    key: ItemKey,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<Statement> {
+    let item = book.get(key)?;
+    let currency = item.currency();
+    let balance = |time| {
+        let breakdown = item.breakdown(time);
+        breakdown.principal.amount() + breakdown.uncapitalized.amount()
+    };
+    let accrued = |from, to| match item.interest() {
+        Some(interest) => interest.interest(from, to, Value::new(currency, balance(from))).amount(),
+        None => 0.0,
+    };
+    let (opening, closing) = (balance(start), balance(end));
+
+    let mut lines = vec![];
+    let mut interest = 0.0;
+    let mut last = None;
+    for (delta, amount) in item
+        .delta_amounts()
+        .into_iter()
+        .filter(|(d, _)| start < d.time && d.time <= end)
+    {
+        if last != Some(delta.time) {
+            interest += accrued(last.unwrap_or(start), delta.time);
+            last = Some(delta.time);
+        }
+        lines.push(StatementLine {
+            time: delta.time,
+            kind: delta.kind,
+            memo: delta.memo.clone(),
+            amount,
+        });
+    }
+    interest += accrued(last.unwrap_or(start), end);
+    let moved: f64 = lines.iter().map(|line| line.amount.amount()).sum();
+    let payouts = item
+        .payouts
+        .iter()
+        .flat_map(|p| p.occurrences(item.inception, end))
+        .filter(|(time, _)| *time > start)
+        .collect();
+
+    Some(Statement {
+        name: book.name(key).map(str::to_string),
+        start,
+        end,
+        opening: Value::new(currency, opening),
+        lines,
+        interest: Value::new(currency, interest),
+        other: Value::new(currency, closing - opening - moved - interest),
+        payouts,
+        closing: Value::new(currency, closing),
+    })
+}
+
+/// The value to the minor unit with thousands separated by commas, such
+/// as `-1,016.52 CAD`.
+fn money(value: &Value) -> String {
+    let plain = format!(
+        "{:.*}",
+        value.currency().decimals() as usize,
+        value.amount().abs()
+    );
+    let (whole, fraction) = plain.split_at(plain.find('.').unwrap_or(plain.len()));
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    // Rounding can leave a negative zero, which is shown as zero.
+    let sign = match value.amount() < 0.0 && plain.parse::<f64>() != Ok(0.0) {
+        true => "-",
+        false => "",
+    };
+    format!("{sign}{grouped}{fraction} {}", value.currency())
+}
+
+impl fmt::Display for Statement {
+    /// Aligned columns of date, description and amount, ready for email.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let day = |time: DateTime<Utc>| time.format("%Y-%m-%d").to_string();
+        let mut rows = vec![(
+            day(self.start),
+            "Opening balance".to_string(),
+            money(&self.opening),
+        )];
+        for line in &self.lines {
+            let description = line
+                .memo
+                .clone()
+                .unwrap_or_else(|| format!("{:?}", line.kind));
+            rows.push((day(line.time), description, money(&line.amount)));
+        }
+        rows.push((day(self.end), "Interest".to_string(), money(&self.interest)));
+        if self.other.to_minor_units() != 0 {
+            rows.push((day(self.end), "Other changes".to_string(), money(&self.other)));
+        }
+        rows.push((
+            day(self.end),
+            "Closing balance".to_string(),
+            money(&self.closing),
+        ));
+        let paid_out = self
+            .payouts
+            .iter()
+            .map(|(time, value)| (day(*time), "Paid out".to_string(), money(value)));
+        let payouts: Vec<_> = paid_out.collect();
+
+        let all = || rows.iter().chain(&payouts);
+        let described = all()
+            .map(|(_, d, _)| d.len())
+            .max()
+            .unwrap_or(0)
+            .max("Description".len());
+        let counted = all()
+            .map(|(_, _, a)| a.len())
+            .max()
+            .unwrap_or(0)
+            .max("Amount".len());
+        let row = |f: &mut fmt::Formatter<'_>,
+                   (date, description, amount): &(String, String, String)| {
+            writeln!(
+                f,
+                "{date:<10}  {description:<described$}  {amount:>counted$}"
+            )
+        };
+
+        writeln!(
+            f,
+            "Statement for {}",
+            self.name.as_deref().unwrap_or("unnamed item")
+        )?;
+        writeln!(f, "{} to {}", day(self.start), day(self.end))?;
+        writeln!(f)?;
+        row(
+            f,
+            &(
+                "Date".to_string(),
+                "Description".to_string(),
+                "Amount".to_string(),
+            ),
+        )?;
+        for r in &rows {
+            row(f, r)?;
+        }
+        if !payouts.is_empty() {
+            writeln!(f)?;
+            for r in &payouts {
+                row(f, r)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::{Book, ItemKey},
+        delta::Delta,
+        item::Item,
+        period::Period,
+        value::{Rounding, Value},
+        Assesible,
+    };
+
+    use super::statement;
+
+    #[test]
+    pub fn test_statement_golden() {
+        // The partially paid credit card, 1000 at 20% with 1000 paid after a
+        // month.
+        let inception = Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap();
+        let mut visa = Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.20,
+            TimeDelta::days(365),
+            inception,
+        );
+        visa.push_delta(
+            Delta::payment(
+                Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(),
+                Value::new("CAD", 1_000.0),
+            )
+            .with_memo("Payment, thank you"),
+        );
+        let mut book = Book::default();
+        let key = book.add_named("Visa", visa);
+
+        let start = Utc.with_ymd_and_hms(2008, 1, 15, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2008, 2, 15, 0, 0, 0).unwrap();
+        let statement = statement(&book, key, start, end).unwrap();
+        assert_eq!(
+            statement.to_string(),
+            include_str!("../../tests/fixtures/statement_visa.txt")
+        );
+
+        let item = book.get(key).unwrap();
+        assert_eq!(statement.closing.amount(), item.assess(end).amount());
+        let lines: f64 = statement.lines.iter().map(|l| l.amount.amount()).sum();
+        let total = statement.opening.amount() + lines + statement.interest.amount();
+        assert_value_eq!(Value::new(statement.closing.currency(), total), statement.closing, 1e-9);
+    }
+
+    /// An item rounded to the dollar every month, the cents the rounding
+    /// takes off are not interest.
+    #[test]
+    pub fn test_statement_other_changes() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut savings = Item::builder(Value::new("CAD", 1_000.0), start)
+            .interest(0.01, Period::Months(1))
+            .build();
+        savings.round_each_period = Some((0, Rounding::HalfAwayFromZero));
+        let mut book = Book::default();
+        let key = book.add_named("Savings", savings);
+
+        // 1000 grows to 1010 and then 1020.10, which is rounded to 1020.
+        let statement = statement(&book, key, start, end).unwrap();
+        assert_value_eq!(statement.interest, Value::new("CAD", 20.1), 1e-9);
+        assert_value_eq!(statement.other, Value::new("CAD", -0.1), 1e-9);
+        assert_value_eq!(statement.closing, Value::new("CAD", 1_020.0), 1e-9);
+        assert!(statement.to_string().contains("Other changes"));
+        assert!(super::statement(&book, ItemKey::default(), start, end).is_none());
+    }
+}
@@ -1,60 +1,716 @@
-use chrono::{DateTime, TimeDelta, Utc};
-use super::Assesible;
+use std::{fmt, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use rand_distr::{Distribution, StandardNormal};
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    convert::{ConversionError, ConversionTable, CURRENCY_EXCHANGE},
+    fees::FeeWrapped,
+    inflation::RealTerms,
+    item::{Interest, Item},
+    period::Period,
+    value::{Currency, KahanSum, Value},
+    Assesible,
+};
 
 /// Adds an element of risk to an [Assesible] item,
 /// the exact function of these depends heavily on the
 /// mechanism selected (the enum variant!)
-pub enum Risk<A: Assesible> {
+///
+/// The FX risks borrow the table they convert with for `'t`.
+pub enum Risk<'t, A: Assesible> {
     /// The asset will drop by value by a certain amount.
     CertainLossPercentage { asset: A, percent: f64 },
     /// Loses value as a function of time, starting at some date.
     LosePercentOverTime {
         asset: A,
         percent: f64,
-        period: TimeDelta,
+        period: Period,
         starting: DateTime<Utc>,
     },
+    /// The asset earns a normally distributed return, the deterministic
+    /// assessment uses the mean and sampling draws from the distribution.
+    NormalReturn { asset: A, mean: f64, deviation: f64 },
+    /// The asset only exists between two dates, outside of
+    /// the window it is worth nothing.
+    Windowed {
+        asset: A,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    /// Converts the asset into the `target` currency after shocking the
+    /// exchange rate. A positive shock means the target currency appreciates,
+    /// so the same asset is worth less of it.
+    FxShock {
+        asset: A,
+        target: Currency,
+        shock_percent: f64,
+        table: &'t ConversionTable,
+    },
+    /// Converts the asset into the `target` currency where the exchange rate
+    /// moves by a normally distributed percentage when sampled.
+    FxVolatility {
+        asset: A,
+        target: Currency,
+        deviation: f64,
+        table: &'t ConversionTable,
+    },
+    /// Applies an arbitrary function to the assessed value.
+    Custom {
+        asset: A,
+        function: Box<dyn Fn(DateTime<Utc>, Value) -> Value + Send + Sync>,
+    },
 }
 
-impl<A: Assesible> Assesible for Risk<A> {
-    fn assess(&self, time: chrono::DateTime<chrono::Utc>) -> super::value::Value {
-        match self {
+/// Converts a value with the given table, scaled by `factor`.
+fn convert_shocked(
+    table: &ConversionTable,
+    value: Value,
+    target: Currency,
+    factor: f64,
+) -> Result<Value, ConversionError> {
+    let rate = table.rate(value.currency(), target)?;
+    Ok(Value::new(target, value.amount() * rate * factor))
+}
+
+/// Counts how much of the value remains after decaying by `percent` every
+/// `period` since `starting`, panicking if the period is not positive.
+fn decay_factor(percent: f64, period: Period, starting: DateTime<Utc>, time: DateTime<Utc>) -> f64 {
+    if starting > time {
+        // The value has not started going down yet.
+        return 1.0;
+    }
+    let period = period
+        .validate()
+        .unwrap_or_else(|e| panic!("invalid decay period: {e}"));
+
+    // Count how many periods of interest have passed
+    (1.0 - percent).powf(period.between(starting, time))
+}
+
+impl<A: Assesible> Risk<'_, A> {
+    /// Like [Assesible::assess], failing if an FX risk's table has no rate
+    /// from the asset's currency to its target.
+    pub fn try_assess(&self, time: DateTime<Utc>) -> Result<Value, ConversionError> {
+        Ok(match self {
             Risk::CertainLossPercentage { asset, percent } => asset.assess(time) * *percent,
             Risk::LosePercentOverTime {
                 asset,
                 percent,
                 period,
                 starting,
+            } => asset.assess(time) * decay_factor(*percent, *period, *starting, time),
+            Risk::NormalReturn { asset, mean, .. } => asset.assess(time) * (1.0 + *mean),
+            Risk::Windowed { asset, from, to } => {
+                if *from <= time && time < *to {
+                    asset.assess(time)
+                } else {
+                    Value::new(asset.currency(), 0.0)
+                }
+            }
+            Risk::FxShock {
+                asset,
+                target,
+                shock_percent,
+                table,
+            } => convert_shocked(
+                table,
+                asset.assess(time),
+                *target,
+                1.0 / (1.0 + *shock_percent),
+            )?,
+            Risk::FxVolatility {
+                asset,
+                target,
+                table,
+                ..
+            } => convert_shocked(table, asset.assess(time), *target, 1.0)?,
+            Risk::Custom { asset, function } => function(time, asset.assess(time)),
+        })
+    }
+    /// Like [Assesible::sample], failing the same way as [Risk::try_assess].
+    pub fn try_sample(
+        &self,
+        time: DateTime<Utc>,
+        rng: &mut dyn RngCore,
+    ) -> Result<Value, ConversionError> {
+        Ok(match self {
+            Risk::CertainLossPercentage { asset, percent } => asset.sample(time, rng) * *percent,
+            Risk::LosePercentOverTime {
+                asset,
+                percent,
+                period,
+                starting,
+            } => asset.sample(time, rng) * decay_factor(*percent, *period, *starting, time),
+            Risk::NormalReturn {
+                asset,
+                mean,
+                deviation,
             } => {
-                if *starting > time {
-                    // The interest has not started going down yet.
-                    return asset.assess(time);
+                let noise: f64 = StandardNormal.sample(rng);
+                asset.sample(time, rng) * (1.0 + *mean + *deviation * noise)
+            }
+            Risk::Windowed { asset, from, to } => {
+                if *from <= time && time < *to {
+                    asset.sample(time, rng)
+                } else {
+                    Value::new(asset.currency(), 0.0)
                 }
-
-                // Count how many periods of interest have passed
-                let periods = (time - *starting).num_nanoseconds().unwrap() as f64
-                    / period.num_nanoseconds().unwrap() as f64;
-                
-                // Count the loss multiplier to multiply the underlying value by.
-                let loss_factor = (1.0 - *percent).powf(periods);
-
-                asset.assess(time) * loss_factor
             }
-        }
+            Risk::FxShock {
+                asset,
+                target,
+                shock_percent,
+                table,
+            } => convert_shocked(
+                table,
+                asset.sample(time, rng),
+                *target,
+                1.0 / (1.0 + *shock_percent),
+            )?,
+            Risk::FxVolatility {
+                asset,
+                target,
+                deviation,
+                table,
+            } => {
+                let noise: f64 = StandardNormal.sample(rng);
+                convert_shocked(
+                    table,
+                    asset.sample(time, rng),
+                    *target,
+                    1.0 + *deviation * noise,
+                )?
+            }
+            Risk::Custom { asset, function } => function(time, asset.sample(time, rng)),
+        })
+    }
+}
+
+impl<A: Assesible> Assesible for Risk<'_, A> {
+    /// Panics if an FX risk has no rate, see [Risk::try_assess].
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        self.try_assess(time).unwrap_or_else(|e| panic!("{e}"))
     }
     fn currency(&self) -> super::value::Currency {
         match self {
             Risk::CertainLossPercentage { asset, .. } => asset.currency(),
             Risk::LosePercentOverTime { asset, .. } => asset.currency(),
+            Risk::NormalReturn { asset, .. } => asset.currency(),
+            Risk::Windowed { asset, .. } => asset.currency(),
+            Risk::FxShock { target, .. } => *target,
+            Risk::FxVolatility { target, .. } => *target,
+            Risk::Custom { asset, .. } => asset.currency(),
+        }
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        self.try_sample(time, rng).unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+/// Fluent combinators for wrapping an [Assesible] in risks, so that
+/// nested risks read in the order they are applied.
+pub trait RiskExt: Assesible + Sized {
+    /// Wraps the asset in [Risk::CertainLossPercentage].
+    fn with_certain_loss(self, percent: f64) -> Risk<'static, Self> {
+        Risk::CertainLossPercentage {
+            asset: self,
+            percent,
+        }
+    }
+    /// Wraps the asset in [Risk::LosePercentOverTime], panicking unless the
+    /// period is longer than zero.
+    fn decaying(
+        self,
+        percent: f64,
+        period: impl Into<Period>,
+        starting: DateTime<Utc>,
+    ) -> Risk<'static, Self> {
+        let period = period
+            .into()
+            .validate()
+            .unwrap_or_else(|e| panic!("invalid decay period: {e}"));
+        Risk::LosePercentOverTime {
+            asset: self,
+            percent,
+            period,
+            starting,
+        }
+    }
+    /// Wraps the asset in [Risk::Windowed].
+    fn windowed(self, from: DateTime<Utc>, to: DateTime<Utc>) -> Risk<'static, Self> {
+        Risk::Windowed {
+            asset: self,
+            from,
+            to,
+        }
+    }
+    /// Wraps the asset in [Risk::Custom].
+    fn with_custom<F>(self, function: F) -> Risk<'static, Self>
+    where
+        F: Fn(DateTime<Utc>, Value) -> Value + Send + Sync + 'static,
+    {
+        Risk::Custom {
+            asset: self,
+            function: Box::new(function),
+        }
+    }
+    /// Wraps the asset in [RealTerms] with a constant rate of inflation.
+    fn in_real_terms(self, inflation: Interest, base_date: DateTime<Utc>) -> RealTerms<Self> {
+        RealTerms::new(self, inflation, base_date)
+    }
+    /// Wraps the asset in a [FeeWrapped] charging a yearly fee since the date.
+    fn with_fee(self, annual_fee: f64, since: DateTime<Utc>) -> FeeWrapped<Self> {
+        FeeWrapped::new(self, annual_fee, since)
+    }
+}
+
+impl<A: Assesible> RiskExt for A {}
+
+/// A [Risk] without its asset, so it can be kept on a [Book] against an
+/// item, see [Book::attach_risk]. Each variant mirrors the [Risk] of the
+/// same name.
+///
+/// Saving the book as JSON keeps the variants that are only numbers and
+/// times. FX risks and custom ones refer to a table or a closure, they last
+/// as long as the book in memory and are dropped when it is saved.
+#[derive(Clone)]
+pub enum RiskSpec {
+    CertainLossPercentage {
+        percent: f64,
+    },
+    LosePercentOverTime {
+        percent: f64,
+        period: Period,
+        starting: DateTime<Utc>,
+    },
+    NormalReturn {
+        mean: f64,
+        deviation: f64,
+    },
+    Windowed {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    FxShock {
+        target: Currency,
+        shock_percent: f64,
+        table: Arc<ConversionTable>,
+    },
+    FxVolatility {
+        target: Currency,
+        deviation: f64,
+        table: Arc<ConversionTable>,
+    },
+    Custom {
+        function: Arc<dyn Fn(DateTime<Utc>, Value) -> Value + Send + Sync>,
+    },
+}
+
+impl fmt::Debug for RiskSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskSpec::CertainLossPercentage { percent } => f
+                .debug_struct("CertainLossPercentage")
+                .field("percent", percent)
+                .finish(),
+            RiskSpec::LosePercentOverTime {
+                percent,
+                period,
+                starting,
+            } => f
+                .debug_struct("LosePercentOverTime")
+                .field("percent", percent)
+                .field("period", period)
+                .field("starting", starting)
+                .finish(),
+            RiskSpec::NormalReturn { mean, deviation } => f
+                .debug_struct("NormalReturn")
+                .field("mean", mean)
+                .field("deviation", deviation)
+                .finish(),
+            RiskSpec::Windowed { from, to } => f
+                .debug_struct("Windowed")
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            RiskSpec::FxShock {
+                target,
+                shock_percent,
+                ..
+            } => f
+                .debug_struct("FxShock")
+                .field("target", target)
+                .field("shock_percent", shock_percent)
+                .finish_non_exhaustive(),
+            RiskSpec::FxVolatility {
+                target, deviation, ..
+            } => f
+                .debug_struct("FxVolatility")
+                .field("target", target)
+                .field("deviation", deviation)
+                .finish_non_exhaustive(),
+            RiskSpec::Custom { .. } => f.debug_struct("Custom").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// FX tables and custom functions are only equal to themselves.
+impl PartialEq for RiskSpec {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                RiskSpec::CertainLossPercentage { percent },
+                RiskSpec::CertainLossPercentage { percent: other },
+            ) => percent == other,
+            (
+                RiskSpec::LosePercentOverTime {
+                    percent,
+                    period,
+                    starting,
+                },
+                RiskSpec::LosePercentOverTime {
+                    percent: other_percent,
+                    period: other_period,
+                    starting: other_starting,
+                },
+            ) => percent == other_percent && period == other_period && starting == other_starting,
+            (
+                RiskSpec::NormalReturn { mean, deviation },
+                RiskSpec::NormalReturn {
+                    mean: other_mean,
+                    deviation: other_deviation,
+                },
+            ) => mean == other_mean && deviation == other_deviation,
+            (
+                RiskSpec::Windowed { from, to },
+                RiskSpec::Windowed {
+                    from: other_from,
+                    to: other_to,
+                },
+            ) => from == other_from && to == other_to,
+            (
+                RiskSpec::FxShock {
+                    target,
+                    shock_percent,
+                    table,
+                },
+                RiskSpec::FxShock {
+                    target: other_target,
+                    shock_percent: other_shock,
+                    table: other_table,
+                },
+            ) => {
+                target == other_target
+                    && shock_percent == other_shock
+                    && Arc::ptr_eq(table, other_table)
+            }
+            (
+                RiskSpec::FxVolatility {
+                    target,
+                    deviation,
+                    table,
+                },
+                RiskSpec::FxVolatility {
+                    target: other_target,
+                    deviation: other_deviation,
+                    table: other_table,
+                },
+            ) => {
+                target == other_target
+                    && deviation == other_deviation
+                    && Arc::ptr_eq(table, other_table)
+            }
+            (RiskSpec::Custom { function }, RiskSpec::Custom { function: other }) => {
+                Arc::ptr_eq(function, other)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl RiskSpec {
+    /// The [Risk] this describes around the asset.
+    pub fn wrap<A: Assesible>(&self, asset: A) -> Risk<'_, A> {
+        match self {
+            RiskSpec::CertainLossPercentage { percent } => asset.with_certain_loss(*percent),
+            RiskSpec::LosePercentOverTime {
+                percent,
+                period,
+                starting,
+            } => asset.decaying(*percent, *period, *starting),
+            RiskSpec::NormalReturn { mean, deviation } => Risk::NormalReturn {
+                asset,
+                mean: *mean,
+                deviation: *deviation,
+            },
+            RiskSpec::Windowed { from, to } => asset.windowed(*from, *to),
+            RiskSpec::FxShock {
+                target,
+                shock_percent,
+                table,
+            } => Risk::FxShock {
+                asset,
+                target: *target,
+                shock_percent: *shock_percent,
+                table: table.as_ref(),
+            },
+            RiskSpec::FxVolatility {
+                target,
+                deviation,
+                table,
+            } => Risk::FxVolatility {
+                asset,
+                target: *target,
+                deviation: *deviation,
+                table: table.as_ref(),
+            },
+            RiskSpec::Custom { function } => {
+                let function = function.clone();
+                asset.with_custom(move |time, value| function(time, value))
+            }
+        }
+    }
+}
+
+impl Book {
+    /// Attaches a risk to an item, risks already attached are applied first.
+    /// `false` if the item is not in the book.
+    pub fn attach_risk(&mut self, key: ItemKey, risk: RiskSpec) -> bool {
+        if self.get(key).is_none() {
+            return false;
+        }
+        match self.risks.get_mut(key) {
+            Some(risks) => risks.push(risk.clone()),
+            None => {
+                self.risks.insert(key, vec![risk.clone()]);
+            }
+        }
+        self.audit.push(AuditEntry::RiskAttached { key, risk });
+        true
+    }
+    /// Takes every risk off an item.
+    pub fn detach_risks(&mut self, key: ItemKey) -> Vec<RiskSpec> {
+        let Some(risks) = self.risks.remove(key) else {
+            return vec![];
+        };
+        self.audit.push(AuditEntry::RisksDetached { key });
+        risks
+    }
+    /// The risks attached to an item in the order they apply.
+    pub fn risks(&self, key: ItemKey) -> &[RiskSpec] {
+        self.risks.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+    /// Assesses the book with every item's attached risks applied, in the
+    /// [Book::report_currency]. [Assesible::assess] leaves them out.
+    ///
+    /// Panics if a rate is missing, see [Book::try_assess_risked].
+    pub fn assess_risked(&self, time: DateTime<Utc>) -> Value {
+        self.try_assess_risked(time)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like [Book::assess_risked], failing if an FX risk's table or the
+    /// conversion into the report currency has no rate.
+    pub fn try_assess_risked(&self, time: DateTime<Utc>) -> Result<Value, ConversionError> {
+        let currency = self.currency();
+        let mut sum = KahanSum::default();
+        for (key, item) in self.iter() {
+            self.check_fx_risks(key, item)?;
+            let value = self.risky(key).unwrap().assess(time);
+            sum.add(value.amount() * self.item_report_rate(item, value.currency(), time)?);
+        }
+        Ok(Value::new(currency, sum.total()).rebased(&CURRENCY_EXCHANGE.snapshot()))
+    }
+    /// Checks that each FX risk on the item has a rate from the currency
+    /// handed to it, the item's own or the target of an FX risk before it.
+    fn check_fx_risks(&self, key: ItemKey, item: &Item) -> Result<(), ConversionError> {
+        let mut currency = item.currency();
+        for risk in self.risks(key) {
+            if let RiskSpec::FxShock { target, table, .. }
+            | RiskSpec::FxVolatility { target, table, .. } = risk
+            {
+                table.rate(currency, *target)?;
+                currency = *target;
+            }
+        }
+        Ok(())
+    }
+    /// The item wrapped in its attached risks, `None` if it is not in the
+    /// book.
+    pub fn risky(&self, key: ItemKey) -> Option<Box<dyn Assesible + '_>> {
+        let mut asset: Box<dyn Assesible + '_> = Box::new(self.get(key)?);
+        for risk in self.risks(key) {
+            asset = Box::new(risk.wrap(asset));
         }
+        Some(asset)
     }
 }
 
+/// Errors produced by the risk analysis functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskError {
+    /// The confidence level must lie strictly between zero and one.
+    InvalidConfidence(f64),
+    /// At least one trial is needed to say anything.
+    NoTrials,
+}
+
+impl fmt::Display for RiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskError::InvalidConfidence(c) => {
+                write!(f, "confidence {c} is outside of the open interval (0, 1)")
+            }
+            RiskError::NoTrials => write!(f, "at least one trial is required"),
+        }
+    }
+}
+
+impl std::error::Error for RiskError {}
+
+/// Runs a seeded Monte Carlo simulation of the asset, returning
+/// one sampled assessment per trial.
+pub fn monte_carlo<A: Assesible + ?Sized>(
+    asset: &A,
+    time: DateTime<Utc>,
+    trials: usize,
+    seed: u64,
+) -> Vec<Value> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..trials).map(|_| asset.sample(time, &mut rng)).collect()
+}
+
+/// The result of a Value-at-Risk calculation.
+#[derive(Debug, Clone)]
+pub struct VarReport {
+    /// The confidence level the report was computed at.
+    pub confidence: f64,
+    /// The deterministic assessment the losses are measured against.
+    pub baseline: Value,
+    /// The loss that will not be exceeded with the given confidence.
+    pub var: Value,
+    /// The average loss in the cases where the VaR is exceeded, also
+    /// known as the conditional VaR.
+    pub expected_shortfall: Value,
+}
+
+/// Calculates the Value-at-Risk and the expected shortfall of an asset,
+/// the losses are positive and measured relative to the deterministic
+/// assessment of the asset.
+pub fn var(
+    book: &impl Assesible,
+    time: DateTime<Utc>,
+    confidence: f64,
+    trials: usize,
+    seed: u64,
+) -> Result<VarReport, RiskError> {
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return Err(RiskError::InvalidConfidence(confidence));
+    }
+    if trials == 0 {
+        return Err(RiskError::NoTrials);
+    }
+
+    let baseline = book.assess(time);
+    let mut losses = monte_carlo(book, time, trials, seed)
+        .iter()
+        .map(|v| baseline.amount() - v.amount())
+        .collect::<Vec<_>>();
+    losses.sort_by(|a, b| b.total_cmp(a));
+
+    // The number of outcomes that make up the tail.
+    let tail = (((1.0 - confidence) * trials as f64).ceil() as usize).clamp(1, trials);
+    let cutoff = losses[tail - 1];
+    let shortfall = losses[..tail].iter().sum::<f64>() / tail as f64;
+
+    Ok(VarReport {
+        confidence,
+        var: Value::new(baseline.currency(), cutoff),
+        expected_shortfall: Value::new(baseline.currency(), shortfall),
+        baseline,
+    })
+}
+
+/// The parameter a [sensitivity] analysis varies across its grid.
+pub enum SweepTarget<'a> {
+    /// Wraps the asset in a [Risk::CertainLossPercentage] with the grid value as the percent.
+    CertainLoss,
+    /// Wraps the asset in a [Risk::LosePercentOverTime] with the grid value as the percent.
+    Decay {
+        period: Period,
+        starting: DateTime<Utc>,
+    },
+    /// Rebuilds the asset from scratch for every grid value, this is how the interest
+    /// rate of an [Item] is swept (see [Item::with_interest_rate]). The asset passed
+    /// to [sensitivity] is ignored.
+    Rebuild(Box<dyn Fn(f64) -> Box<dyn Assesible> + 'a>),
+    /// Converts the asset into `target` using the grid value as the exchange rate.
+    FxRate { target: Currency },
+}
+
+/// Re-assesses the asset once per grid value, returning `(grid value, assessment)`
+/// pairs in the order of `values` so they can be plotted directly.
+pub fn sensitivity(
+    asset: &impl Assesible,
+    time: DateTime<Utc>,
+    param: SweepTarget,
+    values: &[f64],
+) -> Vec<(f64, Value)> {
+    values
+        .iter()
+        .map(|&x| {
+            let value = match &param {
+                SweepTarget::CertainLoss => asset.with_certain_loss(x).assess(time),
+                SweepTarget::Decay { period, starting } => {
+                    asset.decaying(x, *period, *starting).assess(time)
+                }
+                SweepTarget::Rebuild(rebuild) => rebuild(x).assess(time),
+                SweepTarget::FxRate { target } => {
+                    Value::new(*target, asset.assess(time).amount() * x)
+                }
+            };
+            (x, value)
+        })
+        .collect()
+}
+
+/// Sweeps the interest rate of an item from 2% below to 2% above its
+/// current rate in 0.25% steps.
+pub fn interest_sensitivity(item: &Item, time: DateTime<Utc>) -> Vec<(f64, Value)> {
+    let base = item.interest_rate(time);
+    let grid = (-8..=8)
+        .map(|step| base + step as f64 * 0.0025)
+        .collect::<Vec<_>>();
+    sensitivity(
+        item,
+        time,
+        SweepTarget::Rebuild(Box::new(|rate| Box::new(item.with_interest_rate(rate)))),
+        &grid,
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use chrono::{TimeDelta, TimeZone, Utc};
 
-    use crate::instruments::{item::Item, risk::Risk, value::Value, Assesible};
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        convert::{ConversionError, ConversionTable, CURRENCY_EXCHANGE},
+        item::Item,
+        period::Period,
+        risk::{
+            interest_sensitivity, monte_carlo, sensitivity, var, Risk, RiskError, RiskExt,
+            RiskSpec, SweepTarget,
+        },
+        value::{Currency, Value},
+        Assesible,
+    };
 
     #[test]
     pub fn test_always_fail() {
@@ -64,13 +720,13 @@ mod tests {
             TimeDelta::days(30),
             Utc::now(),
         );
-        assert_eq!(main.assess(Utc::now()).non_decimal(), 10);
+        assert_value_eq!(main.assess(Utc::now()), Value::new("CAD", 10.0), 0.005);
 
         let risky = Risk::CertainLossPercentage {
             asset: main,
             percent: 0.5,
         };
-        assert_eq!(risky.assess(Utc::now()).non_decimal(), 5);
+        assert_value_eq!(risky.assess(Utc::now()), Value::new("CAD", 5.0), 0.005);
     }
 
     /// Checks that no interest is being applied before start.
@@ -85,7 +741,7 @@ mod tests {
         let family_car = Risk::LosePercentOverTime {
             asset: Item::fixed(Value::dummy("CAD", 50_000.00), interest_start),
             percent: 0.10,
-            period: TimeDelta::days(365),
+            period: TimeDelta::days(365).into(),
             starting: interest_start,
         };
 
@@ -107,12 +763,284 @@ mod tests {
         let family_car = Risk::LosePercentOverTime {
             asset: Item::fixed(Value::dummy("CAD", 50_000.00), purchase_date),
             percent: 0.10,
-            period: TimeDelta::days(365),
+            period: TimeDelta::days(365).into(),
             starting: purchase_date,
         };
 
         // Should have devaluated to $32,795. Don't forget that 2000 is a leap year,
         // which is taken into account.
-        assert_eq!(family_car.assess(inspect_date).non_decimal(), 32795);
+        assert_value_eq!(family_car.assess(inspect_date), Value::new("CAD", 32_795.53), 0.005);
+
+        // Calendar years ignore the extra day, exactly four years have passed.
+        let family_car = Risk::LosePercentOverTime {
+            asset: Item::fixed(Value::dummy("CAD", 50_000.00), purchase_date),
+            percent: 0.10,
+            period: Period::Years(1),
+            starting: purchase_date,
+        };
+        assert_value_eq!(family_car.assess(inspect_date), Value::new("CAD", 32_805.0), 0.005);
+    }
+
+    /// A normally distributed return has an analytic VaR and expected shortfall
+    /// which the simulation should reproduce.
+    #[test]
+    pub fn test_var_normal_returns() {
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let portfolio = Risk::NormalReturn {
+            asset: Item::fixed(Value::dummy("CAD", 100_000.0), now),
+            mean: 0.05,
+            deviation: 0.20,
+        };
+
+        let report = var(&portfolio, now, 0.95, 200_000, 42).unwrap();
+        assert_value_eq!(report.baseline, Value::new("CAD", 105_000.0), 0.005);
+
+        // z(0.95) = 1.6449, phi(z) / 0.05 = 2.0627
+        let analytic_var = 1.6449 * 0.20 * 100_000.0;
+        let analytic_es = 2.0627 * 0.20 * 100_000.0;
+        let cad = |amount| Value::new("CAD", amount);
+        assert_value_eq!(report.var, cad(analytic_var), analytic_var * 0.02);
+        assert_value_eq!(report.expected_shortfall, cad(analytic_es), analytic_es * 0.02);
+    }
+
+    #[test]
+    pub fn test_var_deterministic_book() {
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::dummy("CAD", 1_000.0), now));
+        book.add(Item::basic_debt(
+            Value::dummy("CAD", -500.0),
+            0.2,
+            TimeDelta::days(365),
+            now,
+        ));
+
+        let report = var(&book, now + TimeDelta::days(400), 0.99, 1_000, 7).unwrap();
+        assert_eq!(report.var.amount(), 0.0);
+        assert_eq!(report.expected_shortfall.amount(), 0.0);
+    }
+
+    #[test]
+    pub fn test_var_rejects_confidence() {
+        let now = Utc::now();
+        let item = Item::fixed(Value::dummy("CAD", 10.0), now);
+        assert_eq!(
+            var(&item, now, 1.0, 10, 0).unwrap_err(),
+            RiskError::InvalidConfidence(1.0)
+        );
+        assert_eq!(
+            var(&item, now, 0.0, 10, 0).unwrap_err(),
+            RiskError::InvalidConfidence(0.0)
+        );
+    }
+
+    #[test]
+    pub fn test_chained_risks() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let inspect = Utc.with_ymd_and_hms(2006, 6, 1, 0, 0, 0).unwrap();
+        let year = TimeDelta::days(365);
+
+        let nested = Risk::CertainLossPercentage {
+            asset: Risk::LosePercentOverTime {
+                asset: Item::fixed(Value::dummy("CAD", 50_000.0), start),
+                percent: 0.1,
+                period: year.into(),
+                starting: start,
+            },
+            percent: 0.2,
+        };
+        let chained = Item::fixed(Value::dummy("CAD", 50_000.0), start)
+            .decaying(0.1, year, start)
+            .with_certain_loss(0.2);
+
+        assert_eq!(
+            nested.assess(inspect).amount(),
+            chained.assess(inspect).amount()
+        );
+    }
+
+    #[test]
+    pub fn test_windowed_and_custom() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+
+        let lease = Item::fixed(Value::dummy("CAD", 100.0), start).windowed(start, end);
+        assert_value_eq!(lease.assess(start), Value::new("CAD", 100.0), 0.005);
+        assert_value_eq!(lease.assess(end), Value::new("CAD", 0.0), 0.005);
+
+        let doubled = Item::fixed(Value::dummy("CAD", 100.0), start).with_custom(|_, v| v * 2.0);
+        assert_value_eq!(doubled.assess(start), Value::new("CAD", 200.0), 0.005);
+    }
+
+    #[test]
+    pub fn test_fx_shock() {
+        let table = ConversionTable::new();
+        table.add_conversion("CAD", "COP", 2911.98);
+
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let unshocked = Risk::FxShock {
+            asset: Item::fixed(Value::dummy("COP", 600_000.0), now),
+            target: Currency::new("CAD"),
+            shock_percent: 0.0,
+            table: &table,
+        };
+        let shocked = Risk::FxShock {
+            asset: Item::fixed(Value::dummy("COP", 600_000.0), now),
+            target: Currency::new("CAD"),
+            shock_percent: 0.10,
+            table: &table,
+        };
+
+        // A 10% appreciation of the CAD leaves the COP asset worth 1/1.1 as much.
+        assert_value_eq!(unshocked.assess(now), Value::new("CAD", 600_000.0 / 2911.98), 1e-9);
+        assert_value_eq!(shocked.assess(now), unshocked.assess(now) * (1.0 / 1.1), 1e-9);
+        assert_eq!(shocked.currency(), Currency::new("CAD"));
+    }
+
+    #[test]
+    pub fn test_fx_volatility_is_seeded() {
+        let table = ConversionTable::new();
+        table.add_conversion("CAD", "COP", 2911.98);
+
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let asset = Risk::FxVolatility {
+            asset: Item::fixed(Value::dummy("COP", 600_000.0), now),
+            target: Currency::new("CAD"),
+            deviation: 0.1,
+            table: &table,
+        };
+
+        let first = monte_carlo(&asset, now, 100, 3);
+        let second = monte_carlo(&asset, now, 100, 3);
+        assert!(first
+            .iter()
+            .zip(&second)
+            .all(|(a, b)| a.amount() == b.amount()));
+        assert!(first
+            .iter()
+            .any(|v| v.amount() != asset.assess(now).amount()));
+    }
+
+    #[test]
+    pub fn test_fx_shock_without_rate() {
+        let table = ConversionTable::new();
+        let now = Utc::now();
+        let risky = Risk::FxShock {
+            asset: Item::fixed(Value::dummy("COP", 1.0), now),
+            target: Currency::new("USD"),
+            shock_percent: 0.1,
+            table: &table,
+        };
+        assert_eq!(
+            risky.try_assess(now).unwrap_err(),
+            ConversionError::NoRate {
+                from: Currency::new("COP"),
+                to: Currency::new("USD"),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid decay period: periods must be longer than zero")]
+    pub fn test_zero_decay_period() {
+        let now = Utc::now();
+        Risk::LosePercentOverTime {
+            asset: Item::fixed(Value::dummy("CAD", 1.0), now),
+            percent: 0.1,
+            period: Period::Months(0),
+            starting: now,
+        }
+        .assess(now + TimeDelta::days(40));
+    }
+
+    #[test]
+    pub fn test_interest_sensitivity() {
+        let start = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let loan = Item::basic_debt(
+            Value::dummy("CAD", -10_000.0),
+            0.05,
+            TimeDelta::days(365),
+            start,
+        );
+
+        let sweep = interest_sensitivity(&loan, later);
+        assert_eq!(sweep.len(), 17);
+        assert!((sweep[0].0 - 0.03).abs() < 1e-12);
+        assert!((sweep[16].0 - 0.07).abs() < 1e-12);
+
+        // Higher rates always make the loan more negative.
+        assert!(sweep.windows(2).all(|w| w[1].1.amount() < w[0].1.amount()));
+
+        let years = (later - start).num_days() as f64 / 365.0;
+        let owed = |rate: f64| Value::new("CAD", -10_000.0 * rate.powf(years));
+        assert_value_eq!(sweep[0].1, owed(1.03), 1e-6);
+        assert_value_eq!(sweep[16].1, owed(1.07), 1e-6);
+    }
+
+    #[test]
+    pub fn test_risk_and_fx_sensitivity() {
+        let now = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let house = Item::fixed(Value::dummy("CAD", 100.0), now);
+
+        let losses = sensitivity(&house, now, SweepTarget::CertainLoss, &[0.25, 0.5]);
+        assert_value_eq!(losses[0].1, Value::new("CAD", 25.0), 0.005);
+        assert_value_eq!(losses[1].1, Value::new("CAD", 50.0), 0.005);
+
+        let fx = sensitivity(
+            &house,
+            now,
+            SweepTarget::FxRate {
+                target: Currency::new("USD"),
+            },
+            &[0.7],
+        );
+        assert_value_eq!(fx[0].1, Value::new("USD", 70.0), 0.005);
+        assert_eq!(fx[0].1.currency(), Currency::new("USD"));
+    }
+
+    /// Risks attached to an item compose in order and only count towards
+    /// [Book::assess_risked].
+    #[test]
+    pub fn test_assess_risked() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let later = Period::Years(1).step(start, 2);
+        let mut book = Book::default();
+        let house = book.add(Item::fixed(Value::new("CAD", 100_000.0), start));
+        book.add(Item::fixed(Value::new("CAD", 1_000.0), start));
+        assert_eq!(book.assess_risked(later).amount(), 101_000.0);
+
+        assert!(book.attach_risk(
+            house,
+            RiskSpec::LosePercentOverTime {
+                percent: 0.1,
+                period: Period::Years(1),
+                starting: start,
+            }
+        ));
+        assert!(book.attach_risk(
+            house,
+            RiskSpec::Custom {
+                function: Arc::new(|_, value| Value::new(value.currency(), value.amount() + 500.0)),
+            }
+        ));
+        assert_value_eq!(book.assess_risked(later), Value::new("CAD", 82_500.0), 1e-6);
+        assert_eq!(book.assess(later).amount(), 101_000.0);
+        assert_eq!(book.risks(house).len(), 2);
+
+        // The shocked house is reported back in CAD at the market rate.
+        CURRENCY_EXCHANGE.add_conversion("RKU", "CAD", 1.25);
+        let table = Arc::new(ConversionTable::new());
+        let shock = RiskSpec::FxShock {
+            target: Currency::new("RKU"),
+            shock_percent: 0.1,
+            table: table.clone(),
+        };
+        assert!(book.attach_risk(house, shock.clone()));
+        assert_eq!(book.risks(house)[2], shock);
+        assert!(book.try_assess_risked(later).is_err());
+        table.add_conversion("CAD", "RKU", 0.8);
+        let shocked = Value::new("CAD", 81_500.0 / 1.1 + 1_000.0);
+        assert_value_eq!(book.assess_risked(later), shocked, 1e-6);
     }
 }
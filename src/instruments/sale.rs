@@ -0,0 +1,243 @@
+//! Selling part of an item and the cost basis of what is left, tracked
+//! with the average cost method.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    delta::{Delta, DeltaAmount, DeltaId, DeltaKind, DeltaStatus},
+    item::Item,
+    value::Value,
+    Assesible,
+};
+
+/// How much of an item a sale is for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaleSize {
+    /// A fraction of the item, `0.5` sells half.
+    Fraction(f64),
+    /// Some of the units held, see [Item::units_at].
+    Units(f64),
+}
+
+/// Part of an item sold by [Item::sell].
+#[derive(Clone, Debug)]
+pub struct Sale {
+    /// The delta that took the part sold off the item.
+    pub delta: DeltaId,
+    pub time: DateTime<Utc>,
+    /// The fraction of the item sold.
+    pub fraction: f64,
+    /// What the sale brought in, received as cash.
+    pub proceeds: Value,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SaleError {
+    /// The fraction is not above zero and at most one.
+    InvalidFraction(f64),
+    /// The sale is in units but the item does not track any.
+    NoUnits,
+    /// More units were sold than were held at the time.
+    TooManyUnits { held: f64, sold: f64 },
+}
+
+impl fmt::Display for SaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaleError::InvalidFraction(fraction) => {
+                write!(f, "cannot sell a fraction of {fraction} of an item")
+            }
+            SaleError::NoUnits => write!(f, "the item does not track units"),
+            SaleError::TooManyUnits { held, sold } => {
+                write!(f, "cannot sell {sold} units, only {held} are held")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaleError {}
+
+impl Item {
+    /// Buys `units` more of the item for `cost`, which is added to the
+    /// balance and the cost basis.
+    pub fn buy(&mut self, time: DateTime<Utc>, units: f64, cost: Value) -> DeltaId {
+        let mut delta = Delta::payment(time, cost);
+        delta.units = Some(units);
+        let id = delta.id;
+        self.push_delta(delta);
+        id
+    }
+    /// The units held at the time, `None` if the item does not track units.
+    pub fn units_at(&self, time: DateTime<Utc>) -> Option<f64> {
+        self.deltas
+            .iter()
+            .filter(|d| d.time <= time && d.status == DeltaStatus::Actual)
+            .filter_map(|d| d.units)
+            .fold(self.units, |held, units| Some(held.unwrap_or(0.0) + units))
+    }
+    /// Sells part of the item at the time. The balance drops by the fraction
+    /// sold and the proceeds are received as cash, see [Item::assess_full].
+    /// The gain is the proceeds less the same fraction of the cost basis,
+    /// see [Item::realized_gains].
+    pub fn sell(
+        &mut self,
+        time: DateTime<Utc>,
+        size: SaleSize,
+        proceeds: Value,
+    ) -> Result<DeltaId, SaleError> {
+        let (fraction, units) = match size {
+            SaleSize::Fraction(fraction) => {
+                (fraction, self.units_at(time).map(|held| -held * fraction))
+            }
+            SaleSize::Units(sold) => {
+                let held = self.units_at(time).ok_or(SaleError::NoUnits)?;
+                if sold > held {
+                    return Err(SaleError::TooManyUnits { held, sold });
+                }
+                (sold / held, Some(-sold))
+            }
+        };
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(SaleError::InvalidFraction(fraction));
+        }
+        let mut delta = Delta::new(time, DeltaAmount::Percent(-fraction), DeltaKind::Adjustment)
+            .with_memo("Sale");
+        delta.units = units;
+        let id = delta.id;
+        self.push_delta(delta);
+        self.sales.push(Sale {
+            delta: id,
+            time,
+            fraction,
+            proceeds,
+        });
+        Ok(id)
+    }
+    /// Walks the deltas keeping the average cost basis. The book value and
+    /// every payment in are bought, each sale takes its fraction of the
+    /// basis. Gives the basis left and what each sale took.
+    fn basis_walk(&self) -> (f64, Vec<(&Sale, f64)>) {
+        let mut basis = self.book_value.amount();
+        let mut sold = vec![];
+        for delta in self
+            .deltas
+            .iter()
+            .filter(|d| d.status == DeltaStatus::Actual)
+        {
+            if let Some(sale) = self.sales.iter().find(|s| s.delta == delta.id) {
+                let taken = basis * sale.fraction;
+                basis -= taken;
+                sold.push((sale, taken));
+            } else if let (DeltaKind::Payment, DeltaAmount::Fixed(cost)) =
+                (delta.kind, &delta.amount)
+            {
+                let rate = match delta.rate {
+                    Some(rate) => rate,
+                    None => self
+                        .conversion_rate(cost.currency(), delta.time)
+                        .unwrap_or_else(|e| panic!("{e}")),
+                };
+                if cost.amount() > 0.0 {
+                    basis += cost.amount() * rate;
+                }
+            }
+        }
+        (basis, sold)
+    }
+    /// What is left of what was paid for the item after every sale, in the
+    /// item's currency.
+    pub fn cost_basis(&self) -> Value {
+        Value::new(self.currency(), self.basis_walk().0)
+    }
+    /// The proceeds of the sales in `(from, to]` less the cost basis they
+    /// sold, in the item's currency converted at the date of each sale.
+    pub fn realized_gains(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Value {
+        let gains: f64 = self
+            .basis_walk()
+            .1
+            .into_iter()
+            .filter(|(sale, _)| from < sale.time && sale.time <= to)
+            .map(|(sale, taken)| {
+                let rate = self
+                    .conversion_rate(sale.proceeds.currency(), sale.time)
+                    .unwrap_or_else(|e| panic!("{e}"));
+                sale.proceeds.amount() * rate - taken
+            })
+            .sum();
+        Value::new(self.currency(), gains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{item::Item, value::Value, Assesible};
+
+    use super::{SaleError, SaleSize};
+
+    /// Three buys then two sales, checked against an average cost sheet:
+    ///
+    /// | step            | units | cost  | basis | avg   | gain |
+    /// |-----------------|-------|-------|-------|-------|------|
+    /// | buy 100 @ 10    | 100   | 1000  | 1000  | 10    |      |
+    /// | buy 50 @ 16     | 150   | 800   | 1800  | 12    |      |
+    /// | buy 50 @ 13     | 200   | 650   | 2450  | 12.25 |      |
+    /// | sell 80 @ 15    | 120   | -980  | 1470  | 12.25 | 220  |
+    /// | buy 30 @ 14     | 150   | 420   | 1890  | 12.6  |      |
+    /// | sell half @ 12  | 75    | -945  | 945   | 12.6  | -45  |
+    #[test]
+    pub fn test_average_cost() {
+        let on = |m| Utc.with_ymd_and_hms(2024, m, 1, 0, 0, 0).unwrap();
+        let cad = |amount| Value::new("CAD", amount);
+        let mut shares = Item::fixed(cad(1_000.0), on(1));
+        shares.units = Some(100.0);
+        shares.buy(on(2), 50.0, cad(800.0));
+        shares.buy(on(3), 50.0, cad(650.0));
+        assert_eq!(shares.units_at(on(3)), Some(200.0));
+        assert_value_eq!(shares.cost_basis(), cad(2_450.0), 1e-9);
+
+        shares
+            .sell(on(4), SaleSize::Units(80.0), cad(1_200.0))
+            .unwrap();
+        assert_eq!(shares.units_at(on(4)), Some(120.0));
+        assert_value_eq!(shares.cost_basis(), cad(1_470.0), 1e-9);
+        assert_value_eq!(shares.realized_gains(on(1), on(4)), cad(220.0), 1e-9);
+
+        shares.buy(on(5), 30.0, cad(420.0));
+        shares
+            .sell(on(6), SaleSize::Fraction(0.5), cad(900.0))
+            .unwrap();
+        assert!((shares.units_at(on(6)).unwrap() - 75.0).abs() < 1e-9);
+        assert_value_eq!(shares.cost_basis(), cad(945.0), 1e-9);
+        assert_value_eq!(shares.realized_gains(on(4), on(6)), cad(-45.0), 1e-9);
+        assert_value_eq!(shares.realized_gains(on(1), on(12)), cad(175.0), 1e-9);
+
+        // The balance follows the basis since the price never moved, and
+        // the proceeds are cash.
+        assert_value_eq!(shares.assess(on(12)), cad(945.0), 1e-9);
+        let full = shares.assess_full(on(12));
+        assert_value_eq!(full.cash().clone(), cad(2_100.0), 1e-9);
+        assert_value_eq!(shares.assess_full(on(5)).cash().clone(), cad(1_200.0), 1e-9);
+
+        assert_eq!(
+            shares.sell(on(7), SaleSize::Units(100.0), cad(1.0)),
+            Err(SaleError::TooManyUnits {
+                held: 75.0,
+                sold: 100.0
+            })
+        );
+        assert_eq!(
+            shares.sell(on(7), SaleSize::Fraction(1.5), cad(1.0)),
+            Err(SaleError::InvalidFraction(1.5))
+        );
+        let mut house = Item::fixed(cad(500_000.0), on(1));
+        assert_eq!(
+            house.sell(on(2), SaleSize::Units(1.0), cad(1.0)),
+            Err(SaleError::NoUnits)
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Summary statistics of how a [Book]'s net worth moved over time.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{book::Book, period::Period, value::Value};
+
+/// The length of an average year, CAGR is taken over these.
+const YEAR_SECONDS: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+
+/// Statistics of a net worth series, see [Book::stats].
+#[derive(Clone, Debug)]
+pub struct BookStats {
+    pub start: Value,
+    pub end: Value,
+    /// The compound annual growth rate, `None` when the series starts at or
+    /// below zero, ends below zero, or spans no time, as there is no real
+    /// rate then.
+    pub cagr: Option<f64>,
+    /// The largest fall from a peak to a later trough.
+    pub max_drawdown: Value,
+    /// The largest fall as a fraction of its peak, `None` if the peak was not
+    /// positive.
+    pub max_drawdown_percent: Option<f64>,
+    /// The longest time from a peak to being back at it, drawdowns not
+    /// recovered from by the end of the series are not counted.
+    pub longest_recovery: Option<TimeDelta>,
+    /// The sample standard deviation of the return from one point to the
+    /// next, `None` with fewer than two returns or when a point is at or
+    /// below zero.
+    pub volatility: Option<f64>,
+}
+
+impl BookStats {
+    /// Computes the statistics of a series such as [Book::series] returns,
+    /// every value is taken to be in the same currency. `None` if the series
+    /// is empty.
+    pub fn of(series: &[(DateTime<Utc>, Value)]) -> Option<Self> {
+        let (first, last) = (series.first()?, series.last()?);
+        let currency = first.1.currency();
+
+        let (start, end) = (first.1.amount(), last.1.amount());
+        let years = (last.0 - first.0).num_seconds() as f64 / YEAR_SECONDS;
+        let cagr = (start > 0.0 && end >= 0.0 && years > 0.0)
+            .then(|| (end / start).powf(1.0 / years) - 1.0);
+
+        let mut peak = first;
+        let (mut drawdown, mut drawdown_peak) = (0.0, start);
+        let mut longest_recovery: Option<TimeDelta> = None;
+        let mut underwater = false;
+        for point in series {
+            let amount = point.1.amount();
+            if amount >= peak.1.amount() {
+                if underwater {
+                    let took = point.0 - peak.0;
+                    longest_recovery = Some(longest_recovery.map_or(took, |l| l.max(took)));
+                    underwater = false;
+                }
+                peak = point;
+                continue;
+            }
+            underwater = true;
+            if peak.1.amount() - amount > drawdown {
+                drawdown = peak.1.amount() - amount;
+                drawdown_peak = peak.1.amount();
+            }
+        }
+
+        let returns: Option<Vec<f64>> = series
+            .windows(2)
+            .map(|w| {
+                let (before, after) = (w[0].1.amount(), w[1].1.amount());
+                (before > 0.0 && after > 0.0).then(|| after / before - 1.0)
+            })
+            .collect();
+        let volatility = returns.filter(|r| r.len() >= 2).map(|returns| {
+            let n = returns.len() as f64;
+            let mean = returns.iter().sum::<f64>() / n;
+            let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            variance.sqrt()
+        });
+
+        Some(Self {
+            start: first.1.clone(),
+            end: last.1.clone(),
+            cagr,
+            max_drawdown: Value::new(currency, drawdown),
+            max_drawdown_percent: (drawdown_peak > 0.0).then(|| drawdown / drawdown_peak),
+            longest_recovery,
+            volatility,
+        })
+    }
+}
+
+impl Book {
+    /// Statistics of the book's net worth from `from` to `to` assessed every
+    /// `step`, in the [Book::report_currency].
+    pub fn stats(&self, from: DateTime<Utc>, to: DateTime<Utc>, step: Period) -> BookStats {
+        BookStats::of(&self.series(from, to, step)).expect("a series always has its first point")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{book::Book, item::Item, period::Period, value::Value};
+
+    /// Net worth of 100k growing 10% a year over four years, by way of a
+    /// 20% fall in the second year that takes two years to recover from.
+    #[test]
+    pub fn test_engineered_drawdown() {
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(
+            Item::builder(Value::new("CAD", 100_000.0), on(2020))
+                .payment(on(2021), Value::new("CAD", 25_000.0))
+                .payment(on(2022), Value::new("CAD", -25_000.0))
+                .payment(on(2023), Value::new("CAD", 25_000.0))
+                .payment(on(2024), Value::new("CAD", 21_410.0))
+                .build(),
+        );
+
+        let stats = book.stats(on(2020), on(2024), Period::Years(1));
+        assert!((stats.cagr.unwrap() - 0.1).abs() < 1e-12);
+        assert_eq!(stats.max_drawdown.amount(), 25_000.0);
+        assert_eq!(stats.max_drawdown_percent, Some(0.2));
+        assert_eq!(stats.longest_recovery, Some(TimeDelta::days(730)));
+
+        let returns = [0.25, -0.2, 0.25, 0.17128];
+        let mean = returns.iter().sum::<f64>() / 4.0;
+        let expected = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / 3.0).sqrt();
+        assert!((stats.volatility.unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    pub fn test_negative_net_worth() {
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(
+            Item::builder(Value::new("CAD", -5_000.0), on(2020))
+                .payment(on(2022), Value::new("CAD", 10_000.0))
+                .build(),
+        );
+
+        let stats = book.stats(on(2020), on(2024), Period::Years(1));
+        assert_eq!(stats.cagr, None);
+        assert_eq!(stats.volatility, None);
+        assert_eq!(stats.max_drawdown.amount(), 0.0);
+        assert_eq!(stats.longest_recovery, None);
+    }
+}
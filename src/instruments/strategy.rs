@@ -0,0 +1,1084 @@
+//! Plans for paying down debts and raising cash.
+
+use std::{collections::HashMap, fmt};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{
+    book::{Book, ItemKey},
+    convert::{ConversionError, CURRENCY_EXCHANGE},
+    delta::Delta,
+    item::{Interest, Item, Liquidity, MinPayment},
+    period::Period,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// The share of the balance paid each month on top of the interest, as a
+/// minimum payment of debts without their own [MinPayment]. This is how
+/// most Canadian card issuers set it.
+const MINIMUM_PRINCIPAL: f64 = 0.01;
+
+/// Plans longer than this are assumed to never finish.
+const MAX_MONTHS: i64 = 1200;
+
+/// Payoffs that take more payments than this are assumed to never finish.
+const MAX_PAYMENTS: u32 = 100_000;
+
+/// Which debt the money left after minimum payments goes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// The smallest balance first.
+    Snowball,
+    /// The highest yearly rate first.
+    Avalanche,
+}
+
+/// What was paid towards each debt in a month.
+#[derive(Clone, Debug)]
+pub struct MonthAllocation {
+    pub time: DateTime<Utc>,
+    /// Payments in the order of the debts passed to [payoff_plan].
+    pub payments: Vec<(ItemKey, Value)>,
+}
+
+/// The outcome of paying down debts with a monthly budget.
+#[derive(Clone, Debug)]
+pub struct PayoffPlan {
+    pub months: Vec<MonthAllocation>,
+    /// When each debt reaches zero, `None` if the budget never clears it.
+    pub payoff: Vec<(ItemKey, Option<DateTime<Utc>>)>,
+    /// The interest accrued across all debts until they were paid off.
+    pub total_interest: Value,
+}
+
+impl PayoffPlan {
+    /// When the last of the debts is paid off.
+    pub fn debt_free(&self) -> Option<DateTime<Utc>> {
+        self.payoff
+            .iter()
+            .map(|(_, time)| *time)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+}
+
+struct Debt<'a> {
+    key: ItemKey,
+    owed: f64,
+    interest: Option<&'a Interest>,
+    minimum: Option<&'a MinPayment>,
+    yearly: f64,
+}
+
+/// Pays `monthly_budget` towards the debts every month after `start`. Every
+/// debt first gets its minimum payment, its [Item::minimum_payment] or else
+/// the interest accrued that month plus one percent of the balance but at
+/// least one minor unit of the budget's currency, and what is left goes to
+/// the debt chosen by the strategy, moving on to the next once it is
+/// cleared. Less than half a minor unit left owing counts as cleared.
+///
+/// Debts are the negative balances of the items, interest accrues following
+/// each item's [Interest] and everything is planned in the budget's currency.
+pub fn payoff_plan(
+    book: &Book,
+    debts: &[ItemKey],
+    monthly_budget: Value,
+    start: DateTime<Utc>,
+    strategy: Strategy,
+) -> PayoffPlan {
+    let currency = monthly_budget.currency();
+    let minor_unit = 10f64.powi(-(currency.decimals() as i32));
+    let month = Period::Months(1);
+    let mut debts = debts
+        .iter()
+        .map(|&key| {
+            let item = book.get(key).expect("debt is not in the book");
+            let rate = CURRENCY_EXCHANGE
+                .rate(item.currency(), currency)
+                .unwrap_or_else(|e| panic!("{e}"));
+            Debt {
+                key,
+                owed: (-item.assess(start).amount() * rate).max(0.0),
+                interest: item.interest.as_ref(),
+                minimum: item.minimum_payment.as_ref(),
+                yearly: item.interest.as_ref().map_or(0.0, |i| {
+                    i.factor(start, Period::Years(1).step(start, 1)) - 1.0
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut payoff = debts
+        .iter()
+        .map(|d| (d.key, (d.owed == 0.0).then_some(start)))
+        .collect::<Vec<_>>();
+    let mut months = vec![];
+    let mut total_interest = 0.0;
+
+    for count in 0..MAX_MONTHS {
+        if debts.iter().all(|d| d.owed == 0.0) {
+            break;
+        }
+        let (from, time) = (month.step(start, count), month.step(start, count + 1));
+        let mut budget = monthly_budget.amount();
+        let mut paid = vec![0.0; debts.len()];
+
+        for (debt, paid) in debts.iter_mut().zip(&mut paid) {
+            let accrued = debt
+                .interest
+                .map_or(0.0, |i| debt.owed * (i.factor(from, time) - 1.0));
+            debt.owed += accrued;
+            total_interest += accrued;
+            let minimum = match debt.minimum {
+                Some(minimum) => minimum.amount(debt.owed, currency),
+                None => (accrued + debt.owed * MINIMUM_PRINCIPAL).max(minor_unit).min(debt.owed),
+            }
+            .min(budget);
+            debt.owed -= minimum;
+            budget -= minimum;
+            *paid += minimum;
+        }
+
+        let mut order = (0..debts.len())
+            .filter(|&i| debts[i].owed > 0.0)
+            .collect::<Vec<_>>();
+        match strategy {
+            Strategy::Snowball => order.sort_by(|&a, &b| debts[a].owed.total_cmp(&debts[b].owed)),
+            Strategy::Avalanche => order.sort_by(|&a, &b| {
+                debts[b]
+                    .yearly
+                    .total_cmp(&debts[a].yearly)
+                    .then(debts[a].owed.total_cmp(&debts[b].owed))
+            }),
+        }
+        for i in order {
+            let payment = debts[i].owed.min(budget);
+            debts[i].owed -= payment;
+            budget -= payment;
+            paid[i] += payment;
+        }
+
+        for (debt, (_, cleared)) in debts.iter_mut().zip(&mut payoff) {
+            // Leftover fractions of a minor unit are rounding, not debt.
+            if debt.owed < minor_unit / 2.0 && cleared.is_none() {
+                debt.owed = 0.0;
+                *cleared = Some(time);
+            }
+        }
+        months.push(MonthAllocation {
+            time,
+            payments: debts
+                .iter()
+                .zip(paid)
+                .map(|(d, paid)| (d.key, Value::new(currency, paid)))
+                .collect(),
+        });
+    }
+
+    PayoffPlan {
+        months,
+        payoff,
+        total_interest: Value::new(currency, total_interest),
+    }
+}
+
+/// How refinancing an item compares to leaving it be.
+#[derive(Clone, Debug)]
+pub struct RefinanceReport {
+    /// The refinanced item, the remaining balance plus the penalty at the new rate.
+    pub refinanced: Item,
+    /// The assessed value of the original and refinanced items at the horizon.
+    pub original_value: Value,
+    pub refinanced_value: Value,
+    /// The interest charged from the refinance date to the horizon,
+    /// positive for debts.
+    pub original_interest: Value,
+    pub refinanced_interest: Value,
+    /// When the refinanced item is first worth at least the original, that
+    /// is when the interest saved has made up for the penalty.
+    pub break_even: Option<DateTime<Utc>>,
+}
+
+impl RefinanceReport {
+    /// How much better off refinancing leaves you at the horizon, negative
+    /// if it costs more than it saves.
+    pub fn difference(&self) -> Value {
+        self.refinanced_value.clone() + self.original_value.negate()
+    }
+}
+
+/// The interest charged on the item between the two times, that is the
+/// change in value that did not come from deltas. Positive for debts.
+fn interest_charged(item: &Item, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    let deltas = item
+        .delta_amounts()
+        .into_iter()
+        .filter(|(d, _)| from < d.time && d.time <= to)
+        .map(|(_, change)| change.amount())
+        .sum::<f64>();
+    -(item.assess(to).amount() - item.assess(from).amount() - deltas)
+}
+
+/// Compares keeping `original` to refinancing it at `new_rate` on
+/// `refinance_date` for a `penalty`. The refinanced item starts at the
+/// refinance date with the balance owed then, the penalty is added to it as
+/// a fee and every later delta carries over.
+pub fn refinance(
+    original: &Item,
+    new_rate: Interest,
+    penalty: Value,
+    refinance_date: DateTime<Utc>,
+    horizon: DateTime<Utc>,
+) -> RefinanceReport {
+    assert!(
+        refinance_date >= original.inception,
+        "refinancing before the item exists"
+    );
+    let mut refinanced = Item::fixed(original.assess(refinance_date), refinance_date);
+    refinanced.interest = Some(new_rate);
+    refinanced.push_delta(Delta::fee(refinance_date, penalty.negate()));
+    refinanced.import_deltas(
+        original
+            .deltas
+            .iter()
+            .filter(|d| d.time > refinance_date)
+            .cloned(),
+    );
+
+    let gap = |time| refinanced.assess(time).amount() - original.assess(time).amount();
+    let break_even = (gap(horizon) >= 0.0).then(|| {
+        // Bisect to the second, the refinanced item starts behind by the penalty.
+        let (mut low, mut high) = (refinance_date, horizon);
+        while high - low > TimeDelta::seconds(1) {
+            let mid = low + (high - low) / 2;
+            if gap(mid) >= 0.0 {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        high
+    });
+
+    let currency = original.currency();
+    RefinanceReport {
+        original_value: original.assess(horizon),
+        refinanced_value: refinanced.assess(horizon),
+        original_interest: Value::new(
+            currency,
+            interest_charged(original, refinance_date, horizon),
+        ),
+        refinanced_interest: Value::new(
+            currency,
+            interest_charged(&refinanced, refinance_date, horizon),
+        ),
+        break_even,
+        refinanced,
+    }
+}
+
+/// When a debt is paid off by regular payments.
+#[derive(Clone, Debug)]
+pub struct Payoff {
+    /// The time of the last payment.
+    pub date: DateTime<Utc>,
+    pub payments: u32,
+    /// The interest charged until the debt was paid off.
+    pub interest: Value,
+}
+
+/// Pays `payment` towards the debt every `frequency`, the first payment
+/// one period after `start`. Returns `None` if the payments never catch up
+/// with the interest.
+pub fn payoff(
+    item: &Item,
+    payment: &Value,
+    frequency: impl Into<Period>,
+    start: DateTime<Utc>,
+) -> Option<Payoff> {
+    let frequency = frequency.into();
+    let currency = item.currency();
+    let payment = CURRENCY_EXCHANGE
+        .rate(payment.currency(), currency)
+        .unwrap_or_else(|e| panic!("{e}"))
+        * payment.amount();
+    let mut owed = -item.assess(start).amount();
+    let mut interest = 0.0;
+    let mut date = start;
+    let mut payments = 0;
+    while owed > 1e-9 {
+        let from = date;
+        date = frequency.step(start, payments as i64 + 1);
+        let accrued = item
+            .interest
+            .as_ref()
+            .map_or(0.0, |i| owed * (i.factor(from, date) - 1.0));
+        if accrued >= payment || payments >= MAX_PAYMENTS {
+            return None;
+        }
+        interest += accrued;
+        owed += accrued - payment.min(owed + accrued);
+        payments += 1;
+    }
+    Some(Payoff {
+        date,
+        payments,
+        interest: Value::new(currency, interest),
+    })
+}
+
+/// One payment of an [amortization_schedule].
+#[derive(Clone, Debug)]
+pub struct ScheduleRow {
+    pub time: DateTime<Utc>,
+    pub payment: Value,
+    /// The interest accrued since the previous payment.
+    pub interest: Value,
+    /// What the payment took off the balance, negative when it grew.
+    pub principal: Value,
+    /// What is still owed after the payment.
+    pub balance: Value,
+    /// The payment was smaller than the interest so the balance grew.
+    pub negative_amortization: bool,
+}
+
+/// Pays `payment` towards the debt every `frequency` like [payoff] and
+/// lists up to `count` payments, stopping early once it is paid off. Rows
+/// whose payment does not cover the interest are marked, where [payoff]
+/// gives up.
+pub fn amortization_schedule(
+    item: &Item,
+    payment: &Value,
+    frequency: impl Into<Period>,
+    start: DateTime<Utc>,
+    count: u32,
+) -> Vec<ScheduleRow> {
+    let frequency = frequency.into();
+    let currency = item.currency();
+    let payment = CURRENCY_EXCHANGE
+        .rate(payment.currency(), currency)
+        .unwrap_or_else(|e| panic!("{e}"))
+        * payment.amount();
+    let mut owed = -item.assess(start).amount();
+    let mut rows = vec![];
+    for k in 1..=count as i64 {
+        if owed <= 1e-9 {
+            break;
+        }
+        let (from, time) = (frequency.step(start, k - 1), frequency.step(start, k));
+        let accrued = item
+            .interest
+            .as_ref()
+            .map_or(0.0, |i| owed * (i.factor(from, time) - 1.0));
+        let paid = payment.min(owed + accrued);
+        owed += accrued - paid;
+        rows.push(ScheduleRow {
+            time,
+            payment: Value::new(currency, paid),
+            interest: Value::new(currency, accrued),
+            principal: Value::new(currency, paid - accrued),
+            balance: Value::new(currency, owed),
+            negative_amortization: paid < accrued,
+        });
+    }
+    rows
+}
+
+/// Pays only the [Item::minimum_payment] every month after `start`. Returns
+/// `None` if the item has no minimum or it is not paid off within a
+/// hundred years.
+pub fn minimum_payoff(item: &Item, start: DateTime<Utc>) -> Option<Payoff> {
+    item.minimum_payment.as_ref()?;
+    let horizon = Period::Months(1).step(start, MAX_MONTHS);
+    let payments = item.project_minimum_payments(start, horizon);
+    let last = payments.last()?;
+    let mut paid_off = item.clone();
+    paid_off.import_deltas(payments.iter().cloned());
+    if -paid_off.assess(last.time).amount() >= 0.005 {
+        return None;
+    }
+    let paid: f64 = payments
+        .iter()
+        .map(|p| p.resolve_amount(0.0, item.currency()))
+        .sum();
+    Some(Payoff {
+        date: last.time,
+        payments: payments.len() as u32,
+        interest: Value::new(item.currency(), paid + item.assess(start).amount()),
+    })
+}
+
+/// Paying a debt off with and without an extra amount on every payment.
+#[derive(Clone, Debug)]
+pub struct ExtraPaymentReport {
+    /// `None` if the base payment never pays the debt off.
+    pub without: Option<Payoff>,
+    pub with: Option<Payoff>,
+    /// The total extra paid, which is less than the extra on every payment
+    /// when the last one is only part of a payment.
+    pub extra_paid: Value,
+}
+
+impl ExtraPaymentReport {
+    /// How much sooner the debt is paid off.
+    pub fn time_saved(&self) -> Option<TimeDelta> {
+        Some(self.without.as_ref()?.date - self.with.as_ref()?.date)
+    }
+    /// How much less interest is paid.
+    pub fn interest_saved(&self) -> Option<Value> {
+        let (without, with) = (self.without.as_ref()?, self.with.as_ref()?);
+        Some(without.interest.clone() + with.interest.negate())
+    }
+    /// The interest saved for every dollar of extra paid.
+    pub fn interest_saved_per_dollar(&self) -> Option<f64> {
+        Some(self.interest_saved()?.amount() / self.extra_paid.amount())
+    }
+    /// The days saved for every dollar of extra paid.
+    pub fn days_saved_per_dollar(&self) -> Option<f64> {
+        let days = self.time_saved()?.num_seconds() as f64 / 86_400.0;
+        Some(days / self.extra_paid.amount())
+    }
+}
+
+/// Compares paying `base_payment` every `frequency` to paying `extra` on
+/// top of it, see [payoff].
+pub fn extra_payment(
+    item: &Item,
+    base_payment: Value,
+    extra: Value,
+    frequency: impl Into<Period>,
+    start: DateTime<Utc>,
+) -> ExtraPaymentReport {
+    let frequency = frequency.into();
+    let total = base_payment.clone() + extra.clone();
+    let without = payoff(item, &base_payment, frequency, start);
+    let with = payoff(item, &total, frequency, start);
+    // Everything paid beyond the base payments that would have been made.
+    let extra_paid = with.as_ref().map_or(0.0, |with| {
+        let owed = -item.assess(start).amount() + with.interest.amount();
+        let base = base_payment.clone() * with.payments as f64;
+        (owed
+            - CURRENCY_EXCHANGE
+                .try_convert(base, item.currency())
+                .unwrap_or_else(|e| panic!("{e}"))
+                .amount())
+        .max(0.0)
+    });
+    ExtraPaymentReport {
+        without,
+        with,
+        extra_paid: Value::new(item.currency(), extra_paid),
+    }
+}
+
+/// What selling an item costs, see [liquidation_plan].
+#[derive(Clone, Debug)]
+pub enum LiquidationCost {
+    /// A share of what is sold, `0.05` loses 5%, such as a redemption fee.
+    Penalty(f64),
+    /// A flat fee for selling any amount, such as a commission.
+    Fee(Value),
+}
+
+/// Selling all or part of an item.
+#[derive(Clone, Debug)]
+pub struct Sale {
+    pub key: ItemKey,
+    /// What is sold, in the currency of the item.
+    pub sold: Value,
+    /// What the sale raises after its costs, in the currency needed.
+    pub proceeds: Value,
+    /// The penalties and fees of the sale, in the currency needed.
+    pub cost: Value,
+}
+
+/// The sales that raise the cash, see [liquidation_plan].
+#[derive(Clone, Debug)]
+pub struct LiquidationPlan {
+    /// In the order they were chosen, the last may be a partial sale.
+    pub sales: Vec<Sale>,
+    pub raised: Value,
+    pub cost: Value,
+}
+
+/// Why a plan could not be made.
+#[derive(Clone, Debug)]
+pub enum PlanError {
+    /// Selling everything that can be sold still leaves `short` of `needed`.
+    Shortfall {
+        needed: Value,
+        short: Value,
+    },
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Shortfall { needed, short } => {
+                write!(
+                    f,
+                    "selling everything still leaves {short} short of {needed}"
+                )
+            }
+            PlanError::Conversion(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+impl From<ConversionError> for PlanError {
+    fn from(value: ConversionError) -> Self {
+        PlanError::Conversion(value)
+    }
+}
+
+/// An item that can be sold by the deadline.
+struct Sellable {
+    key: ItemKey,
+    currency: Currency,
+    /// From the currency of the item into the currency needed.
+    rate: f64,
+    /// The value at the deadline in the currency needed.
+    worth: f64,
+    /// The share lost to penalties and the flat fees, in the currency needed.
+    penalty: f64,
+    fee: f64,
+}
+
+impl Sellable {
+    /// What selling everything raises.
+    fn net(&self) -> f64 {
+        self.worth * (1.0 - self.penalty) - self.fee
+    }
+    /// The cost for every unit raised when selling everything.
+    fn ratio(&self) -> f64 {
+        (self.worth * self.penalty + self.fee) / self.net()
+    }
+}
+
+/// Chooses what to sell to have `needed` in cash by `by`. Every item worth
+/// something at `by` can be sold, less its [LiquidationCost] if it has one,
+/// except [Liquidity::Illiquid] ones. Escrows not yet released by then can be
+/// cancelled, losing their forfeit on top of the cost.
+///
+/// This is greedy rather than optimal: the items are sold whole in the order
+/// of their cost for every unit raised, the cheapest first, and the last is
+/// sold only in part. A flat fee can make a smaller sale of a more expensive
+/// item cheaper than this picks.
+pub fn liquidation_plan(
+    book: &Book,
+    needed: Value,
+    by: DateTime<Utc>,
+    costs: &HashMap<ItemKey, LiquidationCost>,
+) -> Result<LiquidationPlan, PlanError> {
+    let target = needed.currency();
+    let mut sellable = vec![];
+    for (key, item) in book.iter_ordered() {
+        let forfeit = match item.liquidity {
+            Liquidity::Liquid => 0.0,
+            Liquidity::Illiquid => continue,
+            Liquidity::Escrow { release, .. } if by >= release => 0.0,
+            Liquidity::Escrow {
+                forfeit_percent, ..
+            } => forfeit_percent,
+        };
+        let value = item.assess(by);
+        if value.amount() <= 0.0 {
+            continue;
+        }
+        let rate = book.item_rate(item, value.currency(), target, by)?;
+        let (penalty, fee) = match costs.get(&key) {
+            None => (forfeit, 0.0),
+            Some(LiquidationCost::Penalty(penalty)) => (forfeit + penalty, 0.0),
+            Some(LiquidationCost::Fee(fee)) => {
+                let rate = book.rate_into(fee.currency(), target, by)?;
+                (forfeit, fee.amount() * rate)
+            }
+        };
+        let candidate = Sellable {
+            key,
+            currency: value.currency(),
+            rate,
+            worth: value.amount() * rate,
+            penalty,
+            fee,
+        };
+        if candidate.net() > 0.0 {
+            sellable.push(candidate);
+        }
+    }
+    sellable.sort_by(|a, b| a.ratio().total_cmp(&b.ratio()));
+
+    let mut remaining = needed.amount();
+    let (mut sales, mut cost) = (vec![], 0.0);
+    for item in sellable {
+        if remaining <= 0.0 {
+            break;
+        }
+        // Selling `gross` of the worth raises `gross * (1 - penalty) - fee`.
+        let gross = match item.net() <= remaining {
+            true => item.worth,
+            false => (remaining + item.fee) / (1.0 - item.penalty),
+        };
+        let proceeds = (gross * (1.0 - item.penalty) - item.fee).min(remaining);
+        remaining -= proceeds;
+        cost += gross - proceeds;
+        sales.push(Sale {
+            key: item.key,
+            sold: Value::new(item.currency, gross / item.rate),
+            proceeds: Value::new(target, proceeds),
+            cost: Value::new(target, gross - proceeds),
+        });
+    }
+    if remaining > 1e-9 {
+        return Err(PlanError::Shortfall {
+            needed,
+            short: Value::new(target, remaining),
+        });
+    }
+    Ok(LiquidationPlan {
+        sales,
+        raised: needed,
+        cost: Value::new(target, cost),
+    })
+}
+
+/// Moving money from one item to another, see [rebalance].
+#[derive(Clone, Debug)]
+pub struct RebalanceMove {
+    pub from: ItemKey,
+    pub to: ItemKey,
+    /// In the [Book::report_currency].
+    pub amount: Value,
+}
+
+/// Why a rebalance could not be suggested.
+#[derive(Clone, Debug)]
+pub enum RebalanceError {
+    /// The target weights add up to this rather than one.
+    TargetsSum(f64),
+    /// A target weight is below zero.
+    NegativeTarget(ItemKey),
+    /// A target is for an item that is not in the book.
+    Missing(ItemKey),
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for RebalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebalanceError::TargetsSum(sum) => {
+                write!(f, "target weights add up to {sum} rather than 1")
+            }
+            RebalanceError::NegativeTarget(key) => {
+                write!(f, "the target weight of {key:?} is negative")
+            }
+            RebalanceError::Missing(key) => write!(f, "item {key:?} is not in the book"),
+            RebalanceError::Conversion(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RebalanceError {}
+
+impl From<ConversionError> for RebalanceError {
+    fn from(value: ConversionError) -> Self {
+        RebalanceError::Conversion(value)
+    }
+}
+
+/// How far the target weights may add up to something other than one.
+const TARGET_TOLERANCE: f64 = 1e-9;
+
+/// The moves between the items of `targets` that bring each to its share of
+/// their total at `time`, weights being fractions such as `0.6`. Items are
+/// valued in the [Book::report_currency].
+///
+/// The item furthest over its target gives to the one furthest under until
+/// one of them is on target, so there is at most one move fewer than there
+/// are items. Items within `min_move` of their target are left alone and no
+/// move is smaller than it, so weights end up within `min_move` of target.
+pub fn rebalance(
+    book: &Book,
+    targets: &[(ItemKey, f64)],
+    time: DateTime<Utc>,
+    min_move: f64,
+) -> Result<Vec<RebalanceMove>, RebalanceError> {
+    let sum: f64 = targets.iter().map(|(_, weight)| weight).sum();
+    if (sum - 1.0).abs() > TARGET_TOLERANCE {
+        return Err(RebalanceError::TargetsSum(sum));
+    }
+    let mut values = Vec::with_capacity(targets.len());
+    for &(key, weight) in targets {
+        if weight < 0.0 {
+            return Err(RebalanceError::NegativeTarget(key));
+        }
+        let item = book.get(key).ok_or(RebalanceError::Missing(key))?;
+        let value = item.assess(time);
+        values.push(value.amount() * book.item_report_rate(item, value.currency(), time)?);
+    }
+    let total: f64 = values.iter().sum();
+    // Positive when the item holds more than its share.
+    let mut drift: Vec<(ItemKey, f64)> = targets
+        .iter()
+        .zip(&values)
+        .map(|(&(key, weight), value)| (key, value - weight * total))
+        .filter(|(_, drift)| drift.abs() >= min_move)
+        .collect();
+
+    let currency = book.currency();
+    let mut moves = vec![];
+    loop {
+        let over = drift
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1));
+        let under = drift
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1 .1.total_cmp(&b.1 .1));
+        let (Some((giver, &(from, surplus))), Some((taker, &(to, deficit)))) = (over, under) else {
+            break;
+        };
+        let amount = surplus.min(-deficit);
+        if amount < min_move.max(TARGET_TOLERANCE) {
+            break;
+        }
+        drift[giver].1 -= amount;
+        drift[taker].1 += amount;
+        moves.push(RebalanceMove {
+            from,
+            to,
+            amount: Value::new(currency, amount),
+        });
+    }
+    Ok(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::{Interest, Item, Liquidity, MinPayment},
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{
+        extra_payment, liquidation_plan, minimum_payoff, payoff_plan, rebalance, refinance,
+        LiquidationCost, PlanError, RebalanceError, Strategy,
+    };
+
+    /// Two cards of 1,000 at 2% and 500 at 1% a month with a 600 budget,
+    /// worked out by hand. A card's minimum is its interest plus 1% of the
+    /// balance with it, the rest of the budget goes where the strategy says.
+    ///
+    /// | month | snowball 1,000   | snowball 500 | avalanche 1,000  | avalanche 500         |
+    /// |-------|------------------|--------------|------------------|-----------------------|
+    /// | 1     | 1,020 → 925      | 505 → 0      | 1,020 → 430.05   | 505 → 494.95          |
+    /// | 2     | 943.50 → 343.50  |              | 438.651 → 0      | 499.8995 → 338.5505   |
+    /// | 3     | 350.37 → 0       |              |                  | 341.936005 → 0        |
+    ///
+    /// The snowball pays 25 + 18.50 + 6.87 = 50.37 of interest and the
+    /// avalanche 25 + 13.5505 + 3.385505 = 41.936005.
+    #[test]
+    pub fn test_snowball_vs_avalanche() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = |n| Period::Months(1).step(start, n);
+        let mut book = Book::default();
+        let expensive = book.add(Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.02,
+            Period::Months(1),
+            start,
+        ));
+        let cheap = book.add(Item::basic_debt(
+            Value::new("CAD", -500.0),
+            0.01,
+            Period::Months(1),
+            start,
+        ));
+        let debts = [expensive, cheap];
+        let budget = Value::new("CAD", 600.0);
+
+        let snowball = payoff_plan(&book, &debts, budget.clone(), start, Strategy::Snowball);
+        let avalanche = payoff_plan(&book, &debts, budget, start, Strategy::Avalanche);
+
+        // The cheap card is cleared first by the snowball and last by the avalanche.
+        assert!(snowball.payoff[1].1 < snowball.payoff[0].1);
+        assert!(avalanche.payoff[0].1 < avalanche.payoff[1].1);
+        for plan in [&snowball, &avalanche] {
+            for month in &plan.months {
+                let paid: f64 = month.payments.iter().map(|(_, v)| v.amount()).sum();
+                assert!(paid <= 600.0 + 1e-9);
+            }
+        }
+
+        let (snowball_paid, avalanche_paid) =
+            (&snowball.months[0].payments, &avalanche.months[0].payments);
+        assert_value_eq!(snowball_paid[0].1, Value::new("CAD", 95.0), 1e-9);
+        assert_value_eq!(snowball_paid[1].1, Value::new("CAD", 505.0), 1e-9);
+        assert_value_eq!(avalanche_paid[0].1, Value::new("CAD", 589.95), 1e-9);
+        assert_value_eq!(avalanche_paid[1].1, Value::new("CAD", 10.05), 1e-9);
+        assert_value_eq!(snowball.total_interest, Value::new("CAD", 50.37), 1e-9);
+        assert_value_eq!(avalanche.total_interest, Value::new("CAD", 41.936005), 1e-9);
+        assert_eq!(snowball.payoff, [(expensive, Some(month(3))), (cheap, Some(month(1)))]);
+        assert_eq!(avalanche.payoff, [(expensive, Some(month(2))), (cheap, Some(month(3)))]);
+        assert_eq!(snowball.debt_free(), Some(month(3)));
+
+        // Nothing less than a yen is paid, otherwise the avalanche would
+        // put the rest of that yen towards the card.
+        let mut yen = Book::default();
+        let mut card =
+            Item::basic_debt(Value::new("JPY", -1_000.0), 0.01, Period::Months(1), start);
+        card.minimum_payment = Some(MinPayment {
+            floor: Value::new("JPY", 20.0),
+            percent: 0.0,
+        });
+        let card = yen.add(card);
+        let loan = yen.add(Item::fixed(Value::new("JPY", -10.0), start));
+        let budget = Value::new("JPY", 21.0);
+        let plan = payoff_plan(&yen, &[card, loan], budget, start, Strategy::Avalanche);
+        assert_value_eq!(plan.months[0].payments[0].1, Value::new("JPY", 20.0), 1e-9);
+        assert_value_eq!(plan.months[0].payments[1].1, Value::new("JPY", 1.0), 1e-9);
+    }
+
+    /// Refinancing 300k from 5.4% to 4.1% for a 3k penalty breaks even once
+    /// 303000 * 1.041^t = 300000 * 1.054^t, after about 0.8 years.
+    #[test]
+    pub fn test_refinance_break_even() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let horizon = Period::Years(5).step(start, 1);
+        let mortgage = Item::basic_debt(
+            Value::new("CAD", -300_000.0),
+            0.054,
+            Period::Years(1),
+            start,
+        );
+        let report = refinance(
+            &mortgage,
+            Interest::new(0.041, Period::Years(1)),
+            Value::new("CAD", 3_000.0),
+            start,
+            horizon,
+        );
+
+        let years = (303_000.0f64 / 300_000.0).ln() / (1.054f64 / 1.041).ln();
+        let break_even = report.break_even.unwrap();
+        assert!((Period::Years(1).between(start, break_even) - years).abs() < 1e-6);
+
+        let original = 300_000.0 * 1.054f64.powi(5);
+        let refinanced = 303_000.0 * 1.041f64.powi(5);
+        let cad = |amount| Value::new("CAD", amount);
+        assert_value_eq!(report.difference(), cad(original - refinanced), 1e-6);
+        assert_value_eq!(report.original_interest, cad(original - 300_000.0), 1e-6);
+        assert_value_eq!(report.refinanced_interest, cad(refinanced - 303_000.0), 1e-6);
+
+        // A penalty that is never earned back.
+        let report = refinance(
+            &mortgage,
+            Interest::new(0.041, Period::Years(1)),
+            Value::new("CAD", 100_000.0),
+            start,
+            horizon,
+        );
+        assert!(report.break_even.is_none());
+        assert!(report.difference().amount() < 0.0);
+    }
+
+    /// A 300k mortgage over 25 years at 5% compounded monthly, paying 200
+    /// extra a month. Online calculators give 20 years and 6 months and
+    /// 46,351 saved in interest.
+    #[test]
+    pub fn test_extra_payment() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mortgage = Item::basic_debt(
+            Value::new("CAD", -300_000.0),
+            0.05 / 12.0,
+            Period::Months(1),
+            start,
+        );
+        let report = extra_payment(
+            &mortgage,
+            Value::new("CAD", 1_753.77),
+            Value::new("CAD", 200.0),
+            Period::Months(1),
+            start,
+        );
+
+        let (without, with) = (
+            report.without.as_ref().unwrap(),
+            report.with.as_ref().unwrap(),
+        );
+        // The last payment of the schedule without extra is a few cents.
+        assert_eq!(without.payments, 301);
+        assert_eq!(with.payments, 246);
+        assert_eq!(with.date, Period::Months(246).step(start, 1));
+        assert_value_eq!(without.interest, Value::new("CAD", 226_131.07), 1.0);
+        assert_value_eq!(report.interest_saved().unwrap(), Value::new("CAD", 46_351.25), 1.0);
+        // The last extra payment is only partly needed.
+        assert_value_eq!(report.extra_paid, Value::new("CAD", 48_352.40), 1.0);
+        let per_dollar = report.interest_saved_per_dollar().unwrap();
+        assert!((per_dollar - 46_351.25 / 48_352.40).abs() < 1e-4);
+        assert!(report.extra_paid.amount() < 200.0 * 246.0);
+
+        // Paying it all at once.
+        let report = extra_payment(
+            &mortgage,
+            Value::new("CAD", 1_753.77),
+            Value::new("CAD", 1_000_000.0),
+            Period::Months(1),
+            start,
+        );
+        let with = report.with.as_ref().unwrap();
+        assert_eq!(with.payments, 1);
+        assert_value_eq!(with.interest, Value::new("CAD", 1_250.0), 1e-6);
+
+        // Payments that never cover the interest.
+        let report = extra_payment(
+            &mortgage,
+            Value::new("CAD", 1_000.0),
+            Value::new("CAD", 200.0),
+            Period::Months(1),
+            start,
+        );
+        assert!(report.with.is_none() && report.interest_saved().is_none());
+    }
+
+    /// The credit card disclosure box for 5,000 at 19.99% paying only the
+    /// greater of 10 or 2% of the statement balance.
+    #[test]
+    pub fn test_minimum_payments() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let card = Item::credit_card(
+            Value::new("CAD", -5_000.0),
+            0.1999,
+            MinPayment {
+                floor: Value::new("CAD", 10.0),
+                percent: 0.02,
+            },
+            start,
+        );
+
+        // The disclosure calculation month by month.
+        let (mut owed, mut months, mut interest) = (5_000.0f64, 0, 0.0);
+        while owed >= 0.005 {
+            let accrued = owed * 0.1999 / 12.0;
+            owed += accrued;
+            interest += accrued;
+            owed -= (owed * 0.02).max(10.0).min(owed);
+            months += 1;
+        }
+
+        let payments = card.project_minimum_payments(start, Period::Years(100).step(start, 1));
+        assert_eq!(payments.len(), months);
+        assert_eq!(payments[0].time, Period::Months(1).step(start, 1));
+        let first = Value::new("CAD", payments[0].resolve_amount(0.0, "CAD".into()));
+        assert_value_eq!(first, Value::new("CAD", 5_000.0 * (1.0 + 0.1999 / 12.0) * 0.02), 1e-9);
+
+        let payoff = minimum_payoff(&card, start).unwrap();
+        assert_eq!(payoff.payments as usize, months);
+        assert_eq!(payoff.date, payments.last().unwrap().time);
+        assert_value_eq!(payoff.interest, Value::new("CAD", interest), 1e-6);
+        // Decades of payments, more than twice the balance in interest.
+        assert!(months > 600 && payoff.interest.amount() > 10_000.0);
+    }
+
+    /// Raising 30k from a fund with a 100 commission, shares with a 2%
+    /// penalty and a bond with a 5% penalty. The fund is the cheapest for
+    /// every dollar raised, then the shares cover the rest. The house and an
+    /// escrow that forfeits 10% are left alone.
+    #[test]
+    pub fn test_liquidation_plan() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let june = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let bond = book.add(Item::fixed(Value::new("CAD", 10_000.0), start));
+        let fund = book.add(Item::fixed(Value::new("CAD", 20_000.0), start));
+        let shares = book.add(Item::fixed(Value::new("CAD", 50_000.0), start));
+        let mut house = Item::fixed(Value::new("CAD", 500_000.0), start);
+        house.liquidity = Liquidity::Illiquid;
+        book.add(house);
+        let mut escrow = Item::fixed(Value::new("CAD", 5_000.0), start);
+        escrow.liquidity = Liquidity::Escrow {
+            release: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            forfeit_percent: 0.1,
+        };
+        book.add(escrow);
+        let costs = HashMap::from([
+            (bond, LiquidationCost::Penalty(0.05)),
+            (fund, LiquidationCost::Fee(Value::new("CAD", 100.0))),
+            (shares, LiquidationCost::Penalty(0.02)),
+        ]);
+
+        let plan = liquidation_plan(&book, Value::new("CAD", 30_000.0), june, &costs).unwrap();
+        let sold: Vec<_> = plan
+            .sales
+            .iter()
+            .map(|s| (s.key, s.sold.amount()))
+            .collect();
+        assert_eq!(sold[0], (fund, 20_000.0));
+        assert_eq!(sold[1].0, shares);
+        assert_value_eq!(plan.sales[1].sold, Value::new("CAD", 10_100.0 / 0.98), 1e-6);
+        assert_eq!(sold.len(), 2);
+        let raised: Option<Value> = plan.sales.iter().map(|s| &s.proceeds).sum();
+        assert_value_eq!(raised.unwrap(), Value::new("CAD", 30_000.0), 1e-6);
+        assert_value_eq!(plan.cost, Value::new("CAD", 100.0 + 10_100.0 / 0.98 * 0.02), 1e-6);
+
+        // Everything but the house raises 9500 + 19900 + 49000 + 4500.
+        let err = liquidation_plan(&book, Value::new("CAD", 100_000.0), june, &costs).unwrap_err();
+        let PlanError::Shortfall { short, .. } = err else {
+            panic!("the book can not raise 100k");
+        };
+        assert_value_eq!(short, Value::new("CAD", 17_100.0), 1e-6);
+    }
+
+    /// A 70/25/5 portfolio back to 60/30/10 takes two moves out of equities.
+    #[test]
+    pub fn test_rebalance() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let equities = book.add(Item::fixed(Value::new("CAD", 70_000.0), start));
+        let bonds = book.add(Item::fixed(Value::new("CAD", 25_000.0), start));
+        let cash = book.add(Item::fixed(Value::new("CAD", 5_000.0), start));
+        let targets = [(equities, 0.6), (bonds, 0.3), (cash, 0.1)];
+
+        let moves = rebalance(&book, &targets, start, 100.0).unwrap();
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|m| m.from == equities));
+        let to: Vec<_> = moves.iter().map(|m| (m.to, m.amount.amount())).collect();
+        assert!(to.contains(&(bonds, 5_000.0)) && to.contains(&(cash, 5_000.0)));
+
+        for m in &moves {
+            book.transfer(m.from, m.to, start, m.amount.clone());
+        }
+        let total = book.assess(start);
+        for (key, weight) in targets {
+            let held = book.get(key).unwrap().assess(start);
+            assert_value_eq!(held, &total * weight, 100.0);
+        }
+        assert!(rebalance(&book, &targets, start, 100.0).unwrap().is_empty());
+
+        // Dust is left alone.
+        book.transfer(bonds, cash, start, Value::new("CAD", 50.0));
+        assert!(rebalance(&book, &targets, start, 100.0).unwrap().is_empty());
+        assert_eq!(rebalance(&book, &targets, start, 0.0).unwrap().len(), 1);
+
+        let Err(RebalanceError::TargetsSum(sum)) =
+            rebalance(&book, &[(equities, 0.6), (bonds, 0.3)], start, 0.0)
+        else {
+            panic!("the targets add up to 0.9");
+        };
+        assert!((sum - 0.9).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use super::{
+    book::{Book, ItemRef},
+    delta::Delta,
+    import::ImportPlan,
+};
+
+/// Marks how far a [DeltaSource] has been read, the meaning
+/// of the number is up to the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cursor(pub u64);
+
+/// An error reported by a [DeltaSource].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceError {
+    pub message: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "delta source failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// A stream of transactions from outside, such as a bank feed,
+/// that a [Book] pulls from.
+pub trait DeltaSource {
+    /// Returns the deltas after the cursor along with the cursor to resume from.
+    /// Sources may send a delta more than once, the book deduplicates them on
+    /// [Delta::external_id].
+    fn deltas_since(
+        &mut self,
+        cursor: Option<Cursor>,
+    ) -> Result<(Vec<(ItemRef, Delta)>, Cursor), SourceError>;
+}
+
+/// A [DeltaSource] backed by a vector, mostly for tests.
+#[derive(Default)]
+pub struct VecSource {
+    pub entries: Vec<(ItemRef, Delta)>,
+    /// How many already seen entries are sent again on every read, as
+    /// many real feeds do.
+    pub overlap: usize,
+}
+
+impl VecSource {
+    pub fn new(entries: Vec<(ItemRef, Delta)>) -> Self {
+        Self {
+            entries,
+            overlap: 0,
+        }
+    }
+    pub fn push(&mut self, item: ItemRef, delta: Delta) {
+        self.entries.push((item, delta));
+    }
+}
+
+impl DeltaSource for VecSource {
+    fn deltas_since(
+        &mut self,
+        cursor: Option<Cursor>,
+    ) -> Result<(Vec<(ItemRef, Delta)>, Cursor), SourceError> {
+        let seen = cursor.map(|c| c.0 as usize).unwrap_or(0);
+        let from = seen.saturating_sub(self.overlap).min(self.entries.len());
+        Ok((
+            self.entries[from..].to_vec(),
+            Cursor(self.entries.len() as u64),
+        ))
+    }
+}
+
+/// What happened during a [Book::sync].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncReport {
+    pub applied: usize,
+    /// Deltas that were already on their item.
+    pub duplicates: usize,
+    /// Deltas whose item could not be found in the book.
+    pub unresolved: Vec<(ItemRef, Delta)>,
+}
+
+impl Book {
+    /// Pulls new deltas from the source and applies them to their items. Deltas
+    /// with an external identifier already present on the item are skipped, so
+    /// syncing is idempotent. Deltas whose item is not in the book are kept
+    /// and tried again on the next sync, the source is not asked for them
+    /// again.
+    pub fn sync(&mut self, source: &mut dyn DeltaSource) -> Result<SyncReport, SourceError> {
+        let (deltas, cursor) = source.deltas_since(self.sync_cursor)?;
+        let mut report = SyncReport::default();
+        let pending = std::mem::take(&mut self.sync_pending);
+        for (item_ref, delta) in pending.into_iter().chain(deltas) {
+            let Some(key) = self.resolve(&item_ref).filter(|k| self.get(*k).is_some()) else {
+                report.unresolved.push((item_ref, delta));
+                continue;
+            };
+            let duplicate = delta.external_id.is_some()
+                && self
+                    .get(key)
+                    .unwrap()
+                    .deltas
+                    .iter()
+                    .any(|d| d.external_id == delta.external_id);
+            if duplicate {
+                report.duplicates += 1;
+            } else {
+                self.add_delta(key, delta);
+                report.applied += 1;
+            }
+        }
+        self.sync_cursor = Some(cursor);
+        self.sync_pending = report.unresolved.clone();
+        Ok(report)
+    }
+    /// Like [Book::sync] without applying anything, the deltas are planned
+    /// for [Book::apply_plan] which also moves the cursor and keeps what is
+    /// unresolved for the next sync.
+    pub fn plan_sync(&self, source: &mut dyn DeltaSource) -> Result<ImportPlan, SourceError> {
+        let (deltas, cursor) = source.deltas_since(self.sync_cursor)?;
+        let mut plan = self.plan_deltas(self.sync_pending.iter().cloned().chain(deltas));
+        plan.cursor = Some(cursor);
+        Ok(plan)
+    }
+    /// The synced deltas whose item was not in the book, tried again on the
+    /// next [Book::sync] once their item has been added.
+    pub fn sync_pending(&self) -> &[(ItemRef, Delta)] {
+        &self.sync_pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::{Book, ItemRef},
+        delta::Delta,
+        item::Item,
+        value::Value,
+        Assesible,
+    };
+
+    use super::VecSource;
+
+    fn payment(day: i64, amount: f64, id: &str) -> Delta {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        Delta::payment(start + TimeDelta::days(day), Value::dummy("CAD", amount))
+            .with_external_id(id)
+    }
+
+    #[test]
+    pub fn test_sync_is_idempotent() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::dummy("CAD", 0.0), start));
+        let visa = book.add(Item::fixed(Value::dummy("CAD", -500.0), start));
+        book.set_external_id(visa, "visa-4242");
+
+        let mut source = VecSource::new(vec![
+            (ItemRef::Name("Chequing".into()), payment(1, 100.0, "tx-1")),
+            (
+                ItemRef::External("visa-4242".into()),
+                payment(2, 50.0, "tx-2"),
+            ),
+        ]);
+        source.overlap = 2;
+
+        let first = book.sync(&mut source).unwrap();
+        assert_eq!(first.applied, 2);
+
+        source.push(ItemRef::Name("Chequing".into()), payment(3, 25.0, "tx-3"));
+        source.push(ItemRef::Name("Savings".into()), payment(3, 10.0, "tx-4"));
+        let second = book.sync(&mut source).unwrap();
+        assert_eq!(second.applied, 1);
+        assert_eq!(second.duplicates, 2);
+        assert_eq!(second.unresolved.len(), 1);
+        assert_eq!(book.sync_pending().len(), 1);
+
+        let later = start + TimeDelta::days(10);
+        assert_eq!(book.get(chequing).unwrap().deltas.len(), 2);
+        let chequing = book.get(chequing).unwrap().assess(later);
+        assert_value_eq!(chequing, Value::new("CAD", 125.0), 0.005);
+        assert_value_eq!(book.get(visa).unwrap().assess(later), Value::new("CAD", -450.0), 0.005);
+
+        // Planning a sync reads the source but only applying moves the cursor.
+        source.push(ItemRef::Name("Chequing".into()), payment(4, 5.0, "tx-5"));
+        let plan = book.plan_sync(&mut source).unwrap();
+        assert_eq!((plan.deltas.len(), plan.duplicates.len()), (1, 1));
+        assert_eq!(book.plan_sync(&mut source).unwrap().deltas.len(), 1);
+        book.apply_plan(plan).unwrap();
+        assert!(book.plan_sync(&mut source).unwrap().is_empty());
+
+        // The delta for the missing account goes on once it is added.
+        let savings = book.add_named("Savings", Item::fixed(Value::dummy("CAD", 0.0), start));
+        let third = book.sync(&mut source).unwrap();
+        assert_eq!((third.applied, third.unresolved.len()), (1, 0));
+        assert_eq!(book.get(savings).unwrap().deltas.len(), 1);
+        assert!(book.sync_pending().is_empty());
+    }
+}
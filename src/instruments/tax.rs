@@ -0,0 +1,215 @@
+//! Taxes on what assets pay out and on what they gain.
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+
+use super::{
+    book::Book,
+    convert::CURRENCY_EXCHANGE,
+    item::Item,
+    value::{Currency, Value},
+    Assesible, AssessmentResult,
+};
+
+/// The rates income and realized gains are taxed at. Losses are never
+/// taxed, they do not produce a refund either.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TaxPolicy {
+    /// Applies to payouts such as dividends and interest.
+    pub rate_on_income: f64,
+    /// Applies to the gain above the basis when an asset is sold.
+    pub rate_on_gains: f64,
+}
+
+impl TaxPolicy {
+    /// The tax owed on some income.
+    pub fn income_tax(&self, income: f64) -> f64 {
+        income.max(0.0) * self.rate_on_income
+    }
+    /// The tax owed on some gain.
+    pub fn gains_tax(&self, gain: f64) -> f64 {
+        gain.max(0.0) * self.rate_on_gains
+    }
+}
+
+/// The payouts of an item up to the time in `currency`, after income tax.
+fn income_after_tax(
+    item: &Item,
+    time: DateTime<Utc>,
+    policy: &TaxPolicy,
+    currency: Currency,
+) -> f64 {
+    let income: f64 = item
+        .payouts
+        .iter()
+        .flat_map(|payout| payout.flows(item.inception, time))
+        .map(|(_, value)| {
+            CURRENCY_EXCHANGE
+                .rate(value.currency(), currency)
+                .unwrap_or_else(|e| panic!("{e}"))
+                * value.amount()
+        })
+        .sum();
+    income - policy.income_tax(income)
+}
+
+/// An [Assesible] that is taxed when it is sold. Until [Taxed::realize] is
+/// called it is worth what the asset is, afterwards it is worth what was
+/// left of the sale after tax on the gain above `basis`.
+pub struct Taxed<A: Assesible> {
+    pub asset: A,
+    pub policy: TaxPolicy,
+    /// What was paid for the asset, only the value above it is a gain.
+    pub basis: Value,
+    sale: Option<(DateTime<Utc>, Value)>,
+}
+
+impl<A: Assesible> Taxed<A> {
+    pub fn new(asset: A, policy: TaxPolicy, basis: Value) -> Self {
+        Self {
+            asset,
+            policy,
+            basis,
+            sale: None,
+        }
+    }
+    /// Sells the asset at the time, returning what is left after tax. An
+    /// asset can only be sold once, later calls return the first sale.
+    pub fn realize(&mut self, time: DateTime<Utc>) -> Value {
+        if let Some((_, proceeds)) = &self.sale {
+            return proceeds.clone();
+        }
+        let value = self.asset.assess(time);
+        let basis = CURRENCY_EXCHANGE
+            .try_convert(self.basis.clone(), value.currency())
+            .unwrap_or_else(|e| panic!("{e}"));
+        let tax = self.policy.gains_tax(value.amount() - basis.amount());
+        let proceeds = Value::new(value.currency(), value.amount() - tax);
+        self.sale = Some((time, proceeds.clone()));
+        proceeds
+    }
+    /// When the asset was sold, if it was.
+    pub fn realized_at(&self) -> Option<DateTime<Utc>> {
+        self.sale.as_ref().map(|(time, _)| *time)
+    }
+    /// The proceeds of the sale if it happened by the time.
+    fn sold_by(&self, time: DateTime<Utc>) -> Option<Value> {
+        self.sale
+            .as_ref()
+            .filter(|(sold, _)| *sold <= time)
+            .map(|(_, proceeds)| proceeds.clone())
+    }
+}
+
+impl Taxed<Item> {
+    /// Taxes an item with its original book value as the basis.
+    pub fn item(item: Item, policy: TaxPolicy) -> Self {
+        let basis = item.book_value.clone();
+        Self::new(item, policy, basis)
+    }
+    /// The payouts of the item up to and including the time, after income tax.
+    pub fn income_after_tax(&self, time: DateTime<Utc>) -> Value {
+        let currency = self.asset.currency();
+        Value::new(
+            currency,
+            income_after_tax(&self.asset, time, &self.policy, currency),
+        )
+    }
+}
+
+impl<A: Assesible> Assesible for Taxed<A> {
+    fn assess(&self, time: DateTime<Utc>) -> Value {
+        self.sold_by(time)
+            .unwrap_or_else(|| self.asset.assess(time))
+    }
+    fn currency(&self) -> Currency {
+        self.asset.currency()
+    }
+    fn sample(&self, time: DateTime<Utc>, rng: &mut dyn RngCore) -> Value {
+        self.sold_by(time)
+            .unwrap_or_else(|| self.asset.sample(time, rng))
+    }
+}
+
+impl Book {
+    /// Assesses the book alongside the cash its items have paid out up to
+    /// the time after income tax, in the currency of the book. Income is
+    /// taxed per item so one item's losses do not offset another's income.
+    pub fn assess_after_tax(&self, time: DateTime<Utc>, policy: TaxPolicy) -> AssessmentResult {
+        let currency = self.currency();
+        let cash: f64 = self
+            .iter()
+            .map(|(_, item)| income_after_tax(item, time, &policy, currency))
+            .sum();
+        AssessmentResult::new(self.assess(time), Value::new(currency, cash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        item::{Item, Payout, Proration},
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{TaxPolicy, Taxed};
+
+    const POLICY: TaxPolicy = TaxPolicy {
+        rate_on_income: 0.3,
+        rate_on_gains: 0.25,
+    };
+
+    /// A stock paying a quarterly dividend of 100.
+    #[test]
+    pub fn test_dividend_income() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let stock = Item::builder(Value::new("CAD", 10_000.0), start)
+            .payout(Payout::FixedRecurring {
+                amount: Value::new("CAD", 100.0),
+                start,
+                frequency: Period::Months(3),
+                proration: Proration::None,
+            })
+            .build();
+        let end = Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap();
+
+        let taxed = Taxed::item(stock.clone(), POLICY);
+        assert_value_eq!(taxed.income_after_tax(end), Value::new("CAD", 280.0), 1e-9);
+
+        let mut book = Book::default();
+        book.add(stock);
+        let result = book.assess_after_tax(end, POLICY);
+        assert_eq!(result.value().amount(), 10_000.0);
+        assert_value_eq!(*result.cash(), Value::new("CAD", 280.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_realize_gain_and_loss() {
+        let start = Utc.with_ymd_and_hms(2014, 1, 1, 0, 0, 0).unwrap();
+        let sold = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let grows =
+            |rate| Item::basic_debt(Value::new("CAD", 100_000.0), rate, Period::Years(1), start);
+
+        let mut house = Taxed::item(grows(0.05), POLICY);
+        let value = house.asset.assess(sold).amount();
+        let proceeds = house.realize(sold).amount();
+        let after_tax = Value::new("CAD", value - (value - 100_000.0) * 0.25);
+        assert_value_eq!(Value::new("CAD", proceeds), after_tax, 1e-6);
+        assert_eq!(house.realized_at(), Some(sold));
+        // The sale is cash now, it no longer grows with the asset.
+        let later = Period::Years(5).step(sold, 1);
+        assert_eq!(house.assess(later).amount(), proceeds);
+        assert!(house.assess(start).amount() < proceeds);
+
+        // Selling at a loss owes nothing and refunds nothing.
+        let mut car = Taxed::item(grows(-0.1), POLICY);
+        let value = car.asset.assess(sold).amount();
+        assert_eq!(car.realize(sold).amount(), value);
+    }
+}
@@ -0,0 +1,320 @@
+//! Money moving between two items of a [Book].
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    convert::ConversionError,
+    delta::{Delta, DeltaId},
+    events::BookEvent,
+    undo::Change,
+    value::Value,
+    Assesible,
+};
+
+/// Identifies a transfer, handed out in increasing order like [DeltaId].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TransferId(u64);
+
+impl TransferId {
+    /// Hands out a fresh identifier.
+    pub fn next() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+/// An amount taken out of one item and put into another, recorded as a
+/// delta on each side.
+#[derive(Clone, Debug)]
+pub struct Transfer {
+    pub id: TransferId,
+    pub from: ItemKey,
+    pub to: ItemKey,
+    pub time: DateTime<Utc>,
+    pub amount: Value,
+    /// The delta taking the amount out of `from`.
+    pub from_delta: DeltaId,
+    /// The delta putting the amount into `to`.
+    pub to_delta: DeltaId,
+}
+
+/// Why [Book::apply_payment] could not be made.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaymentError {
+    /// No item has the name, `known` is every name in the book.
+    Missing {
+        name: String,
+        known: Vec<String>,
+    },
+    /// More than one item has the name.
+    Ambiguous {
+        name: String,
+        candidates: Vec<ItemKey>,
+    },
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Missing { name, known } => {
+                write!(
+                    f,
+                    "no item named {name:?}, the book has {}",
+                    known.join(", ")
+                )
+            }
+            PaymentError::Ambiguous { name, candidates } => {
+                write!(
+                    f,
+                    "{} items are named {name:?}: {candidates:?}",
+                    candidates.len()
+                )
+            }
+            PaymentError::Conversion(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+impl From<ConversionError> for PaymentError {
+    fn from(value: ConversionError) -> Self {
+        PaymentError::Conversion(value)
+    }
+}
+
+impl Book {
+    /// Moves `amount` from one item to another, each side converts it into
+    /// its own currency when assessed. Returns `None` without changing
+    /// anything if either item is not in the book.
+    pub fn transfer(
+        &mut self,
+        from: ItemKey,
+        to: ItemKey,
+        time: DateTime<Utc>,
+        amount: Value,
+    ) -> Option<TransferId> {
+        self.get(from)?;
+        self.get(to)?;
+        let id = TransferId::next();
+        let memo = format!("Transfer {}", id.get());
+        let out = Delta::payment(time, amount.negate()).with_memo(memo.clone());
+        let into = Delta::payment(time, amount.clone()).with_memo(memo);
+        let transfer = Transfer {
+            id,
+            from,
+            to,
+            time,
+            amount,
+            from_delta: out.id,
+            to_delta: into.id,
+        };
+        self.retransfer(transfer, out, into);
+        Some(id)
+    }
+    /// Pays `amount` from the item named `from` to the item named `to`,
+    /// such as from a chequing account to a card. The amount is converted
+    /// into each item's currency at the book's rate at `time`, or the
+    /// item's [Item::fx_override](super::item::Item::fx_override), and both
+    /// deltas are labelled with the names.
+    pub fn apply_payment(
+        &mut self,
+        from: &str,
+        to: &str,
+        time: DateTime<Utc>,
+        amount: Value,
+    ) -> Result<TransferId, PaymentError> {
+        let (from_key, to_key) = (self.named(from)?, self.named(to)?);
+        let convert = |key| -> Result<Value, ConversionError> {
+            let item = self.get(key).unwrap();
+            let currency = item.currency();
+            let rate = self.item_rate(item, amount.currency(), currency, time)?;
+            Ok(Value::new(currency, amount.amount() * rate))
+        };
+        let (taken, paid) = (convert(from_key)?, convert(to_key)?);
+
+        let id = TransferId::next();
+        let memo = format!("Payment from {from} to {to}");
+        let out = Delta::payment(time, taken.negate()).with_memo(memo.clone());
+        let into = Delta::payment(time, paid).with_memo(memo);
+        let transfer = Transfer {
+            id,
+            from: from_key,
+            to: to_key,
+            time,
+            amount,
+            from_delta: out.id,
+            to_delta: into.id,
+        };
+        self.retransfer(transfer, out, into);
+        Ok(id)
+    }
+    /// The only item with the name.
+    fn named(&self, name: &str) -> Result<ItemKey, PaymentError> {
+        match self.keys_named(name).as_slice() {
+            [key] => Ok(*key),
+            [] => {
+                let mut known: Vec<String> = self
+                    .iter()
+                    .filter_map(|(key, _)| self.name(key))
+                    .map(str::to_string)
+                    .collect();
+                known.sort();
+                Err(PaymentError::Missing {
+                    name: name.to_string(),
+                    known,
+                })
+            }
+            candidates => Err(PaymentError::Ambiguous {
+                name: name.to_string(),
+                candidates: candidates.to_vec(),
+            }),
+        }
+    }
+    /// Puts a transfer's deltas on both items and records it.
+    pub(crate) fn retransfer(&mut self, transfer: Transfer, out: Delta, into: Delta) {
+        let (id, from, to) = (transfer.id, transfer.from, transfer.to);
+        for (key, delta) in [(from, out.clone()), (to, into.clone())] {
+            self.item_mut(key).unwrap().push_delta(delta.clone());
+            self.audit.push(AuditEntry::DeltaAdded { key, delta });
+        }
+        self.transfers.push(transfer.clone());
+        self.audit.push(AuditEntry::Transferred {
+            transfer: transfer.clone(),
+        });
+        self.subscribers
+            .notify(&BookEvent::Transfer { id, from, to });
+        self.record(Change::Transferred {
+            transfer,
+            out,
+            into,
+        });
+    }
+    /// Takes a transfer's deltas back off both items and forgets it.
+    pub(crate) fn untransfer(&mut self, transfer: Transfer, out: Delta, into: Delta) {
+        for (key, delta) in [(transfer.from, &out), (transfer.to, &into)] {
+            let deltas = &mut self.item_mut(key).unwrap().deltas;
+            deltas.retain(|d| d.id != delta.id);
+            self.audit.push(AuditEntry::DeltaRemoved {
+                key,
+                delta: delta.clone(),
+            });
+            self.subscribers.notify(&BookEvent::DeltaRemoved {
+                item: key,
+                delta_id: delta.id,
+            });
+        }
+        self.transfers.retain(|t| t.id != transfer.id);
+        self.audit.push(AuditEntry::Untransferred { id: transfer.id });
+        self.record(Change::Untransferred {
+            transfer,
+            out,
+            into,
+        });
+    }
+    /// Every transfer made through the book, in the order they were made.
+    pub fn transfers(&self) -> &[Transfer] {
+        &self.transfers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        audit::AuditEntry, book::Book, convert::CURRENCY_EXCHANGE, item::Item, value::Value,
+        Assesible,
+    };
+
+    use super::PaymentError;
+
+    #[test]
+    pub fn test_transfer() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let chequing = book.add(Item::fixed(Value::new("CAD", 1_000.0), start));
+        let visa = book.add(Item::fixed(Value::new("CAD", -400.0), start));
+
+        let id = book
+            .transfer(chequing, visa, start, Value::new("CAD", 400.0))
+            .unwrap();
+        assert_eq!(book.get(chequing).unwrap().assess(start).amount(), 600.0);
+        assert_eq!(book.get(visa).unwrap().assess(start).amount(), 0.0);
+        assert_eq!(book.assess(start).amount(), 600.0);
+        assert_eq!(book.transfers()[0].id, id);
+        assert_eq!(
+            book.get(visa).unwrap().deltas[0].id,
+            book.transfers()[0].to_delta
+        );
+
+        book.remove(visa);
+        assert!(book
+            .transfer(chequing, visa, start, Value::new("CAD", 1.0))
+            .is_none());
+        assert_eq!(book.get(chequing).unwrap().deltas.len(), 1);
+    }
+
+    #[test]
+    pub fn test_apply_payment() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let paid = Utc.with_ymd_and_hms(2024, 3, 3, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion("CAD", "COP", 2911.98);
+        let mut book = Book::default();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 2_000.0), start));
+        let loan = book.add_named(
+            "Prestamo",
+            Item::fixed(Value::new("COP", -3_000_000.0), start),
+        );
+        book.take_audit_log();
+
+        let id = book
+            .apply_payment("Chequing", "Prestamo", paid, Value::new("CAD", 500.0))
+            .unwrap();
+        assert_eq!(book.get(chequing).unwrap().assess(paid).amount(), 1_500.0);
+        let owed = book.get(loan).unwrap().assess(paid);
+        assert_value_eq!(owed, Value::new("COP", -3_000_000.0 + 500.0 * 2911.98), 1e-6);
+
+        let transfer = &book.transfers()[0];
+        assert_eq!(
+            (transfer.id, transfer.from, transfer.to),
+            (id, chequing, loan)
+        );
+        let memo = book.get(loan).unwrap().deltas[0].memo.clone();
+        assert_eq!(memo.as_deref(), Some("Payment from Chequing to Prestamo"));
+        let log = book.take_audit_log();
+        assert_eq!(log.len(), 3);
+        assert!(matches!(&log[1], AuditEntry::DeltaAdded { key, .. } if *key == loan));
+        assert!(matches!(&log[2], AuditEntry::Transferred { transfer } if transfer.id == id));
+
+        // Names that do not pick out one item are errors listing the options.
+        book.add_named("Visa", Item::fixed(Value::new("CAD", -10.0), start));
+        let other = book.add_named("Visa", Item::fixed(Value::new("CAD", -20.0), start));
+        let Err(PaymentError::Ambiguous { candidates, .. }) =
+            book.apply_payment("Chequing", "Visa", paid, Value::new("CAD", 5.0))
+        else {
+            panic!("two items are named Visa");
+        };
+        assert!(candidates.contains(&other) && candidates.len() == 2);
+        let missing = book
+            .apply_payment("Savings", "Visa", paid, Value::new("CAD", 5.0))
+            .unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "no item named \"Savings\", the book has Chequing, Prestamo, Visa, Visa"
+        );
+        assert_eq!(book.transfers().len(), 1);
+    }
+}
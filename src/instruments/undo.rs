@@ -0,0 +1,449 @@
+//! Undoing and redoing mutations made through a [Book].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Arc,
+};
+
+use super::{
+    book::{Book, ItemKey},
+    delta::Delta,
+    item::Item,
+    transfer::Transfer,
+};
+
+/// How many mutations [Book::undoable] remembers unless told otherwise.
+const DEFAULT_DEPTH: usize = 100;
+
+/// Why an undo or redo did not happen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UndoError {
+    /// The book was not made [Book::undoable].
+    NotUndoable,
+    NothingToUndo,
+    NothingToRedo,
+    /// An item the mutation touched is no longer in the book.
+    MissingItem(ItemKey),
+}
+
+impl fmt::Display for UndoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UndoError::NotUndoable => write!(f, "the book does not keep an undo history"),
+            UndoError::NothingToUndo => write!(f, "nothing to undo"),
+            UndoError::NothingToRedo => write!(f, "nothing to redo"),
+            UndoError::MissingItem(key) => write!(f, "item {key:?} is no longer in the book"),
+        }
+    }
+}
+
+impl std::error::Error for UndoError {}
+
+/// A single mutation, with what it takes to do the opposite.
+#[derive(Clone, Debug)]
+pub(crate) enum Change {
+    Added {
+        key: ItemKey,
+    },
+    Removed {
+        key: ItemKey,
+        item: Item,
+        name: Option<String>,
+        external_id: Option<String>,
+        parent: Option<ItemKey>,
+        /// Where the item was in [Book::ordered_keys].
+        sequence: u64,
+    },
+    DeltaAdded {
+        key: ItemKey,
+        delta: Delta,
+    },
+    DeltaRemoved {
+        key: ItemKey,
+        delta: Delta,
+    },
+    Transferred {
+        transfer: Transfer,
+        out: Delta,
+        into: Delta,
+    },
+    Untransferred {
+        transfer: Transfer,
+        out: Delta,
+        into: Delta,
+    },
+    /// `from` is the parent the child had before.
+    Reparented {
+        child: ItemKey,
+        from: Option<ItemKey>,
+    },
+    /// `from` is the name the item had before.
+    Renamed {
+        key: ItemKey,
+        from: Option<String>,
+    },
+    /// `item` is what the item was before it was edited.
+    Replaced {
+        key: ItemKey,
+        item: Arc<Item>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Recording,
+    Undoing,
+    Redoing,
+    /// Taking back a step that failed partway, nothing is recorded.
+    RollingBack,
+}
+
+/// The undo and redo stacks of a book. Every entry is a step of changes made
+/// by one call, such as [Book::add_child] adding and linking an item.
+#[derive(Debug)]
+pub(crate) struct History {
+    depth: usize,
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    mode: Mode,
+    /// Whether changes go into the last step rather than one of their own.
+    grouping: bool,
+    /// Items brought back by an undo or redo get a new key, this maps the
+    /// key they had onto it.
+    forwarded: HashMap<ItemKey, ItemKey>,
+}
+
+impl History {
+    fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            undo: VecDeque::new(),
+            redo: vec![],
+            mode: Mode::Recording,
+            grouping: false,
+            forwarded: HashMap::new(),
+        }
+    }
+    /// A history remembering as much as this one, with nothing in it yet.
+    pub(crate) fn emptied(&self) -> Self {
+        Self::new(self.depth)
+    }
+    fn trim(&mut self) {
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+impl Book {
+    /// Keeps a history of the last 100 mutations made through the book so
+    /// they can be undone, see [Book::undo].
+    pub fn undoable(mut self) -> Self {
+        self.history = Some(History::new(DEFAULT_DEPTH));
+        self
+    }
+    /// Changes how many mutations are remembered, forgetting the oldest.
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        if let Some(history) = &mut self.history {
+            history.depth = depth;
+            history.trim();
+        }
+    }
+    /// Undoes the last mutation. An undone add removes the item, so its key
+    /// no longer resolves, and a redone add or undone remove brings the item
+    /// back under a new key, see [Book::translate].
+    pub fn undo(&mut self) -> Result<(), UndoError> {
+        let history = self.history.as_mut().ok_or(UndoError::NotUndoable)?;
+        let step = history.undo.pop_back().ok_or(UndoError::NothingToUndo)?;
+        history.redo.push(vec![]);
+        self.apply_step(step, Mode::Undoing)
+    }
+    /// Redoes the last undone mutation, new mutations clear what can be redone.
+    pub fn redo(&mut self) -> Result<(), UndoError> {
+        let history = self.history.as_mut().ok_or(UndoError::NotUndoable)?;
+        let step = history.redo.pop().ok_or(UndoError::NothingToRedo)?;
+        history.undo.push_back(vec![]);
+        let replayed = self.apply_step(step, Mode::Redoing);
+        self.history.as_mut().unwrap().trim();
+        replayed
+    }
+    /// The key an item has now. Undo and redo bring items back under new
+    /// keys, any key it had before resolves to the current one. `None` if
+    /// the item is not in the book.
+    pub fn translate(&self, key: ItemKey) -> Option<ItemKey> {
+        let key = self.current(key);
+        self.get(key).map(|_| key)
+    }
+    fn current(&self, mut key: ItemKey) -> ItemKey {
+        if let Some(history) = &self.history {
+            while let Some(next) = history.forwarded.get(&key) {
+                key = *next;
+            }
+        }
+        key
+    }
+    /// Does the opposite of every change in the step, last first. The
+    /// opposites are recorded onto the other stack as they happen. If one
+    /// fails those already done are taken back and the step stays where it
+    /// was, so the book is left as it was before.
+    fn apply_step(&mut self, step: Vec<Change>, mode: Mode) -> Result<(), UndoError> {
+        self.history.as_mut().unwrap().mode = mode;
+        let replayed = step
+            .iter()
+            .rev()
+            .try_for_each(|change| self.invert(change.clone()));
+        let history = self.history.as_mut().unwrap();
+        if replayed.is_err() {
+            let done = match mode {
+                Mode::Undoing => history.redo.pop(),
+                _ => history.undo.pop_back(),
+            };
+            history.mode = Mode::RollingBack;
+            for change in done.into_iter().flatten().rev() {
+                self.invert(change)
+                    .expect("changes just made can be taken back");
+            }
+            let history = self.history.as_mut().unwrap();
+            match mode {
+                Mode::Undoing => history.undo.push_back(step),
+                _ => history.redo.push(step),
+            }
+        }
+        self.history.as_mut().unwrap().mode = Mode::Recording;
+        replayed
+    }
+    fn invert(&mut self, change: Change) -> Result<(), UndoError> {
+        let found =
+            |key: ItemKey, book: &Book| book.translate(key).ok_or(UndoError::MissingItem(key));
+        match change {
+            Change::Added { key } => {
+                self.remove(found(key, self)?);
+            }
+            Change::Removed {
+                key,
+                item,
+                name,
+                external_id,
+                parent,
+                sequence,
+            } => {
+                let parent = parent.map(|p| found(p, self)).transpose()?;
+                let restored = self.restore(item, name, external_id, parent, sequence);
+                if let Some(history) = &mut self.history {
+                    history.forwarded.insert(key, restored);
+                }
+            }
+            Change::DeltaAdded { key, delta } => {
+                self.remove_delta(found(key, self)?, delta.id);
+            }
+            Change::DeltaRemoved { key, delta } => {
+                self.add_delta(found(key, self)?, delta);
+            }
+            Change::Transferred {
+                mut transfer,
+                out,
+                into,
+            } => {
+                transfer.from = found(transfer.from, self)?;
+                transfer.to = found(transfer.to, self)?;
+                self.untransfer(transfer, out, into);
+            }
+            Change::Untransferred {
+                mut transfer,
+                out,
+                into,
+            } => {
+                transfer.from = found(transfer.from, self)?;
+                transfer.to = found(transfer.to, self)?;
+                self.retransfer(transfer, out, into);
+            }
+            Change::Reparented { child, from } => {
+                let from = from.map(|p| found(p, self)).transpose()?;
+                self.reparent(found(child, self)?, from);
+            }
+            Change::Renamed { key, from } => {
+                self.set_name(found(key, self)?, from);
+            }
+            Change::Replaced { key, item } => {
+                self.replace_item(found(key, self)?, item);
+            }
+        }
+        Ok(())
+    }
+    /// Remembers a change so it can be undone.
+    pub(crate) fn record(&mut self, change: Change) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        match history.mode {
+            Mode::RollingBack => {}
+            Mode::Undoing => history.redo.last_mut().unwrap().push(change),
+            Mode::Redoing => history.undo.back_mut().unwrap().push(change),
+            Mode::Recording if history.grouping => history.undo.back_mut().unwrap().push(change),
+            Mode::Recording => {
+                history.undo.push_back(vec![change]);
+                history.redo.clear();
+                history.trim();
+            }
+        }
+    }
+    /// Runs the mutations so they are undone as a single step.
+    pub(crate) fn grouped<T>(&mut self, mutate: impl FnOnce(&mut Book) -> T) -> T {
+        let Some(history) = self
+            .history
+            .as_mut()
+            .filter(|h| h.mode == Mode::Recording && !h.grouping)
+        else {
+            return mutate(self);
+        };
+        history.undo.push_back(vec![]);
+        history.redo.clear();
+        history.grouping = true;
+        let result = mutate(self);
+        let history = self.history.as_mut().unwrap();
+        history.grouping = false;
+        if history.undo.back().is_some_and(Vec::is_empty) {
+            history.undo.pop_back();
+        }
+        history.trim();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::instruments::{
+        book::{Book, ItemKey},
+        delta::Delta,
+        item::Item,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{Change, UndoError};
+
+    #[test]
+    pub fn test_undo_redo() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default().undoable();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        let visa = book.add(Item::fixed(Value::new("CAD", -400.0), start));
+        let before = book.assess(start).amount();
+
+        // A transfer and a payment undo back to the same assessment.
+        book.transfer(chequing, visa, start, Value::new("CAD", 400.0));
+        book.add_delta(chequing, Delta::payment(start, Value::new("CAD", 50.0)));
+        assert_eq!(book.assess(start).amount(), 650.0);
+        book.undo().unwrap();
+        book.undo().unwrap();
+        assert_eq!(book.assess(start).amount(), before);
+        assert!(book.transfers().is_empty());
+        book.redo().unwrap();
+        assert_eq!(book.get(visa).unwrap().assess(start).amount(), 0.0);
+
+        // Undoing an add invalidates the key, redoing hands out a new one.
+        book.undo().unwrap();
+        book.undo().unwrap();
+        assert!(book.get(visa).is_none());
+        assert_eq!(book.translate(visa), None);
+        book.redo().unwrap();
+        let restored = book.translate(visa).unwrap();
+        assert_ne!(restored, visa);
+        assert_eq!(book.get(restored).unwrap().assess(start).amount(), -400.0);
+        // The transfer is redone onto the restored item.
+        book.redo().unwrap();
+        assert_eq!(book.get(restored).unwrap().assess(start).amount(), 0.0);
+        assert_eq!(book.transfers()[0].to, restored);
+        book.redo().unwrap();
+        assert_eq!(book.assess(start).amount(), 650.0);
+        assert_eq!(book.redo(), Err(UndoError::NothingToRedo));
+
+        // Removing keeps the name and the parent link when undone.
+        let bonus = book.add_child(Item::fixed(Value::new("CAD", 5.0), start), chequing);
+        book.rename(chequing, "Joint");
+        let total = book.assess(start).amount();
+        book.remove(chequing);
+        book.undo().unwrap();
+        let chequing = book.translate(chequing).unwrap();
+        assert_eq!(book.name(chequing), Some("Joint"));
+        assert_eq!(book.get(chequing).unwrap().children, [bonus]);
+        assert_eq!(book.assess(start).amount(), total);
+        book.undo().unwrap();
+        assert_eq!(book.by_name("Chequing"), Some(chequing));
+        // Adding a child is a single step.
+        book.undo().unwrap();
+        assert!(book.get(bonus).is_none());
+        assert!(book.get(chequing).unwrap().children.is_empty());
+
+        // An edit through get_mut goes back to the item it was.
+        let opened = |book: &Book| book.get(chequing).unwrap().book_value.amount();
+        book.get_mut(chequing).unwrap().book_value = Value::new("CAD", 10.0);
+        assert_eq!(opened(&book), 10.0);
+        book.undo().unwrap();
+        assert_eq!(opened(&book), 1_000.0);
+        book.redo().unwrap();
+        assert_eq!(opened(&book), 10.0);
+    }
+
+    /// Undoing a removal puts the item back where it was in the order, and
+    /// the audit log replays to the same order.
+    #[test]
+    pub fn test_undo_remove_keeps_order() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default().undoable();
+        let keys: Vec<_> = [1.0, 2.0, 3.0]
+            .into_iter()
+            .map(|amount| book.add(Item::fixed(Value::new("CAD", amount), start)))
+            .collect();
+        book.remove(keys[1]);
+        book.undo().unwrap();
+
+        let amounts = |book: &Book| -> Vec<f64> {
+            book.iter_ordered()
+                .map(|(_, item)| item.book_value.amount())
+                .collect()
+        };
+        assert_eq!(amounts(&book), [1.0, 2.0, 3.0]);
+        let replayed = Book::replay(book.audit_log()).unwrap();
+        assert_eq!(amounts(&replayed), [1.0, 2.0, 3.0]);
+    }
+
+    /// A step that fails partway is taken back and can still be undone.
+    #[test]
+    pub fn test_failed_step_kept() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default().undoable();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 1.0), start));
+        book.rename(chequing, "Joint");
+        // The rename is undone first, then the step runs into an item that
+        // is not in the book.
+        let gone = ItemKey::default();
+        let history = book.history.as_mut().unwrap();
+        history.undo.back_mut().unwrap().insert(0, Change::Renamed { key: gone, from: None });
+
+        assert_eq!(book.undo(), Err(UndoError::MissingItem(gone)));
+        assert_eq!(book.name(chequing), Some("Joint"));
+        let history = book.history.as_ref().unwrap();
+        assert_eq!((history.undo.len(), history.redo.len()), (2, 0));
+        assert_eq!(book.redo(), Err(UndoError::NothingToRedo));
+    }
+
+    #[test]
+    pub fn test_undo_depth() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(Book::default().undo(), Err(UndoError::NotUndoable));
+
+        let mut book = Book::default().undoable();
+        book.set_undo_depth(2);
+        for amount in [1.0, 2.0, 3.0] {
+            book.add(Item::fixed(Value::new("CAD", amount), start));
+        }
+        book.undo().unwrap();
+        book.undo().unwrap();
+        assert_eq!(book.undo(), Err(UndoError::NothingToUndo));
+        assert_eq!(book.assess(start).amount(), 1.0);
+    }
+}
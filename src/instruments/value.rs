@@ -1,79 +1,377 @@
-use std::{borrow::Borrow, collections::HashMap, fmt::Debug, hash::Hash, iter::Sum, ops::{Add, Mul}, sync::RwLock};
+use std::{borrow::Borrow, cell::RefCell, collections::{HashMap, HashSet}, fmt::{self, Debug}, iter::Sum, ops::{Add, Mul}, str::FromStr, sync::{atomic::{AtomicUsize, Ordering}, Mutex}};
 
 use once_cell::sync::Lazy;
 
-use super::convert::{ConversionTable, CURRENCY_EXCHANGE};
+use super::convert::{ConversionError, ConversionTable, RateSnapshot, CURRENCY_EXCHANGE};
 
+/// Currency codes that only became known at runtime, each is leaked once.
+static INTERNED: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(Mutex::default);
+
+/// Minor unit counts registered with [Currency::custom_with_decimals].
+static DECIMALS: Lazy<Mutex<HashMap<Currency, u32>>> = Lazy::new(Mutex::default);
+/// Bumped on every registration, zero while there are none.
+static DECIMALS_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Each thread's copy of [DECIMALS] and the version it was taken at, so
+    /// [Currency::decimals] only locks after a registration.
+    static DECIMALS_SEEN: RefCell<(usize, HashMap<Currency, u32>)> = RefCell::default();
+}
 
 
 
+
+/// A currency identified by its code, such as `CAD`.
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
-pub struct Currency(Option<&'static str>);
+pub struct Currency(&'static str);
 
 impl Currency {
-    pub fn null() -> Self {
-        Self(None)
-    }
     pub fn new(currency: &'static str) -> Self {
-        Self(Some(currency))
+        Self(currency)
+    }
+    /// Creates a currency from a code that is only known at runtime,
+    /// such as one read from a file. The code is used as is, see
+    /// [Currency::from_str] for a validating alternative.
+    pub fn intern(code: &str) -> Self {
+        let mut interned = INTERNED.lock().unwrap();
+        match interned.get(code) {
+            Some(existing) => Self(existing),
+            None => {
+                let leaked: &'static str = Box::leak(code.to_string().into_boxed_str());
+                interned.insert(leaked);
+                Self(leaked)
+            }
+        }
+    }
+    /// The code of the currency, such as `CAD`.
+    pub fn code(&self) -> &'static str {
+        self.0
     }
-    pub fn is_null(&self) -> bool {
-        self.0.is_none()
+    /// The most decimal places a currency can have, minor units past this
+    /// would overflow [Value::to_minor_units].
+    pub const MAX_DECIMALS: u32 = 18;
+    /// Creates a currency with its own number of decimal places, such as
+    /// `BTC` with 8. This overrides the ISO 4217 count for known codes, and
+    /// is capped at [Currency::MAX_DECIMALS].
+    pub fn custom_with_decimals(code: &str, decimals: u32) -> Self {
+        let currency = Self::intern(code);
+        let mut registered = DECIMALS.lock().unwrap();
+        registered.insert(currency, decimals.min(Self::MAX_DECIMALS));
+        DECIMALS_VERSION.fetch_add(1, Ordering::Release);
+        currency
     }
-    pub fn name(&self) -> &'static str {
+    /// How many decimal places the minor unit has, 2 for cents. Unless set
+    /// with [Currency::custom_with_decimals] this follows ISO 4217 and
+    /// defaults to 2 for codes it does not know.
+    pub fn decimals(&self) -> u32 {
+        let version = DECIMALS_VERSION.load(Ordering::Acquire);
+        if version != 0 {
+            let custom = DECIMALS_SEEN.with(|seen| {
+                let mut seen = seen.borrow_mut();
+                if seen.0 != version {
+                    *seen = (version, DECIMALS.lock().unwrap().clone());
+                }
+                seen.1.get(self).copied()
+            });
+            if let Some(decimals) = custom {
+                return decimals;
+            }
+        }
         match self.0 {
-            None => "NaN",
-            Some(a) => a
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF"
+            | "UGX" | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl AsRef<str> for Currency {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+/// Why a currency code could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CurrencyError {
+    /// Codes are between three and eight characters long.
+    Length(usize),
+    /// Codes are ASCII letters and digits.
+    Character(char),
+}
+
+impl fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurrencyError::Length(length) => {
+                write!(f, "currency codes are 3 to 8 characters long, not {length}")
+            }
+            CurrencyError::Character(c) => write!(f, "{c:?} is not allowed in a currency code"),
         }
     }
 }
 
+impl std::error::Error for CurrencyError {}
 
+impl FromStr for Currency {
+    type Err = CurrencyError;
+    /// Parses a code of three to eight ASCII letters or digits, the code
+    /// is uppercased so `cad` is [Currency] `CAD`.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        if let Some(c) = code.chars().find(|c| !c.is_ascii_alphanumeric()) {
+            return Err(CurrencyError::Character(c));
+        }
+        if !(3..=8).contains(&code.len()) {
+            return Err(CurrencyError::Length(code.len()));
+        }
+        Ok(Self::intern(&code.to_ascii_uppercase()))
+    }
+}
 
 #[derive(Clone)]
 pub struct Value {
     currency: Currency,
-    // conversion_table: Option<&'static ConversionTable>,
+    /// The rates the value converts at, the global exchange if detached.
+    rates: Option<RateSnapshot>,
     amount: f64
 }
 
-impl Sum<Value> for Value {
+/// Sums of values are `None` when empty, there is no currency to give
+/// their zero. See [kahan_sum].
+impl Sum<Value> for Option<Value> {
     fn sum<I: Iterator<Item = Value>>(iter: I) -> Self {
         kahan_sum(iter)
     }
 }
 
-impl<'a> Sum<&'a Value> for Value {
+impl<'a> Sum<&'a Value> for Option<Value> {
     fn sum<I: Iterator<Item = &'a Value>>(iter: I) -> Self {
         kahan_sum(iter)
     }
 }
 
 
-/// Calculates the Kahan sum and returns a new currency sum.
-pub fn kahan_sum<I, V>(iter: I) -> Value
-    where 
-        I: Iterator<Item = V>,
-        V: Borrow<Value>
+/// Calculates the Kahan sum in the currency of the first value, the others
+/// are converted into it like [Add] does. `None` for an empty sum as it has
+/// no currency.
+pub fn kahan_sum<I, V>(iter: I) -> Option<Value>
+where
+    I: Iterator<Item = V>,
+    V: Borrow<Value>,
 {
-    let mut sum = 0.0;
-    let mut c = 0.0;
+    let mut sum = KahanSum::default();
+    let mut iter = iter.peekable();
+    let first = iter.peek().map(|v| v.borrow().clone())?;
+    for item in iter {
+        sum.add(first.amount_of(item.borrow()));
+    }
+    Some(Value {
+        amount: sum.total(),
+        ..first
+    })
+}
 
-    let mut cur = Currency::new("CAD");
+/// A running compensated sum of bare amounts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KahanSum {
+    sum: f64,
+    c: f64,
+    /// The magnitudes of every compensation, see [SumDiagnostics].
+    compensation: f64,
+    /// The magnitudes of every amount added.
+    magnitude: f64,
+}
 
-    for item in iter {
-        let item = item.borrow();
-        cur = item.currency;
+impl KahanSum {
+    pub fn add(&mut self, amount: f64) {
+        let y = amount + self.c;
+        (self.sum, self.c) = fast2sum(self.sum, y);
+        self.compensation += self.c.abs();
+        self.magnitude += amount.abs();
+    }
+    pub fn total(&self) -> f64 {
+        self.sum
+    }
+    /// How far the total can be trusted.
+    pub fn diagnostics(&self) -> SumDiagnostics {
+        SumDiagnostics {
+            total: self.sum,
+            compensation: self.compensation,
+            magnitude: self.magnitude,
+        }
+    }
+}
+
+/// The fraction of a total the error of a float sum may reach before
+/// [SumDiagnostics::warning] warns, see
+/// [Book::set_precision_tolerance](super::book::Book::set_precision_tolerance).
+pub const PRECISION_TOLERANCE: f64 = 1e-9;
+
+/// What a [KahanSum] knows about its own error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SumDiagnostics {
+    pub total: f64,
+    /// The magnitudes of the low order parts carried along the sum.
+    pub compensation: f64,
+    /// The magnitudes of every amount added.
+    pub magnitude: f64,
+}
+
+impl SumDiagnostics {
+    /// The most the total may be off by, the compensation plus the rounding
+    /// each amount already carried when it was added.
+    pub fn error_bound(&self) -> f64 {
+        self.compensation + self.magnitude * f64::EPSILON
+    }
+    /// A warning when the error bound is more than `tolerance` of the total,
+    /// as happens when large amounts cancel out. One per sum however many
+    /// amounts cancel.
+    pub fn warning(&self, tolerance: f64) -> Option<PrecisionWarning> {
+        let error_bound = self.error_bound();
+        (error_bound > tolerance * self.total.abs()).then_some(PrecisionWarning {
+            total: self.total,
+            error_bound,
+            magnitude: self.magnitude,
+        })
+    }
+}
+
+/// A float total that may be mostly rounding error, see
+/// [SumDiagnostics::warning]. The decimal path gives the exact total.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrecisionWarning {
+    pub total: f64,
+    pub error_bound: f64,
+    /// The magnitudes of every amount that went into the total.
+    pub magnitude: f64,
+}
+
+impl PrecisionWarning {
+    /// The error bound as a fraction of the total.
+    pub fn fraction(&self) -> f64 {
+        self.error_bound / self.total.abs()
+    }
+}
+
+impl fmt::Display for PrecisionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the total of {} may be off by up to {}, amounts of {} cancelled out",
+            self.total, self.error_bound, self.magnitude
+        )
+    }
+}
 
+/// Running compensated sums of values kept apart by currency, in the order
+/// each currency was first added.
+#[derive(Clone, Debug, Default)]
+pub struct MultiCurrencySum {
+    sums: Vec<(Currency, KahanSum)>,
+}
 
-        let y = item.amount + c;
-        (sum, c) = fast2sum(sum, y)
+impl MultiCurrencySum {
+    pub fn add(&mut self, value: &Value) {
+        match self.sums.iter_mut().find(|(c, _)| *c == value.currency) {
+            Some((_, sum)) => sum.add(value.amount),
+            None => {
+                let mut sum = KahanSum::default();
+                sum.add(value.amount);
+                self.sums.push((value.currency, sum));
+            }
+        }
+    }
+    /// The total of each currency.
+    pub fn totals(&self) -> impl Iterator<Item = (Currency, Value)> + '_ {
+        self.sums
+            .iter()
+            .map(|(currency, sum)| (*currency, Value::new(*currency, sum.total())))
     }
+    /// Converts every total into `target` with the undated rates of the
+    /// table and sums them.
+    pub fn into_single(self, target: Currency, table: &ConversionTable) -> Result<Value, ConversionError> {
+        let mut total = KahanSum::default();
+        for (currency, sum) in self.sums {
+            total.add(sum.total() * table.rate(currency, target)?);
+        }
+        Ok(Value::new(target, total.total()))
+    }
+}
 
+/// How an amount is rounded to a number of decimals, see
+/// [Item::round_each_period](super::item::Item::round_each_period).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Halves go away from zero, as most statements do.
+    #[default]
+    HalfAwayFromZero,
+    /// Halves go to the even neighbour, banker's rounding.
+    HalfEven,
+    /// Drops anything past the decimals.
+    TowardZero,
+}
 
-    Value {
-        amount: sum,
-        currency: cur,
+impl Rounding {
+    pub fn round(&self, amount: f64, decimals: u32) -> f64 {
+        self.scaled(amount, decimals) / 10f64.powi(decimals as i32)
+    }
+    /// The amount as a whole number of `10^-decimals`.
+    fn scaled(&self, amount: f64, decimals: u32) -> f64 {
+        let scaled = amount * 10f64.powi(decimals as i32);
+        match self {
+            Rounding::HalfAwayFromZero => scaled.round(),
+            Rounding::HalfEven => scaled.round_ties_even(),
+            Rounding::TowardZero => scaled.trunc(),
+        }
+    }
+}
+
+/// A [Value] rounded to a whole number of units, so it can be hashed and
+/// compared exactly to key caches and deduplicate results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ValueKey {
+    currency: Currency,
+    decimals: u32,
+    units: i128,
+}
+
+impl ValueKey {
+    /// The value rounded to `decimals` places, which may be finer than the
+    /// minor unit of its currency.
+    pub fn new(value: &Value, decimals: u32, rounding: Rounding) -> Self {
+        Self {
+            currency: value.currency,
+            decimals,
+            units: rounding.scaled(value.amount, decimals) as i128,
+        }
+    }
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+    /// The amount in units of `10^-decimals`.
+    pub fn units(&self) -> i128 {
+        self.units
+    }
+    pub fn decimals(&self) -> u32 {
+        self.decimals
+    }
+    /// The value the key stands for.
+    pub fn value(&self) -> Value {
+        Value::new(self.currency, self.units as f64 / 10f64.powi(self.decimals as i32))
+    }
+}
+
+impl From<(&Value, Rounding)> for ValueKey {
+    /// Rounds to the minor unit of the currency, see [Currency::decimals].
+    fn from((value, rounding): (&Value, Rounding)) -> Self {
+        ValueKey::new(value, value.currency.decimals(), rounding)
     }
 }
     
@@ -89,9 +387,9 @@ impl Debug for Value {
         let cents= ((self.amount.abs() - (main as f64)) * 100.0).floor() as usize;
 
         if self.amount < 0.0 {
-            write!(f, "-{principal}.{cents}{}", self.currency.name())
+            write!(f, "-{principal}.{cents}{}", self.currency.code())
         } else {
-            write!(f, "{principal}.{cents}{}", self.currency.name())
+            write!(f, "{principal}.{cents}{}", self.currency.code())
         }
         
     }
@@ -106,15 +404,17 @@ impl Value {
         Self {
             amount: amount.into(),
             currency: cur.into(),
+            rates: None,
         }
     }
-    pub fn zero<C: Into<Currency>>() -> Self {
-        Self::new(Currency::null(), 0.0)
+    pub fn zero<C: Into<Currency>>(cur: C) -> Self {
+        Self::new(cur, 0.0)
     }
     pub fn negate(&self) -> Self {
         Self {
-            amount: self.amount * -1.0,
-            currency: self.currency
+            amount: -self.amount,
+            currency: self.currency,
+            rates: self.rates.clone(),
         }
     }
 
@@ -132,9 +432,96 @@ impl Value {
     pub fn currency(&self) -> Currency {
         self.currency
     }
+    /// Whether the two are at most `tolerance` apart in the currency of
+    /// `self`. The other value is converted like in a sum, at the rates
+    /// either is attached to or else the global exchange, values that
+    /// cannot be converted are never equal. See
+    /// [assert_value_eq!](crate::assert_value_eq).
+    pub fn approx_eq(&self, other: &Value, tolerance: f64) -> bool {
+        self.try_amount_of(other)
+            .is_ok_and(|amount| (self.amount - amount).abs() <= tolerance)
+    }
+    /// The same value attached to `rates`, it converts at them from now on
+    /// however the [Exchange](super::convert::Exchange) they came from changes.
+    pub fn rebased(&self, rates: &RateSnapshot) -> Value {
+        Self {
+            rates: Some(rates.clone()),
+            ..self.clone()
+        }
+    }
+    /// The rates the value is attached to, `None` if it converts at the
+    /// global exchange.
+    pub fn rates(&self) -> Option<&RateSnapshot> {
+        self.rates.as_ref()
+    }
+    /// Converts into `target` at the attached rates or else the global
+    /// exchange, the result is attached to the rates it converted at.
+    pub fn convert_to(&self, target: Currency) -> Result<Value, ConversionError> {
+        match &self.rates {
+            Some(rates) => rates.try_convert(self, target),
+            None => CURRENCY_EXCHANGE.try_convert(self.clone(), target),
+        }
+    }
+    /// The amount of `other` in the currency of `self`, converted at the
+    /// rates `self` is attached to, else those of `other`, else the global
+    /// exchange. Panics without a rate.
+    fn amount_of(&self, other: &Value) -> f64 {
+        self.try_amount_of(other).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// [Value::amount_of], failing without a rate.
+    fn try_amount_of(&self, other: &Value) -> Result<f64, ConversionError> {
+        if self.currency == other.currency {
+            return Ok(other.amount);
+        }
+        match self.rates.as_ref().or(other.rates.as_ref()) {
+            Some(rates) => rates.rate(other.currency, self.currency),
+            None => CURRENCY_EXCHANGE.rate(other.currency, self.currency),
+        }
+        .map(|rate| other.amount * rate)
+    }
+    /// Rounds to the minor unit of the currency, halves away from zero.
+    pub fn rounded_to_currency(&self) -> Self {
+        let scale = 10f64.powi(self.currency.decimals() as i32);
+        Self {
+            amount: (self.amount * scale).round() / scale,
+            currency: self.currency,
+            rates: self.rates.clone(),
+        }
+    }
+    /// The amount as a whole number of minor units, such as cents or
+    /// satoshis, rounded halves away from zero.
+    pub fn to_minor_units(&self) -> i128 {
+        (self.amount * 10f64.powi(self.currency.decimals() as i32)).round() as i128
+    }
+    /// The key of the value at the minor unit of its currency.
+    pub fn key(&self, rounding: Rounding) -> ValueKey {
+        ValueKey::from((self, rounding))
+    }
+    /// Groups values by their currency, keeping the order within each.
+    pub fn currency_buckets<I>(values: I) -> HashMap<Currency, Vec<Value>>
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        let mut buckets: HashMap<Currency, Vec<Value>> = HashMap::new();
+        for value in values {
+            buckets.entry(value.currency).or_default().push(value);
+        }
+        buckets
+    }
+    /// The inverse of [Value::to_minor_units].
+    pub fn from_minor_units<C: Into<Currency>>(cur: C, units: i128) -> Self {
+        let currency = cur.into();
+        Self::new(currency, units as f64 / 10f64.powi(currency.decimals() as i32))
+    }
+}
 
-  
-  
+impl fmt::Display for Value {
+    /// The amount to the minor unit of the currency followed by its code,
+    /// such as `0.01530000 BTC`. A precision given to the formatter wins.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(self.currency.decimals() as usize);
+        write!(f, "{:.*} {}", decimals, self.amount, self.currency)
+    }
 }
 
 impl Mul<f64> for Value {
@@ -142,7 +529,7 @@ impl Mul<f64> for Value {
     fn mul(self, rhs: f64) -> Self::Output {
         Self {
             amount: self.amount * rhs,
-            currency: self.currency
+            ..self
         }
     }    
 }
@@ -152,24 +539,23 @@ impl Mul<f64> for &Value {
     fn mul(self, rhs: f64) -> Self::Output {
         Value {
             amount: self.amount * rhs,
-            currency: self.currency
+            currency: self.currency,
+            rates: self.rates.clone(),
         }
     }    
 }
 
+/// The sum is in the currency of the left operand, the right is converted
+/// at the rates the left is attached to, else those of the right, else the
+/// global exchange.
 impl Add<Value> for Value {
     type Output = Value;
     fn add(self, rhs: Value) -> Self::Output {
-        if self.currency == rhs.currency {
-            Self {
-                amount: self.amount + rhs.amount,
-                currency: rhs.currency
-            }
-        } else {
-            // println!("yes {:?}", CURRENCY_EXCHANGE.convert(rhs.clone(), self.currency));
-            // CURRENCY_EXCHANGE.convert(rhs, self.currency).unwrap() + self
-         
-            CURRENCY_EXCHANGE.convert(rhs, self.currency).unwrap() + self
+        let rates = self.rates.clone().or_else(|| rhs.rates.clone());
+        let left = Self { rates, ..self };
+        Self {
+            amount: left.amount + left.amount_of(&rhs),
+            ..left
         }
     }
 }
@@ -177,9 +563,9 @@ impl Add<Value> for Value {
 
 
 
-impl Into<Currency> for &'static str {
-    fn into(self) -> Currency {
-        Currency::new(self)
+impl From<&'static str> for Currency {
+    fn from(value: &'static str) -> Self {
+        Currency::new(value)
     }
 }
 
@@ -195,11 +581,17 @@ fn fast2sum(a: f64, b: f64) -> (f64, f64) {
 
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
   
-    use crate::instruments::{convert::CURRENCY_EXCHANGE, value::Currency};
+    use crate::instruments::convert::CURRENCY_EXCHANGE;
 
-    use super::{ConversionTable, Value};
+    use std::collections::HashSet;
+
+    use super::{
+        kahan_sum, Currency, CurrencyError, KahanSum, MultiCurrencySum, Rounding,
+        Value, ValueKey, PRECISION_TOLERANCE,
+    };
 
 
     /// Checks to see if Kahan summation formulae
@@ -212,7 +604,31 @@ mod tests {
             Value::dummy("CAD", 32773.022123)
         ];
 
-        assert!((values.iter().sum::<Value>().amount - 3972187.07).abs() < 0.01)
+        let total = values.iter().sum::<Option<Value>>().unwrap();
+        crate::assert_value_eq!(total, Value::new("CAD", 3972187.07), 0.01);
+    }
+
+    #[test]
+    pub fn test_sum_diagnostics() {
+        let mut everyday = KahanSum::default();
+        for amount in [1_250.37, -480.12, 99.99] {
+            everyday.add(amount);
+        }
+        assert!(everyday.diagnostics().warning(PRECISION_TOLERANCE).is_none());
+
+        // Offsetting amounts leave a total smaller than their rounding.
+        let mut offsetting = KahanSum::default();
+        for amount in [1e12 + 0.01, -1e12 - 0.03, 5.0] {
+            offsetting.add(amount);
+        }
+        let diagnostics = offsetting.diagnostics();
+        assert_eq!(diagnostics.magnitude, 2e12 + 5.04);
+        let warning = diagnostics.warning(PRECISION_TOLERANCE).unwrap();
+        assert_eq!(warning.total, offsetting.total());
+        assert!(warning.fraction() > 1e-5);
+        // A loose enough tolerance accepts it.
+        assert!(diagnostics.warning(1e-3).is_none());
+        assert_eq!(KahanSum::default().diagnostics().warning(0.0), None);
     }
 
 
@@ -229,7 +645,132 @@ mod tests {
 
 
         let total = bob + alice;
-        assert!((total.amount - (206.0 + 28.0)).abs() < 0.1)
+        crate::assert_value_eq!(total, Value::new("CAD", 206.0 + 28.0), 0.1);
        
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_currency_codes() {
+        assert_eq!("cad".parse::<Currency>(), Ok(Currency::new("CAD")));
+        assert_eq!("C A D".parse::<Currency>(), Err(CurrencyError::Character(' ')));
+        assert_eq!("CA".parse::<Currency>(), Err(CurrencyError::Length(2)));
+        assert_eq!("BTC2".parse::<Currency>().unwrap().code(), "BTC2");
+
+        let cop = Currency::new("COP");
+        assert_eq!(cop.to_string().parse::<Currency>(), Ok(cop));
+        assert_eq!(cop.as_ref(), "COP");
+    }
+
+    #[test]
+    pub fn test_currency_decimals() {
+        let btc = Currency::custom_with_decimals("BTC", 8);
+        assert_eq!(Currency::new("BTC").decimals(), 8);
+        assert_eq!(Currency::new("CAD").decimals(), 2);
+        assert_eq!(Currency::new("JPY").decimals(), 0);
+
+        let holding = Value::new(btc, 0.0153);
+        assert_eq!(holding.to_string(), "0.01530000 BTC");
+        assert_eq!(holding.to_minor_units(), 1_530_000);
+        let back = Value::from_minor_units(btc, holding.to_minor_units());
+        assert_eq!(back.to_string(), "0.01530000 BTC");
+        assert_eq!(back.amount(), 0.0153);
+
+        // Sub-satoshi dust is rounded away, not the satoshis.
+        let dusty = Value::new(btc, 0.015_300_004_9);
+        assert_eq!(dusty.rounded_to_currency().amount(), 0.0153);
+        assert_eq!(Value::new("CAD", 1_395.888_768).to_string(), "1395.89 CAD");
+        assert_eq!(format!("{:.1}", Value::new("CAD", 2.26)), "2.3 CAD");
+
+        // Registrations on other threads are seen here too, and capped.
+        std::thread::spawn(|| Currency::custom_with_decimals("RDX", u32::MAX))
+            .join()
+            .unwrap();
+        assert_eq!(Currency::new("RDX").decimals(), Currency::MAX_DECIMALS);
+        assert_eq!(Value::new("RDX", 1.5).to_minor_units(), 1_500_000_000_000_000_000);
+    }
+
+    /// Each currency sums exactly as it would on its own.
+    #[test]
+    pub fn test_multi_currency_sum() {
+        let codes = ["MCA", "MCB", "MCC"];
+        let mut sum = MultiCurrencySum::default();
+        let mut apart = [KahanSum::default(); 3];
+        for i in 0..3_000u32 {
+            let amount = 1e8 / (i as f64 + 1.0) * if i % 7 == 0 { -1.0 } else { 1.0 };
+            let which = (i * 5 + i / 3) as usize % 3;
+            sum.add(&Value::new(codes[which], amount));
+            apart[which].add(amount);
+        }
+        let totals: Vec<_> = sum.totals().collect();
+        assert_eq!(totals.len(), 3);
+        for (currency, total) in &totals {
+            let which = codes.iter().position(|c| *c == currency.code()).unwrap();
+            assert_eq!(total.amount().to_bits(), apart[which].total().to_bits());
+        }
+
+        CURRENCY_EXCHANGE.add_conversion("MCB", "MCA", 2.0);
+        let mut small = MultiCurrencySum::default();
+        small.add(&Value::new("MCA", 10.0));
+        small.add(&Value::new("MCB", 5.0));
+        small.add(&Value::new("MCA", 1.5));
+        let single = small.clone().into_single(Currency::new("MCA"), &CURRENCY_EXCHANGE);
+        assert_eq!(single.unwrap().amount(), 21.5);
+        assert!(small.into_single(Currency::new("MCC"), &CURRENCY_EXCHANGE).is_err());
+    }
+
+    #[test]
+    pub fn test_approx_eq() {
+        let cad = |amount| Value::new("CAD", amount);
+        assert!(cad(10.0).approx_eq(&cad(10.5), 0.5));
+        assert!(!cad(10.0).approx_eq(&cad(10.5), 0.4999));
+        assert!(cad(-55.11).approx_eq(&cad(-55.11), 0.0));
+
+        // Either direction of a rate converts, the tolerance is in the
+        // currency of the left side.
+        CURRENCY_EXCHANGE.add_conversion("AQA", "AQB", 4.0);
+        let (a, b) = (Value::new("AQA", 1.0), Value::new("AQB", 4.5));
+        assert!(a.approx_eq(&b, 0.125) && !a.approx_eq(&b, 0.12));
+        assert!(b.approx_eq(&a, 0.5) && !b.approx_eq(&a, 0.49));
+        assert!(!a.approx_eq(&Value::new("AQC", 1.0), f64::INFINITY));
+
+        crate::assert_value_eq!(a, Value::new("AQB", 4.0), 0.0);
+        let failed =
+            std::panic::catch_unwind(|| crate::assert_value_eq!(cad(1.0), cad(1.001), 1e-4));
+        let message = *failed.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("left: 1.000000 CAD") && message.contains("right: 1.001000 CAD"));
+    }
+
+    #[test]
+    pub fn test_value_keys() {
+        let a = Value::new("CAD", 1.0000015);
+        let b = Value::new("CAD", 1.0000015 - 1e-12);
+        let cents = Rounding::HalfAwayFromZero;
+        assert_eq!(a.key(cents), b.key(cents));
+        assert_eq!(a.key(cents).units(), 100);
+        let micro = |v: &Value| ValueKey::new(v, 6, cents);
+        assert_ne!(micro(&a), micro(&b));
+        assert_eq!(micro(&a).units(), 1_000_002);
+        assert_eq!(micro(&b).value().amount(), 1.000001);
+
+        // Currencies never share a key, and keys deduplicate in sets.
+        let usd = Value::new("USD", 1.0);
+        let keys: HashSet<ValueKey> = [&a, &b, &usd].iter().map(|v| v.key(cents)).collect();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(ValueKey::from((&usd, Rounding::TowardZero)).currency(), usd.currency());
+
+        let buckets = Value::currency_buckets([a, usd, b]);
+        assert_eq!(buckets[&Currency::new("CAD")].len(), 2);
+        assert_eq!(buckets[&Currency::new("USD")].len(), 1);
+    }
+
+    /// Empty sums have no currency of their own.
+    #[test]
+    pub fn test_empty_sums() {
+        assert!(kahan_sum(std::iter::empty::<Value>()).is_none());
+        assert!(Vec::<Value>::new().into_iter().sum::<Option<Value>>().is_none());
+
+        let zero = [Value::new("USD", 1.0), Value::new("USD", -1.0)];
+        let sum = kahan_sum(zero.iter()).unwrap();
+        assert_eq!((sum.currency().code(), sum.amount()), ("USD", 0.0));
+    }
+}
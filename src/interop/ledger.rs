@@ -0,0 +1,283 @@
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+
+use crate::instruments::{
+    book::{Book, ItemKey},
+    delta::{DeltaAmount, DeltaKind},
+    period::Period,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// Whether interest that accrues on items shows up in the journal.
+#[derive(Clone, Debug)]
+pub enum InterestPostings {
+    /// Interest is left out, balances in ledger will not include it.
+    Omit,
+    /// The interest accrued in every period up to `until` is posted as a
+    /// virtual posting, these do not need to balance.
+    Periodic {
+        period: Period,
+        until: DateTime<Utc>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct LedgerOptions {
+    /// The account deltas are balanced against.
+    pub counter_account: String,
+    /// The account opening balances are balanced against.
+    pub opening_account: String,
+    pub interest: InterestPostings,
+}
+
+impl Default for LedgerOptions {
+    fn default() -> Self {
+        Self {
+            counter_account: "Assets:Cash".to_string(),
+            opening_account: "Equity:Opening Balances".to_string(),
+            interest: InterestPostings::Omit,
+        }
+    }
+}
+
+/// Formats an amount with the currency code as the commodity.
+fn amount(value: &Value) -> String {
+    value.to_string()
+}
+
+fn posting(w: &mut impl Write, account: &str, value: Option<&Value>) -> io::Result<()> {
+    match value {
+        Some(value) => writeln!(w, "    {account:<40}  {:>16}", amount(value)),
+        None => writeln!(w, "    {account}"),
+    }
+}
+
+/// A posting of an amount in another currency than the account, priced at
+/// the rate it was converted at.
+fn priced_posting(
+    w: &mut impl Write,
+    account: &str,
+    value: &Value,
+    rate: f64,
+    into: Currency,
+) -> io::Result<()> {
+    let priced = format!("{} @ {rate} {}", amount(value), into.code());
+    writeln!(w, "    {account:<40}  {priced:>16}")
+}
+
+fn date(time: DateTime<Utc>) -> String {
+    time.format("%Y/%m/%d").to_string()
+}
+
+impl Book {
+    /// The ledger account of an item, top level items go under `Assets` or
+    /// `Liabilities` depending on their sign and children nest under their parent.
+    fn ledger_account(&self, key: ItemKey) -> String {
+        let segment = |k: ItemKey| {
+            self.name(k)
+                .map(|n| n.replace(':', "-"))
+                .unwrap_or_else(|| "Unnamed".to_string())
+        };
+
+        let mut path = vec![segment(key)];
+        let mut root = key;
+        while let Some(parent) = self.parent(root) {
+            path.push(segment(parent));
+            root = parent;
+        }
+        let kind = if self.get(root).unwrap().book_value.amount() < 0.0 {
+            "Liabilities"
+        } else {
+            "Assets"
+        };
+        path.push(kind.to_string());
+        path.reverse();
+        path.join(":")
+    }
+
+    /// Writes the book as a ledger-cli journal. Every item gets an opening
+    /// balance transaction and every delta becomes a transaction balanced
+    /// against the counter account.
+    pub fn export_ledger(&self, mut writer: impl Write, options: &LedgerOptions) -> io::Result<()> {
+        let w = &mut writer;
+        for (key, item) in self.iter_ordered() {
+            let account = self.ledger_account(key);
+
+            writeln!(w, "{} Opening balance", date(item.inception))?;
+            posting(w, &account, Some(&item.book_value))?;
+            posting(w, &options.opening_account, None)?;
+            writeln!(w)?;
+
+            let amounts = item.delta_amounts();
+            for (delta, change) in &amounts {
+                let payee = match delta.kind {
+                    DeltaKind::Payment => "Payment",
+                    DeltaKind::Fee => "Fee",
+                    DeltaKind::Adjustment => "Adjustment",
+                    DeltaKind::Interest => "Interest",
+                };
+                writeln!(w, "{} {payee}", date(delta.time))?;
+                if let Some(memo) = &delta.memo {
+                    writeln!(w, "    ; {memo}")?;
+                }
+                match (&delta.amount, delta.rate) {
+                    (DeltaAmount::Fixed(value), Some(rate))
+                        if value.currency() != item.currency() =>
+                    {
+                        priced_posting(w, &account, value, rate, item.currency())?
+                    }
+                    _ => posting(w, &account, Some(change))?,
+                }
+                posting(w, &options.counter_account, None)?;
+                writeln!(w)?;
+            }
+
+            let InterestPostings::Periodic { period, until } = &options.interest else {
+                continue;
+            };
+            if item.interest.is_none() {
+                continue;
+            }
+            // Stepping by an empty period would post forever.
+            period
+                .validate()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mut previous = item.inception;
+            for count in 1.. {
+                let current = period.step(item.inception, count);
+                if current > *until {
+                    break;
+                }
+                let paid = amounts
+                    .iter()
+                    .filter(|(d, _)| previous < d.time && d.time <= current)
+                    .map(|(_, change)| change.amount())
+                    .sum::<f64>();
+                let accrued = item.assess(current).amount() - item.assess(previous).amount() - paid;
+                writeln!(w, "{} Accrued interest", date(current))?;
+                posting(
+                    w,
+                    &format!("({account})"),
+                    Some(&Value::new(item.currency(), accrued)),
+                )?;
+                writeln!(w)?;
+                previous = current;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        delta::{Delta, DeltaAmount, DeltaKind},
+        item::Item,
+        period::Period,
+        value::Value,
+        Assesible,
+    };
+
+    use super::{InterestPostings, LedgerOptions};
+
+    fn book() -> Book {
+        let mut book = Book::default();
+
+        let mut visa = Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.20,
+            TimeDelta::days(365),
+            Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap(),
+        );
+        visa.add_delta(
+            Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(),
+            Value::dummy("CAD", 1000),
+        );
+        visa.deltas[0].memo = Some("Thank you for your payment".to_string());
+        book.add_named("Visa", visa);
+
+        let inception = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let house = book.add_named(
+            "House",
+            Item::fixed(Value::new("CAD", 150_000.0), inception),
+        );
+        let mortgage = book.add_named(
+            "Mortgage",
+            Item::fixed(Value::new("CAD", -120_000.0), inception),
+        );
+        book.get_mut(house).unwrap().add_child(mortgage);
+        book
+    }
+
+    #[test]
+    pub fn test_ledger_golden() {
+        let mut out = vec![];
+        book()
+            .export_ledger(&mut out, &LedgerOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            include_str!("../../tests/fixtures/book.ledger")
+        );
+    }
+
+    #[test]
+    pub fn test_ledger_interest_postings() {
+        let options = LedgerOptions {
+            interest: InterestPostings::Periodic {
+                period: Period::Years(1),
+                // The anniversaries of the visa are on the first at 01:01:01.
+                until: Utc.with_ymd_and_hms(2010, 1, 1, 1, 1, 1).unwrap(),
+            },
+            ..Default::default()
+        };
+        let mut out = vec![];
+        book().export_ledger(&mut out, &options).unwrap();
+        let journal = String::from_utf8(out).unwrap();
+
+        assert_eq!(journal.matches("Accrued interest").count(), 2);
+        assert!(journal.contains("(Liabilities:Visa)"));
+    }
+
+    /// A year of monthly interest on a loan with a percentage fee part way
+    /// through, written out as deltas.
+    #[test]
+    pub fn test_posted_interest() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = TimeDelta::days(30);
+        let mut loan =
+            Item::basic_debt(Value::new("CAD", -10_000.0), 0.12, Period::Years(1), start);
+        loan.add_delta(start + TimeDelta::days(45), Value::new("CAD", 500.0));
+        loan.push_delta(Delta::new(
+            start + month * 3,
+            DeltaAmount::Percent(0.01),
+            DeltaKind::Fee,
+        ));
+        let implied = loan.clone();
+
+        loan.post_interest(start + month * 12, month);
+        assert_eq!(loan.interest_posted, Some(start + month * 12));
+        for k in 0..=14 {
+            let time = start + month * k;
+            assert_value_eq!(loan.assess(time), implied.assess(time), 1e-6);
+        }
+        // Posting again only covers what has not been posted.
+        loan.post_interest(start + month * 12, month);
+        assert_eq!(loan.deltas.len(), 14);
+
+        let mut book = Book::default();
+        book.add_named("Loan", loan);
+        let mut out = vec![];
+        book.export_ledger(&mut out, &LedgerOptions::default())
+            .unwrap();
+        let journal = String::from_utf8(out).unwrap();
+        let payees = journal.lines().filter(|l| !l.starts_with(' '));
+        assert_eq!(payees.filter(|l| l.ends_with(" Interest")).count(), 12);
+    }
+}
@@ -0,0 +1,8 @@
+//! Conversions between the crate's types and the formats
+//! other financial tools speak.
+
+#[cfg(feature = "ofx")]
+pub mod ofx;
+pub mod qif;
+pub mod ledger;
+pub mod series;
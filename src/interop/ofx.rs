@@ -0,0 +1,349 @@
+use std::{collections::HashMap, fmt, io::Read};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+use crate::instruments::{
+    book::Book,
+    delta::Delta,
+    import::{ImportPlan, PlanTarget, PlannedItem},
+    item::Item,
+    value::{Currency, Value},
+};
+
+#[derive(Debug)]
+pub enum OfxError {
+    Io(std::io::Error),
+    /// The file does not contain an `<OFX>` element.
+    MissingHeader,
+    /// A required element is missing from the statement.
+    MissingField(&'static str),
+    InvalidDate(String),
+    InvalidAmount(String),
+}
+
+impl fmt::Display for OfxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OfxError::Io(e) => write!(f, "failed to read ofx: {e}"),
+            OfxError::MissingHeader => write!(f, "not an ofx file, no <OFX> element"),
+            OfxError::MissingField(field) => write!(f, "missing <{field}> in statement"),
+            OfxError::InvalidDate(date) => write!(f, "invalid ofx date \"{date}\""),
+            OfxError::InvalidAmount(amount) => write!(f, "invalid ofx amount \"{amount}\""),
+        }
+    }
+}
+
+impl std::error::Error for OfxError {}
+
+impl From<std::io::Error> for OfxError {
+    fn from(value: std::io::Error) -> Self {
+        OfxError::Io(value)
+    }
+}
+
+/// A single transaction on an OFX statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfxTransaction {
+    pub kind: String,
+    pub posted: DateTime<Utc>,
+    pub amount: f64,
+    /// The financial institution's identifier of the transaction.
+    pub fitid: String,
+    pub name: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// A bank statement read from an OFX file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfxStatement {
+    pub bank_id: Option<String>,
+    pub account_id: String,
+    pub account_type: Option<String>,
+    pub currency: Currency,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// The balance before any of the transactions, OFX only states the
+    /// closing balance so this is worked back from it.
+    pub opening_balance: f64,
+    pub closing_balance: f64,
+    pub transactions: Vec<OfxTransaction>,
+}
+
+impl OfxStatement {
+    /// Builds an item with the opening balance as the book value and every
+    /// transaction as a delta, the FITID is kept as the external identifier.
+    pub fn into_item(self) -> Item {
+        let mut item = self.opening_item();
+        item.import_deltas(self.deltas());
+        item
+    }
+    fn opening_item(&self) -> Item {
+        Item::fixed(Value::new(self.currency, self.opening_balance), self.start)
+    }
+    fn deltas(&self) -> impl Iterator<Item = Delta> + '_ {
+        self.transactions.iter().map(|t| {
+            let mut delta = Delta::payment(t.posted, Value::new(self.currency, t.amount))
+                .with_external_id(t.fitid.clone());
+            delta.memo = match (&t.name, &t.memo) {
+                (Some(name), Some(memo)) => Some(format!("{name}: {memo}")),
+                (name, memo) => name.clone().or(memo.clone()),
+            };
+            delta
+        })
+    }
+}
+
+impl Book {
+    /// Plans importing the statement onto the item with the account number
+    /// as its external identifier, or onto a new one like
+    /// [OfxStatement::into_item] if there is none. Transactions whose FITID
+    /// is already on the item are duplicates.
+    pub fn plan_ofx(&self, statement: &OfxStatement) -> ImportPlan {
+        let mut plan = ImportPlan::default();
+        let target = match self.by_external_id(&statement.account_id) {
+            Some(key) => PlanTarget::Existing(key),
+            None => plan.add_item(PlannedItem {
+                name: None,
+                external_id: Some(statement.account_id.clone()),
+                item: statement.opening_item(),
+            }),
+        };
+        for delta in statement.deltas() {
+            plan.add_delta(self, target, delta);
+        }
+        plan
+    }
+}
+
+/// Splits the body of an OFX file into `(tag, text)` pairs. Closing tags
+/// come through with their slash, and leaf elements may or may not be
+/// closed so this works for both the SGML and the XML flavors.
+fn tokenize(body: &str) -> Vec<(&str, String)> {
+    let mut tokens = vec![];
+    let mut rest = body;
+    while let Some(open) = rest.find('<') {
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        tokens.push((tag.trim(), decode(text.trim())));
+    }
+    tokens
+}
+
+fn decode(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parses an OFX date, `YYYYMMDD[HHMMSS[.XXX]][[offset:TZ]]`.
+fn parse_date(text: &str) -> Result<DateTime<Utc>, OfxError> {
+    let invalid = || OfxError::InvalidDate(text.to_string());
+    let (stamp, zone) = match text.split_once('[') {
+        Some((stamp, zone)) => (stamp, Some(zone.trim_end_matches(']'))),
+        None => (text, None),
+    };
+    let stamp = stamp.split('.').next().unwrap_or(stamp);
+    if stamp.len() < 8 || !stamp.is_ascii() {
+        return Err(invalid());
+    }
+
+    let date = NaiveDate::parse_from_str(&stamp[..8], "%Y%m%d").map_err(|_| invalid())?;
+    let time = match &stamp[8..] {
+        "" => NaiveTime::MIN,
+        clock => NaiveTime::parse_from_str(clock, "%H%M%S").map_err(|_| invalid())?,
+    };
+
+    let hours: f64 = match zone {
+        Some(zone) => zone
+            .split(':')
+            .next()
+            .unwrap_or("0")
+            .parse()
+            .map_err(|_| invalid())?,
+        None => 0.0,
+    };
+    let offset = FixedOffset::east_opt((hours * 3600.0) as i32).ok_or_else(invalid)?;
+    offset
+        .from_local_datetime(&NaiveDateTime::new(date, time))
+        .single()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(invalid)
+}
+
+/// A lone comma without a point is a decimal comma, which some banks
+/// write, any other comma separates thousands.
+fn parse_amount(text: &str) -> Result<f64, OfxError> {
+    let plain = match !text.contains('.') && text.matches(',').count() == 1 {
+        true => text.replace(',', "."),
+        false => text.replace(',', ""),
+    };
+    plain
+        .parse()
+        .map_err(|_| OfxError::InvalidAmount(text.to_string()))
+}
+
+/// Parses a bank statement from either an OFX 1.x (SGML) or 2.x (XML) file.
+pub fn parse_ofx<R: Read>(mut reader: R) -> Result<OfxStatement, OfxError> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    // The headers differ between versions but the body always starts here.
+    let body = &contents[contents.find("<OFX>").ok_or(OfxError::MissingHeader)?..];
+
+    let mut fields = HashMap::new();
+    let mut transactions = vec![];
+    let mut current: Option<HashMap<&str, String>> = None;
+    let mut aggregate = "";
+    for (tag, text) in tokenize(body) {
+        match tag {
+            "STMTTRN" => current = Some(Default::default()),
+            "/STMTTRN" => transactions.push(current.take().unwrap_or_default()),
+            "LEDGERBAL" | "AVAILBAL" => aggregate = tag,
+            "/LEDGERBAL" | "/AVAILBAL" => aggregate = "",
+            _ if tag.starts_with('/') || text.is_empty() => {}
+            _ => match &mut current {
+                Some(transaction) => {
+                    transaction.insert(tag, text);
+                }
+                None if aggregate == "AVAILBAL" => {}
+                None => {
+                    fields.insert(tag, text);
+                }
+            },
+        }
+    }
+
+    let field = |name: &'static str| fields.get(name).ok_or(OfxError::MissingField(name));
+    let transactions = transactions
+        .into_iter()
+        .map(|mut t| {
+            let mut take = |name: &'static str| t.remove(name).ok_or(OfxError::MissingField(name));
+            Ok(OfxTransaction {
+                kind: take("TRNTYPE").unwrap_or_default(),
+                posted: parse_date(&take("DTPOSTED")?)?,
+                amount: parse_amount(&take("TRNAMT")?)?,
+                fitid: take("FITID")?,
+                name: take("NAME").ok(),
+                memo: take("MEMO").ok(),
+            })
+        })
+        .collect::<Result<Vec<_>, OfxError>>()?;
+
+    let closing_balance = parse_amount(field("BALAMT")?)?;
+    let opening_balance = closing_balance - transactions.iter().map(|t| t.amount).sum::<f64>();
+    Ok(OfxStatement {
+        bank_id: fields.get("BANKID").cloned(),
+        account_id: field("ACCTID")?.clone(),
+        account_type: fields.get("ACCTTYPE").cloned(),
+        currency: Currency::intern(field("CURDEF")?),
+        start: parse_date(field("DTSTART")?)?,
+        end: parse_date(field("DTEND")?)?,
+        opening_balance,
+        closing_balance,
+        transactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::Book,
+        value::{Currency, Value},
+        Assesible,
+    };
+
+    use super::{parse_amount, parse_ofx, OfxError};
+
+    #[test]
+    pub fn test_sgml_statement() {
+        let file = include_str!("../../tests/fixtures/statement_v1.ofx");
+        let statement = parse_ofx(file.as_bytes()).unwrap();
+
+        assert_eq!(statement.account_id, "987654321");
+        assert_eq!(statement.currency, Currency::new("CAD"));
+        assert_eq!(statement.transactions.len(), 3);
+        assert_eq!(
+            statement.transactions[0].posted,
+            Utc.with_ymd_and_hms(2008, 1, 15, 17, 0, 0).unwrap()
+        );
+        assert_eq!(
+            statement.transactions[2].name.as_deref(),
+            Some("Bookstore & Cafe")
+        );
+        let opening = Value::new(statement.currency, statement.opening_balance);
+        assert_value_eq!(opening, Value::new("CAD", 2_000.0), 1e-9);
+
+        let (end, closing) = (statement.end, statement.closing_balance);
+        let item = statement.into_item();
+        assert_eq!(item.deltas[0].external_id.as_deref(), Some("2008011501"));
+        assert_eq!(
+            item.deltas[0].memo.as_deref(),
+            Some("PAYROLL: January salary")
+        );
+        assert_value_eq!(item.assess(end), Value::new("CAD", closing), 1e-9);
+    }
+
+    #[test]
+    pub fn test_xml_statement() {
+        let file = include_str!("../../tests/fixtures/statement_v2.ofx");
+        let statement = parse_ofx(file.as_bytes()).unwrap();
+
+        assert_eq!(statement.currency, Currency::new("USD"));
+        assert_eq!(statement.account_type.as_deref(), Some("SAVINGS"));
+        let opening = Value::new(statement.currency, statement.opening_balance);
+        assert_value_eq!(opening, Value::new("USD", 10_000.0), 1e-9);
+
+        let (end, closing) = (statement.end, statement.closing_balance);
+        let item = statement.into_item();
+        assert_eq!(item.deltas.len(), 2);
+        assert_value_eq!(item.assess(end), Value::new("USD", closing), 1e-9);
+    }
+
+    #[test]
+    pub fn test_plan_ofx() {
+        let file = include_str!("../../tests/fixtures/statement_v1.ofx");
+        let statement = parse_ofx(file.as_bytes()).unwrap();
+        let mut book = Book::default();
+
+        let plan = book.plan_ofx(&statement);
+        assert_eq!((plan.items.len(), plan.deltas.len()), (1, 3));
+        let receipt = book.apply_plan(plan).unwrap();
+        let account = receipt.items[0];
+        assert_eq!(book.by_external_id("987654321"), Some(account));
+        let balance = book.get(account).unwrap().assess(statement.end);
+        assert_value_eq!(balance, Value::new("CAD", statement.closing_balance), 1e-9);
+
+        // The same statement again only has duplicates.
+        let again = book.plan_ofx(&statement);
+        assert!(again.is_empty());
+        assert_eq!(again.duplicates.len(), 3);
+    }
+
+    #[test]
+    pub fn test_amount_separators() {
+        assert_eq!(parse_amount("1,234.56").unwrap(), 1_234.56);
+        assert_eq!(parse_amount("-1,234,567.5").unwrap(), -1_234_567.5);
+        assert_eq!(parse_amount("12,50").unwrap(), 12.5);
+        assert_eq!(parse_amount("-42.10").unwrap(), -42.1);
+        assert!(matches!(
+            parse_amount("1.234.56"),
+            Err(OfxError::InvalidAmount(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_not_ofx() {
+        assert!(matches!(
+            parse_ofx("hello".as_bytes()),
+            Err(OfxError::MissingHeader)
+        ));
+    }
+}
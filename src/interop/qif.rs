@@ -0,0 +1,318 @@
+use std::{fmt, io::Read};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use crate::instruments::{
+    book::{Book, ItemRef},
+    delta::Delta,
+    import::ImportPlan,
+    value::{Currency, Value},
+};
+
+/// Which way around QIF dates are written, the format itself does not say.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateOrder {
+    /// `12/25/2023`, what US exports use.
+    MonthFirst,
+    /// `25/12/2023`.
+    DayFirst,
+}
+
+#[derive(Debug)]
+pub enum QifError {
+    Io(std::io::Error),
+    InvalidDate {
+        line: usize,
+        text: String,
+    },
+    InvalidAmount {
+        line: usize,
+        text: String,
+    },
+    /// A transaction ended without a date or amount.
+    Incomplete {
+        line: usize,
+    },
+}
+
+impl fmt::Display for QifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QifError::Io(e) => write!(f, "failed to read qif: {e}"),
+            QifError::InvalidDate { line, text } => {
+                write!(f, "line {line}: invalid date \"{text}\"")
+            }
+            QifError::InvalidAmount { line, text } => {
+                write!(f, "line {line}: invalid amount \"{text}\"")
+            }
+            QifError::Incomplete { line } => {
+                write!(f, "line {line}: transaction is missing a date or amount")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QifError {}
+
+impl From<std::io::Error> for QifError {
+    fn from(value: std::io::Error) -> Self {
+        QifError::Io(value)
+    }
+}
+
+/// A part of a split transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QifSplit {
+    pub category: Option<String>,
+    pub memo: Option<String>,
+    pub amount: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QifTransaction {
+    /// The `!Type:` of the section the transaction was in.
+    pub account_type: String,
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub payee: Option<String>,
+    pub memo: Option<String>,
+    pub category: Option<String>,
+    pub splits: Vec<QifSplit>,
+}
+
+impl QifTransaction {
+    /// Converts the transaction into deltas, split transactions become one
+    /// delta per split so each keeps its category.
+    pub fn to_deltas(&self, currency: Currency) -> Vec<Delta> {
+        let time = Utc.from_utc_datetime(&self.date.and_hms_opt(0, 0, 0).unwrap());
+        let memo = |memo: &Option<String>| match (&self.payee, memo) {
+            (Some(payee), Some(memo)) => Some(format!("{payee}: {memo}")),
+            (payee, memo) => payee.clone().or(memo.clone()),
+        };
+
+        if self.splits.is_empty() {
+            let mut delta = Delta::payment(time, Value::new(currency, self.amount));
+            delta.memo = memo(&self.memo);
+            delta.category = self.category.clone();
+            return vec![delta];
+        }
+        self.splits
+            .iter()
+            .map(|split| {
+                let mut delta = Delta::payment(time, Value::new(currency, split.amount));
+                delta.memo = memo(&split.memo);
+                delta.category = split.category.clone();
+                delta
+            })
+            .collect()
+    }
+}
+
+impl Book {
+    /// Plans importing the transactions, written in `currency`, onto the
+    /// item. QIF has no transaction identifiers so duplicates are not
+    /// detected.
+    pub fn plan_qif(
+        &self,
+        transactions: &[QifTransaction],
+        item: ItemRef,
+        currency: Currency,
+    ) -> ImportPlan {
+        let deltas = transactions.iter().flat_map(|t| t.to_deltas(currency));
+        self.plan_deltas(deltas.map(|d| (item.clone(), d)))
+    }
+}
+
+fn parse_date(text: &str, order: DateOrder, line: usize) -> Result<NaiveDate, QifError> {
+    let invalid = || QifError::InvalidDate {
+        line,
+        text: text.to_string(),
+    };
+    // Quicken writes years after 1999 with an apostrophe, `1/ 5'04`.
+    let millennium = text.contains('\'');
+    let parts = text
+        .replace(' ', "")
+        .split(['/', '\'', '-', '.'])
+        .map(|p| p.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [first, second, year] = parts[..] else {
+        return Err(invalid());
+    };
+
+    let year = match year {
+        y if y >= 100 => y,
+        y if millennium => 2000 + y,
+        y => 1900 + y,
+    };
+    let (month, day) = match order {
+        DateOrder::MonthFirst => (first, second),
+        DateOrder::DayFirst => (second, first),
+    };
+    NaiveDate::from_ymd_opt(year as i32, month, day).ok_or_else(invalid)
+}
+
+fn parse_amount(text: &str, line: usize) -> Result<f64, QifError> {
+    text.replace(',', "")
+        .parse()
+        .map_err(|_| QifError::InvalidAmount {
+            line,
+            text: text.to_string(),
+        })
+}
+
+/// Parses a QIF file with US style month-first dates.
+pub fn parse<R: Read>(reader: R) -> Result<Vec<QifTransaction>, QifError> {
+    parse_with(reader, DateOrder::MonthFirst)
+}
+
+/// Parses a QIF file, reading dates in the given order.
+pub fn parse_with<R: Read>(
+    mut reader: R,
+    order: DateOrder,
+) -> Result<Vec<QifTransaction>, QifError> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+
+    let mut transactions = vec![];
+    let mut account_type = String::new();
+    let mut date = None;
+    let mut amount = None;
+    let mut payee = None;
+    let mut memo = None;
+    let mut category = None;
+    let mut splits: Vec<QifSplit> = vec![];
+
+    for (index, raw) in contents.lines().enumerate() {
+        let line = index + 1;
+        let raw = raw.trim_end();
+        if let Some(header) = raw.strip_prefix("!Type:") {
+            account_type = header.trim().to_string();
+            continue;
+        }
+        let Some(code) = raw.chars().next() else {
+            continue;
+        };
+        let field = raw[code.len_utf8()..].trim();
+        match code {
+            'D' => date = Some(parse_date(field, order, line)?),
+            'T' | 'U' => amount = Some(parse_amount(field, line)?),
+            'P' => payee = Some(field.to_string()),
+            'M' => memo = Some(field.to_string()),
+            'L' => category = Some(field.to_string()),
+            'S' => splits.push(QifSplit {
+                category: Some(field.to_string()),
+                memo: None,
+                amount: 0.0,
+            }),
+            'E' => {
+                if let Some(split) = splits.last_mut() {
+                    split.memo = Some(field.to_string());
+                }
+            }
+            '$' => {
+                if let Some(split) = splits.last_mut() {
+                    split.amount = parse_amount(field, line)?;
+                }
+            }
+            '^' => {
+                let (Some(date), Some(amount)) = (date.take(), amount.take()) else {
+                    return Err(QifError::Incomplete { line });
+                };
+                transactions.push(QifTransaction {
+                    account_type: account_type.clone(),
+                    date,
+                    amount,
+                    payee: payee.take(),
+                    memo: memo.take(),
+                    category: category.take(),
+                    splits: std::mem::take(&mut splits),
+                });
+            }
+            // Cleared status, check numbers, addresses and the like.
+            _ => {}
+        }
+    }
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        book::{Book, ItemRef},
+        item::Item,
+        value::{Currency, Value},
+    };
+
+    use super::{parse, parse_with, DateOrder};
+
+    #[test]
+    pub fn test_month_first_register() {
+        let file = include_str!("../../tests/fixtures/register_us.qif");
+        let transactions = parse(file.as_bytes()).unwrap();
+
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[0].account_type, "Bank");
+        assert_eq!(
+            transactions[0].date,
+            NaiveDate::from_ymd_opt(2023, 12, 28).unwrap()
+        );
+        assert_eq!(
+            transactions[1].date,
+            NaiveDate::from_ymd_opt(2024, 1, 5).unwrap()
+        );
+        assert_eq!(transactions[1].splits.len(), 2);
+
+        let deltas = transactions
+            .iter()
+            .flat_map(|t| t.to_deltas(Currency::new("CAD")))
+            .collect::<Vec<_>>();
+        assert_eq!(deltas.len(), 4);
+        assert_eq!(deltas[1].category.as_deref(), Some("Groceries"));
+        assert_eq!(
+            deltas[2].memo.as_deref(),
+            Some("Superstore: Cleaning supplies")
+        );
+
+        let total = Value::new("CAD", transactions.iter().map(|t| t.amount).sum::<f64>());
+        let summed: f64 = deltas
+            .iter()
+            .map(|d| d.resolve(&Value::dummy("CAD", 0.0)).amount())
+            .sum();
+        assert_value_eq!(total, Value::new("CAD", summed), 1e-9);
+        assert_value_eq!(total, Value::new("CAD", 1_115.44), 1e-9);
+
+        let mut book = Book::default();
+        let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 0.0), start));
+        let chequing = ItemRef::Name("Chequing".to_string());
+        let plan = book.plan_qif(&transactions, chequing, Currency::new("CAD"));
+        assert_eq!(plan.deltas.len(), 4);
+        assert!(plan.deltas.iter().all(|p| p.conversion.is_none()));
+    }
+
+    #[test]
+    pub fn test_day_first_register() {
+        let file = include_str!("../../tests/fixtures/register_eu.qif");
+        let transactions = parse_with(file.as_bytes(), DateOrder::DayFirst).unwrap();
+
+        assert_eq!(transactions[0].account_type, "CCard");
+        assert_eq!(
+            transactions[0].date,
+            NaiveDate::from_ymd_opt(2023, 12, 28).unwrap()
+        );
+        assert_eq!(
+            transactions[1].date,
+            NaiveDate::from_ymd_opt(2024, 1, 5).unwrap()
+        );
+        assert_eq!(
+            transactions[2].date,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+
+        // Reading day-first dates month-first fails on the 28th "month".
+        assert!(parse(file.as_bytes()).is_err());
+    }
+}
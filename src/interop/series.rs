@@ -0,0 +1,127 @@
+//! Turning assessments over time into plain points for plotting libraries.
+
+use chrono::{DateTime, Utc};
+
+use crate::instruments::{
+    convert::CURRENCY_EXCHANGE,
+    value::{Currency, Value},
+};
+
+const NANOS_PER_DAY: f64 = 86_400.0 * 1e9;
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Converts a series into `(days since epoch, amount in target)` points.
+pub fn to_points(
+    series: &[(DateTime<Utc>, Value)],
+    target: Currency,
+    epoch: DateTime<Utc>,
+) -> Vec<(f64, f64)> {
+    series
+        .iter()
+        .map(|(time, value)| {
+            let days = (*time - epoch).num_nanoseconds().unwrap() as f64 / NANOS_PER_DAY;
+            let amount = CURRENCY_EXCHANGE
+                .try_convert(value.clone(), target)
+                .unwrap_or_else(|e| panic!("{e}"))
+                .amount();
+            (days, amount)
+        })
+        .collect()
+}
+
+/// Like [to_points] but in fractional Julian years since the epoch.
+pub fn to_year_points(
+    series: &[(DateTime<Utc>, Value)],
+    target: Currency,
+    epoch: DateTime<Utc>,
+) -> Vec<(f64, f64)> {
+    to_points(series, target, epoch)
+        .into_iter()
+        .map(|(days, amount)| (days / DAYS_PER_YEAR, amount))
+        .collect()
+}
+
+/// Reduces the points to at most `max_points` with largest triangle three
+/// buckets, which keeps the visual shape of the series. The first and last
+/// points are always kept.
+pub fn downsample(points: &[(f64, f64)], max_points: usize) -> Vec<(f64, f64)> {
+    if max_points >= points.len() || max_points < 3 {
+        return points.to_vec();
+    }
+
+    let every = (points.len() - 2) as f64 / (max_points - 2) as f64;
+    let mut sampled = Vec::with_capacity(max_points);
+    let mut a = 0;
+    sampled.push(points[0]);
+
+    for bucket in 0..max_points - 2 {
+        let start = (bucket as f64 * every) as usize + 1;
+        let end = ((bucket + 1) as f64 * every) as usize + 1;
+
+        // The average of the next bucket is the third corner of the triangle.
+        let next_end = (((bucket + 2) as f64 * every) as usize + 1).min(points.len());
+        let next = &points[end..next_end];
+        let (avg_x, avg_y) = next.iter().fold((0.0, 0.0), |(x, y), p| (x + p.0, y + p.1));
+        let (avg_x, avg_y) = (avg_x / next.len() as f64, avg_y / next.len() as f64);
+
+        let (ax, ay) = points[a];
+        let mut best = start;
+        let mut best_area = -1.0;
+        for (i, &(x, y)) in points.iter().enumerate().take(end).skip(start) {
+            let area = ((ax - avg_x) * (y - ay) - (ax - x) * (avg_y - ay)).abs();
+            if area > best_area {
+                best_area = area;
+                best = i;
+            }
+        }
+        sampled.push(points[best]);
+        a = best;
+    }
+
+    sampled.push(points[points.len() - 1]);
+    sampled
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use crate::instruments::{book::Book, item::Item, value::Value, Assesible};
+
+    use super::{downsample, to_points, to_year_points};
+
+    #[test]
+    pub fn test_series_points() {
+        let start = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add(Item::basic_debt(
+            Value::dummy("CAD", 1_000.0),
+            0.05,
+            TimeDelta::days(365),
+            start,
+        ));
+
+        let series = book.series(start, end, TimeDelta::days(1));
+        let points = to_points(&series, book.currency(), start);
+        assert_eq!(points.len(), 7306);
+        assert_eq!(points[0], (0.0, 1_000.0));
+        assert_eq!(points[7305], (7305.0, book.assess(end).amount()));
+        assert!((to_year_points(&series, book.currency(), start)[7305].0 - 20.0).abs() < 1e-9);
+
+        let sampled = downsample(&points, 500);
+        assert_eq!(sampled.len(), 500);
+        assert_eq!(sampled.first(), points.first());
+        assert_eq!(sampled.last(), points.last());
+        assert!(sampled.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    pub fn test_downsample_keeps_peak() {
+        let mut points: Vec<(f64, f64)> = (0..1000).map(|i| (i as f64, 0.0)).collect();
+        points[437].1 = 100.0;
+        let sampled = downsample(&points, 20);
+        assert!(sampled.contains(&(437.0, 100.0)));
+        assert_eq!(downsample(&points[..10], 20).len(), 10);
+    }
+}
@@ -1 +1,44 @@
-pub mod instruments;
\ No newline at end of file
+//! Personal finance bookkeeping, items are put on a [Book] and assessed
+//! at any point in time.
+//!
+//! ```
+//! use alacran::prelude::*;
+//! use chrono::{TimeDelta, TimeZone, Utc};
+//!
+//! let inception = Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap();
+//! let visa = Item::builder(Value::new("CAD", -1_000.0), inception)
+//!     .interest(0.20, TimeDelta::days(365))
+//!     .payment(Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(), Value::new("CAD", 1_000.0))
+//!     .build();
+//!
+//! let mut book = Book::default();
+//! book.add(visa);
+//! book.add(Item::fixed(Value::new("CAD", 100.0), inception));
+//!
+//! let total = book.assess(Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap());
+//! assert_eq!(total.non_decimal(), 44);
+//! ```
+
+#[doc(hidden)]
+pub mod macros;
+pub mod instruments;
+pub mod interop;
+pub mod store;
+
+pub use instruments::{
+    book::{Book, ItemKey},
+    convert::ConversionTable,
+    item::{Interest, Item, ItemBuilder, Payout},
+    period::Period,
+    risk::Risk,
+    value::{Currency, Value},
+    Assesible,
+};
+
+/// The types needed to build and assess a book.
+pub mod prelude {
+    pub use crate::{
+        Assesible, Book, ConversionTable, Currency, Interest, Item, ItemBuilder, ItemKey, Payout,
+        Period, Risk, Value,
+    };
+}
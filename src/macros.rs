@@ -0,0 +1,196 @@
+//! Declarative construction of books and items, and assertions on the
+//! values they assess to.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::Value;
+
+/// Parses the digits of a literal, underscores are skipped.
+#[doc(hidden)]
+pub const fn number(text: &str) -> u32 {
+    let bytes = text.as_bytes();
+    let mut value = 0u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => {}
+            b @ b'0'..=b'9' => value = value * 10 + (b - b'0') as u32,
+            _ => panic!("dates in book! must be written as YYYY-MM-DD or YYYY-MM-DD HH:MM:SS"),
+        }
+        i += 1;
+    }
+    value
+}
+
+/// Builds a UTC time from the pieces of a date literal, this runs at
+/// compile time so invalid dates fail the build.
+#[doc(hidden)]
+pub const fn datetime(year: &str, month: &str, day: &str, time: [&str; 3]) -> DateTime<Utc> {
+    let Some(date) = NaiveDate::from_ymd_opt(number(year) as i32, number(month), number(day)) else {
+        panic!("invalid date in book!");
+    };
+    let Some(time) = date.and_hms_opt(number(time[0]), number(time[1]), number(time[2])) else {
+        panic!("invalid time of day in book!");
+    };
+    time.and_utc()
+}
+
+/// The message [assert_value_eq!] fails with.
+#[doc(hidden)]
+pub fn value_mismatch(left: &Value, right: &Value, tolerance: f64) -> String {
+    let decimals = left.currency().decimals().max(right.currency().decimals()) as usize + 4;
+    format!(
+        "assertion `left ≈ right` failed within {tolerance}\n  \
+         left: {left:.decimals$}\n right: {right:.decimals$}"
+    )
+}
+
+/// Asserts that two [Value](crate::Value)s are within a tolerance of each
+/// other, converting currencies when they differ, see
+/// [Value::approx_eq](crate::Value::approx_eq). Both values are printed
+/// with their currency when they are not.
+///
+/// ```
+/// use alacran::{assert_value_eq, Value};
+///
+/// assert_value_eq!(Value::new("CAD", 10.004), Value::new("CAD", 10.0), 0.005);
+/// ```
+#[macro_export]
+macro_rules! assert_value_eq {
+    ($left:expr, $right:expr, $tolerance:expr $(,)?) => {{
+        let (left, right): (&$crate::Value, &$crate::Value) = (&$left, &$right);
+        let tolerance: f64 = $tolerance;
+        if !left.approx_eq(right, tolerance) {
+            panic!("{}", $crate::macros::value_mismatch(left, right, tolerance));
+        }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __date {
+    ($y:literal - $m:literal - $d:literal) => {
+        $crate::__date!($y - $m - $d 0:0:0)
+    };
+    ($y:literal - $m:literal - $d:literal $h:literal : $mi:literal : $s:literal) => {
+        const {
+            $crate::macros::datetime(
+                stringify!($y),
+                stringify!($m),
+                stringify!($d),
+                [stringify!($h), stringify!($mi), stringify!($s)],
+            )
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __period {
+    (day) => {
+        ::chrono::TimeDelta::days(1)
+    };
+    (week) => {
+        ::chrono::TimeDelta::days(7)
+    };
+    (month) => {
+        $crate::Period::Months(1)
+    };
+    (year) => {
+        $crate::Period::Years(1)
+    };
+    ($other:ident) => {
+        compile_error!(concat!(
+            "unknown period `",
+            stringify!($other),
+            "`, expected day, week, month or year"
+        ))
+    };
+}
+
+/// Builds a single [Item](crate::Item) in a currency, see [book!] for the
+/// forms it accepts.
+///
+/// ```
+/// use alacran::{item, Assesible};
+/// use chrono::{TimeZone, Utc};
+///
+/// let card = item!("CAD", debt(-1_000, 0.20 / year, 2008-01-01 01:01:01) {
+///     delta(2008-02-01 01:01:01, 1_000)
+/// });
+/// let value = card.assess(Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap());
+/// assert_eq!(value.non_decimal(), -54);
+/// ```
+#[macro_export]
+macro_rules! item {
+    ($base:expr, fixed($amount:expr, $($date:tt)+) $({ $($body:tt)* })?) => {
+        $crate::item!(@body
+            $crate::ItemBuilder::new($crate::Value::new($base, ($amount) as f64), $crate::__date!($($date)+)),
+            $base, $($($body)*)?
+        )
+    };
+    ($base:expr, debt($amount:expr, $rate:literal / $unit:ident, $($date:tt)+) $({ $($body:tt)* })?) => {
+        $crate::item!(@body
+            $crate::ItemBuilder::new($crate::Value::new($base, ($amount) as f64), $crate::__date!($($date)+))
+                .interest($rate, $crate::__period!($unit)),
+            $base, $($($body)*)?
+        )
+    };
+    (@body $builder:expr, $base:expr, $(delta($y:literal - $m:literal - $d:literal $($h:literal : $mi:literal : $s:literal)?, $amount:expr)),* $(,)?) => {
+        $builder
+            $(.payment(
+                $crate::__date!($y - $m - $d $($h : $mi : $s)?),
+                $crate::Value::new($base, ($amount) as f64),
+            ))*
+            .build()
+    };
+}
+
+/// Builds a [Book](crate::Book) of named items, all in the base currency.
+///
+/// Items are either `fixed(amount, date)` or `debt(amount, rate / period,
+/// date)` where the period is `day`, `week` or a calendar `month` or `year`,
+/// followed by an optional block of `delta(date, amount)` payments. Dates are
+/// written `YYYY-MM-DD` with an optional `HH:MM:SS` and are checked at
+/// compile time. The items are added like [Book::add_named](crate::Book::add_named)
+/// so the audit log has them.
+///
+/// ```
+/// use alacran::{book, Assesible};
+/// use chrono::{TimeZone, Utc};
+///
+/// let book = book! {
+///     base: "CAD",
+///     "Visa" => debt(-10_000, 0.20 / year, 2008-01-01 01:01:01),
+///     "Store card" => debt(-100, 0.02 / year, 2008-01-01 01:01:01),
+///     "House" => debt(150_000, 0.04 / year, 2000-01-01 01:01:01),
+/// };
+/// let now = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+/// assert_eq!(book.assess(now).non_decimal(), 175994);
+///
+/// let book = book! {
+///     base: "CAD",
+///     "Visa" => debt(-15_000, 0.20 / year, 2008-01-01 01:01:01),
+///     "Car" => fixed(20_000, 2020-02-29) {
+///         delta(2021-02-28, -5_000),
+///     },
+/// };
+/// assert_eq!(book.get(book.by_name("Visa").unwrap()).unwrap().assess(now).non_decimal(), -337381);
+/// assert_eq!(book.get(book.by_name("Car").unwrap()).unwrap().assess(now).non_decimal(), 15_000);
+/// ```
+///
+/// Invalid dates fail to compile.
+///
+/// ```compile_fail
+/// let book = alacran::book! { base: "CAD", "House" => fixed(1, 2021-02-29) };
+/// ```
+#[macro_export]
+macro_rules! book {
+    (base: $base:expr $(, $name:literal => $kind:ident ($($args:tt)*) $({ $($body:tt)* })?)* $(,)?) => {{
+        let mut book = $crate::Book::default();
+        $(
+            book.add_named($name, $crate::item!($base, $kind($($args)*) $({ $($body)* })?));
+        )*
+        book
+    }};
+}
@@ -0,0 +1,2012 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeDelta, Utc};
+use serde_json::{json, Value as Json};
+use slotmap::{Key, KeyData};
+
+use crate::instruments::{
+    audit::AuditEntry,
+    benchmark::{Benchmark, BenchmarkId, BENCHMARKS},
+    book::{Book, ItemKey, ItemRef},
+    calendar::{BusinessDayConvention, BusinessDays, HolidayCalendar},
+    convert::RatePolicy,
+    delta::{Delta, DeltaAmount, DeltaId, DeltaKind, DeltaStatus},
+    groups::GroupId,
+    import::{ImportPlan, PlanTarget},
+    item::{
+        ApplicationOrder, InceptionPolicy, Interest, Item, Liquidity, MinPayment, Payout,
+        Proration,
+    },
+    lease::Lease,
+    period::{Period, PeriodAnchor, PeriodError},
+    position::{Interpolation, OutOfRange, PriceSeries},
+    risk::RiskSpec,
+    sale::Sale,
+    transfer::{Transfer, TransferId},
+    value::{Currency, Rounding, Value},
+};
+
+use super::migrate::{migrate_with, Defaults, MigrationError, FORMAT_VERSION};
+
+#[derive(Debug)]
+pub enum JsonError {
+    Json(serde_json::Error),
+    Migration(MigrationError),
+    /// The document is valid JSON but does not describe a book.
+    Invalid(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Json(e) => write!(f, "json error: {e}"),
+            JsonError::Migration(e) => write!(f, "{e}"),
+            JsonError::Invalid(message) => write!(f, "invalid book document: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(value: serde_json::Error) -> Self {
+        JsonError::Json(value)
+    }
+}
+
+impl From<MigrationError> for JsonError {
+    fn from(value: MigrationError) -> Self {
+        JsonError::Migration(value)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> JsonError {
+    JsonError::Invalid(message.into())
+}
+
+fn time_json(time: DateTime<Utc>) -> Json {
+    json!(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Fixed periods are plain nanoseconds, calendar periods are objects.
+fn period_json(period: Period) -> Json {
+    match period {
+        Period::Delta(delta) => json!(delta.num_nanoseconds().unwrap()),
+        Period::Months(months) => json!({ "months": months }),
+        Period::Years(years) => json!({ "years": years }),
+    }
+}
+
+fn value_json(value: &Value) -> Json {
+    json!({
+        "currency": value.currency().code(),
+        "amount": value.amount(),
+    })
+}
+
+/// Indexed interest carries its benchmark, so the document can be read where
+/// the benchmark was never registered.
+fn interest_json(interest: &Interest) -> Json {
+    let anchor = match interest.anchor() {
+        PeriodAnchor::Inception => "inception",
+        PeriodAnchor::CalendarMonthEnd => "calendar_month_end",
+        PeriodAnchor::CalendarQuarterEnd => "calendar_quarter_end",
+    };
+    let mut json = json!({
+        "percent": interest.rate(),
+        "period": period_json(interest.period()),
+        "anchor": anchor,
+        "before_inception": match interest.inception_policy() {
+            InceptionPolicy::Discount => "discount",
+            InceptionPolicy::Hold => "hold",
+        },
+    });
+    if let Some(id) = interest.benchmark() {
+        let benchmark = BENCHMARKS.get(id);
+        json["benchmark"] = json!({
+            "id": id.get(),
+            "name": benchmark.as_ref().map(|b| &b.name),
+            "rates": benchmark
+                .iter()
+                .flat_map(Benchmark::rates)
+                .map(|(on, rate)| json!([time_json(*on), rate]))
+                .collect::<Vec<_>>(),
+        });
+    }
+    json
+}
+
+fn delta_json(delta: &Delta) -> Json {
+    let kind = match delta.kind {
+        DeltaKind::Payment => "payment",
+        DeltaKind::Fee => "fee",
+        DeltaKind::Adjustment => "adjustment",
+        DeltaKind::Interest => "interest",
+    };
+    let amount = match &delta.amount {
+        DeltaAmount::Fixed(value) => json!({ "fixed": value_json(value) }),
+        DeltaAmount::Percent(percent) => json!({ "percent": percent }),
+    };
+    let status = match delta.status {
+        DeltaStatus::Actual => "actual",
+        DeltaStatus::Planned => "planned",
+    };
+    json!({
+        "time": time_json(delta.time),
+        "kind": kind,
+        "amount": amount,
+        "memo": delta.memo,
+        "category": delta.category,
+        "external_id": delta.external_id,
+        "units": delta.units,
+        "status": status,
+        "rate": delta.rate,
+    })
+}
+
+fn proration_json(proration: Proration) -> Json {
+    match proration {
+        Proration::None => json!("none"),
+        Proration::Linear => json!("linear"),
+    }
+}
+
+fn payout_json(payout: &Payout) -> Json {
+    match payout {
+        Payout::OneTime { amount, time } => json!({
+            "variant": "one_time",
+            "amount": value_json(amount),
+            "time": time_json(*time),
+        }),
+        Payout::InterestOneTime {
+            principal,
+            time,
+            interest,
+        } => json!({
+            "variant": "interest_one_time",
+            "principal": value_json(principal),
+            "time": time_json(*time),
+            "interest": interest_json(interest),
+        }),
+        Payout::FixedRecurring {
+            amount,
+            start,
+            frequency,
+            proration,
+        } => json!({
+            "variant": "fixed_recurring",
+            "amount": value_json(amount),
+            "start": time_json(*start),
+            "frequency": period_json(*frequency),
+            "proration": proration_json(*proration),
+        }),
+        Payout::InterestRecurring {
+            principal,
+            start,
+            frequency,
+            interest,
+            proration,
+        } => json!({
+            "variant": "interest_recurring",
+            "principal": value_json(principal),
+            "start": time_json(*start),
+            "frequency": period_json(*frequency),
+            "interest": interest_json(interest),
+            "proration": proration_json(*proration),
+        }),
+        Payout::Adjusted { payout, days } => json!({
+            "variant": "adjusted",
+            "payout": payout_json(payout),
+            "convention": convention_json(days.convention),
+            "holidays": days
+                .calendar
+                .holidays()
+                .iter()
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn liquidity_json(liquidity: &Liquidity) -> Json {
+    match liquidity {
+        Liquidity::Liquid => json!("liquid"),
+        Liquidity::Illiquid => json!("illiquid"),
+        Liquidity::Escrow {
+            release,
+            forfeit_percent,
+        } => json!({
+            "escrow": {
+                "release": time_json(*release),
+                "forfeit_percent": forfeit_percent,
+            }
+        }),
+    }
+}
+
+fn rounding_json(rounding: Rounding) -> Json {
+    match rounding {
+        Rounding::HalfAwayFromZero => json!("half_away_from_zero"),
+        Rounding::HalfEven => json!("half_even"),
+        Rounding::TowardZero => json!("toward_zero"),
+    }
+}
+
+fn lease_json(lease: &Lease) -> Json {
+    json!({
+        "payment": value_json(&lease.payment),
+        "frequency": lease.frequency.num_nanoseconds().unwrap(),
+        "term": lease.term,
+        "residual": value_json(&lease.residual),
+        "start": time_json(lease.start),
+        "discount": lease.discount.as_ref().map(interest_json),
+    })
+}
+
+fn prices_json(prices: &PriceSeries) -> Json {
+    let out_of_range = |range: OutOfRange| match range {
+        OutOfRange::Nearest => "nearest",
+        OutOfRange::Zero => "zero",
+    };
+    json!({
+        "points": prices
+            .points()
+            .iter()
+            .map(|(time, price)| json!([time_json(*time), price]))
+            .collect::<Vec<_>>(),
+        "interpolation": match prices.interpolation {
+            Interpolation::Step => "step",
+            Interpolation::Linear => "linear",
+        },
+        "before": out_of_range(prices.before),
+        "after": out_of_range(prices.after),
+    })
+}
+
+fn sale_json(sale: &Sale, delta_id: impl Fn(DeltaId) -> Json) -> Json {
+    json!({
+        "delta": delta_id(sale.delta),
+        "time": time_json(sale.time),
+        "fraction": sale.fraction,
+        "proceeds": value_json(&sale.proceeds),
+    })
+}
+
+/// What the book and its audit entries store of an item, without its place
+/// in the book. Deltas are given the id `delta_id` numbers them with, which
+/// is also how sales and the balloon refer to them.
+fn item_json(
+    item: &Item,
+    delta_json: impl Fn(&Delta) -> Json,
+    delta_id: impl Fn(DeltaId) -> Json,
+) -> Json {
+    let deltas = item.deltas.iter().map(|delta| {
+        let mut json = delta_json(delta);
+        json["id"] = delta_id(delta.id);
+        json
+    });
+    json!({
+        "book_value": value_json(&item.book_value),
+        "inception": time_json(item.inception),
+        "interest": item.interest.as_ref().map(interest_json),
+        "interest_posted": item.interest_posted.map(time_json),
+        "deltas": deltas.collect::<Vec<_>>(),
+        "payouts": item.payouts.iter().map(payout_json).collect::<Vec<_>>(),
+        "liquidity": liquidity_json(&item.liquidity),
+        "units": item.units,
+        "sales": item.sales.iter().map(|s| sale_json(s, &delta_id)).collect::<Vec<_>>(),
+        "minimum_payment": item.minimum_payment.as_ref().map(|m| json!({
+            "floor": value_json(&m.floor),
+            "percent": m.percent,
+        })),
+        "capitalization": item.capitalization().map(|c| c.num_nanoseconds().unwrap()),
+        "lease": item.lease.as_deref().map(lease_json),
+        "prices": item.prices.as_deref().map(prices_json),
+        "round_each_period": item.round_each_period.map(|(decimals, rounding)| json!({
+            "decimals": decimals,
+            "rounding": rounding_json(rounding),
+        })),
+        "application_order": match item.application_order {
+            ApplicationOrder::InterestFirst => "interest_first",
+            ApplicationOrder::PaymentFirst => "payment_first",
+        },
+        "balloon": item.balloon.map(&delta_id),
+        "fx_overrides": item
+            .fx_overrides
+            .iter()
+            .map(|(currency, rate)| json!([currency.code(), rate]))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn rate_policy_json(policy: RatePolicy) -> Json {
+    match policy {
+        RatePolicy::RateAtAssessmentDate => json!("assessment_date"),
+        RatePolicy::RateAtFixedDate(date) => json!({ "fixed_date": time_json(date) }),
+        RatePolicy::Latest => json!("latest"),
+    }
+}
+
+/// FX and custom risks hold a table or a closure and are not written.
+fn risk_json(risk: &RiskSpec) -> Option<Json> {
+    Some(match risk {
+        RiskSpec::CertainLossPercentage { percent } => json!({
+            "variant": "certain_loss_percentage",
+            "percent": percent,
+        }),
+        RiskSpec::LosePercentOverTime {
+            percent,
+            period,
+            starting,
+        } => json!({
+            "variant": "lose_percent_over_time",
+            "percent": percent,
+            "period": period_json(*period),
+            "starting": time_json(*starting),
+        }),
+        RiskSpec::NormalReturn { mean, deviation } => json!({
+            "variant": "normal_return",
+            "mean": mean,
+            "deviation": deviation,
+        }),
+        RiskSpec::Windowed { from, to } => json!({
+            "variant": "windowed",
+            "from": time_json(*from),
+            "to": time_json(*to),
+        }),
+        RiskSpec::FxShock { .. } | RiskSpec::FxVolatility { .. } | RiskSpec::Custom { .. } => {
+            return None
+        }
+    })
+}
+
+fn convention_json(convention: BusinessDayConvention) -> Json {
+    match convention {
+        BusinessDayConvention::None => json!("none"),
+        BusinessDayConvention::Following => json!("following"),
+        BusinessDayConvention::ModifiedFollowing => json!("modified_following"),
+        BusinessDayConvention::Preceding => json!("preceding"),
+    }
+}
+
+impl Book {
+    /// The book as a JSON document at the current format version. Items are
+    /// numbered in the order of [Book::ordered_keys] and children refer to
+    /// those numbers, or to their external id when they have one so
+    /// documents can be merged. Deltas are numbered in the same order, and
+    /// items refer to their group by its place in the groups.
+    pub fn to_json(&self) -> Json {
+        let ids: HashMap<ItemKey, usize> =
+            self.iter_ordered().enumerate().map(|(i, (k, _))| (k, i)).collect();
+        let group_ids: HashMap<GroupId, usize> =
+            self.groups.keys().enumerate().map(|(i, id)| (id, i)).collect();
+        let groups = self.groups.values().map(|group| {
+            json!({
+                "name": group.name,
+                "currency": group.currency.map(|c| c.code()),
+            })
+        });
+        let delta_ids: HashMap<DeltaId, usize> = self
+            .iter_ordered()
+            .flat_map(|(_, item)| &item.deltas)
+            .enumerate()
+            .map(|(i, d)| (d.id, i))
+            .collect();
+        let items = self
+            .iter_ordered()
+            .map(|(key, item)| {
+                let mut json = item_json(item, delta_json, |id| json!(delta_ids.get(&id)));
+                json["id"] = json!(ids[&key]);
+                json["name"] = json!(self.name(key));
+                json["external_id"] = json!(self.external_id(key));
+                json["group"] = json!(self.membership.get(key).map(|g| group_ids[g]));
+                json["risks"] = self.risks(key).iter().filter_map(risk_json).collect();
+                json["frozen"] = json!(self.frozen_since(key).map(time_json));
+                json["children"] = item
+                    .children
+                    .iter()
+                    .map(|c| match self.external_id(*c) {
+                        Some(external) => json!(external),
+                        None => json!(ids[c]),
+                    })
+                    .collect();
+                json
+            })
+            .collect::<Vec<_>>();
+        let mut cash_interest: Vec<_> = self.cash_interest.iter().collect();
+        cash_interest.sort_by_key(|(currency, _)| currency.code());
+        let cash_interest = cash_interest.into_iter().map(|(currency, interest)| {
+            json!({ "currency": currency.code(), "interest": interest_json(interest) })
+        });
+        json!({
+            "format_version": FORMAT_VERSION,
+            "items": items,
+            "groups": groups.collect::<Vec<_>>(),
+            "reporting": self.reporting().map(|(currency, policy)| json!({
+                "currency": currency.code(),
+                "policy": rate_policy_json(policy),
+            })),
+            "cash_interest": cash_interest.collect::<Vec<_>>(),
+            "precision_tolerance": self.precision_tolerance,
+        })
+    }
+
+    pub fn write_json(&self, writer: impl Write) -> Result<(), JsonError> {
+        Ok(serde_json::to_writer_pretty(writer, &self.to_json())?)
+    }
+
+    /// Reads a book from a document of any supported version, older
+    /// documents are migrated first.
+    pub fn from_json(document: Json) -> Result<Book, JsonError> {
+        Book::from_json_with(document, &Defaults::default())
+    }
+
+    /// Reads a book like [Book::from_json], migrating older documents with
+    /// `defaults` for what their version did not keep. Documents from before
+    /// amounts needed a currency are read this way with a default currency.
+    pub fn from_json_with(document: Json, defaults: &Defaults) -> Result<Book, JsonError> {
+        let document = migrate_with(document, defaults)?;
+        let items = document["items"]
+            .as_array()
+            .ok_or_else(|| invalid("items is not an array"))?;
+
+        let mut book = Book::default();
+        let reporting = &document["reporting"];
+        if !reporting.is_null() {
+            let code = reporting["currency"]
+                .as_str()
+                .ok_or_else(|| invalid("reporting without a currency"))?;
+            book.set_reporting(parse_code(code)?, parse_rate_policy(&reporting["policy"])?);
+        }
+        let mut groups = vec![];
+        for group in array(&document["groups"])? {
+            let name = group["name"]
+                .as_str()
+                .ok_or_else(|| invalid("group without a name"))?;
+            let id = book.create_group(name);
+            if let Some(code) = group["currency"].as_str() {
+                book.set_group_currency(id, parse_code(code)?);
+            }
+            groups.push(id);
+        }
+        let mut keys = HashMap::new();
+        let mut delta_ids = EventIds::default();
+        for item in items {
+            let id = item["id"]
+                .as_u64()
+                .ok_or_else(|| invalid("item without an id"))?;
+            let parsed = parse_item(item, &mut delta_ids)?;
+
+            let key = match item["name"].as_str() {
+                Some(name) => book.add_named(name, parsed),
+                None => book.add(parsed),
+            };
+            if let Some(external) = item["external_id"].as_str() {
+                if !book.set_external_id(key, external) {
+                    return Err(invalid(format!("duplicate external id {external:?}")));
+                }
+            }
+            if let Some(group) = item["group"].as_u64() {
+                let group = groups
+                    .get(group as usize)
+                    .ok_or_else(|| invalid(format!("unknown group {group}")))?;
+                book.assign(key, *group);
+            }
+            for risk in array(&item["risks"])? {
+                book.attach_risk(key, parse_risk(risk)?);
+            }
+            if !item["frozen"].is_null() {
+                book.freeze(key, parse_time(&item["frozen"])?);
+            }
+            keys.insert(id, key);
+        }
+        for item in items {
+            let parent = keys[&item["id"].as_u64().unwrap()];
+            for child in array(&item["children"])? {
+                let found = match child.as_str() {
+                    Some(external) => book.by_external_id(external),
+                    None => child.as_u64().and_then(|c| keys.get(&c)).copied(),
+                };
+                let child = found.ok_or_else(|| invalid(format!("dangling child {child}")))?;
+                book.link_child(parent, child);
+            }
+        }
+        for cash in array(&document["cash_interest"])? {
+            let code = cash["currency"]
+                .as_str()
+                .ok_or_else(|| invalid("cash interest without a currency"))?;
+            let interest = delta_ids.parse_interest(&cash["interest"])?;
+            book.set_cash_interest(parse_code(code)?, Some(interest));
+        }
+        if !document["precision_tolerance"].is_null() {
+            let tolerance = document["precision_tolerance"]
+                .as_f64()
+                .ok_or_else(|| invalid("precision tolerance is not a number"))?;
+            book.set_precision_tolerance(Some(tolerance));
+        }
+        book.take_audit_log();
+        Ok(book)
+    }
+
+    pub fn read_json(reader: impl Read) -> Result<Book, JsonError> {
+        Book::from_json(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Missing arrays are treated as empty.
+impl ImportPlan {
+    /// The plan as JSON for confirming it before it is applied. Deltas going
+    /// to items already in the book name them, ones going to new items give
+    /// the index into `items`.
+    pub fn to_json(&self, book: &Book) -> Json {
+        let target = |target: &PlanTarget| match target {
+            PlanTarget::Existing(key) => json!({
+                "name": book.name(*key),
+                "external_id": book.external_id(*key),
+            }),
+            PlanTarget::New(index) => json!({ "new": index }),
+        };
+        let items = self.items.iter().map(|planned| {
+            json!({
+                "name": planned.name,
+                "external_id": planned.external_id,
+                "book_value": value_json(&planned.item.book_value),
+                "inception": time_json(planned.item.inception),
+            })
+        });
+        let deltas = self.deltas.iter().map(|planned| {
+            let conversion = planned.conversion.as_ref().map(|c| {
+                json!({
+                    "from": c.from.code(),
+                    "to": c.to.code(),
+                    "rate": c.rate.as_ref().ok(),
+                    "error": c.rate.as_ref().err().map(ToString::to_string),
+                })
+            });
+            json!({
+                "target": target(&planned.target),
+                "delta": delta_json(&planned.delta),
+                "conversion": conversion,
+            })
+        });
+        let duplicates = self.duplicates.iter().map(|(to, delta)| {
+            json!({ "target": target(to), "delta": delta_json(delta) })
+        });
+        let unresolved = self.unresolved.iter().map(|(item, delta)| {
+            let item = match item {
+                ItemRef::Name(name) => json!({ "name": name }),
+                ItemRef::External(id) => json!({ "external_id": id }),
+            };
+            json!({ "target": item, "delta": delta_json(delta) })
+        });
+        json!({
+            "items": items.collect::<Vec<_>>(),
+            "deltas": deltas.collect::<Vec<_>>(),
+            "duplicates": duplicates.collect::<Vec<_>>(),
+            "unresolved": unresolved.collect::<Vec<_>>(),
+            "cursor": self.cursor.map(|c| c.0),
+        })
+    }
+}
+
+fn key_json(key: ItemKey) -> Json {
+    json!(key.data().as_ffi())
+}
+
+fn group_json(id: GroupId) -> Json {
+    json!(id.data().as_ffi())
+}
+
+/// Deltas in audit entries keep their id so they can be told apart when
+/// they are removed again.
+fn event_delta_json(delta: &Delta) -> Json {
+    let mut json = delta_json(delta);
+    json["id"] = json!(delta.id.get());
+    json
+}
+
+/// Children in audit entries are the keys of the items.
+fn event_item_json(item: &Item) -> Json {
+    let mut json = item_json(item, event_delta_json, |id| json!(id.get()));
+    json["children"] = item.children.iter().map(|c| key_json(*c)).collect();
+    json
+}
+
+impl AuditEntry {
+    /// The entry as JSON for sending to another device, see
+    /// [Book::events_since]. Keys and ids are written as numbers, which
+    /// [AuditEntry::parse_stream] maps back onto fresh ids.
+    pub fn to_json(&self) -> Json {
+        match self {
+            AuditEntry::ItemAdded { key, name, item } => json!({
+                "event": "item_added",
+                "key": key_json(*key),
+                "name": name,
+                "item": event_item_json(item),
+            }),
+            AuditEntry::ItemRemoved { key } => {
+                json!({ "event": "item_removed", "key": key_json(*key) })
+            }
+            AuditEntry::ItemReplaced { key, item } => json!({
+                "event": "item_replaced",
+                "key": key_json(*key),
+                "item": event_item_json(item),
+            }),
+            AuditEntry::ChildAdded { parent, child } => json!({
+                "event": "child_added",
+                "parent": key_json(*parent),
+                "child": key_json(*child),
+            }),
+            AuditEntry::ChildRemoved { parent, child } => json!({
+                "event": "child_removed",
+                "parent": key_json(*parent),
+                "child": key_json(*child),
+            }),
+            AuditEntry::ExternalIdSet { key, id } => {
+                json!({ "event": "external_id_set", "key": key_json(*key), "id": id })
+            }
+            AuditEntry::DeltaAdded { key, delta } => json!({
+                "event": "delta_added",
+                "key": key_json(*key),
+                "delta": event_delta_json(delta),
+            }),
+            AuditEntry::DeltaRemoved { key, delta } => json!({
+                "event": "delta_removed",
+                "key": key_json(*key),
+                "delta": event_delta_json(delta),
+            }),
+            AuditEntry::Renamed { key, name } => {
+                json!({ "event": "renamed", "key": key_json(*key), "name": name })
+            }
+            AuditEntry::ImportApplied {
+                items,
+                deltas,
+                duplicates,
+            } => json!({
+                "event": "import_applied",
+                "items": items,
+                "deltas": deltas,
+                "duplicates": duplicates,
+            }),
+            AuditEntry::CurrencyMigrated { old, new, factor } => json!({
+                "event": "currency_migrated",
+                "old": old.code(),
+                "new": new.code(),
+                "factor": factor,
+            }),
+            AuditEntry::Transferred { transfer } => json!({
+                "event": "transferred",
+                "id": transfer.id.get(),
+                "from": key_json(transfer.from),
+                "to": key_json(transfer.to),
+                "time": time_json(transfer.time),
+                "amount": value_json(&transfer.amount),
+                "from_delta": transfer.from_delta.get(),
+                "to_delta": transfer.to_delta.get(),
+            }),
+            AuditEntry::Untransferred { id } => {
+                json!({ "event": "untransferred", "id": id.get() })
+            }
+            AuditEntry::Reordered { key, before } => json!({
+                "event": "reordered",
+                "key": key_json(*key),
+                "before": key_json(*before),
+            }),
+            AuditEntry::GroupCreated { id, name } => {
+                json!({ "event": "group_created", "id": group_json(*id), "name": name })
+            }
+            AuditEntry::GroupCurrencySet { id, currency } => json!({
+                "event": "group_currency_set",
+                "id": group_json(*id),
+                "currency": currency.code(),
+            }),
+            AuditEntry::Assigned { key, group } => json!({
+                "event": "assigned",
+                "key": key_json(*key),
+                "group": group_json(*group),
+            }),
+            // FX and custom risks are written as null and fail to read back.
+            AuditEntry::RiskAttached { key, risk } => json!({
+                "event": "risk_attached",
+                "key": key_json(*key),
+                "risk": risk_json(risk),
+            }),
+            AuditEntry::RisksDetached { key } => {
+                json!({ "event": "risks_detached", "key": key_json(*key) })
+            }
+            AuditEntry::Frozen { key, at } => {
+                json!({ "event": "frozen", "key": key_json(*key), "at": time_json(*at) })
+            }
+            AuditEntry::ReportingSet { currency, policy } => json!({
+                "event": "reporting_set",
+                "currency": currency.code(),
+                "policy": rate_policy_json(*policy),
+            }),
+            AuditEntry::CashInterestSet { currency, interest } => json!({
+                "event": "cash_interest_set",
+                "currency": currency.code(),
+                "interest": interest.as_ref().map(interest_json),
+            }),
+            AuditEntry::PrecisionToleranceSet { tolerance } => {
+                json!({ "event": "precision_tolerance_set", "tolerance": tolerance })
+            }
+        }
+    }
+
+    /// Reads back an array of entries written by [AuditEntry::to_json].
+    /// Delta and transfer ids are handed out afresh, an id that shows up
+    /// twice in the array gets the same new id both times.
+    pub fn parse_stream(json: &Json) -> Result<Vec<AuditEntry>, JsonError> {
+        let mut ids = EventIds::default();
+        json.as_array()
+            .ok_or_else(|| invalid("events are not an array"))?
+            .iter()
+            .map(|entry| ids.parse(entry))
+            .collect()
+    }
+}
+
+/// The ids read so far in a document or stream and the fresh ones they were
+/// given.
+#[derive(Default)]
+struct EventIds {
+    deltas: HashMap<u64, DeltaId>,
+    transfers: HashMap<u64, TransferId>,
+    benchmarks: HashMap<u64, BenchmarkId>,
+}
+
+impl EventIds {
+    /// Registers the benchmark the first time its id is read, interest
+    /// indexed to the same benchmark shares the registered one.
+    fn benchmark(&mut self, json: &Json) -> Result<BenchmarkId, JsonError> {
+        let id = json["id"]
+            .as_u64()
+            .ok_or_else(|| invalid("benchmark without an id"))?;
+        if let Some(registered) = self.benchmarks.get(&id) {
+            return Ok(*registered);
+        }
+        let name = json["name"]
+            .as_str()
+            .ok_or_else(|| invalid(format!("benchmark {id} without a name")))?;
+        let mut benchmark = Benchmark::new(name);
+        for rate in array(&json["rates"])? {
+            let value = rate[1]
+                .as_f64()
+                .ok_or_else(|| invalid(format!("bad rate {rate} of benchmark {name}")))?;
+            benchmark.set_rate(parse_time(&rate[0])?, value);
+        }
+        let registered = BENCHMARKS.register(benchmark);
+        self.benchmarks.insert(id, registered);
+        Ok(registered)
+    }
+
+    fn parse_interest(&mut self, json: &Json) -> Result<Interest, JsonError> {
+        let interest = parse_interest(json)?;
+        if json["benchmark"].is_null() {
+            return Ok(interest);
+        }
+        let id = self.benchmark(&json["benchmark"])?;
+        Ok(Interest::indexed(id, interest.rate(), interest.period())
+            .anchored(interest.anchor())
+            .before_inception(interest.inception_policy()))
+    }
+
+    fn delta(&mut self, json: &Json) -> Result<DeltaId, JsonError> {
+        let id = json.as_u64().ok_or_else(|| invalid("delta without an id"))?;
+        Ok(*self.deltas.entry(id).or_insert_with(DeltaId::next))
+    }
+
+    fn transfer(&mut self, json: &Json) -> Result<TransferId, JsonError> {
+        let id = json.as_u64().ok_or_else(|| invalid("transfer without an id"))?;
+        Ok(*self.transfers.entry(id).or_insert_with(TransferId::next))
+    }
+
+    /// Documents written before deltas were numbered keep the id the delta
+    /// was made with.
+    fn parse_delta(&mut self, json: &Json) -> Result<Delta, JsonError> {
+        let mut delta = parse_delta(json)?;
+        if !json["id"].is_null() {
+            delta.id = self.delta(&json["id"])?;
+        }
+        Ok(delta)
+    }
+
+    fn parse_sale(&mut self, json: &Json) -> Result<Sale, JsonError> {
+        Ok(Sale {
+            delta: self.delta(&json["delta"])?,
+            time: parse_time(&json["time"])?,
+            fraction: json["fraction"]
+                .as_f64()
+                .ok_or_else(|| invalid("sale without a fraction"))?,
+            proceeds: parse_value(&json["proceeds"])?,
+        })
+    }
+
+    /// An item written by [event_item_json].
+    fn parse_item(&mut self, json: &Json) -> Result<Item, JsonError> {
+        let mut item = parse_item(json, self)?;
+        item.children = array(&json["children"])?
+            .iter()
+            .map(|c| {
+                c.as_u64()
+                    .map(|k| ItemKey::from(KeyData::from_ffi(k)))
+                    .ok_or_else(|| invalid(format!("bad child {c}")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(item)
+    }
+
+    fn parse(&mut self, json: &Json) -> Result<AuditEntry, JsonError> {
+        let key = |field: &str| {
+            json[field]
+                .as_u64()
+                .map(|k| ItemKey::from(KeyData::from_ffi(k)))
+                .ok_or_else(|| invalid(format!("event without a {field}")))
+        };
+        let text = |field: &str| json[field].as_str().map(str::to_string);
+        let currency = |field: &str| {
+            let code = json[field]
+                .as_str()
+                .ok_or_else(|| invalid(format!("event without a {field} currency")))?;
+            code.parse::<Currency>()
+                .map_err(|e| invalid(format!("currency {code:?}: {e}")))
+        };
+        let count = |field: &str| {
+            json[field]
+                .as_u64()
+                .map(|n| n as usize)
+                .ok_or_else(|| invalid(format!("event without a {field} count")))
+        };
+        let group = |field: &str| {
+            json[field]
+                .as_u64()
+                .map(|id| GroupId::from(KeyData::from_ffi(id)))
+                .ok_or_else(|| invalid(format!("event without a {field}")))
+        };
+        Ok(match json["event"].as_str() {
+            Some("item_added") => AuditEntry::ItemAdded {
+                key: key("key")?,
+                name: text("name"),
+                item: Arc::new(self.parse_item(&json["item"])?),
+            },
+            Some("item_removed") => AuditEntry::ItemRemoved { key: key("key")? },
+            Some("item_replaced") => AuditEntry::ItemReplaced {
+                key: key("key")?,
+                item: Arc::new(self.parse_item(&json["item"])?),
+            },
+            Some("child_added") => AuditEntry::ChildAdded {
+                parent: key("parent")?,
+                child: key("child")?,
+            },
+            Some("child_removed") => AuditEntry::ChildRemoved {
+                parent: key("parent")?,
+                child: key("child")?,
+            },
+            Some("external_id_set") => AuditEntry::ExternalIdSet {
+                key: key("key")?,
+                id: text("id").ok_or_else(|| invalid("external id is not a string"))?,
+            },
+            Some("delta_added") => AuditEntry::DeltaAdded {
+                key: key("key")?,
+                delta: self.parse_delta(&json["delta"])?,
+            },
+            Some("delta_removed") => AuditEntry::DeltaRemoved {
+                key: key("key")?,
+                delta: self.parse_delta(&json["delta"])?,
+            },
+            Some("renamed") => AuditEntry::Renamed {
+                key: key("key")?,
+                name: text("name"),
+            },
+            Some("import_applied") => AuditEntry::ImportApplied {
+                items: count("items")?,
+                deltas: count("deltas")?,
+                duplicates: count("duplicates")?,
+            },
+            Some("currency_migrated") => AuditEntry::CurrencyMigrated {
+                old: currency("old")?,
+                new: currency("new")?,
+                factor: json["factor"]
+                    .as_f64()
+                    .ok_or_else(|| invalid("migration without a factor"))?,
+            },
+            Some("transferred") => AuditEntry::Transferred {
+                transfer: Transfer {
+                    id: self.transfer(&json["id"])?,
+                    from: key("from")?,
+                    to: key("to")?,
+                    time: parse_time(&json["time"])?,
+                    amount: parse_value(&json["amount"])?,
+                    from_delta: self.delta(&json["from_delta"])?,
+                    to_delta: self.delta(&json["to_delta"])?,
+                },
+            },
+            Some("untransferred") => AuditEntry::Untransferred {
+                id: self.transfer(&json["id"])?,
+            },
+            Some("reordered") => AuditEntry::Reordered {
+                key: key("key")?,
+                before: key("before")?,
+            },
+            Some("group_created") => AuditEntry::GroupCreated {
+                id: group("id")?,
+                name: text("name").ok_or_else(|| invalid("group without a name"))?,
+            },
+            Some("group_currency_set") => AuditEntry::GroupCurrencySet {
+                id: group("id")?,
+                currency: currency("currency")?,
+            },
+            Some("assigned") => AuditEntry::Assigned {
+                key: key("key")?,
+                group: group("group")?,
+            },
+            Some("risk_attached") if json["risk"].is_null() => {
+                return Err(invalid("attached risk is FX or custom, which are not written"))
+            }
+            Some("risk_attached") => AuditEntry::RiskAttached {
+                key: key("key")?,
+                risk: parse_risk(&json["risk"])?,
+            },
+            Some("risks_detached") => AuditEntry::RisksDetached { key: key("key")? },
+            Some("frozen") => AuditEntry::Frozen {
+                key: key("key")?,
+                at: parse_time(&json["at"])?,
+            },
+            Some("reporting_set") => AuditEntry::ReportingSet {
+                currency: currency("currency")?,
+                policy: parse_rate_policy(&json["policy"])?,
+            },
+            Some("cash_interest_set") => AuditEntry::CashInterestSet {
+                currency: currency("currency")?,
+                interest: match json["interest"].is_null() {
+                    true => None,
+                    false => Some(self.parse_interest(&json["interest"])?),
+                },
+            },
+            Some("precision_tolerance_set") => AuditEntry::PrecisionToleranceSet {
+                tolerance: json["tolerance"].as_f64(),
+            },
+            other => return Err(invalid(format!("unknown event {other:?}"))),
+        })
+    }
+}
+
+fn array(json: &Json) -> Result<&[Json], JsonError> {
+    match json {
+        Json::Null => Ok(&[]),
+        Json::Array(values) => Ok(values),
+        other => Err(invalid(format!("expected an array, got {other}"))),
+    }
+}
+
+fn parse_time(json: &Json) -> Result<DateTime<Utc>, JsonError> {
+    let text = json
+        .as_str()
+        .ok_or_else(|| invalid("time is not a string"))?;
+    DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| invalid(format!("bad time {text}: {e}")))
+}
+
+fn parse_period(json: &Json) -> Result<Period, JsonError> {
+    let count = |unit: &str, months: i64| {
+        let count = json[unit].as_i64()?;
+        Some(u32::try_from(count).map_err(|_| PeriodError::Months(count.saturating_mul(months))))
+    };
+    let period = if let Some(nanos) = json.as_i64() {
+        Ok(Period::Delta(TimeDelta::nanoseconds(nanos)))
+    } else if let Some(months) = count("months", 1) {
+        months.map(Period::Months)
+    } else if let Some(years) = count("years", 12) {
+        years.map(Period::Years)
+    } else {
+        return Err(invalid(format!("bad period {json}")));
+    };
+    period
+        .and_then(Period::validate)
+        .map_err(|e| invalid(format!("bad period {json}: {e}")))
+}
+
+fn parse_code(code: &str) -> Result<Currency, JsonError> {
+    code.parse()
+        .map_err(|e| invalid(format!("currency {code:?}: {e}")))
+}
+
+fn parse_value(json: &Json) -> Result<Value, JsonError> {
+    let amount = json["amount"]
+        .as_f64()
+        .ok_or_else(|| invalid("value without an amount"))?;
+    let currency = json["currency"]
+        .as_str()
+        .ok_or_else(|| invalid("value without a currency"))?;
+    let currency: Currency = currency
+        .parse()
+        .map_err(|e| invalid(format!("currency {currency:?}: {e}")))?;
+    Ok(Value::new(currency, amount))
+}
+
+fn parse_interest(json: &Json) -> Result<Interest, JsonError> {
+    let percent = json["percent"]
+        .as_f64()
+        .ok_or_else(|| invalid("interest without a rate"))?;
+    let anchor = match json["anchor"].as_str() {
+        None | Some("inception") => PeriodAnchor::Inception,
+        Some("calendar_month_end") => PeriodAnchor::CalendarMonthEnd,
+        Some("calendar_quarter_end") => PeriodAnchor::CalendarQuarterEnd,
+        Some(other) => return Err(invalid(format!("unknown period anchor {other:?}"))),
+    };
+    let policy = match json["before_inception"].as_str() {
+        None | Some("discount") => InceptionPolicy::Discount,
+        Some("hold") => InceptionPolicy::Hold,
+        Some(other) => return Err(invalid(format!("unknown inception policy {other:?}"))),
+    };
+    Interest::try_new(percent, parse_period(&json["period"])?)
+        .map(|interest| interest.anchored(anchor).before_inception(policy))
+        .map_err(|e| invalid(format!("interest: {e}")))
+}
+
+fn parse_delta(json: &Json) -> Result<Delta, JsonError> {
+    let kind = match json["kind"].as_str() {
+        Some("payment") => DeltaKind::Payment,
+        Some("fee") => DeltaKind::Fee,
+        Some("adjustment") => DeltaKind::Adjustment,
+        Some("interest") => DeltaKind::Interest,
+        other => return Err(invalid(format!("unknown delta kind {other:?}"))),
+    };
+    let amount = if let Some(percent) = json["amount"]["percent"].as_f64() {
+        DeltaAmount::Percent(percent)
+    } else {
+        DeltaAmount::Fixed(parse_value(&json["amount"]["fixed"])?)
+    };
+    let text = |field: &str| json[field].as_str().map(str::to_string);
+
+    let mut delta = Delta::new(parse_time(&json["time"])?, amount, kind);
+    delta.memo = text("memo");
+    delta.category = text("category");
+    delta.external_id = text("external_id");
+    delta.units = json["units"].as_f64();
+    delta.rate = json["rate"].as_f64();
+    // Documents written before deltas could be planned have no status.
+    delta.status = match json["status"].as_str() {
+        None | Some("actual") => DeltaStatus::Actual,
+        Some("planned") => DeltaStatus::Planned,
+        Some(other) => return Err(invalid(format!("unknown delta status {other:?}"))),
+    };
+    Ok(delta)
+}
+
+fn parse_item(json: &Json, ids: &mut EventIds) -> Result<Item, JsonError> {
+    let mut item = Item::fixed(parse_value(&json["book_value"])?, parse_time(&json["inception"])?);
+    if !json["interest"].is_null() {
+        item.interest = Some(ids.parse_interest(&json["interest"])?);
+    }
+    if !json["interest_posted"].is_null() {
+        item.interest_posted = Some(parse_time(&json["interest_posted"])?);
+    }
+    item.deltas = array(&json["deltas"])?
+        .iter()
+        .map(|delta| ids.parse_delta(delta))
+        .collect::<Result<_, _>>()?;
+    item.sort_deltas();
+    item.payouts = array(&json["payouts"])?
+        .iter()
+        .map(|payout| parse_payout(payout, ids))
+        .collect::<Result<_, _>>()?;
+    item.liquidity = parse_liquidity(&json["liquidity"])?;
+    item.units = json["units"].as_f64();
+    item.sales = array(&json["sales"])?
+        .iter()
+        .map(|sale| ids.parse_sale(sale))
+        .collect::<Result<_, _>>()?;
+    let minimum = &json["minimum_payment"];
+    if !minimum.is_null() {
+        item.minimum_payment = Some(MinPayment {
+            floor: parse_value(&minimum["floor"])?,
+            percent: minimum["percent"]
+                .as_f64()
+                .ok_or_else(|| invalid("minimum payment without a percent"))?,
+        });
+    }
+    if !json["capitalization"].is_null() {
+        let nanos = json["capitalization"]
+            .as_i64()
+            .ok_or_else(|| invalid(format!("bad capitalization {}", json["capitalization"])))?;
+        item.set_capitalization(Some(TimeDelta::nanoseconds(nanos)))
+            .map_err(|e| invalid(format!("bad capitalization: {e}")))?;
+    }
+    if !json["lease"].is_null() {
+        item.lease = Some(Box::new(parse_lease(&json["lease"], ids)?));
+    }
+    if !json["prices"].is_null() {
+        item.prices = Some(Box::new(parse_prices(&json["prices"])?));
+    }
+    let rounded = &json["round_each_period"];
+    if !rounded.is_null() {
+        let decimals = rounded["decimals"]
+            .as_u64()
+            .and_then(|decimals| u32::try_from(decimals).ok())
+            .ok_or_else(|| invalid(format!("bad rounding decimals {}", rounded["decimals"])))?;
+        item.round_each_period = Some((decimals, parse_rounding(&rounded["rounding"])?));
+    }
+    item.application_order = match json["application_order"].as_str() {
+        None | Some("interest_first") => ApplicationOrder::InterestFirst,
+        Some("payment_first") => ApplicationOrder::PaymentFirst,
+        Some(other) => return Err(invalid(format!("unknown application order {other:?}"))),
+    };
+    if !json["balloon"].is_null() {
+        item.balloon = Some(ids.delta(&json["balloon"])?);
+    }
+    for locked in array(&json["fx_overrides"])? {
+        let (Some(code), Some(rate)) = (locked[0].as_str(), locked[1].as_f64()) else {
+            return Err(invalid(format!("bad conversion override {locked}")));
+        };
+        item.set_fx_override(parse_code(code)?, rate);
+    }
+    Ok(item)
+}
+
+fn parse_rounding(json: &Json) -> Result<Rounding, JsonError> {
+    match json.as_str() {
+        Some("half_away_from_zero") => Ok(Rounding::HalfAwayFromZero),
+        Some("half_even") => Ok(Rounding::HalfEven),
+        Some("toward_zero") => Ok(Rounding::TowardZero),
+        _ => Err(invalid(format!("unknown rounding {json}"))),
+    }
+}
+
+/// The payments of a lease are read with the rest of the payouts.
+fn parse_lease(json: &Json, ids: &mut EventIds) -> Result<Lease, JsonError> {
+    let frequency = json["frequency"]
+        .as_i64()
+        .filter(|nanos| *nanos > 0)
+        .ok_or_else(|| invalid(format!("bad lease frequency {}", json["frequency"])))?;
+    let term = json["term"]
+        .as_u64()
+        .and_then(|term| u32::try_from(term).ok())
+        .ok_or_else(|| invalid(format!("bad lease term {}", json["term"])))?;
+    let discount = match &json["discount"] {
+        Json::Null => None,
+        discount => Some(ids.parse_interest(discount)?),
+    };
+    Ok(Lease {
+        payment: parse_value(&json["payment"])?,
+        frequency: TimeDelta::nanoseconds(frequency),
+        term,
+        residual: parse_value(&json["residual"])?,
+        start: parse_time(&json["start"])?,
+        discount,
+    })
+}
+
+fn parse_prices(json: &Json) -> Result<PriceSeries, JsonError> {
+    let points = array(&json["points"])?
+        .iter()
+        .map(|point| match (parse_time(&point[0]), point[1].as_f64()) {
+            (Ok(time), Some(price)) => Ok((time, price)),
+            _ => Err(invalid(format!("bad price {point}"))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if points.is_empty() {
+        return Err(invalid("price series without prices"));
+    }
+    let interpolation = match json["interpolation"].as_str() {
+        Some("step") => Interpolation::Step,
+        Some("linear") => Interpolation::Linear,
+        _ => return Err(invalid(format!("unknown interpolation {}", json["interpolation"]))),
+    };
+    let out_of_range = |json: &Json| match json.as_str() {
+        Some("nearest") => Ok(OutOfRange::Nearest),
+        Some("zero") => Ok(OutOfRange::Zero),
+        _ => Err(invalid(format!("unknown out of range policy {json}"))),
+    };
+    Ok(PriceSeries::new(points)
+        .with_interpolation(interpolation)
+        .with_out_of_range(out_of_range(&json["before"])?, out_of_range(&json["after"])?))
+}
+
+/// Documents written before liquidity was kept only have liquid items.
+fn parse_liquidity(json: &Json) -> Result<Liquidity, JsonError> {
+    match json.as_str() {
+        None if json.is_null() => Ok(Liquidity::Liquid),
+        Some("liquid") => Ok(Liquidity::Liquid),
+        Some("illiquid") => Ok(Liquidity::Illiquid),
+        None if json["escrow"].is_object() => Ok(Liquidity::Escrow {
+            release: parse_time(&json["escrow"]["release"])?,
+            forfeit_percent: json["escrow"]["forfeit_percent"]
+                .as_f64()
+                .ok_or_else(|| invalid("escrow without a forfeit"))?,
+        }),
+        _ => Err(invalid(format!("unknown liquidity {json}"))),
+    }
+}
+
+fn parse_rate_policy(json: &Json) -> Result<RatePolicy, JsonError> {
+    match json.as_str() {
+        Some("assessment_date") => Ok(RatePolicy::RateAtAssessmentDate),
+        Some("latest") => Ok(RatePolicy::Latest),
+        None if !json["fixed_date"].is_null() => {
+            Ok(RatePolicy::RateAtFixedDate(parse_time(&json["fixed_date"])?))
+        }
+        _ => Err(invalid(format!("unknown rate policy {json}"))),
+    }
+}
+
+fn parse_risk(json: &Json) -> Result<RiskSpec, JsonError> {
+    let number = |field: &str| {
+        json[field]
+            .as_f64()
+            .ok_or_else(|| invalid(format!("risk without a {field}")))
+    };
+    Ok(match json["variant"].as_str() {
+        Some("certain_loss_percentage") => RiskSpec::CertainLossPercentage {
+            percent: number("percent")?,
+        },
+        Some("lose_percent_over_time") => RiskSpec::LosePercentOverTime {
+            percent: number("percent")?,
+            period: parse_period(&json["period"])?,
+            starting: parse_time(&json["starting"])?,
+        },
+        Some("normal_return") => RiskSpec::NormalReturn {
+            mean: number("mean")?,
+            deviation: number("deviation")?,
+        },
+        Some("windowed") => RiskSpec::Windowed {
+            from: parse_time(&json["from"])?,
+            to: parse_time(&json["to"])?,
+        },
+        other => return Err(invalid(format!("unknown risk {other:?}"))),
+    })
+}
+
+/// Documents written before proration existed have none.
+fn parse_proration(json: &Json) -> Result<Proration, JsonError> {
+    match json.as_str() {
+        None | Some("none") => Ok(Proration::None),
+        Some("linear") => Ok(Proration::Linear),
+        Some(other) => Err(invalid(format!("unknown proration {other:?}"))),
+    }
+}
+
+fn parse_payout(json: &Json, ids: &mut EventIds) -> Result<Payout, JsonError> {
+    Ok(match json["variant"].as_str() {
+        Some("one_time") => Payout::OneTime {
+            amount: parse_value(&json["amount"])?,
+            time: parse_time(&json["time"])?,
+        },
+        Some("interest_one_time") => Payout::InterestOneTime {
+            principal: parse_value(&json["principal"])?,
+            time: parse_time(&json["time"])?,
+            interest: ids.parse_interest(&json["interest"])?,
+        },
+        Some("fixed_recurring") => Payout::FixedRecurring {
+            amount: parse_value(&json["amount"])?,
+            start: parse_time(&json["start"])?,
+            frequency: parse_period(&json["frequency"])?,
+            proration: parse_proration(&json["proration"])?,
+        },
+        Some("interest_recurring") => Payout::InterestRecurring {
+            principal: parse_value(&json["principal"])?,
+            start: parse_time(&json["start"])?,
+            frequency: parse_period(&json["frequency"])?,
+            interest: ids.parse_interest(&json["interest"])?,
+            proration: parse_proration(&json["proration"])?,
+        },
+        Some("adjusted") => {
+            let holidays = json["holidays"]
+                .as_array()
+                .ok_or_else(|| invalid("adjusted payout without holidays"))?
+                .iter()
+                .map(|d| {
+                    d.as_str()
+                        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                        .ok_or_else(|| invalid(format!("invalid holiday {d}")))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let days = BusinessDays::new(
+                parse_convention(&json["convention"])?,
+                HolidayCalendar::new(holidays),
+            );
+            parse_payout(&json["payout"], ids)?.adjusted(days)
+        }
+        other => return Err(invalid(format!("unknown payout {other:?}"))),
+    })
+}
+
+fn parse_convention(json: &Json) -> Result<BusinessDayConvention, JsonError> {
+    match json.as_str() {
+        Some("none") => Ok(BusinessDayConvention::None),
+        Some("following") => Ok(BusinessDayConvention::Following),
+        Some("modified_following") => Ok(BusinessDayConvention::ModifiedFollowing),
+        Some("preceding") => Ok(BusinessDayConvention::Preceding),
+        other => Err(invalid(format!("unknown business day convention {other:?}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeDelta, TimeZone, Utc};
+
+    use serde_json::Value as Json;
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        audit::AuditEntry,
+        benchmark::{Benchmark, BENCHMARKS},
+        book::{Book, ItemRef, Upcoming},
+        convert::{RatePolicy, CURRENCY_EXCHANGE},
+        delta::{AssessMode, Delta, DeltaAmount, DeltaKind},
+        groups::GroupId,
+        item::{
+            ApplicationOrder, InceptionPolicy, Interest, Item, Liquidity, MinPayment, Payout,
+            Proration,
+        },
+        period::{Period, PeriodAnchor},
+        position::{Interpolation, OutOfRange, PriceSeries},
+        risk::RiskSpec,
+        sale::SaleSize,
+        value::{Rounding, Value},
+        Assesible,
+    };
+
+    /// The book described by the v1 fixture, built by hand.
+    fn expected() -> Book {
+        let mut book = Book::default();
+        let mut visa = Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.20,
+            TimeDelta::days(365),
+            Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap(),
+        );
+        visa.add_delta(
+            Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(),
+            Value::dummy("CAD", 1000),
+        );
+        book.add_named("Visa", visa);
+
+        let inception = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let house = book.add_named(
+            "House",
+            Item::fixed(Value::new("CAD", 150_000.0), inception),
+        );
+        book.add_child(Item::fixed(Value::new("CAD", -120_000.0), inception), house);
+        book
+    }
+
+    #[test]
+    pub fn test_load_v1_fixture() {
+        let book =
+            Book::read_json(&include_bytes!("../../tests/fixtures/book_v1.json")[..]).unwrap();
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+
+        let visa = book.by_name("Visa").unwrap();
+        assert_eq!(book.get(visa).unwrap().deltas.len(), 1);
+        assert_eq!(book.assess(time).amount(), expected().assess(time).amount());
+    }
+
+    #[test]
+    pub fn test_import_plan_json() {
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add_named("Visa", Item::fixed(Value::new("CAD", -500.0), start));
+        let visa = ItemRef::Name("Visa".to_string());
+        let plan = book.plan_deltas([
+            (visa.clone(), Delta::payment(start, Value::new("CAD", 100.0))),
+            (visa, Delta::payment(start, Value::new("IPJ", 10.0))),
+        ]);
+        let json = plan.to_json(&book);
+        assert_eq!(json["deltas"][0]["target"]["name"], "Visa");
+        assert_eq!(json["deltas"][0]["conversion"], Json::Null);
+        let conversion = &json["deltas"][1]["conversion"];
+        assert_eq!(conversion["rate"], Json::Null);
+        assert_eq!(conversion["error"], "no conversion rate from IPJ to CAD");
+    }
+
+    #[test]
+    pub fn test_round_trip() {
+        let book =
+            Book::read_json(&include_bytes!("../../tests/fixtures/book_v1.json")[..]).unwrap();
+        let mut out = vec![];
+        book.write_json(&mut out).unwrap();
+        let reloaded = Book::read_json(&out[..]).unwrap();
+
+        let time = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(reloaded.assess(time).amount(), book.assess(time).amount());
+        assert_eq!(reloaded.to_json(), book.to_json());
+    }
+
+    #[test]
+    pub fn test_external_ids() {
+        let inception = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let house = book
+            .add_with_external_id("house-1", Item::fixed(Value::new("CAD", 150_000.0), inception))
+            .unwrap();
+        let mortgage = book.add_child(Item::fixed(Value::new("CAD", -120_000.0), inception), house);
+        assert!(book.set_external_id(mortgage, "mortgage-1"));
+        book.add_child(Item::fixed(Value::new("CAD", 5_000.0), inception), house);
+
+        // Identifiers are unique within a book.
+        let cash = Item::fixed(Value::new("CAD", 10.0), inception);
+        assert_eq!(book.add_with_external_id("house-1", cash), None);
+        assert!(!book.set_external_id(house, "mortgage-1"));
+        assert!(book.set_external_id(house, "house-1"));
+
+        let document = book.to_json();
+        assert_eq!(document["items"][0]["children"], serde_json::json!(["mortgage-1", 2]));
+        let loaded = Book::from_json(document.clone()).unwrap();
+        let (house, mortgage) = (
+            loaded.by_external_id("house-1").unwrap(),
+            loaded.by_external_id("mortgage-1").unwrap(),
+        );
+        assert_eq!(loaded.parent(mortgage), Some(house));
+        assert_eq!(loaded.get(house).unwrap().children.len(), 2);
+
+        let mut duplicated = document;
+        duplicated["items"][1]["external_id"] = serde_json::json!("house-1");
+        assert!(Book::from_json(duplicated).is_err());
+    }
+
+    #[test]
+    pub fn test_order_round_trip() {
+        let inception = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        for name in ["Cash", "Gone", "Visa"] {
+            book.add_named(name, Item::fixed(Value::new("CAD", 1.0), inception));
+        }
+        book.remove(book.by_name("Gone").unwrap());
+        let loan = book.add_named("Loan", Item::fixed(Value::new("CAD", 1.0), inception));
+        book.move_before(loan, book.by_name("Visa").unwrap());
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let names = |book: &Book| {
+            book.iter_ordered()
+                .map(|(key, _)| book.name(key).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&loaded), ["Cash", "Loan", "Visa"]);
+        assert_eq!(names(&loaded), names(&book));
+    }
+
+    #[test]
+    pub fn test_posted_interest_round_trip() {
+        let inception = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let year = TimeDelta::days(365);
+        let mut loan = Item::basic_debt(Value::new("CAD", -1_000.0), 0.1, year, inception);
+        loan.post_interest(inception + TimeDelta::days(90), TimeDelta::days(30));
+        let mut book = Book::default();
+        book.add(loan);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let time = inception + TimeDelta::days(200);
+        assert_eq!(loaded.assess(time).amount(), book.assess(time).amount());
+    }
+
+    #[test]
+    pub fn test_minimum_payment_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let card = Item::credit_card(
+            Value::new("CAD", -5_000.0),
+            0.1999,
+            MinPayment {
+                floor: Value::new("CAD", 10.0),
+                percent: 0.02,
+            },
+            start,
+        );
+        book.add_named("Visa", card);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let card = loaded.get(loaded.by_name("Visa").unwrap()).unwrap();
+        let minimum = card.minimum_payment.as_ref().unwrap();
+        assert_value_eq!(minimum.floor.clone(), Value::new("CAD", 10.0), 1e-9);
+        assert_eq!(minimum.percent, 0.02);
+        let horizon = Period::Years(30).step(start, 1);
+        let original = book.get(book.by_name("Visa").unwrap()).unwrap();
+        assert_eq!(
+            card.project_minimum_payments(start, horizon).len(),
+            original.project_minimum_payments(start, horizon).len()
+        );
+    }
+
+    #[test]
+    pub fn test_capitalization_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let day = |n| start + TimeDelta::days(n);
+        let mut book = Book::default();
+        let line = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.073, TimeDelta::days(365))
+            .capitalization(TimeDelta::days(30))
+            .payment(day(15), Value::new("CAD", 1_000.0))
+            .build();
+        book.add_named("Line of credit", line);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let line = loaded.get(loaded.by_name("Line of credit").unwrap()).unwrap();
+        assert_eq!(line.capitalization(), Some(TimeDelta::days(30)));
+        let mid = line.breakdown(day(45));
+        assert_value_eq!(mid.uncapitalized, Value::new("CAD", -27.171), 1e-9);
+        assert_value_eq!(line.assess(day(60)), Value::new("CAD", -9_111.342), 1e-9);
+    }
+
+    #[test]
+    pub fn test_lease_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = TimeDelta::days(30);
+        let car = Item::lease(
+            Value::new("CAD", 500.0),
+            month,
+            36,
+            Value::new("CAD", 15_000.0),
+            start,
+        )
+        .discounted(0.005, month);
+        let mut book = Book::default();
+        book.add_named("Car", car);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let mid = start + month * 18;
+        assert_value_eq!(loaded.assess(mid), book.assess(mid), 1e-9);
+        let end = start + month * 36;
+        let last = start + month * 35;
+        let upcoming = loaded.upcoming(last, Period::Years(1).step(last, 1));
+        assert_eq!(upcoming.len(), 1);
+        assert_eq!(upcoming[0].0, end);
+        assert!(matches!(&upcoming[0].2, Upcoming::Buyout(residual) if residual.amount() == 15_000.0));
+    }
+
+    #[test]
+    pub fn test_position_round_trip() {
+        let on = |day| Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap();
+        let prices = PriceSeries::new(vec![(on(1), 20.0), (on(11), 30.0)])
+            .with_interpolation(Interpolation::Linear)
+            .with_out_of_range(OutOfRange::Zero, OutOfRange::Nearest);
+        let mut xeqt = Item::position(120.0, prices, "CAD");
+        xeqt.trade_at_market(on(6), 30.0);
+        let mut book = Book::default();
+        book.add_named("XEQT", xeqt);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        for time in [on(1) - TimeDelta::days(1), on(4), on(8), on(20)] {
+            assert_value_eq!(loaded.assess(time), book.assess(time), 1e-9);
+        }
+    }
+
+    #[test]
+    pub fn test_rounding_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = Period::Months(1);
+        let mut builder = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.015, month)
+            .round_each_period(2, Rounding::HalfEven);
+        for count in 1..=24 {
+            builder = builder.payment(month.step(start, count), Value::new("CAD", 300.0));
+        }
+        let mut book = Book::default();
+        book.add_named("Loan", builder.build());
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let loan = loaded.get(loaded.by_name("Loan").unwrap()).unwrap();
+        assert_eq!(loan.round_each_period, Some((2, Rounding::HalfEven)));
+        let end = month.step(start, 24);
+        assert_eq!(loaded.assess(end).amount(), book.assess(end).amount());
+    }
+
+    #[test]
+    pub fn test_application_order_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let boundary = start + TimeDelta::days(30);
+        let paydown = Delta::new(boundary, DeltaAmount::Percent(-0.1), DeltaKind::Payment);
+        let line = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.073, TimeDelta::days(365))
+            .capitalization(TimeDelta::days(30))
+            .application_order(ApplicationOrder::PaymentFirst)
+            .delta(paydown)
+            .build();
+        let mut book = Book::default();
+        book.add_named("Line of credit", line);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let line = loaded.get(loaded.by_name("Line of credit").unwrap()).unwrap();
+        assert_eq!(line.application_order, ApplicationOrder::PaymentFirst);
+        assert_value_eq!(line.assess(boundary), Value::new("CAD", -9_060.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_balloon_round_trip() {
+        let on = |year, month| Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+        let loan = Item::interest_only_loan(
+            Value::new("CAD", 400_000.0),
+            0.005,
+            Period::Months(1),
+            on(2025, 1),
+            on(2025, 4),
+            on(2024, 1),
+        );
+        let mut book = Book::default();
+        book.add_named("Mortgage", loan);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let key = loaded.by_name("Mortgage").unwrap();
+        let upcoming = loaded.upcoming(on(2025, 1), on(2026, 1));
+        assert_eq!(upcoming.len(), 1);
+        let (time, item, event) = &upcoming[0];
+        assert_eq!((*time, *item), (on(2025, 4), key));
+        let Upcoming::Balloon(value) = event else {
+            panic!("the balloon is upcoming");
+        };
+        assert_value_eq!(value.clone(), Value::new("CAD", 400_000.0 * 1.005f64.powi(3)), 1e-6);
+    }
+
+    #[test]
+    pub fn test_period_anchor_round_trip() {
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        let card = Item::builder(Value::new("CAD", -1_000.0), on(1, 31))
+            .interest(0.01, Period::Months(1))
+            .period_anchor(PeriodAnchor::CalendarMonthEnd)
+            .capitalization(TimeDelta::days(30))
+            .build();
+        let mut book = Book::default();
+        book.add_named("Card", card);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let card = loaded.get(loaded.by_name("Card").unwrap()).unwrap();
+        let interest = card.interest.as_ref().unwrap();
+        assert_eq!(interest.anchor(), PeriodAnchor::CalendarMonthEnd);
+        let march = card.breakdown(on(3, 15));
+        let february = (-1_000.0 - 10.0 / 31.0) * 1.01;
+        assert_value_eq!(march.principal, Value::new("CAD", february), 1e-9);
+    }
+
+    #[test]
+    pub fn test_inception_policy_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let year = Period::Years(1).step(start, 1);
+        let mut item = Item::fixed(Value::new("CAD", 100.0), year);
+        let held = Interest::new(0.1, Period::Years(1)).before_inception(InceptionPolicy::Hold);
+        item.set_interest(Some(held));
+        let mut book = Book::default();
+        book.add_named("Bond", item);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let bond = loaded.get(loaded.by_name("Bond").unwrap()).unwrap();
+        let interest = bond.interest.as_ref().unwrap();
+        assert_eq!(interest.inception_policy(), InceptionPolicy::Hold);
+        assert_eq!(loaded.assess(start).amount(), 100.0);
+    }
+
+    #[test]
+    pub fn test_fx_override_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        let mut invoice = Item::fixed(Value::new("USD", -100.0), start);
+        invoice.set_fx_override("CAD", 1.30);
+        book.add_named("Invoice", invoice);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let invoice = loaded.get(loaded.by_name("Invoice").unwrap()).unwrap();
+        assert_eq!(invoice.fx_override("CAD".into()), Some(1.30));
+        assert_value_eq!(loaded.assess(start), Value::new("CAD", 870.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_captured_rate_round_trip() {
+        let on = |m| Utc.with_ymd_and_hms(2025, m, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("JFXC", "JFXU", on(1), 0.75);
+        let mut book = Book::default();
+        let debt = book.add_named("Debt", Item::fixed(Value::new("JFXU", -1_000.0), on(1)));
+        let payment = Delta::payment(on(2), Value::new("JFXC", 400.0));
+        book.add_delta_converted(debt, payment, Some(0.72)).unwrap();
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let debt = loaded.get(loaded.by_name("Debt").unwrap()).unwrap();
+        assert_eq!(debt.deltas[0].rate, Some(0.72));
+        assert_value_eq!(debt.assess(on(3)), Value::new("JFXU", -712.0), 1e-9);
+        let gains = debt.fx_gains(on(1), on(3), RatePolicy::RateAtFixedDate(on(1)));
+        assert_value_eq!(gains, Value::new("JFXU", -12.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_groups_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let retirement = book.create_group("Retirement");
+        book.set_group_currency(retirement, "USD");
+        let house = book.create_group("House");
+        let rrsp = book.add_named("RRSP", Item::fixed(Value::new("USD", 10_000.0), start));
+        let home = book.add_named("Home", Item::fixed(Value::new("CAD", 500_000.0), start));
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        book.assign(rrsp, retirement);
+        book.assign(home, house);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let names: Vec<_> = loaded.groups().map(|(_, g)| g.name.clone()).collect();
+        assert_eq!(names, ["Ungrouped", "Retirement", "House"]);
+        let group = |name: &str| loaded.groups().find(|(_, g)| g.name == name).unwrap();
+        let (retirement, group_of_rrsp) = group("Retirement");
+        assert_eq!(group_of_rrsp.currency, Some("USD".into()));
+        assert_eq!(loaded.group_of(loaded.by_name("RRSP").unwrap()), retirement);
+        assert_eq!(loaded.group_of(loaded.by_name("Home").unwrap()), group("House").0);
+        assert_eq!(
+            loaded.group_of(loaded.by_name("Chequing").unwrap()),
+            GroupId::default()
+        );
+        assert_eq!(loaded.to_json(), book.to_json());
+    }
+
+    #[test]
+    pub fn test_risks_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let later = Period::Years(2).step(start, 1);
+        let mut book = Book::default();
+        let car = book.add_named("Car", Item::fixed(Value::new("CAD", 20_000.0), start));
+        book.attach_risk(
+            car,
+            RiskSpec::LosePercentOverTime {
+                percent: 0.15,
+                period: Period::Years(1),
+                starting: start,
+            },
+        );
+        book.attach_risk(car, RiskSpec::CertainLossPercentage { percent: 0.1 });
+        book.attach_risk(
+            car,
+            RiskSpec::Custom {
+                function: Arc::new(|_, value| value),
+            },
+        );
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let car = loaded.by_name("Car").unwrap();
+        assert_eq!(loaded.risks(car).len(), 2);
+        assert_value_eq!(loaded.assess_risked(later), book.assess_risked(later), 1e-9);
+    }
+
+    #[test]
+    pub fn test_reporting_round_trip() {
+        let on = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("JRU", "JRC", on(2015), 1.16);
+        CURRENCY_EXCHANGE.add_conversion_on("JRU", "JRC", on(2025), 1.44);
+        let mut book = Book::default();
+        book.add(Item::fixed(Value::new("JRU", 1_000.0), on(2010)));
+        book.set_reporting("JRC", RatePolicy::RateAtFixedDate(on(2015)));
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        assert_eq!(
+            loaded.reporting(),
+            Some(("JRC".into(), RatePolicy::RateAtFixedDate(on(2015))))
+        );
+        assert_value_eq!(loaded.assess(on(2026)), Value::new("JRC", 1_160.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_cash_interest_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let quarter = Period::Months(3);
+        let end = Period::Years(2).step(start, 1);
+        let dividends = Payout::FixedRecurring {
+            amount: Value::new("CAD", 250.0),
+            start: quarter.step(start, 1),
+            frequency: quarter,
+            proration: Proration::None,
+        };
+        let mut book = Book::default();
+        book.add_named(
+            "Shares",
+            Item::builder(Value::new("CAD", 20_000.0), start)
+                .payout(dividends)
+                .build(),
+        );
+        book.set_cash_interest("CAD".into(), Some(Interest::new(0.03, Period::Years(1))));
+        book.set_cash_interest("USD".into(), Some(Interest::new(0.01, Period::Years(1))));
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        assert_eq!(loaded.cash_interest("USD".into()).unwrap().rate(), 0.01);
+        assert_value_eq!(
+            loaded.assess_full(end).cash().clone(),
+            book.assess_full(end).cash().clone(),
+            1e-9
+        );
+        assert!(loaded.assess_full(end).cash().amount() > 2_000.0);
+        assert_eq!(loaded.to_json(), book.to_json());
+    }
+
+    #[test]
+    pub fn test_precision_tolerance_round_trip() {
+        let mut book = Book::default();
+        assert_eq!(Book::from_json(book.to_json()).unwrap().precision_tolerance, None);
+        book.set_precision_tolerance(Some(0.01));
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        assert_eq!(loaded.precision_tolerance(), 0.01);
+    }
+
+    #[test]
+    pub fn test_frozen_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let frozen = start + TimeDelta::days(30);
+        let mut book = Book::default();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 100.0), start));
+        book.add_named("Savings", Item::fixed(Value::new("CAD", 100.0), start));
+        let late = Delta::payment(frozen + TimeDelta::days(1), Value::new("CAD", 5.0));
+        book.add_delta(chequing, late);
+        book.freeze(chequing, frozen);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let chequing = loaded.by_name("Chequing").unwrap();
+        assert_eq!(loaded.frozen_since(chequing), Some(frozen));
+        assert_eq!(loaded.frozen_since(loaded.by_name("Savings").unwrap()), None);
+        assert_eq!(loaded.lint().len(), 1);
+    }
+
+    #[test]
+    pub fn test_liquidity_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 150.0), start));
+        let mut house = Item::fixed(Value::new("CAD", 500_000.0), start);
+        house.liquidity = Liquidity::Illiquid;
+        book.add_named("House", house);
+        let release = start + TimeDelta::days(90);
+        book.add_named(
+            "Deposit",
+            Item::escrow(Value::new("CAD", 1_000.0), release, 0.1, start),
+        );
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        for time in [start, release] {
+            assert_eq!(
+                loaded.liquid_assess(time).amount(),
+                book.liquid_assess(time).amount()
+            );
+        }
+        assert_eq!(loaded.liquid_assess(start).amount(), 150.0);
+        assert_eq!(loaded.liquid_assess(release).amount(), 1_150.0);
+    }
+
+    #[test]
+    pub fn test_indexed_interest_round_trip() {
+        let on = |y, m| Utc.with_ymd_and_hms(y, m, 1, 0, 0, 0).unwrap();
+        let prime = BENCHMARKS.register(
+            Benchmark::new("Prime")
+                .with_rate(on(2024, 1), 0.07)
+                .with_rate(on(2024, 7), 0.0625),
+        );
+        let mut heloc = Item::fixed(Value::new("CAD", -50_000.0), on(2024, 1));
+        heloc.interest = Some(Interest::indexed(prime, 0.005, Period::Years(1)));
+        let mut book = Book::default();
+        book.add_named("HELOC", heloc);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let time = on(2025, 1);
+        assert_eq!(loaded.assess(time).amount(), book.assess(time).amount());
+        let interest = loaded
+            .get(loaded.by_name("HELOC").unwrap())
+            .unwrap()
+            .interest
+            .clone()
+            .unwrap();
+        let registered = BENCHMARKS.get(interest.benchmark().unwrap()).unwrap();
+        assert_eq!(registered, BENCHMARKS.get(prime).unwrap());
+        assert_eq!(interest.rate_at(on(2024, 8)), 0.0675);
+    }
+
+    #[test]
+    pub fn test_planned_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        let rent = Delta::payment(start + TimeDelta::days(31), Value::new("CAD", -600.0));
+        book.add_delta(chequing, rent.planned());
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let later = start + TimeDelta::days(60);
+        for mode in [AssessMode::ActualsOnly, AssessMode::IncludePlanned] {
+            assert_eq!(
+                loaded.assess_mode(later, mode).amount(),
+                book.assess_mode(later, mode).amount()
+            );
+        }
+        assert_eq!(loaded.assess_mode(later, AssessMode::ActualsOnly).amount(), 1_000.0);
+    }
+
+    #[test]
+    pub fn test_sales_round_trip() {
+        let on = |m| Utc.with_ymd_and_hms(2024, m, 1, 0, 0, 0).unwrap();
+        let cad = |amount| Value::new("CAD", amount);
+        let mut shares = Item::fixed(cad(1_000.0), on(1));
+        shares.units = Some(100.0);
+        shares.buy(on(2), 50.0, cad(800.0));
+        shares
+            .sell(on(3), SaleSize::Units(60.0), cad(900.0))
+            .unwrap();
+        let mut book = Book::default();
+        let key = book.add_named("Shares", shares);
+
+        let loaded = Book::from_json(book.to_json()).unwrap();
+        let (shares, reloaded) = (
+            book.get(key).unwrap(),
+            loaded.get(loaded.by_name("Shares").unwrap()).unwrap(),
+        );
+        assert_eq!(reloaded.units_at(on(3)), Some(90.0));
+        assert_value_eq!(reloaded.cost_basis(), shares.cost_basis(), 1e-9);
+        assert_value_eq!(
+            reloaded.realized_gains(on(1), on(12)),
+            shares.realized_gains(on(1), on(12)),
+            1e-9
+        );
+        assert_value_eq!(reloaded.realized_gains(on(1), on(12)), cad(180.0), 1e-9);
+        assert_eq!(loaded.to_json(), book.to_json());
+    }
+
+    /// A stream sent over as JSON replays into the same book, even when it
+    /// arrives in two batches.
+    #[test]
+    pub fn test_event_stream_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let cad = |amount: f64| Value::new("CAD", amount);
+        let mut book = Book::default().undoable();
+        let chequing = book.add_named("Chequing", Item::fixed(cad(1_000.0), start));
+        let visa = book
+            .add_with_external_id(
+                "visa-1",
+                Item::basic_debt(cad(-800.0), 0.2, TimeDelta::days(365), start),
+            )
+            .unwrap();
+        let rent = Delta::payment(start + TimeDelta::days(31), cad(-600.0)).planned();
+        let rent = book.add_delta(chequing, rent).unwrap();
+        book.transfer(chequing, visa, start + TimeDelta::days(5), cad(100.0))
+            .unwrap();
+        let synced = book.event_seq();
+
+        book.confirm_delta(chequing, rent);
+        book.transfer(chequing, visa, start + TimeDelta::days(9), cad(50.0))
+            .unwrap();
+        book.undo().unwrap();
+        book.move_before(visa, chequing);
+        book.add_child(Item::fixed(cad(20.0), start), visa);
+        book.get_mut(visa).unwrap().set_interest(Some(Interest::new(0.1, Period::Years(1))));
+        let bills = book.create_group("Bills");
+        book.assign(visa, bills);
+        book.attach_risk(visa, RiskSpec::CertainLossPercentage { percent: 0.5 });
+        book.freeze(chequing, start + TimeDelta::days(60));
+        book.set_reporting("CAD", RatePolicy::Latest);
+        book.set_cash_interest("CAD".into(), Some(Interest::new(0.02, Period::Years(1))));
+
+        let send = |events: &[AuditEntry]| {
+            let json = Json::Array(events.iter().map(AuditEntry::to_json).collect());
+            AuditEntry::parse_stream(&serde_json::from_str(&json.to_string()).unwrap()).unwrap()
+        };
+        let mut events = send(book.events_since(0).unwrap());
+        let replayed = Book::replay(&events).unwrap();
+        events.truncate(synced as usize);
+        events.extend(send(book.events_since(synced).unwrap()));
+        let merged = Book::replay(&events).unwrap();
+
+        let time = start + TimeDelta::days(400);
+        for copy in [&replayed, &merged] {
+            assert_eq!(copy.assess(time).amount(), book.assess(time).amount());
+            assert_eq!(copy.ordered_keys(), book.ordered_keys());
+            assert_eq!(copy.by_external_id("visa-1"), Some(visa));
+            assert_eq!(copy.transfers().len(), 1);
+            assert_eq!(copy.get(visa).unwrap().children.len(), 1);
+            assert_eq!(copy.get(visa).unwrap().interest().map(Interest::rate), Some(0.1));
+            assert_eq!(copy.group(copy.group_of(visa)).unwrap().name, "Bills");
+            assert_eq!(copy.risks(visa).len(), 1);
+            assert_eq!(copy.frozen_since(chequing), book.frozen_since(chequing));
+            assert_eq!(copy.reporting(), book.reporting());
+            assert!(copy.cash_interest("CAD".into()).is_some());
+        }
+
+        let mut edited = book.events_since(0).unwrap()[0].to_json();
+        edited["event"] = serde_json::json!("item_rescued");
+        assert!(AuditEntry::parse_stream(&Json::Array(vec![edited])).is_err());
+    }
+}
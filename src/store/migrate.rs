@@ -0,0 +1,258 @@
+//! Upgrades serialized books written by older versions of the library.
+//!
+//! Every document carries a `format_version`. On load the steps in
+//! [MIGRATIONS] are applied in order until the document reaches
+//! [FORMAT_VERSION], step `n` takes a version `n + 1` document to `n + 2`.
+//! Some steps need what older documents never wrote down, the caller
+//! supplies it as [Defaults].
+
+use std::fmt;
+
+use serde_json::{json, Value as Json};
+
+use crate::instruments::value::Currency;
+
+/// The version of the documents written by this library.
+pub const FORMAT_VERSION: u64 = 6;
+
+/// What the caller knows about a document that its version did not keep.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Defaults {
+    /// The currency of amounts written without one.
+    pub currency: Option<Currency>,
+}
+
+/// A single upgrade from one version of a document to the next.
+pub type Migration = fn(Json, &Defaults) -> Result<Json, MigrationError>;
+
+/// The registered upgrades, the first takes version 1 to version 2.
+pub const MIGRATIONS: &[Migration] = &[
+    v1_delta_tuples,
+    v2_numbered_children,
+    v3_interest_posted,
+    v4_item_details,
+    v5_currencies,
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The document has no `format_version` field.
+    MissingVersion,
+    /// The document was written by a newer version of the library.
+    Unsupported(u64),
+    /// The document could not be upgraded from the given version.
+    Malformed { version: u64, message: String },
+    /// The document has amounts without a currency and no default currency
+    /// was given for them.
+    MissingCurrency,
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::MissingVersion => write!(f, "document has no format_version"),
+            MigrationError::Unsupported(v) => write!(
+                f,
+                "document has format version {v} but at most {FORMAT_VERSION} is supported"
+            ),
+            MigrationError::Malformed { version, message } => {
+                write!(f, "could not migrate version {version} document: {message}")
+            }
+            MigrationError::MissingCurrency => {
+                write!(f, "document has amounts without a currency and no default was given")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// The version a document claims to be.
+pub fn version_of(document: &Json) -> Result<u64, MigrationError> {
+    document
+        .get("format_version")
+        .and_then(Json::as_u64)
+        .ok_or(MigrationError::MissingVersion)
+}
+
+/// Brings a document up to [FORMAT_VERSION].
+pub fn migrate(document: Json) -> Result<Json, MigrationError> {
+    migrate_with(document, &Defaults::default())
+}
+
+/// Brings a document up to [FORMAT_VERSION], filling in what older versions
+/// did not keep from `defaults`.
+pub fn migrate_with(mut document: Json, defaults: &Defaults) -> Result<Json, MigrationError> {
+    let mut version = version_of(&document)?;
+    if version > FORMAT_VERSION || version == 0 {
+        return Err(MigrationError::Unsupported(version));
+    }
+    while version < FORMAT_VERSION {
+        document = MIGRATIONS[version as usize - 1](document, defaults)?;
+        version += 1;
+        document["format_version"] = json!(version);
+    }
+    Ok(document)
+}
+
+/// Version 1 stored deltas as `[time, amount]` payment tuples, version 2
+/// stores them as structs with a kind, memo and ids.
+fn v1_delta_tuples(mut document: Json, _: &Defaults) -> Result<Json, MigrationError> {
+    let malformed = |message: &str| MigrationError::Malformed {
+        version: 1,
+        message: message.to_string(),
+    };
+    let items = document
+        .get_mut("items")
+        .and_then(Json::as_array_mut)
+        .ok_or_else(|| malformed("items is not an array"))?;
+    for item in items {
+        let Some(deltas) = item.get_mut("deltas").and_then(Json::as_array_mut) else {
+            continue;
+        };
+        for delta in deltas.iter_mut() {
+            let [time, amount] = delta
+                .as_array()
+                .map(Vec::as_slice)
+                .ok_or_else(|| malformed("delta is not a tuple"))?
+            else {
+                return Err(malformed("delta tuple must have two elements"));
+            };
+            *delta = json!({
+                "time": time,
+                "kind": "payment",
+                "amount": { "fixed": amount },
+                "memo": null,
+                "category": null,
+                "external_id": null,
+            });
+        }
+    }
+    Ok(document)
+}
+
+/// Version 3 lets children refer to an item by its external id, version 2
+/// documents only number them which is still read the same way.
+fn v2_numbered_children(document: Json, _: &Defaults) -> Result<Json, MigrationError> {
+    Ok(document)
+}
+
+/// Version 4 items may have interest posted as deltas up to an
+/// `interest_posted` time, version 3 items never do.
+fn v3_interest_posted(document: Json, _: &Defaults) -> Result<Json, MigrationError> {
+    Ok(document)
+}
+
+/// Version 5 keeps more of each item, version 4 documents are read with the
+/// defaults for what they lack:
+///
+/// - deltas are numbered and keep their units, whether they are planned and
+///   the conversion rate they were applied at
+/// - items keep the units they hold and their sales, which refer to their
+///   delta by number
+/// - items keep whether they are liquid or held in escrow
+/// - indexed interest carries the benchmark it floats over
+/// - credit cards keep their minimum payment
+/// - items keep how often their interest is capitalized
+/// - leases keep their terms
+/// - items keep how their balance is rounded
+/// - items keep whether deltas or interest come first at a capitalization
+/// - interest only loans keep which delta is the balloon, by its number
+/// - interest keeps what its periods are anchored to and how it treats times
+///   before it starts
+/// - items keep the rates they are locked to convert at
+/// - the book keeps its groups and which group each item is in
+/// - items keep the risks attached to them, other than FX and custom ones
+/// - the book keeps the currency and rates it reports in
+/// - the book keeps the interest its cash earns in each currency
+/// - the book keeps the precision tolerance it was given
+/// - items keep when they were frozen
+/// - positions keep the price series they are valued off
+fn v4_item_details(document: Json, _: &Defaults) -> Result<Json, MigrationError> {
+    Ok(document)
+}
+
+/// Version 6 requires every amount to have a currency, older documents
+/// could write it as null. Those amounts are in the default currency, a
+/// document that has any cannot be migrated without one.
+fn v5_currencies(mut document: Json, defaults: &Defaults) -> Result<Json, MigrationError> {
+    fill_currencies(&mut document, defaults.currency)?;
+    Ok(document)
+}
+
+/// Gives every amount under `json` without a currency the default one.
+fn fill_currencies(json: &mut Json, currency: Option<Currency>) -> Result<(), MigrationError> {
+    match json {
+        Json::Object(fields) => {
+            let amount = fields.get("amount").is_some_and(Json::is_number);
+            if amount && fields.get("currency").is_none_or(Json::is_null) {
+                let currency = currency.ok_or(MigrationError::MissingCurrency)?;
+                fields.insert("currency".to_string(), json!(currency.code()));
+            }
+            fields
+                .values_mut()
+                .try_for_each(|field| fill_currencies(field, currency))
+        }
+        Json::Array(values) => values
+            .iter_mut()
+            .try_for_each(|value| fill_currencies(value, currency)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::instruments::value::Currency;
+
+    use super::{migrate, migrate_with, Defaults, MigrationError, FORMAT_VERSION};
+
+    #[test]
+    pub fn test_newer_document_rejected() {
+        let document = json!({ "format_version": FORMAT_VERSION + 1, "items": [] });
+        assert!(matches!(
+            migrate(document),
+            Err(MigrationError::Unsupported(v)) if v == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    pub fn test_delta_tuples_migrated() {
+        let document = json!({
+            "format_version": 1,
+            "items": [{ "deltas": [["2008-02-01T01:01:01Z", { "currency": "CAD", "amount": 5.0 }]] }]
+        });
+        let migrated = migrate(document).unwrap();
+        assert_eq!(migrated["format_version"], json!(FORMAT_VERSION));
+        assert_eq!(migrated["items"][0]["deltas"][0]["kind"], json!("payment"));
+        assert_eq!(
+            migrated["items"][0]["deltas"][0]["amount"]["fixed"]["amount"],
+            json!(5.0)
+        );
+    }
+
+    #[test]
+    pub fn test_null_currencies_need_a_default() {
+        let document = json!({
+            "format_version": 5,
+            "items": [{
+                "book_value": { "currency": null, "amount": 100.0 },
+                "deltas": [{ "kind": "payment", "amount": { "fixed": { "amount": 5.0 } } }],
+            }],
+        });
+        assert!(matches!(
+            migrate(document.clone()),
+            Err(MigrationError::MissingCurrency)
+        ));
+
+        let defaults = Defaults {
+            currency: Some(Currency::new("USD")),
+        };
+        let migrated = migrate_with(document, &defaults).unwrap();
+        let item = &migrated["items"][0];
+        assert_eq!(item["book_value"]["currency"], json!("USD"));
+        assert_eq!(item["deltas"][0]["amount"]["fixed"]["currency"], json!("USD"));
+        assert!(item["deltas"][0].get("currency").is_none());
+    }
+}
@@ -0,0 +1,13 @@
+//! Persistence backends for books.
+
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub mod migrate;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "json")]
+pub use json::JsonError;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteStore, StoreError};
@@ -0,0 +1,1929 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use super::migrate::Defaults;
+use crate::instruments::{
+    audit::AuditEntry,
+    book::{Book, ItemKey},
+    calendar::{BusinessDayConvention, BusinessDays, HolidayCalendar},
+    delta::{Delta, DeltaAmount, DeltaId, DeltaKind, DeltaStatus},
+    item::{
+        ApplicationOrder, InceptionPolicy, Interest, Item, Liquidity, MinPayment, Payout,
+        Proration,
+    },
+    period::{Period, PeriodAnchor},
+    sale::Sale,
+    value::{Currency, Rounding, Value},
+};
+
+/// The version of the schema written by this library.
+const FORMAT_VERSION: i64 = 7;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS items (
+    id INTEGER PRIMARY KEY,
+    name TEXT,
+    external_id TEXT,
+    currency TEXT,
+    book_value REAL NOT NULL,
+    inception INTEGER NOT NULL,
+    interest_percent REAL,
+    interest_period INTEGER,
+    interest_months INTEGER,
+    interest_posted INTEGER,
+    units REAL,
+    liquidity TEXT,
+    escrow_release INTEGER,
+    forfeit_percent REAL,
+    minimum_floor REAL,
+    minimum_currency TEXT,
+    minimum_percent REAL,
+    capitalization INTEGER,
+    round_decimals INTEGER,
+    rounding TEXT,
+    application_order TEXT,
+    balloon INTEGER,
+    interest_anchor TEXT,
+    interest_before_inception TEXT
+);
+CREATE TABLE IF NOT EXISTS children (
+    parent_id INTEGER NOT NULL,
+    child_id INTEGER NOT NULL,
+    position INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS deltas (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    percent REAL,
+    amount REAL,
+    currency TEXT,
+    memo TEXT,
+    category TEXT,
+    external_id TEXT,
+    units REAL,
+    status TEXT,
+    rate REAL
+);
+CREATE TABLE IF NOT EXISTS payouts (
+    item_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    variant TEXT NOT NULL,
+    amount REAL NOT NULL,
+    currency TEXT,
+    time INTEGER NOT NULL,
+    frequency INTEGER,
+    frequency_months INTEGER,
+    interest_percent REAL,
+    interest_period INTEGER,
+    interest_months INTEGER,
+    proration TEXT,
+    convention TEXT,
+    holidays TEXT,
+    interest_anchor TEXT,
+    interest_before_inception TEXT
+);
+CREATE TABLE IF NOT EXISTS sales (
+    item_id INTEGER NOT NULL,
+    delta_id INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    fraction REAL NOT NULL,
+    proceeds REAL NOT NULL,
+    currency TEXT
+);
+CREATE TABLE IF NOT EXISTS fx_overrides (
+    item_id INTEGER NOT NULL,
+    currency TEXT NOT NULL,
+    rate REAL NOT NULL
+);
+";
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    /// An audit entry refers to an item the store has not seen.
+    UnknownItem(ItemKey),
+    /// The file was written by a newer version of the library.
+    UnsupportedVersion(i64),
+    /// A row could not be turned back into the crate's types.
+    Corrupt(String),
+    /// The book holds something the store has no columns for.
+    Unsupported(String),
+    /// The store has amounts without a currency and no default currency was
+    /// given for them, see [SqliteStore::open_with].
+    MissingCurrency,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(e) => write!(f, "sqlite error: {e}"),
+            StoreError::UnknownItem(key) => write!(f, "item {key:?} is not in the store"),
+            StoreError::UnsupportedVersion(v) => write!(
+                f,
+                "store has format version {v} but at most {FORMAT_VERSION} is supported"
+            ),
+            StoreError::Corrupt(message) => write!(f, "corrupt store: {message}"),
+            StoreError::Unsupported(message) => write!(f, "cannot store {message}"),
+            StoreError::MissingCurrency => {
+                write!(f, "store has amounts without a currency and no default was given")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(value: rusqlite::Error) -> Self {
+        StoreError::Sqlite(value)
+    }
+}
+
+fn nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt()
+        .expect("timestamps must fit in 64 bits of nanoseconds")
+}
+
+fn from_nanos(nanos: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_nanos(nanos)
+}
+
+/// Fixed periods are stored as nanoseconds and calendar periods as months.
+fn period_columns(period: Period) -> (Option<i64>, Option<i64>) {
+    match period {
+        Period::Delta(delta) => (Some(delta.num_nanoseconds().unwrap()), None),
+        Period::Months(months) => (None, Some(months as i64)),
+        Period::Years(years) => (None, Some(years as i64 * 12)),
+    }
+}
+
+fn period_from(nanos: Option<i64>, months: Option<i64>) -> Result<Option<Period>, StoreError> {
+    let period = match (nanos, months) {
+        (Some(nanos), _) => Period::Delta(TimeDelta::nanoseconds(nanos)).validate(),
+        (None, Some(months)) => Period::calendar(months),
+        (None, None) => return Ok(None),
+    };
+    period
+        .map(Some)
+        .map_err(|e| StoreError::Corrupt(format!("period: {e}")))
+}
+
+/// The anchor and inception policy columns of interest.
+fn interest_option_columns(interest: &Interest) -> (&'static str, &'static str) {
+    let anchor = match interest.anchor() {
+        PeriodAnchor::Inception => "inception",
+        PeriodAnchor::CalendarMonthEnd => "calendar_month_end",
+        PeriodAnchor::CalendarQuarterEnd => "calendar_quarter_end",
+    };
+    let policy = match interest.inception_policy() {
+        InceptionPolicy::Discount => "discount",
+        InceptionPolicy::Hold => "hold",
+    };
+    (anchor, policy)
+}
+
+/// Sets the anchor and inception policy of interest from their columns,
+/// stores from before they were kept take the defaults.
+fn interest_options(
+    interest: Interest,
+    anchor: Option<&str>,
+    policy: Option<&str>,
+) -> Result<Interest, StoreError> {
+    let anchor = match anchor {
+        None | Some("inception") => PeriodAnchor::Inception,
+        Some("calendar_month_end") => PeriodAnchor::CalendarMonthEnd,
+        Some("calendar_quarter_end") => PeriodAnchor::CalendarQuarterEnd,
+        Some(other) => return Err(StoreError::Corrupt(format!("unknown period anchor {other}"))),
+    };
+    let policy = match policy {
+        None | Some("discount") => InceptionPolicy::Discount,
+        Some("hold") => InceptionPolicy::Hold,
+        Some(other) => {
+            return Err(StoreError::Corrupt(format!("unknown inception policy {other}")))
+        }
+    };
+    Ok(interest.anchored(anchor).before_inception(policy))
+}
+
+/// The registered schema upgrades, step `n` takes a version `n + 1` store
+/// to `n + 2`.
+const MIGRATIONS: &[&str] = &[
+    // Version 1 stores predate calendar periods.
+    "ALTER TABLE items ADD COLUMN interest_months INTEGER;
+     ALTER TABLE payouts ADD COLUMN frequency_months INTEGER;
+     ALTER TABLE payouts ADD COLUMN interest_months INTEGER;",
+    // Version 2 stores predate prorated payouts.
+    "ALTER TABLE payouts ADD COLUMN proration TEXT;",
+    // Version 3 stores predate posted interest.
+    "ALTER TABLE items ADD COLUMN interest_posted INTEGER;",
+    // Version 4 stores predate units, sales, planned deltas, captured rates,
+    // illiquid items, minimum payments and business day adjustments.
+    "CREATE TABLE sales (
+         item_id INTEGER NOT NULL,
+         delta_id INTEGER NOT NULL,
+         time INTEGER NOT NULL,
+         fraction REAL NOT NULL,
+         proceeds REAL NOT NULL,
+         currency TEXT
+     );
+     ALTER TABLE items ADD COLUMN units REAL;
+     ALTER TABLE items ADD COLUMN liquidity TEXT;
+     ALTER TABLE items ADD COLUMN escrow_release INTEGER;
+     ALTER TABLE items ADD COLUMN forfeit_percent REAL;
+     ALTER TABLE items ADD COLUMN minimum_floor REAL;
+     ALTER TABLE items ADD COLUMN minimum_currency TEXT;
+     ALTER TABLE items ADD COLUMN minimum_percent REAL;
+     ALTER TABLE deltas ADD COLUMN units REAL;
+     ALTER TABLE deltas ADD COLUMN status TEXT;
+     ALTER TABLE deltas ADD COLUMN rate REAL;
+     ALTER TABLE payouts ADD COLUMN convention TEXT;
+     ALTER TABLE payouts ADD COLUMN holidays TEXT;",
+    // Version 5 stores could leave amounts without a currency, they take the
+    // default currency the store is opened with.
+    "UPDATE items SET currency = (SELECT code FROM default_currency)
+         WHERE currency IS NULL;
+     UPDATE items SET minimum_currency = (SELECT code FROM default_currency)
+         WHERE minimum_currency IS NULL AND minimum_floor IS NOT NULL;
+     UPDATE deltas SET currency = (SELECT code FROM default_currency)
+         WHERE currency IS NULL AND amount IS NOT NULL;
+     UPDATE payouts SET currency = (SELECT code FROM default_currency)
+         WHERE currency IS NULL;
+     UPDATE sales SET currency = (SELECT code FROM default_currency)
+         WHERE currency IS NULL;",
+    // Version 6 stores predate capitalization, rounding each period, the
+    // application order, balloons, the anchor and inception policy of
+    // interest and conversion overrides.
+    "CREATE TABLE fx_overrides (
+         item_id INTEGER NOT NULL,
+         currency TEXT NOT NULL,
+         rate REAL NOT NULL
+     );
+     ALTER TABLE items ADD COLUMN capitalization INTEGER;
+     ALTER TABLE items ADD COLUMN round_decimals INTEGER;
+     ALTER TABLE items ADD COLUMN rounding TEXT;
+     ALTER TABLE items ADD COLUMN application_order TEXT;
+     ALTER TABLE items ADD COLUMN balloon INTEGER;
+     ALTER TABLE items ADD COLUMN interest_anchor TEXT;
+     ALTER TABLE items ADD COLUMN interest_before_inception TEXT;
+     ALTER TABLE payouts ADD COLUMN interest_anchor TEXT;
+     ALTER TABLE payouts ADD COLUMN interest_before_inception TEXT;",
+];
+
+/// Whether any amount is still without a currency after the migrations.
+const MISSING_CURRENCY: &str = "
+SELECT EXISTS (SELECT 1 FROM items WHERE currency IS NULL
+                   OR (minimum_currency IS NULL AND minimum_floor IS NOT NULL))
+    OR EXISTS (SELECT 1 FROM deltas WHERE currency IS NULL AND amount IS NOT NULL)
+    OR EXISTS (SELECT 1 FROM payouts WHERE currency IS NULL)
+    OR EXISTS (SELECT 1 FROM sales WHERE currency IS NULL)
+";
+
+const _: () = assert!(MIGRATIONS.len() as i64 == FORMAT_VERSION - 1);
+
+fn currency_from(code: Option<String>) -> Result<Currency, StoreError> {
+    let code = code.ok_or_else(|| StoreError::Corrupt("amount without a currency".into()))?;
+    code.parse()
+        .map_err(|e| StoreError::Corrupt(format!("currency {code:?}: {e}")))
+}
+
+/// Stores a [Book] in SQLite, items are given stable integer ids which are
+/// mapped back onto whatever [ItemKey]s the loaded book hands out.
+pub struct SqliteStore {
+    conn: Connection,
+    ids: HashMap<ItemKey, i64>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::open_with(path, &Defaults::default())
+    }
+    /// Opens a store like [SqliteStore::open], migrating an older one with
+    /// `defaults` for what its version did not keep. Stores from before
+    /// amounts needed a currency are opened this way with a default currency.
+    pub fn open_with(path: impl AsRef<Path>, defaults: &Defaults) -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open(path)?, defaults)
+    }
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open_in_memory()?, &Defaults::default())
+    }
+    fn from_connection(mut conn: Connection, defaults: &Defaults) -> Result<Self, StoreError> {
+        let version: Option<String> = conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'format_version'",
+                [],
+                |r| r.get(0),
+            )
+            .optional()
+            .or_else(|e| match e {
+                // A fresh database has no meta table yet.
+                rusqlite::Error::SqliteFailure(..) => Ok(None),
+                e => Err(e),
+            })?;
+        if let Some(Ok(mut v)) = version.as_deref().map(str::parse::<i64>) {
+            if (1..FORMAT_VERSION).contains(&v) {
+                // All or none of the steps are kept, a store missing a
+                // default currency is left as it was.
+                let tx = conn.transaction()?;
+                tx.execute_batch("CREATE TEMP TABLE default_currency (code TEXT)")?;
+                if let Some(currency) = defaults.currency {
+                    tx.execute(
+                        "INSERT INTO default_currency (code) VALUES (?1)",
+                        params![currency.code()],
+                    )?;
+                }
+                while (1..FORMAT_VERSION).contains(&v) {
+                    tx.execute_batch(MIGRATIONS[v as usize - 1])?;
+                    v += 1;
+                    tx.execute(
+                        "UPDATE meta SET value = ?1 WHERE key = 'format_version'",
+                        params![v.to_string()],
+                    )?;
+                }
+                if tx.query_row(MISSING_CURRENCY, [], |r| r.get(0))? {
+                    return Err(StoreError::MissingCurrency);
+                }
+                tx.execute_batch("DROP TABLE default_currency")?;
+                tx.commit()?;
+            }
+        }
+        conn.execute_batch(SCHEMA)?;
+        let version: Option<String> = conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'format_version'",
+                [],
+                |r| r.get(0),
+            )
+            .optional()?;
+        match version.map(|v| v.parse::<i64>()) {
+            None => {
+                conn.execute(
+                    "INSERT INTO meta (key, value) VALUES ('format_version', ?1)",
+                    params![FORMAT_VERSION.to_string()],
+                )?;
+            }
+            Some(Ok(v)) if v > FORMAT_VERSION => return Err(StoreError::UnsupportedVersion(v)),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(StoreError::Corrupt(format!("format version: {e}"))),
+        }
+        Ok(Self {
+            conn,
+            ids: HashMap::new(),
+        })
+    }
+
+    /// Replaces the contents of the store with the book.
+    pub fn save(&mut self, book: &Book) -> Result<(), StoreError> {
+        let tx = self.conn.transaction()?;
+        for table in ["items", "children", "deltas", "payouts", "sales", "fx_overrides"] {
+            tx.execute(&format!("DELETE FROM {table}"), [])?;
+        }
+
+        let mut ids = HashMap::new();
+        for (key, item) in book.iter_ordered() {
+            let id = insert_item(&tx, book.name(key), item)?;
+            if let Some(external) = book.external_id(key) {
+                tx.execute(
+                    "UPDATE items SET external_id = ?1 WHERE id = ?2",
+                    params![external, id],
+                )?;
+            }
+            ids.insert(key, id);
+        }
+        for (key, item) in book.iter_ordered() {
+            for child in &item.children {
+                insert_child(&tx, ids[&key], ids[child])?;
+            }
+        }
+        tx.commit()?;
+        self.ids = ids;
+        Ok(())
+    }
+
+    /// Reads the book back out of the store.
+    pub fn load(&mut self) -> Result<Book, StoreError> {
+        let mut book = Book::default();
+        let mut ids = HashMap::new();
+        let mut keys = HashMap::new();
+
+        let mut items = self.conn.prepare(
+            "SELECT id, name, external_id, currency, book_value, inception,
+                    interest_percent, interest_period, interest_months, interest_posted, units,
+                    liquidity, escrow_release, forfeit_percent, minimum_floor, minimum_currency,
+                    minimum_percent, capitalization, round_decimals, rounding, application_order,
+                    balloon, interest_anchor, interest_before_inception
+             FROM items ORDER BY id",
+        )?;
+        let rows = items.query_map([], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                r.get::<_, Option<String>>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<String>>(3)?,
+                r.get::<_, f64>(4)?,
+                r.get::<_, i64>(5)?,
+                r.get::<_, Option<f64>>(6)?,
+                r.get::<_, Option<i64>>(7)?,
+                r.get::<_, Option<i64>>(8)?,
+                r.get::<_, Option<i64>>(9)?,
+                r.get::<_, Option<f64>>(10)?,
+                r.get::<_, Option<String>>(11)?,
+                r.get::<_, Option<i64>>(12)?,
+                r.get::<_, Option<f64>>(13)?,
+                r.get::<_, Option<f64>>(14)?,
+                r.get::<_, Option<String>>(15)?,
+                r.get::<_, Option<f64>>(16)?,
+                r.get::<_, Option<i64>>(17)?,
+                r.get::<_, Option<u32>>(18)?,
+                r.get::<_, Option<String>>(19)?,
+                r.get::<_, Option<String>>(20)?,
+                r.get::<_, Option<i64>>(21)?,
+                r.get::<_, Option<String>>(22)?,
+                r.get::<_, Option<String>>(23)?,
+            ))
+        })?;
+        for row in rows {
+            let (
+                id,
+                name,
+                external,
+                currency,
+                value,
+                inception,
+                percent,
+                period,
+                months,
+                posted,
+                units,
+                liquidity,
+                release,
+                forfeit,
+                floor,
+                floor_currency,
+                minimum_percent,
+                capitalization,
+                round_decimals,
+                rounding,
+                order,
+                balloon,
+                anchor,
+                before_inception,
+            ) = row?;
+            let mut item = Item::fixed(
+                Value::new(currency_from(currency)?, value),
+                from_nanos(inception),
+            );
+            if let (Some(percent), Some(period)) = (percent, period_from(period, months)?) {
+                let interest = Interest::try_new(percent, period)
+                    .map_err(|e| StoreError::Corrupt(format!("interest of item {id}: {e}")))?;
+                item.interest = Some(interest_options(
+                    interest,
+                    anchor.as_deref(),
+                    before_inception.as_deref(),
+                )?);
+            }
+            item.interest_posted = posted.map(from_nanos);
+            item.units = units;
+            item.liquidity = match (liquidity.as_deref(), release, forfeit) {
+                (None | Some("liquid"), ..) => Liquidity::Liquid,
+                (Some("illiquid"), ..) => Liquidity::Illiquid,
+                (Some("escrow"), Some(release), Some(forfeit_percent)) => Liquidity::Escrow {
+                    release: from_nanos(release),
+                    forfeit_percent,
+                },
+                (Some(other), ..) => {
+                    return Err(StoreError::Corrupt(format!(
+                        "liquidity {other} of item {id}"
+                    )))
+                }
+            };
+            if let (Some(floor), Some(percent)) = (floor, minimum_percent) {
+                item.minimum_payment = Some(MinPayment {
+                    floor: Value::new(currency_from(floor_currency)?, floor),
+                    percent,
+                });
+            }
+            item.set_capitalization(capitalization.map(TimeDelta::nanoseconds))
+                .map_err(|e| StoreError::Corrupt(format!("capitalization of item {id}: {e}")))?;
+            if let Some(decimals) = round_decimals {
+                let rounding = match rounding.as_deref() {
+                    Some("half_away_from_zero") => Rounding::HalfAwayFromZero,
+                    Some("half_even") => Rounding::HalfEven,
+                    Some("toward_zero") => Rounding::TowardZero,
+                    other => {
+                        return Err(StoreError::Corrupt(format!(
+                            "rounding {other:?} of item {id}"
+                        )))
+                    }
+                };
+                item.round_each_period = Some((decimals, rounding));
+            }
+            item.application_order = match order.as_deref() {
+                None | Some("interest_first") => ApplicationOrder::InterestFirst,
+                Some("payment_first") => ApplicationOrder::PaymentFirst,
+                Some(other) => {
+                    return Err(StoreError::Corrupt(format!(
+                        "application order {other} of item {id}"
+                    )))
+                }
+            };
+            let (deltas, delta_ids) = self.load_deltas(id)?;
+            item.deltas = deltas;
+            item.payouts = self.load_payouts(id)?;
+            item.sales = self.load_sales(id, &delta_ids)?;
+            // The balloon may have been removed since, it is then no more.
+            item.balloon = balloon.and_then(|row| delta_ids.get(&row).copied());
+            for (target, rate) in self.load_fx_overrides(id)? {
+                item.set_fx_override(target, rate);
+            }
+
+            let key = match name {
+                Some(name) => book.add_named(name, item),
+                None => book.add(item),
+            };
+            if let Some(external) = external {
+                book.set_external_id(key, external);
+            }
+            ids.insert(key, id);
+            keys.insert(id, key);
+        }
+
+        let mut children = self
+            .conn
+            .prepare("SELECT parent_id, child_id FROM children ORDER BY parent_id, position")?;
+        let rows = children.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)))?;
+        for row in rows {
+            let (parent, child) = row?;
+            let (Some(parent), Some(child)) = (keys.get(&parent), keys.get(&child)) else {
+                return Err(StoreError::Corrupt(format!(
+                    "dangling child {child} of {parent}"
+                )));
+            };
+            book.link_child(*parent, *child);
+        }
+
+        // Loading is not a mutation worth auditing.
+        book.take_audit_log();
+        self.ids = ids;
+        Ok(book)
+    }
+
+    /// The deltas of the item and the ids they were given by row.
+    fn load_deltas(&self, item: i64) -> Result<(Vec<Delta>, HashMap<i64, DeltaId>), StoreError> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, time, kind, percent, amount, currency, memo, category, external_id, units,
+                    status, rate
+             FROM deltas WHERE item_id = ?1 ORDER BY time, id",
+        )?;
+        let rows = statement.query_map(params![item], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, Option<f64>>(3)?,
+                r.get::<_, Option<f64>>(4)?,
+                r.get::<_, Option<String>>(5)?,
+                r.get::<_, Option<String>>(6)?,
+                r.get::<_, Option<String>>(7)?,
+                r.get::<_, Option<String>>(8)?,
+                r.get::<_, Option<f64>>(9)?,
+                r.get::<_, Option<String>>(10)?,
+                r.get::<_, Option<f64>>(11)?,
+            ))
+        })?;
+
+        let mut deltas = vec![];
+        let mut ids = HashMap::new();
+        for row in rows {
+            let (
+                row,
+                time,
+                kind,
+                percent,
+                amount,
+                currency,
+                memo,
+                category,
+                external,
+                units,
+                status,
+                rate,
+            ) = row?;
+            let kind = match kind.as_str() {
+                "payment" => DeltaKind::Payment,
+                "fee" => DeltaKind::Fee,
+                "adjustment" => DeltaKind::Adjustment,
+                "interest" => DeltaKind::Interest,
+                other => return Err(StoreError::Corrupt(format!("unknown delta kind {other}"))),
+            };
+            let amount = match (percent, amount) {
+                (Some(percent), _) => DeltaAmount::Percent(percent),
+                (None, Some(amount)) => {
+                    DeltaAmount::Fixed(Value::new(currency_from(currency)?, amount))
+                }
+                (None, None) => return Err(StoreError::Corrupt("delta without an amount".into())),
+            };
+            let mut delta = Delta::new(from_nanos(time), amount, kind);
+            delta.memo = memo;
+            delta.category = category;
+            delta.external_id = external;
+            delta.units = units;
+            delta.rate = rate;
+            delta.status = match status.as_deref() {
+                None | Some("actual") => DeltaStatus::Actual,
+                Some("planned") => DeltaStatus::Planned,
+                Some(other) => {
+                    return Err(StoreError::Corrupt(format!("unknown delta status {other}")))
+                }
+            };
+            ids.insert(row, delta.id);
+            deltas.push(delta);
+        }
+        Ok((deltas, ids))
+    }
+
+    fn load_sales(&self, item: i64, deltas: &HashMap<i64, DeltaId>) -> Result<Vec<Sale>, StoreError> {
+        let mut statement = self.conn.prepare(
+            "SELECT delta_id, time, fraction, proceeds, currency
+             FROM sales WHERE item_id = ?1 ORDER BY rowid",
+        )?;
+        let rows = statement.query_map(params![item], |r| {
+            Ok((
+                r.get::<_, i64>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get::<_, f64>(2)?,
+                r.get::<_, f64>(3)?,
+                r.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+
+        let mut sales = vec![];
+        for row in rows {
+            let (delta, time, fraction, proceeds, currency) = row?;
+            let delta = *deltas
+                .get(&delta)
+                .ok_or_else(|| StoreError::Corrupt(format!("sale of missing delta {delta}")))?;
+            sales.push(Sale {
+                delta,
+                time: from_nanos(time),
+                fraction,
+                proceeds: Value::new(currency_from(currency)?, proceeds),
+            });
+        }
+        Ok(sales)
+    }
+
+    /// The locked rates of the item in the order they were set.
+    fn load_fx_overrides(&self, item: i64) -> Result<Vec<(Currency, f64)>, StoreError> {
+        let mut statement = self.conn.prepare(
+            "SELECT currency, rate FROM fx_overrides WHERE item_id = ?1 ORDER BY rowid",
+        )?;
+        let rows = statement.query_map(params![item], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?))
+        })?;
+        let mut overrides = vec![];
+        for row in rows {
+            let (currency, rate) = row?;
+            overrides.push((currency_from(Some(currency))?, rate));
+        }
+        Ok(overrides)
+    }
+
+    fn load_payouts(&self, item: i64) -> Result<Vec<Payout>, StoreError> {
+        let mut statement = self.conn.prepare(
+            "SELECT variant, amount, currency, time, frequency, frequency_months,
+                    interest_percent, interest_period, interest_months, proration, convention,
+                    holidays, interest_anchor, interest_before_inception
+             FROM payouts WHERE item_id = ?1 ORDER BY position",
+        )?;
+        let rows = statement.query_map(params![item], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, f64>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, i64>(3)?,
+                r.get::<_, Option<i64>>(4)?,
+                r.get::<_, Option<i64>>(5)?,
+                r.get::<_, Option<f64>>(6)?,
+                r.get::<_, Option<i64>>(7)?,
+                r.get::<_, Option<i64>>(8)?,
+                r.get::<_, Option<String>>(9)?,
+                r.get::<_, Option<String>>(10)?,
+                r.get::<_, Option<String>>(11)?,
+                r.get::<_, Option<String>>(12)?,
+                r.get::<_, Option<String>>(13)?,
+            ))
+        })?;
+
+        let mut payouts = vec![];
+        for row in rows {
+            let (
+                variant,
+                amount,
+                currency,
+                time,
+                frequency,
+                frequency_months,
+                percent,
+                period,
+                months,
+                proration,
+                convention,
+                holidays,
+                anchor,
+                before_inception,
+            ) = row?;
+            let amount = Value::new(currency_from(currency)?, amount);
+            let time = from_nanos(time);
+            let frequency = period_from(frequency, frequency_months)?;
+            let interest = percent
+                .zip(period_from(period, months)?)
+                .map(|(p, d)| Interest::try_new(p, d))
+                .transpose()
+                .map_err(|e| StoreError::Corrupt(format!("payout interest: {e}")))?
+                .map(|i| interest_options(i, anchor.as_deref(), before_inception.as_deref()))
+                .transpose()?;
+            let proration = match proration.as_deref() {
+                None | Some("none") => Proration::None,
+                Some("linear") => Proration::Linear,
+                Some(other) => return Err(StoreError::Corrupt(format!("unknown proration {other}"))),
+            };
+            let days = match convention.as_deref() {
+                None => None,
+                Some(convention) => {
+                    let convention = match convention {
+                        "none" => BusinessDayConvention::None,
+                        "following" => BusinessDayConvention::Following,
+                        "modified_following" => BusinessDayConvention::ModifiedFollowing,
+                        "preceding" => BusinessDayConvention::Preceding,
+                        other => {
+                            return Err(StoreError::Corrupt(format!("unknown convention {other}")))
+                        }
+                    };
+                    let holidays = holidays
+                        .as_deref()
+                        .unwrap_or_default()
+                        .split(',')
+                        .filter(|day| !day.is_empty())
+                        .map(|day| {
+                            NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                                .map_err(|e| StoreError::Corrupt(format!("holiday {day}: {e}")))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Some(BusinessDays::new(convention, HolidayCalendar::new(holidays)))
+                }
+            };
+            let corrupt = || StoreError::Corrupt(format!("incomplete {variant} payout"));
+            let payout = match variant.as_str() {
+                "one_time" => Payout::OneTime { amount, time },
+                "interest_one_time" => Payout::InterestOneTime {
+                    principal: amount,
+                    time,
+                    interest: interest.ok_or_else(corrupt)?,
+                },
+                "fixed_recurring" => Payout::FixedRecurring {
+                    amount,
+                    start: time,
+                    frequency: frequency.ok_or_else(corrupt)?,
+                    proration,
+                },
+                "interest_recurring" => Payout::InterestRecurring {
+                    principal: amount,
+                    start: time,
+                    frequency: frequency.ok_or_else(corrupt)?,
+                    interest: interest.ok_or_else(corrupt)?,
+                    proration,
+                },
+                other => return Err(StoreError::Corrupt(format!("unknown payout {other}"))),
+            };
+            payouts.push(match days {
+                Some(days) => payout.adjusted(days),
+                None => payout,
+            });
+        }
+        Ok(payouts)
+    }
+
+    /// Persists a single mutation without rewriting the rest of the store.
+    pub fn apply(&mut self, entry: &AuditEntry) -> Result<(), StoreError> {
+        let tx = self.conn.transaction()?;
+        match entry {
+            AuditEntry::ItemAdded { key, name, item } => {
+                let id = insert_item(&tx, name.as_deref(), item)?;
+                self.ids.insert(*key, id);
+            }
+            AuditEntry::ItemRemoved { key } => {
+                let item = self.ids.remove(key).ok_or(StoreError::UnknownItem(*key))?;
+                tx.execute("DELETE FROM items WHERE id = ?1", params![item])?;
+                tx.execute(
+                    "DELETE FROM children WHERE parent_id = ?1 OR child_id = ?1",
+                    params![item],
+                )?;
+                tx.execute("DELETE FROM deltas WHERE item_id = ?1", params![item])?;
+                tx.execute("DELETE FROM payouts WHERE item_id = ?1", params![item])?;
+                tx.execute("DELETE FROM sales WHERE item_id = ?1", params![item])?;
+                tx.execute("DELETE FROM fx_overrides WHERE item_id = ?1", params![item])?;
+            }
+            // The item is written afresh and moved back onto its old row, so
+            // it keeps its place and the links to its parent.
+            AuditEntry::ItemReplaced { key, item } => {
+                let id = *self.ids.get(key).ok_or(StoreError::UnknownItem(*key))?;
+                let (name, external_id): (Option<String>, Option<String>) = tx.query_row(
+                    "SELECT name, external_id FROM items WHERE id = ?1",
+                    params![id],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )?;
+                for table in ["deltas", "payouts", "sales", "fx_overrides"] {
+                    tx.execute(&format!("DELETE FROM {table} WHERE item_id = ?1"), params![id])?;
+                }
+                tx.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+                tx.execute("DELETE FROM children WHERE parent_id = ?1", params![id])?;
+                let written = insert_item(&tx, name.as_deref(), item)?;
+                tx.execute(
+                    "UPDATE items SET id = ?1, external_id = ?2 WHERE id = ?3",
+                    params![id, external_id, written],
+                )?;
+                for table in ["deltas", "payouts", "sales", "fx_overrides"] {
+                    tx.execute(
+                        &format!("UPDATE {table} SET item_id = ?1 WHERE item_id = ?2"),
+                        params![id, written],
+                    )?;
+                }
+                for child in &item.children {
+                    let child = *self.ids.get(child).ok_or(StoreError::UnknownItem(*child))?;
+                    insert_child(&tx, id, child)?;
+                }
+            }
+            AuditEntry::ChildRemoved { parent, child } => {
+                let parent = *self
+                    .ids
+                    .get(parent)
+                    .ok_or(StoreError::UnknownItem(*parent))?;
+                let child = *self.ids.get(child).ok_or(StoreError::UnknownItem(*child))?;
+                tx.execute(
+                    "DELETE FROM children WHERE parent_id = ?1 AND child_id = ?2",
+                    params![parent, child],
+                )?;
+            }
+            AuditEntry::ChildAdded { parent, child } => {
+                let parent = *self
+                    .ids
+                    .get(parent)
+                    .ok_or(StoreError::UnknownItem(*parent))?;
+                let child = *self.ids.get(child).ok_or(StoreError::UnknownItem(*child))?;
+                insert_child(&tx, parent, child)?;
+            }
+            AuditEntry::ExternalIdSet { key, id } => {
+                let item = *self.ids.get(key).ok_or(StoreError::UnknownItem(*key))?;
+                tx.execute(
+                    "UPDATE items SET external_id = ?1 WHERE id = ?2",
+                    params![id, item],
+                )?;
+            }
+            AuditEntry::DeltaAdded { key, delta } => {
+                let item = *self.ids.get(key).ok_or(StoreError::UnknownItem(*key))?;
+                insert_delta(&tx, item, delta)?;
+            }
+            AuditEntry::DeltaRemoved { key, delta } => {
+                let item = *self.ids.get(key).ok_or(StoreError::UnknownItem(*key))?;
+                // Rows do not keep the delta's id, one that matches on every
+                // column is the same delta.
+                let (kind, percent, amount, currency) = delta_columns(delta);
+                tx.execute(
+                    "DELETE FROM deltas WHERE id = (
+                         SELECT id FROM deltas
+                         WHERE item_id = ?1 AND time = ?2 AND kind = ?3 AND percent IS ?4
+                           AND amount IS ?5 AND currency IS ?6 AND memo IS ?7
+                           AND category IS ?8 AND external_id IS ?9 AND units IS ?10
+                           AND COALESCE(status, 'actual') = ?11
+                         LIMIT 1)",
+                    params![
+                        item,
+                        nanos(delta.time),
+                        kind,
+                        percent,
+                        amount,
+                        currency,
+                        delta.memo,
+                        delta.category,
+                        delta.external_id,
+                        delta.units,
+                        status_column(delta.status)
+                    ],
+                )?;
+            }
+            AuditEntry::Renamed { key, name } => {
+                let item = *self.ids.get(key).ok_or(StoreError::UnknownItem(*key))?;
+                tx.execute(
+                    "UPDATE items SET name = ?1 WHERE id = ?2",
+                    params![name, item],
+                )?;
+            }
+            // The items and deltas of the import are entries of their own.
+            AuditEntry::ImportApplied { .. } => {}
+            // The store keeps neither transfers nor the order of items
+            // beyond the one they were saved in, the deltas of a transfer
+            // are entries of their own. Groups, risks, freezes and the
+            // settings of the book are only kept by the JSON store.
+            AuditEntry::Transferred { .. }
+            | AuditEntry::Untransferred { .. }
+            | AuditEntry::Reordered { .. }
+            | AuditEntry::GroupCreated { .. }
+            | AuditEntry::GroupCurrencySet { .. }
+            | AuditEntry::Assigned { .. }
+            | AuditEntry::RiskAttached { .. }
+            | AuditEntry::RisksDetached { .. }
+            | AuditEntry::Frozen { .. }
+            | AuditEntry::ReportingSet { .. }
+            | AuditEntry::CashInterestSet { .. }
+            | AuditEntry::PrecisionToleranceSet { .. } => {}
+            AuditEntry::CurrencyMigrated { old, new, factor } => {
+                let (old, new) = (old.code(), new.code());
+                // Rates out of an item in `old` buy `factor` times more, the
+                // items are still in `old` at this point.
+                tx.execute(
+                    "UPDATE fx_overrides SET rate = rate * ?1
+                     WHERE item_id IN (SELECT id FROM items WHERE currency = ?2)",
+                    params![factor, old],
+                )?;
+                tx.execute(
+                    "UPDATE fx_overrides SET rate = rate / ?1, currency = ?2 WHERE currency = ?3",
+                    params![factor, new, old],
+                )?;
+                tx.execute(
+                    "UPDATE deltas SET rate = rate * ?1 WHERE currency = ?2",
+                    params![factor, old],
+                )?;
+                tx.execute(
+                    "UPDATE deltas SET rate = rate / ?1
+                     WHERE item_id IN (SELECT id FROM items WHERE currency = ?2)",
+                    params![factor, old],
+                )?;
+                tx.execute(
+                    "UPDATE items SET book_value = book_value / ?1, currency = ?2
+                     WHERE currency = ?3",
+                    params![factor, new, old],
+                )?;
+                for table in ["deltas", "payouts"] {
+                    tx.execute(
+                        &format!(
+                            "UPDATE {table} SET amount = amount / ?1, currency = ?2
+                             WHERE currency = ?3"
+                        ),
+                        params![factor, new, old],
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn payout_interest(payout: &Payout) -> Option<&Interest> {
+    match payout.unadjusted() {
+        Payout::InterestOneTime { interest, .. } | Payout::InterestRecurring { interest, .. } => {
+            Some(interest)
+        }
+        _ => None,
+    }
+}
+
+fn insert_item(tx: &Transaction, name: Option<&str>, item: &Item) -> Result<i64, StoreError> {
+    let indexed = |interest: Option<&Interest>| interest.and_then(Interest::benchmark).is_some();
+    if indexed(item.interest.as_ref())
+        || item.payouts.iter().any(|p| indexed(payout_interest(p)))
+    {
+        return Err(StoreError::Unsupported(format!(
+            "indexed interest of {}, use the JSON store",
+            name.unwrap_or("an item")
+        )));
+    }
+    if item.prices.is_some() {
+        return Err(StoreError::Unsupported(format!(
+            "the prices of {}, use the JSON store",
+            name.unwrap_or("a position")
+        )));
+    }
+    if item.lease.is_some() {
+        return Err(StoreError::Unsupported(format!(
+            "the lease of {}, use the JSON store",
+            name.unwrap_or("an item")
+        )));
+    }
+    let interest_period = item
+        .interest
+        .as_ref()
+        .map(|i| period_columns(i.period()))
+        .unwrap_or_default();
+    let (liquidity, release, forfeit) = match item.liquidity {
+        Liquidity::Liquid => ("liquid", None, None),
+        Liquidity::Illiquid => ("illiquid", None, None),
+        Liquidity::Escrow {
+            release,
+            forfeit_percent,
+        } => ("escrow", Some(nanos(release)), Some(forfeit_percent)),
+    };
+    let minimum = item.minimum_payment.as_ref();
+    let interest_options = item.interest.as_ref().map(interest_option_columns);
+    tx.execute(
+        "INSERT INTO items (name, currency, book_value, inception, interest_percent, interest_period,
+                            interest_months, interest_posted, units, liquidity, escrow_release,
+                            forfeit_percent, minimum_floor, minimum_currency, minimum_percent,
+                            capitalization, round_decimals, rounding, application_order,
+                            interest_anchor, interest_before_inception)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                 ?18, ?19, ?20, ?21)",
+        params![
+            name,
+            item.book_value.currency().code(),
+            item.book_value.amount(),
+            nanos(item.inception),
+            item.interest.as_ref().map(|i| i.rate()),
+            interest_period.0,
+            interest_period.1,
+            item.interest_posted.map(nanos),
+            item.units,
+            liquidity,
+            release,
+            forfeit,
+            minimum.map(|m| m.floor.amount()),
+            minimum.map(|m| m.floor.currency().code()),
+            minimum.map(|m| m.percent),
+            item.capitalization().map(|c| c.num_nanoseconds().unwrap()),
+            item.round_each_period.map(|(decimals, _)| decimals),
+            item.round_each_period.map(|(_, rounding)| match rounding {
+                Rounding::HalfAwayFromZero => "half_away_from_zero",
+                Rounding::HalfEven => "half_even",
+                Rounding::TowardZero => "toward_zero",
+            }),
+            match item.application_order {
+                ApplicationOrder::InterestFirst => "interest_first",
+                ApplicationOrder::PaymentFirst => "payment_first",
+            },
+            interest_options.map(|(anchor, _)| anchor),
+            interest_options.map(|(_, policy)| policy),
+        ],
+    )?;
+    let id = tx.last_insert_rowid();
+    let mut rows = HashMap::new();
+    for delta in &item.deltas {
+        rows.insert(delta.id, insert_delta(tx, id, delta)?);
+    }
+    if let Some(row) = item.balloon.and_then(|balloon| rows.get(&balloon)) {
+        tx.execute("UPDATE items SET balloon = ?1 WHERE id = ?2", params![row, id])?;
+    }
+    for (position, payout) in item.payouts.iter().enumerate() {
+        insert_payout(tx, id, position, payout)?;
+    }
+    for (target, rate) in &item.fx_overrides {
+        tx.execute(
+            "INSERT INTO fx_overrides (item_id, currency, rate) VALUES (?1, ?2, ?3)",
+            params![id, target.code(), rate],
+        )?;
+    }
+    for sale in &item.sales {
+        let row = rows
+            .get(&sale.delta)
+            .ok_or_else(|| StoreError::Corrupt(format!("sale of missing delta {:?}", sale.delta)))?;
+        tx.execute(
+            "INSERT INTO sales (item_id, delta_id, time, fraction, proceeds, currency)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                row,
+                nanos(sale.time),
+                sale.fraction,
+                sale.proceeds.amount(),
+                sale.proceeds.currency().code(),
+            ],
+        )?;
+    }
+    // Children are linked separately once both sides have ids.
+    Ok(id)
+}
+
+fn insert_child(tx: &Transaction, parent: i64, child: i64) -> Result<(), StoreError> {
+    tx.execute(
+        "INSERT INTO children (parent_id, child_id, position)
+         VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM children WHERE parent_id = ?1))",
+        params![parent, child],
+    )?;
+    Ok(())
+}
+
+/// The kind, percent, amount and currency columns of a delta.
+fn delta_columns(
+    delta: &Delta,
+) -> (
+    &'static str,
+    Option<f64>,
+    Option<f64>,
+    Option<&'static str>,
+) {
+    let kind = match delta.kind {
+        DeltaKind::Payment => "payment",
+        DeltaKind::Fee => "fee",
+        DeltaKind::Adjustment => "adjustment",
+        DeltaKind::Interest => "interest",
+    };
+    match &delta.amount {
+        DeltaAmount::Percent(percent) => (kind, Some(*percent), None, None),
+        DeltaAmount::Fixed(value) => (
+            kind,
+            None,
+            Some(value.amount()),
+            Some(value.currency().code()),
+        ),
+    }
+}
+
+fn status_column(status: DeltaStatus) -> &'static str {
+    match status {
+        DeltaStatus::Actual => "actual",
+        DeltaStatus::Planned => "planned",
+    }
+}
+
+/// Inserts the delta, giving its row id.
+fn insert_delta(tx: &Transaction, item: i64, delta: &Delta) -> Result<i64, StoreError> {
+    let (kind, percent, amount, currency) = delta_columns(delta);
+    tx.execute(
+        "INSERT INTO deltas (item_id, time, kind, percent, amount, currency, memo, category,
+                             external_id, units, status, rate)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            item,
+            nanos(delta.time),
+            kind,
+            percent,
+            amount,
+            currency,
+            delta.memo,
+            delta.category,
+            delta.external_id,
+            delta.units,
+            status_column(delta.status),
+            delta.rate,
+        ],
+    )?;
+    Ok(tx.last_insert_rowid())
+}
+
+fn insert_payout(
+    tx: &Transaction,
+    item: i64,
+    position: usize,
+    payout: &Payout,
+) -> Result<(), StoreError> {
+    // The payments are stored on their unadjusted days alongside the
+    // business day adjustment, holidays as dates separated by commas.
+    let days = match payout {
+        Payout::Adjusted { payout, .. } if matches!(**payout, Payout::Adjusted { .. }) => {
+            return Err(StoreError::Unsupported(
+                "a payout adjusted twice, use the JSON store".into(),
+            ))
+        }
+        Payout::Adjusted { days, .. } => Some(days),
+        _ => None,
+    };
+    let convention = days.map(|d| match d.convention {
+        BusinessDayConvention::None => "none",
+        BusinessDayConvention::Following => "following",
+        BusinessDayConvention::ModifiedFollowing => "modified_following",
+        BusinessDayConvention::Preceding => "preceding",
+    });
+    let holidays = days.map(|d| {
+        d.calendar
+            .holidays()
+            .iter()
+            .map(|h| h.format("%Y-%m-%d").to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    let (variant, amount, time, frequency, interest, proration) = match payout.unadjusted() {
+        Payout::OneTime { amount, time } => ("one_time", amount, time, None, None, None),
+        Payout::InterestOneTime {
+            principal,
+            time,
+            interest,
+        } => (
+            "interest_one_time",
+            principal,
+            time,
+            None,
+            Some(interest),
+            None,
+        ),
+        Payout::FixedRecurring {
+            amount,
+            start,
+            frequency,
+            proration,
+        } => (
+            "fixed_recurring",
+            amount,
+            start,
+            Some(frequency),
+            None,
+            Some(proration),
+        ),
+        Payout::InterestRecurring {
+            principal,
+            start,
+            frequency,
+            interest,
+            proration,
+        } => (
+            "interest_recurring",
+            principal,
+            start,
+            Some(frequency),
+            Some(interest),
+            Some(proration),
+        ),
+        Payout::Adjusted { .. } => unreachable!(),
+    };
+    let proration = proration.map(|p| match p {
+        Proration::None => "none",
+        Proration::Linear => "linear",
+    });
+    let frequency = frequency.map(|f| period_columns(*f)).unwrap_or_default();
+    let interest_period = interest
+        .map(|i| period_columns(i.period()))
+        .unwrap_or_default();
+    let interest_options = interest.map(interest_option_columns);
+    tx.execute(
+        "INSERT INTO payouts (item_id, position, variant, amount, currency, time, frequency,
+                              frequency_months, interest_percent, interest_period, interest_months,
+                              proration, convention, holidays, interest_anchor,
+                              interest_before_inception)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            item,
+            position as i64,
+            variant,
+            amount.amount(),
+            amount.currency().code(),
+            nanos(*time),
+            frequency.0,
+            frequency.1,
+            interest.map(|i| i.rate()),
+            interest_period.0,
+            interest_period.1,
+            proration,
+            convention,
+            holidays,
+            interest_options.map(|(anchor, _)| anchor),
+            interest_options.map(|(_, policy)| policy),
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+    use crate::assert_value_eq;
+    use crate::instruments::{
+        benchmark::{Benchmark, BENCHMARKS},
+        book::{Book, Upcoming},
+        calendar::{BusinessDayConvention, BusinessDays, HolidayCalendar},
+        convert::{RatePolicy, CURRENCY_EXCHANGE},
+        delta::{AssessMode, Delta, DeltaAmount, DeltaKind},
+        item::{
+            ApplicationOrder, InceptionPolicy, Interest, Item, Liquidity, MinPayment, Payout,
+            Proration,
+        },
+        period::{Period, PeriodAnchor},
+        sale::SaleSize,
+        value::{Currency, Rounding, Value},
+        Assesible,
+    };
+
+    use super::{Defaults, SqliteStore, StoreError};
+
+    fn book() -> Book {
+        let mut book = Book::default();
+        let mut visa = Item::basic_debt(
+            Value::new("CAD", -1_000.0),
+            0.20,
+            Duration::days(365),
+            Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap(),
+        );
+        visa.add_delta(
+            Utc.with_ymd_and_hms(2008, 2, 1, 1, 1, 1).unwrap(),
+            Value::dummy("CAD", 1000),
+        );
+        let visa = book.add_named("Visa", visa);
+        book.set_external_id(visa, "visa-4242");
+
+        let mut house = Item::fixed(
+            Value::new("CAD", 150_000.0),
+            Utc.with_ymd_and_hms(2000, 1, 1, 1, 1, 1).unwrap(),
+        );
+        house.interest = Some(Interest::new(0.04, Period::Years(1)));
+        house.payouts.push(Payout::OneTime {
+            amount: Value::new("CAD", 10.0),
+            time: Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap(),
+        });
+        house.payouts.push(Payout::FixedRecurring {
+            amount: Value::new("CAD", 1_500.0),
+            start: Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap(),
+            frequency: Period::Months(1),
+            proration: Proration::Linear,
+        });
+        let house = book.add_named("House", house);
+        book.add_child(
+            Item::fixed(
+                Value::new("CAD", -100_000.0),
+                Utc.with_ymd_and_hms(2000, 1, 1, 1, 1, 1).unwrap(),
+            ),
+            house,
+        );
+        book
+    }
+
+    #[test]
+    pub fn test_save_load_equivalence() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let original = book();
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&original).unwrap();
+        let loaded = store.load().unwrap();
+
+        assert_eq!(loaded.assess(time).amount(), original.assess(time).amount());
+        let house = loaded.by_name("House").unwrap();
+        assert_eq!(loaded.get(house).unwrap().children.len(), 1);
+        assert_eq!(loaded.get(house).unwrap().payouts.len(), 2);
+        assert!(matches!(
+            loaded.get(house).unwrap().payouts[1],
+            Payout::FixedRecurring {
+                proration: Proration::Linear,
+                ..
+            }
+        ));
+        assert_eq!(
+            loaded
+                .get(house)
+                .unwrap()
+                .interest
+                .as_ref()
+                .unwrap()
+                .period(),
+            Period::Years(1)
+        );
+        assert_eq!(
+            loaded.external_id(loaded.by_name("Visa").unwrap()),
+            Some("visa-4242")
+        );
+        assert!(loaded.audit_log().is_empty());
+    }
+
+    #[test]
+    pub fn test_minimum_payment_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let card = Item::credit_card(
+            Value::new("CAD", -5_000.0),
+            0.1999,
+            MinPayment {
+                floor: Value::new("CAD", 10.0),
+                percent: 0.02,
+            },
+            start,
+        );
+        book.add_named("Visa", card);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let card = loaded.get(loaded.by_name("Visa").unwrap()).unwrap();
+        let minimum = card.minimum_payment.as_ref().unwrap();
+        assert_value_eq!(minimum.floor.clone(), Value::new("CAD", 10.0), 1e-9);
+        assert_eq!(minimum.percent, 0.02);
+        let horizon = Period::Years(30).step(start, 1);
+        let original = book.get(book.by_name("Visa").unwrap()).unwrap();
+        assert_eq!(
+            card.project_minimum_payments(start, horizon).len(),
+            original.project_minimum_payments(start, horizon).len()
+        );
+    }
+
+    #[test]
+    pub fn test_captured_rate_round_trip() {
+        let on = |m| Utc.with_ymd_and_hms(2025, m, 1, 0, 0, 0).unwrap();
+        CURRENCY_EXCHANGE.add_conversion_on("SFXC", "SFXU", on(1), 0.75);
+        let mut book = Book::default();
+        let debt = book.add_named("Debt", Item::fixed(Value::new("SFXU", -1_000.0), on(1)));
+        let payment = Delta::payment(on(2), Value::new("SFXC", 400.0));
+        book.add_delta_converted(debt, payment, Some(0.72)).unwrap();
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let debt = loaded.get(loaded.by_name("Debt").unwrap()).unwrap();
+        assert_eq!(debt.deltas[0].rate, Some(0.72));
+        assert_value_eq!(debt.assess(on(3)), Value::new("SFXU", -712.0), 1e-9);
+        let gains = debt.fx_gains(on(1), on(3), RatePolicy::RateAtFixedDate(on(1)));
+        assert_value_eq!(gains, Value::new("SFXU", -12.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_adjusted_payout_round_trip() {
+        let on = |m, d| Utc.with_ymd_and_hms(2025, m, d, 0, 0, 0).unwrap();
+        let holidays = HolidayCalendar::new([NaiveDate::from_ymd_opt(2025, 4, 30).unwrap()]);
+        let days = BusinessDays::new(BusinessDayConvention::ModifiedFollowing, holidays);
+        let rent = Payout::FixedRecurring {
+            amount: Value::new("CAD", 50.0),
+            start: on(1, 31),
+            frequency: Period::Months(1),
+            proration: Proration::None,
+        }
+        .adjusted(days);
+        let mut book = Book::default();
+        book.add_named(
+            "Rent",
+            Item::builder(Value::new("CAD", 0.0), on(1, 1)).payout(rent.clone()).build(),
+        );
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let payouts = &loaded.get(loaded.by_name("Rent").unwrap()).unwrap().payouts;
+        let until = on(12, 31);
+        let times = |payout: &Payout| -> Vec<_> {
+            payout.occurrences(on(1, 1), until).map(|(t, _)| t).collect()
+        };
+        assert_eq!(times(&payouts[0]), times(&rent));
+        // April 30th is a holiday and May 31st a Saturday, the payments
+        // roll back rather than leave the month.
+        assert_eq!(times(&payouts[0])[3], on(4, 29));
+        assert_eq!(times(&payouts[0])[4], on(5, 30));
+
+        let twice = rent.adjusted(BusinessDays::default());
+        book.add(Item::builder(Value::new("CAD", 0.0), on(1, 1)).payout(twice).build());
+        assert!(matches!(store.save(&book), Err(StoreError::Unsupported(_))));
+    }
+
+    #[test]
+    pub fn test_liquidity_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 150.0), start));
+        let mut house = Item::fixed(Value::new("CAD", 500_000.0), start);
+        house.liquidity = Liquidity::Illiquid;
+        book.add_named("House", house);
+        let release = start + Duration::days(90);
+        book.add_named(
+            "Deposit",
+            Item::escrow(Value::new("CAD", 1_000.0), release, 0.1, start),
+        );
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.liquid_assess(start).amount(), 150.0);
+        assert_eq!(loaded.liquid_assess(release).amount(), 1_150.0);
+    }
+
+    #[test]
+    pub fn test_planned_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        let chequing = book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        let rent = Delta::payment(start + Duration::days(31), Value::new("CAD", -600.0));
+        book.add_delta(chequing, rent.planned());
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let later = start + Duration::days(60);
+        for mode in [AssessMode::ActualsOnly, AssessMode::IncludePlanned] {
+            assert_eq!(
+                loaded.assess_mode(later, mode).amount(),
+                book.assess_mode(later, mode).amount()
+            );
+        }
+        assert_eq!(loaded.assess_mode(later, AssessMode::ActualsOnly).amount(), 1_000.0);
+    }
+
+    #[test]
+    pub fn test_sales_round_trip() {
+        let on = |m| Utc.with_ymd_and_hms(2024, m, 1, 0, 0, 0).unwrap();
+        let cad = |amount| Value::new("CAD", amount);
+        let mut shares = Item::fixed(cad(1_000.0), on(1));
+        shares.units = Some(100.0);
+        shares.buy(on(2), 50.0, cad(800.0));
+        shares
+            .sell(on(3), SaleSize::Units(60.0), cad(900.0))
+            .unwrap();
+        let mut book = Book::default();
+        let key = book.add_named("Shares", shares);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let (shares, reloaded) = (
+            book.get(key).unwrap(),
+            loaded.get(loaded.by_name("Shares").unwrap()).unwrap(),
+        );
+        assert_eq!(reloaded.units_at(on(3)), Some(90.0));
+        assert_value_eq!(reloaded.cost_basis(), shares.cost_basis(), 1e-9);
+        assert_value_eq!(
+            reloaded.realized_gains(on(1), on(12)),
+            shares.realized_gains(on(1), on(12)),
+            1e-9
+        );
+        assert_value_eq!(reloaded.realized_gains(on(1), on(12)), cad(180.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_capitalization_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let day = |n| start + Duration::days(n);
+        let mut book = Book::default();
+        let line = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.073, Duration::days(365))
+            .capitalization(Duration::days(30))
+            .payment(day(15), Value::new("CAD", 1_000.0))
+            .build();
+        book.add_named("Line of credit", line);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let line = loaded.get(loaded.by_name("Line of credit").unwrap()).unwrap();
+        assert_eq!(line.capitalization(), Some(Duration::days(30)));
+        assert_value_eq!(line.assess(day(60)), Value::new("CAD", -9_111.342), 1e-9);
+    }
+
+    #[test]
+    pub fn test_rounding_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let month = Period::Months(1);
+        let mut builder = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.015, month)
+            .round_each_period(2, Rounding::HalfEven);
+        for count in 1..=24 {
+            builder = builder.payment(month.step(start, count), Value::new("CAD", 300.0));
+        }
+        let mut book = Book::default();
+        book.add_named("Loan", builder.build());
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let loan = loaded.get(loaded.by_name("Loan").unwrap()).unwrap();
+        assert_eq!(loan.round_each_period, Some((2, Rounding::HalfEven)));
+        let end = month.step(start, 24);
+        assert_eq!(loaded.assess(end).amount(), book.assess(end).amount());
+    }
+
+    #[test]
+    pub fn test_application_order_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let boundary = start + Duration::days(30);
+        let paydown = Delta::new(boundary, DeltaAmount::Percent(-0.1), DeltaKind::Payment);
+        let line = Item::builder(Value::new("CAD", -10_000.0), start)
+            .interest(0.073, Duration::days(365))
+            .capitalization(Duration::days(30))
+            .application_order(ApplicationOrder::PaymentFirst)
+            .delta(paydown)
+            .build();
+        let mut book = Book::default();
+        book.add_named("Line of credit", line);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let line = loaded.get(loaded.by_name("Line of credit").unwrap()).unwrap();
+        assert_eq!(line.application_order, ApplicationOrder::PaymentFirst);
+        assert_value_eq!(line.assess(boundary), Value::new("CAD", -9_060.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_balloon_round_trip() {
+        let on = |year, month| Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+        let loan = Item::interest_only_loan(
+            Value::new("CAD", 400_000.0),
+            0.005,
+            Period::Months(1),
+            on(2025, 1),
+            on(2025, 4),
+            on(2024, 1),
+        );
+        let mut book = Book::default();
+        book.add_named("Mortgage", loan);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let key = loaded.by_name("Mortgage").unwrap();
+        let upcoming = loaded.upcoming(on(2025, 1), on(2026, 1));
+        assert_eq!(upcoming.len(), 1);
+        let (time, item, event) = &upcoming[0];
+        assert_eq!((*time, *item), (on(2025, 4), key));
+        let Upcoming::Balloon(value) = event else {
+            panic!("the balloon is upcoming");
+        };
+        assert_value_eq!(value.clone(), Value::new("CAD", 400_000.0 * 1.005f64.powi(3)), 1e-6);
+        assert_value_eq!(loaded.assess(on(2025, 5)), Value::new("CAD", 0.0), 1e-6);
+    }
+
+    #[test]
+    pub fn test_interest_options_round_trip() {
+        let on = |month, day| Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+        let card = Item::builder(Value::new("CAD", -1_000.0), on(1, 31))
+            .interest(0.01, Period::Months(1))
+            .period_anchor(PeriodAnchor::CalendarMonthEnd)
+            .capitalization(Duration::days(30))
+            .build();
+        let held = Interest::new(0.1, Period::Years(1)).before_inception(InceptionPolicy::Hold);
+        let mut bond = Item::fixed(Value::new("CAD", 100.0), on(6, 1));
+        bond.set_interest(Some(held.clone()));
+        bond.payouts.push(Payout::InterestOneTime {
+            principal: Value::new("CAD", 100.0),
+            time: on(12, 1),
+            interest: held.anchored(PeriodAnchor::CalendarQuarterEnd),
+        });
+        let mut book = Book::default();
+        book.add_named("Card", card);
+        book.add_named("Bond", bond);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let loaded = store.load().unwrap();
+        let card = loaded.get(loaded.by_name("Card").unwrap()).unwrap();
+        assert_eq!(card.interest().unwrap().anchor(), PeriodAnchor::CalendarMonthEnd);
+        let march = card.breakdown(on(3, 15));
+        let february = (-1_000.0 - 10.0 / 31.0) * 1.01;
+        assert_value_eq!(march.principal, Value::new("CAD", february), 1e-9);
+
+        let bond = loaded.get(loaded.by_name("Bond").unwrap()).unwrap();
+        assert_eq!(bond.interest().unwrap().inception_policy(), InceptionPolicy::Hold);
+        assert_eq!(bond.assess(on(1, 1)).amount(), 100.0);
+        let Payout::InterestOneTime { interest, .. } = &bond.payouts[0] else {
+            panic!("the payout is kept");
+        };
+        assert_eq!(interest.anchor(), PeriodAnchor::CalendarQuarterEnd);
+        assert_eq!(interest.inception_policy(), InceptionPolicy::Hold);
+    }
+
+    /// A foreign invoice payable at a locked 1.30 and a receipt captured at 1.40
+    /// keep counting at those rates in a CAD book, also after a migration.
+    #[test]
+    pub fn test_fx_override_round_trip() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut book = Book::default();
+        book.add_named("Chequing", Item::fixed(Value::new("CAD", 1_000.0), start));
+        let mut invoice = Item::fixed(Value::new("SFXO", -100.0), start);
+        invoice.set_fx_override("CAD", 1.30);
+        book.add_named("Invoice", invoice);
+        let receipt = Delta::adjustment(start, Value::new("SFXO", 50.0)).with_rate(1.40);
+        let mut deposit = Item::fixed(Value::new("CAD", 0.0), start);
+        deposit.push_delta(receipt);
+        book.add_named("Receipt", deposit);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book).unwrap();
+        let mut loaded = store.load().unwrap();
+        let invoice = loaded.get(loaded.by_name("Invoice").unwrap()).unwrap();
+        assert_eq!(invoice.fx_override("CAD".into()), Some(1.30));
+        assert_value_eq!(loaded.assess(start), Value::new("CAD", 940.0), 1e-9);
+
+        loaded.migrate_currency("SFXO", "SFXN", 100.0);
+        for entry in loaded.take_audit_log() {
+            store.apply(&entry).unwrap();
+        }
+        let reloaded = store.load().unwrap();
+        let invoice = reloaded.get(reloaded.by_name("Invoice").unwrap()).unwrap();
+        assert_eq!(invoice.fx_override("CAD".into()), Some(130.0));
+        let receipt = reloaded.get(reloaded.by_name("Receipt").unwrap()).unwrap();
+        assert_eq!(receipt.deltas[0].rate, Some(140.0));
+        assert_value_eq!(reloaded.assess(start), Value::new("CAD", 940.0), 1e-9);
+    }
+
+    #[test]
+    pub fn test_incremental_delta() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book()).unwrap();
+
+        let mut book = store.load().unwrap();
+        let visa = book.by_name("Visa").unwrap();
+        book.add_delta(
+            visa,
+            Delta::payment(
+                Utc.with_ymd_and_hms(2009, 1, 1, 0, 0, 0).unwrap(),
+                Value::dummy("CAD", 50),
+            ),
+        );
+        for entry in book.take_audit_log() {
+            store.apply(&entry).unwrap();
+        }
+
+        let reloaded = store.load().unwrap();
+        let visa = reloaded.by_name("Visa").unwrap();
+        assert_eq!(reloaded.get(visa).unwrap().deltas.len(), 2);
+        assert_eq!(reloaded.assess(time).amount(), book.assess(time).amount());
+    }
+
+    #[test]
+    pub fn test_incremental_remove() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book()).unwrap();
+
+        let mut book = store.load().unwrap();
+        let house = book.by_name("House").unwrap();
+        let mortgage = book.get(house).unwrap().children[0];
+        assert!(book.reparent(mortgage, None));
+        book.remove(book.by_name("Visa").unwrap());
+        for entry in book.take_audit_log() {
+            store.apply(&entry).unwrap();
+        }
+
+        let reloaded = store.load().unwrap();
+        assert!(reloaded.by_name("Visa").is_none());
+        let house = reloaded.by_name("House").unwrap();
+        assert!(reloaded.get(house).unwrap().children.is_empty());
+        assert_eq!(reloaded.iter().count(), 2);
+        assert_eq!(reloaded.assess(time).amount(), book.assess(time).amount());
+    }
+
+    #[test]
+    pub fn test_incremental_replace() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book()).unwrap();
+
+        let mut book = store.load().unwrap();
+        let house = book.by_name("House").unwrap();
+        let mut edited = book.get_mut(house).unwrap();
+        edited.set_interest(Some(Interest::new(0.06, Period::Years(1))));
+        edited.payouts.pop();
+        drop(edited);
+        for entry in book.take_audit_log() {
+            store.apply(&entry).unwrap();
+        }
+
+        let reloaded = store.load().unwrap();
+        let house = reloaded.by_name("House").unwrap();
+        let names: Vec<_> = reloaded.ordered_keys().into_iter().map(|k| reloaded.name(k)).collect();
+        assert_eq!(names, [Some("Visa"), Some("House"), None]);
+        assert_eq!(reloaded.get(house).unwrap().children.len(), 1);
+        assert_eq!(reloaded.get(house).unwrap().payouts.len(), 1);
+        assert_eq!(reloaded.assess(time).amount(), book.assess(time).amount());
+    }
+
+    #[test]
+    pub fn test_incremental_currency_migration() {
+        let time = Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap();
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        store.save(&book()).unwrap();
+
+        let mut book = store.load().unwrap();
+        book.migrate_currency("CAD", "SQN", 100.0);
+        for entry in book.take_audit_log() {
+            store.apply(&entry).unwrap();
+        }
+
+        let reloaded = store.load().unwrap();
+        let assessed = reloaded.assess(time);
+        assert_eq!(assessed.currency().code(), "SQN");
+        assert_value_eq!(assessed, book.assess(time), 1e-6);
+    }
+
+    #[test]
+    pub fn test_version_1_store_migrated() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO meta VALUES ('format_version', '1');
+             CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, external_id TEXT,
+                                 currency TEXT, book_value REAL NOT NULL,
+                                 inception INTEGER NOT NULL, interest_percent REAL,
+                                 interest_period INTEGER);
+             CREATE TABLE children (parent_id INTEGER NOT NULL, child_id INTEGER NOT NULL,
+                                    position INTEGER NOT NULL);
+             CREATE TABLE deltas (id INTEGER PRIMARY KEY, item_id INTEGER NOT NULL,
+                                  time INTEGER NOT NULL, kind TEXT NOT NULL, percent REAL,
+                                  amount REAL, currency TEXT, memo TEXT, category TEXT,
+                                  external_id TEXT);
+             CREATE TABLE payouts (item_id INTEGER NOT NULL, position INTEGER NOT NULL,
+                                   variant TEXT NOT NULL, amount REAL NOT NULL,
+                                   currency TEXT, time INTEGER NOT NULL, frequency INTEGER,
+                                   interest_percent REAL, interest_period INTEGER);
+             INSERT INTO items (name, currency, book_value, inception)
+             VALUES ('House', 'CAD', 150000.0, 946688461000000000);",
+        )
+        .unwrap();
+
+        let mut store = SqliteStore::from_connection(conn, &Defaults::default()).unwrap();
+        let version: String = store
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'format_version'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(version, super::FORMAT_VERSION.to_string());
+        let book = store.load().unwrap();
+        assert!(book.by_name("House").is_some());
+    }
+
+    #[test]
+    pub fn test_null_currencies_need_a_default() {
+        let legacy = || {
+            let mut store = SqliteStore::open_in_memory().unwrap();
+            store.save(&book()).unwrap();
+            store
+                .conn
+                .execute_batch(
+                    "UPDATE meta SET value = '5' WHERE key = 'format_version';
+                     DROP TABLE fx_overrides;
+                     ALTER TABLE items DROP COLUMN capitalization;
+                     ALTER TABLE items DROP COLUMN round_decimals;
+                     ALTER TABLE items DROP COLUMN rounding;
+                     ALTER TABLE items DROP COLUMN application_order;
+                     ALTER TABLE items DROP COLUMN balloon;
+                     ALTER TABLE items DROP COLUMN interest_anchor;
+                     ALTER TABLE items DROP COLUMN interest_before_inception;
+                     ALTER TABLE payouts DROP COLUMN interest_anchor;
+                     ALTER TABLE payouts DROP COLUMN interest_before_inception;
+                     UPDATE items SET currency = NULL;
+                     UPDATE deltas SET currency = NULL;",
+                )
+                .unwrap();
+            store.conn
+        };
+        assert!(matches!(
+            SqliteStore::from_connection(legacy(), &Defaults::default()),
+            Err(StoreError::MissingCurrency)
+        ));
+
+        let defaults = Defaults {
+            currency: Some(Currency::new("USD")),
+        };
+        let mut store = SqliteStore::from_connection(legacy(), &defaults).unwrap();
+        let book = store.load().unwrap();
+        assert!(book.iter().all(|(_, item)| item.currency().code() == "USD"));
+    }
+
+    #[test]
+    pub fn test_indexed_interest_rejected() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let prime = BENCHMARKS.register(Benchmark::new("Prime").with_rate(start, 0.07));
+        let mut heloc = Item::fixed(Value::new("CAD", -50_000.0), start);
+        heloc.interest = Some(Interest::indexed(prime, 0.005, Period::Years(1)));
+        let mut book = Book::default();
+        book.add_named("HELOC", heloc);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        assert!(matches!(
+            store.save(&book),
+            Err(StoreError::Unsupported(message)) if message.contains("HELOC")
+        ));
+    }
+
+    #[test]
+    pub fn test_lease_rejected() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let car = Item::lease(
+            Value::new("CAD", 500.0),
+            Duration::days(30),
+            36,
+            Value::new("CAD", 15_000.0),
+            start,
+        );
+        let mut book = Book::default();
+        book.add_named("Car", car);
+
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        assert!(matches!(
+            store.save(&book),
+            Err(StoreError::Unsupported(message)) if message.contains("Car")
+        ));
+    }
+
+    #[test]
+    pub fn test_unknown_item() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        let mut book = book();
+        let visa = book.by_name("Visa").unwrap();
+        book.add_delta(visa, Delta::payment(Utc::now(), Value::dummy("CAD", 1)));
+        let entry = book.take_audit_log().pop().unwrap();
+        assert!(matches!(
+            store.apply(&entry),
+            Err(StoreError::UnknownItem(_))
+        ));
+    }
+}
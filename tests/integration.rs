@@ -0,0 +1,110 @@
+//! The scenarios in `examples/` with their milestones pinned, so a change
+//! in any module they go through shows up here.
+
+#[allow(dead_code)]
+#[path = "../examples/household.rs"]
+mod household;
+
+#[allow(dead_code)]
+#[path = "../examples/debt_payoff.rs"]
+mod debt_payoff;
+
+#[allow(dead_code)]
+#[path = "../examples/retirement.rs"]
+mod retirement;
+
+use alacran::{assert_value_eq, instruments::risk::RiskExt, Assesible, Interest, Period, Value};
+use chrono::{TimeZone, Utc};
+
+fn assert_cents(actual: Value, expected: f64) {
+    let expected = Value::new(actual.currency(), expected);
+    assert_value_eq!(actual, expected, 0.005);
+}
+
+#[test]
+pub fn test_household() {
+    let horizon = Utc.with_ymd_and_hms(2040, 1, 1, 0, 0, 0).unwrap();
+    let household::Household {
+        book,
+        chequing,
+        mortgage,
+        brokerage,
+        start,
+    } = household::household(horizon);
+    let years = |n| Period::Years(1).step(start, n);
+
+    assert_cents(book.assess(start), 299_400.0);
+    assert_cents(book.assess(years(5)), 515_337.51);
+    assert_cents(book.assess(years(10)), 768_357.02);
+    assert_cents(book.assess_risked(years(10)), 744_263.25);
+    assert_cents(book.get(mortgage).unwrap().assess(years(10)), -331_610.74);
+
+    // Chequing keeps 700 a month after expenses and the mortgage.
+    assert_cents(
+        book.get(chequing).unwrap().assess(years(1)),
+        15_000.0 + 12.0 * 700.0,
+    );
+    // The brokerage stays in US dollars and is converted for the total.
+    let usd = book.get(brokerage).unwrap().assess(years(1));
+    assert_eq!(usd.currency().code(), "USD");
+    assert_cents(usd, 42_400.0);
+
+    let millionaire = book.when_reaches(Value::new("CAD", 1_000_000.0), start, horizon);
+    assert_eq!(millionaire, Some(years(14)));
+}
+
+#[test]
+pub fn test_debt_payoff() {
+    let debt_payoff::Comparison {
+        book,
+        debts,
+        snowball,
+        avalanche,
+        start,
+    } = debt_payoff::comparison();
+    let months = |n| Period::Months(1).step(start, n);
+
+    assert_cents(snowball.total_interest.clone(), 3_089.33);
+    assert_cents(avalanche.total_interest.clone(), 2_740.21);
+    assert_eq!(snowball.debt_free(), Some(months(26)));
+    assert_eq!(avalanche.debt_free(), Some(months(25)));
+
+    // The snowball clears the smallest balance first, the avalanche the
+    // most expensive.
+    let [visa, line, car] = debts[..] else {
+        panic!("three debts");
+    };
+    assert_eq!(book.name(line), Some("Line of credit"));
+    assert_eq!(snowball.payoff[1], (line, Some(months(6))));
+    assert_eq!(avalanche.payoff[0], (visa, Some(months(10))));
+    assert!(avalanche.payoff[2].1 > avalanche.payoff[1].1);
+    assert_eq!(avalanche.payoff[2].0, car);
+    for month in snowball.months.iter().chain(&avalanche.months) {
+        let paid: f64 = month.payments.iter().map(|(_, v)| v.amount()).sum();
+        assert!(paid <= 1_200.0 + 1e-9);
+    }
+}
+
+#[test]
+pub fn test_retirement() {
+    let retirement::Drawdown {
+        book,
+        account,
+        start,
+        horizon,
+    } = retirement::drawdown();
+    let years = |n| Period::Years(1).step(start, n);
+
+    assert_cents(book.assess(years(10)), 694_097.78);
+    assert_cents(book.get(account).unwrap().assess(years(20)), 358_699.87);
+    let real = (&book)
+        .in_real_terms(Interest::new(0.02, Period::Years(1)), start)
+        .assess(years(20));
+    assert_cents(real, 241_394.73);
+
+    let empty = book.when_reaches(Value::new("CAD", 0.0), start, horizon);
+    assert_eq!(
+        empty,
+        Some(Utc.with_ymd_and_hms(2052, 3, 1, 0, 0, 0).unwrap())
+    );
+}
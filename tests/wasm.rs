@@ -0,0 +1,34 @@
+//! Runs under `cargo test --target wasm32-unknown-unknown` with
+//! `wasm-bindgen-test-runner` installed, the numbers must match native.
+#![cfg(target_arch = "wasm32")]
+
+use alacran::assert_value_eq;
+use alacran::instruments::{
+    book::Book,
+    convert::CURRENCY_EXCHANGE,
+    item::Item,
+    value::{Currency, Value},
+    Assesible,
+};
+use chrono::{Duration, TimeZone, Utc};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+pub fn test_credit_card() {
+    let mut book = Book::default();
+    book.add(Item::basic_debt(
+        Value::new("CAD", -15_000.0),
+        0.20,
+        Duration::days(365),
+        Utc.with_ymd_and_hms(2008, 1, 1, 1, 1, 1).unwrap(),
+    ));
+    let debt = book.assess(Utc.with_ymd_and_hms(2025, 1, 28, 11, 7, 0).unwrap());
+    assert_eq!(debt.non_decimal(), -338224);
+}
+
+#[wasm_bindgen_test]
+pub fn test_conversion() {
+    CURRENCY_EXCHANGE.add_conversion("CAD", "COP", 2911.98);
+    let total = Value::new("CAD", 28.0) + Value::new(Currency::intern("COP"), 600_000.0);
+    assert_value_eq!(total, Value::new("CAD", 206.0 + 28.0), 0.1);
+}